@@ -0,0 +1,484 @@
+//! Rust code for parsed definitions.
+//!
+//! Namespaces become nested modules and every type gets a module of its own named after it
+//! and its version, like `uavcan::node::heartbeat_1_0::Heartbeat`, so that several versions
+//! of a type can coexist. Serialization is derived through `cyphal::dsdl::DataType`.
+
+use std::collections::BTreeMap;
+
+use crate::expression::Value;
+use crate::parse::{
+    Array, Composite, Constant, Definition, Element, FieldKind, Kind, Primitive, TypeName,
+};
+
+const KEYWORDS: [&str; 48] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Names that can't be raw identifiers either.
+const RESERVED: [&str; 4] = ["crate", "self", "Self", "super"];
+
+/// Turns a DSDL name into a Rust identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.into()
+    }
+}
+
+/// `GetInfo` to `get_info`, `CANFrame` to `can_frame`.
+fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_lowercase = chars.get(index + 1).is_some_and(char::is_ascii_lowercase);
+            if previous.is_ascii_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_ascii_uppercase() && next_lowercase)
+            {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// `natural_16` to `Natural16`.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
+
+fn module_name(name: &TypeName) -> String {
+    format!(
+        "{}_{}_{}",
+        snake_case(&name.name),
+        name.version.0,
+        name.version.1
+    )
+}
+
+/// Smallest Rust integer that holds `bits`.
+fn integer_width(bits: u8) -> u8 {
+    bits.next_power_of_two().max(8)
+}
+
+/// Writes lines of code at the current indentation.
+struct Writer {
+    code: String,
+    indent: usize,
+}
+
+impl Writer {
+    fn line(&mut self, line: &str) {
+        if !line.is_empty() {
+            self.code.extend(core::iter::repeat_n("    ", self.indent));
+        }
+        self.code.push_str(line);
+        self.code.push('\n');
+    }
+
+    fn open(&mut self, line: &str) {
+        self.line(line);
+        self.indent += 1;
+    }
+
+    fn close(&mut self, line: &str) {
+        self.indent -= 1;
+        self.line(line);
+    }
+
+    fn doc(&mut self, doc: &[String]) {
+        for line in doc {
+            match line.is_empty() {
+                true => self.line("///"),
+                false => self.line(&format!("/// {}", line)),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Module<'a> {
+    definitions: Vec<&'a Definition>,
+    children: BTreeMap<&'a str, Module<'a>>,
+}
+
+pub fn emit(definitions: &[Definition], krate: &str) -> String {
+    let mut root = Module::default();
+    for definition in definitions {
+        let module = definition
+            .name
+            .namespace
+            .iter()
+            .fold(&mut root, |module, segment| {
+                module.children.entry(segment).or_default()
+            });
+        module.definitions.push(definition);
+    }
+
+    let mut writer = Writer {
+        code: String::new(),
+        indent: 0,
+    };
+    writer.line("// Generated by cyphal-codegen from DSDL definitions, do not edit.");
+    let emitter = Emitter { krate };
+    for (name, module) in &root.children {
+        writer.line("");
+        writer.line(
+            "#[allow(clippy::all, deprecated, non_camel_case_types, non_snake_case, rustdoc::all)]",
+        );
+        emitter.module(&mut writer, name, module);
+    }
+    writer.code
+}
+
+struct Emitter<'a> {
+    krate: &'a str,
+}
+
+impl Emitter<'_> {
+    fn module(&self, writer: &mut Writer, name: &str, module: &Module) {
+        writer.open(&format!("pub mod {} {{", identifier(name)));
+        let mut first = true;
+        for definition in &module.definitions {
+            if !first {
+                writer.line("");
+            }
+            first = false;
+            self.definition(writer, definition);
+        }
+        for (name, child) in &module.children {
+            if !first {
+                writer.line("");
+            }
+            first = false;
+            self.module(writer, name, child);
+        }
+        writer.close("}");
+    }
+
+    fn definition(&self, writer: &mut Writer, definition: &Definition) {
+        let krate = self.krate;
+        let name = &definition.name;
+        writer.line(&format!("/// `{}`", name));
+        writer.open(&format!("pub mod {} {{", module_name(name)));
+        match &definition.kind {
+            Kind::Message(composite) => {
+                if let Some(port_id) = definition.port_id {
+                    writer.line(&format!(
+                        "pub const SUBJECT_ID: {}::types::PortId = {};",
+                        krate, port_id
+                    ));
+                    writer.line("");
+                }
+                self.composite(writer, definition, &identifier(&name.name), composite);
+            }
+            Kind::Service { request, response } => {
+                let request_name = format!("{}Request", name.name);
+                let response_name = format!("{}Response", name.name);
+                if let Some(port_id) = definition.port_id {
+                    writer.line(&format!(
+                        "pub const SERVICE_ID: {}::types::PortId = {};",
+                        krate, port_id
+                    ));
+                    writer.line("");
+                    writer.line(&format!("/// `{}`", name));
+                    if definition.deprecated {
+                        writer.line("#[deprecated]");
+                    }
+                    writer.line(&format!("pub struct {};", identifier(&name.name)));
+                    writer.line("");
+                    self.service(writer, &name.name, &request_name, &response_name);
+                    writer.line("");
+                }
+                self.composite(writer, definition, &request_name, request);
+                writer.line("");
+                self.composite(writer, definition, &response_name, response);
+            }
+        }
+        writer.close("}");
+    }
+
+    fn service(&self, writer: &mut Writer, name: &str, request: &str, response: &str) {
+        let krate = self.krate;
+        writer.open(&format!(
+            "impl {}::service::Service for {} {{",
+            krate,
+            identifier(name)
+        ));
+        writer.line(&format!(
+            "const SERVICE_ID: {}::types::PortId = SERVICE_ID;",
+            krate
+        ));
+        writer.line(&format!(
+            "const REQUEST_MAX_SIZE: usize = <{} as {}::dsdl::DataType>::MAX_SIZE_BYTES;",
+            request, krate
+        ));
+        writer.line(&format!(
+            "const RESPONSE_EXTENT: usize = <{} as {}::dsdl::DataType>::EXTENT;",
+            response, krate
+        ));
+        writer.line("");
+        writer.line(&format!("type Request = {};", request));
+        writer.line(&format!("type Response = {};", response));
+        writer.line("");
+        writer.open(&format!(
+            "fn serialize_request(request: &{}, buffer: &mut [u8]) -> usize {{",
+            request
+        ));
+        writer.line(&format!(
+            "{}::dsdl::Serialize::serialize_to_bytes(request, buffer)",
+            krate
+        ));
+        writer.close("}");
+        writer.line("");
+        writer.open(&format!(
+            "fn deserialize_response(buffer: &[u8]) -> Option<{}> {{",
+            response
+        ));
+        writer.line(&format!(
+            "{}::dsdl::Deserialize::deserialize_from_bytes(buffer).ok()",
+            krate
+        ));
+        writer.close("}");
+        writer.close("}");
+    }
+
+    fn composite(
+        &self,
+        writer: &mut Writer,
+        definition: &Definition,
+        name: &str,
+        composite: &Composite,
+    ) {
+        let krate = self.krate;
+        writer.doc(&composite.doc);
+        if definition.deprecated {
+            writer.line("#[deprecated]");
+        }
+        writer.line(&format!(
+            "#[derive(Clone, Debug, PartialEq, {}::dsdl::DataType)]",
+            krate
+        ));
+        let extent = match composite.extent {
+            Some(Some(extent)) => format!("extent = {}", extent),
+            _ => "sealed".into(),
+        };
+        writer.line(&format!("#[dsdl(crate = {}, {})]", krate, extent));
+
+        let fields = &composite.fields;
+        if composite.union {
+            writer.open(&format!("pub enum {} {{", name));
+        } else if fields.is_empty() {
+            writer.line(&format!("pub struct {} {{}}", name));
+        } else {
+            writer.open(&format!("pub struct {} {{", name));
+        }
+        let mut padding = 0;
+        for field in fields {
+            writer.doc(&field.doc);
+            match &field.kind {
+                FieldKind::Padding(bits) => {
+                    writer.line(&format!("#[dsdl(void = {})]", bits));
+                    writer.line(&format!("pub _void{}: (),", padding));
+                    padding += 1;
+                }
+                FieldKind::Value {
+                    name,
+                    element,
+                    array,
+                } => {
+                    let (ty, attribute) = self.field_type(&definition.name, element, *array);
+                    let variant = identifier(&camel_case(name));
+                    match (composite.union, attribute) {
+                        (true, Some(attribute)) => {
+                            writer.line(&format!("{}(#[dsdl({})] {}),", variant, attribute, ty))
+                        }
+                        (true, None) => writer.line(&format!("{}({}),", variant, ty)),
+                        (false, attribute) => {
+                            if let Some(attribute) = attribute {
+                                writer.line(&format!("#[dsdl({})]", attribute));
+                            }
+                            writer.line(&format!("pub {}: {},", identifier(name), ty));
+                        }
+                    }
+                }
+            }
+        }
+        if composite.union || !fields.is_empty() {
+            writer.close("}");
+        }
+
+        if !composite.constants.is_empty() {
+            writer.line("");
+            writer.open(&format!("impl {} {{", name));
+            for constant in &composite.constants {
+                self.constant(writer, constant);
+            }
+            writer.close("}");
+        }
+    }
+
+    fn constant(&self, writer: &mut Writer, constant: &Constant) {
+        writer.doc(&constant.doc);
+        let ty = match constant.ty {
+            Primitive::Bool => "bool".into(),
+            Primitive::Unsigned(bits) => format!("u{}", integer_width(bits)),
+            Primitive::Signed(bits) => format!("i{}", integer_width(bits)),
+            Primitive::Float(bits) if bits <= 32 => "f32".into(),
+            Primitive::Float(_) => "f64".into(),
+        };
+        let value = match &constant.value {
+            Value::Rational(rational) if matches!(constant.ty, Primitive::Float(_)) => {
+                format!("{:?}", rational.to_f64())
+            }
+            value => value.to_string(),
+        };
+        writer.line(&format!(
+            "pub const {}: {} = {};",
+            identifier(&constant.name),
+            ty,
+            value
+        ));
+    }
+
+    /// Rust type of a field and the `dsdl` attribute it needs, if any.
+    fn field_type(
+        &self,
+        scope: &TypeName,
+        element: &Element,
+        array: Array,
+    ) -> (String, Option<String>) {
+        let mut attributes = Vec::new();
+        let element = match element {
+            Element::Primitive(primitive, truncated) => {
+                let (ty, natural) = match *primitive {
+                    Primitive::Bool => ("bool".into(), true),
+                    Primitive::Unsigned(bits) => (
+                        format!("u{}", integer_width(bits)),
+                        integer_width(bits) == bits,
+                    ),
+                    Primitive::Signed(bits) => (
+                        format!("i{}", integer_width(bits)),
+                        integer_width(bits) == bits,
+                    ),
+                    Primitive::Float(16) => ("f32".into(), false),
+                    Primitive::Float(bits) => (format!("f{}", bits), true),
+                };
+                if !natural {
+                    let bits = match *primitive {
+                        Primitive::Unsigned(bits)
+                        | Primitive::Signed(bits)
+                        | Primitive::Float(bits) => bits,
+                        Primitive::Bool => 1,
+                    };
+                    attributes.push(format!("bits = {}", bits));
+                    if *truncated {
+                        attributes.push("truncated".into());
+                    }
+                }
+                ty
+            }
+            Element::Composite(name) => self.path(scope, name),
+        };
+        let ty = match array {
+            Array::None => element,
+            Array::Fixed(len) => format!("[{}; {}]", element, len),
+            Array::Variable(capacity) => {
+                if !attributes.is_empty() {
+                    attributes.push(format!("capacity = {}", capacity));
+                }
+                format!(
+                    "{}::dsdl::heapless::Vec<{}, {}>",
+                    self.krate, element, capacity
+                )
+            }
+        };
+        (ty, (!attributes.is_empty()).then(|| attributes.join(", ")))
+    }
+
+    /// Path to the type `name` from the module of the type `scope`.
+    fn path(&self, scope: &TypeName, name: &TypeName) -> String {
+        let common = scope
+            .namespace
+            .iter()
+            .zip(&name.namespace)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut path = vec!["super"; scope.namespace.len() - common + 1]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        path.extend(
+            name.namespace[common..]
+                .iter()
+                .map(|segment| identifier(segment)),
+        );
+        path.push(module_name(name));
+        path.push(identifier(&name.name));
+        path.join("::")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(snake_case("GetInfo"), "get_info");
+        assert_eq!(snake_case("CANFrame"), "can_frame");
+        assert_eq!(snake_case("Natural16"), "natural16");
+        assert_eq!(snake_case("ID"), "id");
+        assert_eq!(camel_case("natural_16"), "Natural16");
+        assert_eq!(camel_case("empty"), "Empty");
+        assert_eq!(identifier("type"), "r#type");
+        assert_eq!(identifier("self"), "self_");
+        assert_eq!(identifier("value"), "value");
+    }
+
+    #[test]
+    fn paths() {
+        let name = |namespace: &[&str], name: &str| TypeName {
+            namespace: namespace
+                .iter()
+                .map(|segment| segment.to_string())
+                .collect(),
+            name: name.into(),
+            version: (1, 0),
+        };
+        let emitter = Emitter { krate: "::cyphal" };
+        let scope = name(&["uavcan", "node"], "Heartbeat");
+        assert_eq!(
+            emitter.path(&scope, &name(&["uavcan", "node"], "Health")),
+            "super::health_1_0::Health"
+        );
+        assert_eq!(
+            emitter.path(&scope, &name(&["uavcan", "si", "unit"], "Length")),
+            "super::super::si::unit::length_1_0::Length"
+        );
+        assert_eq!(
+            emitter.path(&scope, &name(&["reg"], "Type")),
+            "super::super::super::reg::type_1_0::Type"
+        );
+    }
+}
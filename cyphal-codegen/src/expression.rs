@@ -0,0 +1,410 @@
+//! Constant expressions, as used for constants, array capacities and `@extent`.
+//!
+//! DSDL evaluates expressions on exact rational numbers, so `1 / 3` is a third rather than
+//! zero. Sets and the attribute access operator are not supported, as they only show up in
+//! `@assert`, which the generator skips.
+
+use std::fmt;
+
+/// Value of an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Rational(Rational),
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    /// The value as an integer, if it is one.
+    pub fn integer(&self) -> Option<i128> {
+        match self {
+            Value::Rational(rational) => rational.integer(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Rational(rational) if rational.den == 1 => write!(f, "{}", rational.num),
+            Value::Rational(rational) => write!(f, "{}/{}", rational.num, rational.den),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// Exact rational number, always reduced and with a positive denominator.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rational {
+    num: i128,
+    den: i128,
+}
+
+impl Rational {
+    pub fn new(num: i128, den: i128) -> Result<Self, String> {
+        if den == 0 {
+            return Err("division by zero".into());
+        }
+        let divisor = gcd(num, den) * den.signum();
+        Ok(Self {
+            num: num / divisor,
+            den: den / divisor,
+        })
+    }
+
+    pub fn integer(&self) -> Option<i128> {
+        (self.den == 1).then_some(self.num)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    fn checked(
+        self,
+        other: Self,
+        num: impl Fn(i128, i128, i128, i128) -> Option<i128>,
+        den: impl Fn(i128, i128) -> Option<i128>,
+    ) -> Result<Self, String> {
+        let overflow = || "arithmetic overflow".to_string();
+        Rational::new(
+            num(self.num, self.den, other.num, other.den).ok_or_else(overflow)?,
+            den(self.den, other.den).ok_or_else(overflow)?,
+        )
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        self.checked(
+            other,
+            |a, b, c, d| a.checked_mul(d)?.checked_add(c.checked_mul(b)?),
+            i128::checked_mul,
+        )
+    }
+
+    fn sub(self, other: Self) -> Result<Self, String> {
+        self.checked(
+            other,
+            |a, b, c, d| a.checked_mul(d)?.checked_sub(c.checked_mul(b)?),
+            i128::checked_mul,
+        )
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        self.checked(other, |a, _, c, _| a.checked_mul(c), i128::checked_mul)
+    }
+
+    fn div(self, other: Self) -> Result<Self, String> {
+        self.mul(Rational::new(other.den, other.num)?)
+    }
+
+    fn rem(self, other: Self) -> Result<Self, String> {
+        match (self.integer(), other.integer()) {
+            (Some(_), Some(0)) => Err("division by zero".into()),
+            (Some(a), Some(b)) => Rational::new(a.rem_euclid(b), 1),
+            _ => Err("modulo is only defined for integers".into()),
+        }
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self, String> {
+        let exponent = exponent.integer().ok_or("exponents must be integers")?;
+        let power = u32::try_from(exponent.unsigned_abs()).map_err(|_| "arithmetic overflow")?;
+        let num = self.num.checked_pow(power).ok_or("arithmetic overflow")?;
+        let den = self.den.checked_pow(power).ok_or("arithmetic overflow")?;
+        if exponent < 0 {
+            Rational::new(den, num)
+        } else {
+            Rational::new(num, den)
+        }
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(Rational),
+    String(String),
+    Identifier(String),
+    Operator(&'static str),
+}
+
+const OPERATORS: [&str; 18] = [
+    "**", "==", "!=", "<=", ">=", "||", "&&", "+", "-", "*", "/", "%", "<", ">", "!", "(", ")", ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            // Exponents can be signed
+            let len = match rest[..len].ends_with(['e', 'E']) && !rest.starts_with("0x") {
+                true => {
+                    len + 1
+                        + rest[len + 1..]
+                            .find(|c: char| !c.is_ascii_digit())
+                            .unwrap_or(rest.len() - len - 1)
+                }
+                false => len,
+            };
+            tokens.push(Token::Number(parse_number(&rest[..len])?));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            // Including constants of other types, like `uavcan.file.Path.2.0.MAX_LENGTH`
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Identifier(rest[..len].to_string()));
+            len
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..].find(c).ok_or("unterminated string literal")?;
+            tokens.push(Token::String(rest[1..end + 1].to_string()));
+            end + 2
+        } else {
+            let operator = OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(**operator))
+                .ok_or_else(|| format!("unexpected character `{}`", c))?;
+            tokens.push(Token::Operator(operator));
+            operator.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(literal: &str) -> Result<Rational, String> {
+    let invalid = || format!("invalid number `{}`", literal);
+    let digits = literal.replace('_', "");
+    for (prefix, radix) in [("0x", 16), ("0b", 2), ("0o", 8)] {
+        if let Some(digits) = digits.strip_prefix(prefix) {
+            return Rational::new(
+                i128::from_str_radix(digits, radix).map_err(|_| invalid())?,
+                1,
+            );
+        }
+    }
+
+    let (mantissa, exponent) = match digits.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().map_err(|_| invalid())?),
+        None => (digits.as_str(), 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let num = format!("{}{}", integer, fraction)
+        .parse::<i128>()
+        .map_err(|_| invalid())?;
+    let scale =
+        Rational::new(10, 1)?.pow(Rational::new(exponent as i128 - fraction.len() as i128, 1)?)?;
+    Rational::new(num, 1)?.mul(scale)
+}
+
+/// Recursive descent over the tokens, from the lowest precedence up.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    constants: &'a dyn Fn(&str) -> Option<Value>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, operators: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(&Token::Operator(operator)) if operators.contains(&operator) => {
+                self.position += 1;
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operators: &[&'static str],
+        operand: fn(&mut Self) -> Result<Value, String>,
+    ) -> Result<Value, String> {
+        let mut left = operand(self)?;
+        while let Some(operator) = self.eat(operators) {
+            let right = operand(self)?;
+            left = apply(operator, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Value, String> {
+        self.binary(&["||"], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Value, String> {
+        self.binary(&["&&"], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Value, String> {
+        self.binary(&["==", "!=", "<=", ">=", "<", ">"], Self::additive)
+    }
+
+    fn additive(&mut self) -> Result<Value, String> {
+        self.binary(&["+", "-"], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> Result<Value, String> {
+        self.binary(&["*", "/", "%"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        match self.eat(&["-", "+", "!"]) {
+            Some("-") => apply("-", Value::Rational(Rational::new(0, 1)?), self.unary()?),
+            Some("!") => match self.unary()? {
+                Value::Bool(value) => Ok(Value::Bool(!value)),
+                _ => Err("`!` only applies to booleans".into()),
+            },
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Value, String> {
+        let base = self.primary()?;
+        match self.eat(&["**"]) {
+            // Right-associative, and binding tighter than a unary operator on its left
+            Some(_) => apply("**", base, self.unary()?),
+            None => Ok(base),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        let token = self.peek().cloned().ok_or("unexpected end of expression")?;
+        self.position += 1;
+        match token {
+            Token::Number(number) => Ok(Value::Rational(number)),
+            Token::String(string) => Ok(Value::String(string)),
+            Token::Identifier(name) => match name.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => (self.constants)(&name).ok_or_else(|| format!("unknown constant `{}`", name)),
+            },
+            Token::Operator("(") => {
+                let value = self.or()?;
+                self.eat(&[")"]).ok_or("expected `)`")?;
+                Ok(value)
+            }
+            Token::Operator(operator) => Err(format!("unexpected `{}`", operator)),
+        }
+    }
+}
+
+fn apply(operator: &str, left: Value, right: Value) -> Result<Value, String> {
+    use Value::*;
+    Ok(match (operator, left, right) {
+        ("+", Rational(a), Rational(b)) => Rational(a.add(b)?),
+        ("+", String(a), String(b)) => String(a + &b),
+        ("-", Rational(a), Rational(b)) => Rational(a.sub(b)?),
+        ("*", Rational(a), Rational(b)) => Rational(a.mul(b)?),
+        ("/", Rational(a), Rational(b)) => Rational(a.div(b)?),
+        ("%", Rational(a), Rational(b)) => Rational(a.rem(b)?),
+        ("**", Rational(a), Rational(b)) => Rational(a.pow(b)?),
+        ("==", a, b) => Bool(a == b),
+        ("!=", a, b) => Bool(a != b),
+        ("<" | "<=" | ">" | ">=", Rational(a), Rational(b)) => {
+            let difference = a.sub(b)?.num;
+            Bool(match operator {
+                "<" => difference < 0,
+                "<=" => difference <= 0,
+                ">" => difference > 0,
+                _ => difference >= 0,
+            })
+        }
+        ("||", Bool(a), Bool(b)) => Bool(a || b),
+        ("&&", Bool(a), Bool(b)) => Bool(a && b),
+        (operator, a, b) => {
+            return Err(format!(
+                "`{}` can't be applied to {} and {}",
+                operator, a, b
+            ));
+        }
+    })
+}
+
+/// Evaluates an expression, looking up names through `constants`.
+pub fn evaluate(source: &str, constants: &dyn Fn(&str) -> Option<Value>) -> Result<Value, String> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        constants,
+    };
+    let value = parser.or()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected {:?}", token)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str) -> Result<Value, String> {
+        evaluate(source, &|name| {
+            matches!(name, "CAPACITY" | "ns.Other.1.0.CAPACITY")
+                .then(|| Value::Rational(Rational::new(50, 1).unwrap()))
+        })
+    }
+
+    fn rational(num: i128, den: i128) -> Result<Value, String> {
+        Ok(Value::Rational(Rational::new(num, den).unwrap()))
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("64 * 8"), rational(512, 1));
+        assert_eq!(eval("1 + 2 * 3 - 4"), rational(3, 1));
+        assert_eq!(eval("(1 + 2) * 3"), rational(9, 1));
+        assert_eq!(eval("1 / 3"), rational(1, 3));
+        assert_eq!(eval("2 / 4 + 1 / 2"), rational(1, 1));
+        assert_eq!(eval("-7 % 3"), rational(2, 1));
+        assert_eq!(eval("2 ** 10 - 1"), rational(1023, 1));
+        assert_eq!(eval("-2 ** 2"), rational(-4, 1));
+        assert_eq!(eval("2 ** -2"), rational(1, 4));
+        assert_eq!(eval("2 ** 3 ** 2"), rational(512, 1));
+        assert_eq!(eval("CAPACITY * 2"), rational(100, 1));
+        assert_eq!(eval("ns.Other.1.0.CAPACITY+1"), rational(51, 1));
+        assert_eq!(eval("0xFF + 0b11 + 0o10 + 1_000"), rational(1266, 1));
+        assert_eq!(eval("1.5e3"), rational(1500, 1));
+        assert_eq!(eval("2.5E-1"), rational(1, 4));
+        assert_eq!(eval("3.14"), rational(157, 50));
+    }
+
+    #[test]
+    fn other_values() {
+        assert_eq!(eval("true && !false"), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 / 2 < 2 / 3 || false"), Ok(Value::Bool(true)));
+        assert_eq!(eval("3 == 6 / 2"), Ok(Value::Bool(true)));
+        assert_eq!(eval("'ab' + \"c\""), Ok(Value::String("abc".into())));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("1 / 2 % 1").is_err());
+        assert!(eval("2 ** 1000").is_err());
+        assert!(eval("UNKNOWN").is_err());
+        assert!(eval("(1 + 2").is_err());
+        assert!(eval("1 2").is_err());
+        assert!(eval("true + 1").is_err());
+        assert!(eval("1 $ 2").is_err());
+    }
+}
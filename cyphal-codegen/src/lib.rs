@@ -0,0 +1,263 @@
+//! Generates Rust types from Cyphal DSDL definitions.
+//!
+//! Every DSDL type becomes a `no_std` struct, or an enum for unions, deriving
+//! `cyphal::dsdl::DataType` for its serialization. Fixed port IDs become `SUBJECT_ID` or
+//! `SERVICE_ID` constants, and services with one also get a marker type implementing
+//! `cyphal::service::Service` for typed requests. The generated code depends on the `derive`
+//! feature of `cyphal`.
+//!
+//! Types are placed in modules following their namespace and named after their version,
+//! like `uavcan::node::heartbeat_1_0::Heartbeat` for `uavcan/node/7509.Heartbeat.1.0.dsdl`.
+//!
+//! Generation works on local files only. From a build script:
+//!
+//! ```no_run
+//! // build.rs
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("dsdl.rs");
+//! cyphal_codegen::Generator::new()
+//!     .namespace("dsdl/uavcan")
+//!     .namespace("dsdl/vendor")
+//!     .write(out)
+//!     .unwrap();
+//! println!("cargo:rerun-if-changed=dsdl");
+//! ```
+//!
+//! with the output included in the crate through
+//! `include!(concat!(env!("OUT_DIR"), "/dsdl.rs"));`. The `cyphal-codegen` binary does the same
+//! from the command line, to check the generated code in instead.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+mod emit;
+mod expression;
+mod parse;
+
+use expression::Value;
+use parse::{Definition, Element, FieldKind, Kind, TypeName};
+
+/// Errors from loading DSDL definitions.
+#[derive(Debug)]
+pub enum Error {
+    /// A file or directory couldn't be read or written
+    Io(PathBuf, io::Error),
+    /// A definition is invalid, at the given line or at its file name for line 0
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Error::Parse {
+                path,
+                line: 0,
+                message,
+            } => write!(f, "{}: {}", path.display(), message),
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, error) => Some(error),
+            Error::Parse { .. } => None,
+        }
+    }
+}
+
+/// Generates the code for a set of root namespaces.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    namespaces: Vec<PathBuf>,
+    krate: String,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self {
+            namespaces: Vec::new(),
+            krate: "::cyphal".into(),
+        }
+    }
+
+    /// Adds the root namespace in `directory`, which is named after the directory. Its
+    /// subdirectories are nested namespaces.
+    ///
+    /// Types can refer to types in any of the namespaces.
+    pub fn namespace(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.namespaces.push(directory.into());
+        self
+    }
+
+    /// Path to the `cyphal` crate in the generated code, `::cyphal` by default.
+    pub fn crate_path(mut self, path: impl Into<String>) -> Self {
+        self.krate = path.into();
+        self
+    }
+
+    /// Generates the code for all the types in the namespaces.
+    pub fn generate(&self) -> Result<String, Error> {
+        let mut sources = Vec::new();
+        for directory in &self.namespaces {
+            let name = directory
+                .canonicalize()
+                .map_err(|error| Error::Io(directory.clone(), error))?
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(String::from)
+                .unwrap_or_default();
+            load(directory, vec![name], &mut sources)?;
+        }
+
+        // Types can use the constants of other types, which are parsed first
+        let mut definitions = Vec::<Definition>::new();
+        while !sources.is_empty() {
+            let mut error = None;
+            let parsed = definitions.len();
+            for (path, namespace, source) in core::mem::take(&mut sources) {
+                let external =
+                    |name: &TypeName, constant: &str| constant_value(&definitions, name, constant);
+                match parse::parse(&path, namespace.clone(), &source, &external) {
+                    Ok(definition) => definitions.push(definition),
+                    Err(parse_error) => {
+                        error.get_or_insert(parse_error);
+                        sources.push((path, namespace, source));
+                    }
+                }
+            }
+            if definitions.len() == parsed {
+                return Err(error.unwrap());
+            }
+        }
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        check(&definitions)?;
+        Ok(emit::emit(&definitions, &self.krate))
+    }
+
+    /// Generates the code into the file at `path`, which is left untouched if it already
+    /// holds the same code.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let code = self.generate()?;
+        if fs::read_to_string(path).is_ok_and(|existing| existing == code) {
+            return Ok(());
+        }
+        fs::write(path, code).map_err(|error| Error::Io(path.into(), error))
+    }
+}
+
+/// Path, namespace and contents of a definition file.
+type Source = (PathBuf, Vec<String>, String);
+
+/// Reads the definitions in `directory` and its subdirectories.
+fn load(directory: &Path, namespace: Vec<String>, sources: &mut Vec<Source>) -> Result<(), Error> {
+    let io_error = |error| Error::Io(directory.into(), error);
+    let mut entries = fs::read_dir(directory)
+        .map_err(io_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io_error)?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let mut namespace = namespace.clone();
+            namespace.push(name.into());
+            load(&path, namespace, sources)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "dsdl")
+        {
+            let source =
+                fs::read_to_string(&path).map_err(|error| Error::Io(path.clone(), error))?;
+            sources.push((path, namespace.clone(), source));
+        }
+    }
+    Ok(())
+}
+
+/// Value of a constant of the message type `name`.
+fn constant_value(definitions: &[Definition], name: &TypeName, constant: &str) -> Option<Value> {
+    let definition = definitions
+        .iter()
+        .find(|definition| &definition.name == name)?;
+    let Kind::Message(composite) = &definition.kind else {
+        return None;
+    };
+    composite
+        .constants
+        .iter()
+        .find(|other| other.name == constant)
+        .map(|constant| constant.value.clone())
+}
+
+/// Checks that names are unique and that referenced types exist. `definitions` is sorted.
+fn check(definitions: &[Definition]) -> Result<(), Error> {
+    for pair in definitions.windows(2) {
+        if pair[0].name == pair[1].name {
+            return Err(Error::Parse {
+                path: pair[1].path.clone(),
+                line: 0,
+                message: format!(
+                    "`{}` is also defined in {}",
+                    pair[1].name,
+                    pair[0].path.display()
+                ),
+            });
+        }
+    }
+
+    for definition in definitions {
+        let composites = match &definition.kind {
+            Kind::Message(composite) => vec![composite],
+            Kind::Service { request, response } => vec![request, response],
+        };
+        for field in composites
+            .into_iter()
+            .flat_map(|composite| &composite.fields)
+        {
+            let FieldKind::Value {
+                element: Element::Composite(name),
+                ..
+            } = &field.kind
+            else {
+                continue;
+            };
+            let message = match definitions.binary_search_by(|other| other.name.cmp(name)) {
+                Ok(index) => match definitions[index].kind {
+                    Kind::Message(_) => continue,
+                    Kind::Service { .. } => format!("`{}` is a service", name),
+                },
+                Err(_) => format!("unknown type `{}`", name),
+            };
+            return Err(Error::Parse {
+                path: definition.path.clone(),
+                line: field.line,
+                message,
+            });
+        }
+    }
+    Ok(())
+}
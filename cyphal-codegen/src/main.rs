@@ -0,0 +1,61 @@
+//! Generates Rust types from DSDL definitions, see the library for details.
+
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: cyphal-codegen [--crate <path>] [-o <output>] <namespace directory>...
+
+Generates Rust types for the DSDL definitions in the given root namespaces.
+
+Options:
+  --crate <path>  Path to the cyphal crate in the generated code [default: ::cyphal]
+  -o <output>     File to write the code to [default: standard output]";
+
+fn main() -> ExitCode {
+    let mut generator = cyphal_codegen::Generator::new();
+    let mut output = None;
+    let mut namespaces = 0;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "--crate" | "-o" => {
+                let Some(value) = args.next() else {
+                    eprintln!("{} needs a value\n\n{}", arg, USAGE);
+                    return ExitCode::FAILURE;
+                };
+                match arg.as_str() {
+                    "-o" => output = Some(value),
+                    _ => generator = generator.crate_path(value),
+                }
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option {}\n\n{}", arg, USAGE);
+                return ExitCode::FAILURE;
+            }
+            _ => {
+                generator = generator.namespace(arg);
+                namespaces += 1;
+            }
+        }
+    }
+    if namespaces == 0 {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let result = match output {
+        Some(output) => generator.write(output),
+        None => generator.generate().map(|code| print!("{}", code)),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,778 @@
+//! Parsing of DSDL definition files.
+//!
+//! Each file holds one type, named after the file: `[<port ID>.]<Name>.<major>.<minor>.dsdl`,
+//! in the namespace given by its directory. Composite types referenced by fields are resolved
+//! to their full name here and checked against the other definitions by the generator.
+
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+use crate::expression::{self, Value};
+
+/// Full name of a type, like `uavcan.node.Heartbeat.1.0`.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct TypeName {
+    pub namespace: Vec<String>,
+    pub name: String,
+    pub version: (u8, u8),
+}
+
+impl std::fmt::Display for TypeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for segment in &self.namespace {
+            write!(f, "{}.", segment)?;
+        }
+        write!(f, "{}.{}.{}", self.name, self.version.0, self.version.1)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub path: PathBuf,
+    pub name: TypeName,
+    pub port_id: Option<u16>,
+    pub deprecated: bool,
+    pub kind: Kind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kind {
+    Message(Composite),
+    Service {
+        request: Composite,
+        response: Composite,
+    },
+}
+
+/// A message, or the request or response of a service.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Composite {
+    pub doc: Vec<String>,
+    pub union: bool,
+    /// Extent in bytes, `None` for sealed types
+    pub extent: Option<Option<u64>>,
+    pub fields: Vec<Field>,
+    pub constants: Vec<Constant>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub doc: Vec<String>,
+    pub line: usize,
+    pub kind: FieldKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldKind {
+    /// `voidN`
+    Padding(u8),
+    Value {
+        name: String,
+        element: Element,
+        array: Array,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Element {
+    /// A primitive, which is truncated rather than saturated if the flag is set
+    Primitive(Primitive, bool),
+    Composite(TypeName),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Primitive {
+    Bool,
+    Unsigned(u8),
+    Signed(u8),
+    Float(u8),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Array {
+    None,
+    Fixed(u64),
+    /// Variable length, with the capacity
+    Variable(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constant {
+    pub doc: Vec<String>,
+    pub name: String,
+    pub ty: Primitive,
+    pub value: Value,
+}
+
+/// Fixed port ID, name and version of a type, from its file name.
+type FileName = (Option<u16>, String, (u8, u8));
+
+/// Parses `[<port ID>.]<Name>.<major>.<minor>.dsdl`.
+fn parse_file_name(file_name: &str) -> Result<FileName, String> {
+    let invalid = || {
+        format!(
+            "`{}` isn't named `[<port ID>.]<Name>.<major>.<minor>.dsdl`",
+            file_name
+        )
+    };
+    let stem = file_name.strip_suffix(".dsdl").ok_or_else(invalid)?;
+    let segments = stem.split('.').collect::<Vec<_>>();
+    let (port_id, segments) = match segments.len() {
+        3 => (None, &segments[..]),
+        4 => (
+            Some(segments[0].parse::<u16>().map_err(|_| invalid())?),
+            &segments[1..],
+        ),
+        _ => return Err(invalid()),
+    };
+    if !is_identifier(segments[0]) {
+        return Err(invalid());
+    }
+    let major = segments[1].parse().map_err(|_| invalid())?;
+    let minor = segments[2].parse().map_err(|_| invalid())?;
+    Ok((port_id, segments[0].to_string(), (major, minor)))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits a line at its comment, if any.
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '#') => return (&line[..index], Some(line[index + 1..].trim())),
+            _ => (),
+        }
+    }
+    (line, None)
+}
+
+/// Parses a primitive type like `uint7`, `float16` or `byte`, as well as `voidN`, which is
+/// returned as `Err(N)`.
+fn parse_primitive(ty: &str) -> Option<Result<Primitive, u8>> {
+    let width = |prefix: &str| {
+        ty.strip_prefix(prefix)
+            .and_then(|bits| bits.parse::<u8>().ok())
+            .filter(|bits| (1..=64).contains(bits))
+    };
+    Some(match ty {
+        "bool" => Ok(Primitive::Bool),
+        "byte" | "utf8" => Ok(Primitive::Unsigned(8)),
+        "float16" | "float32" | "float64" => Ok(Primitive::Float(width("float")?)),
+        _ => {
+            if let Some(bits) = width("uint") {
+                Ok(Primitive::Unsigned(bits))
+            } else if let Some(bits) = width("int") {
+                Ok(Primitive::Signed(bits))
+            } else {
+                Err(width("void")?)
+            }
+        }
+    })
+}
+
+/// Resolves a composite type name, which is either absolute or relative to `namespace` when
+/// it has no namespace of its own.
+fn parse_type_name(ty: &str, namespace: &[String]) -> Result<TypeName, String> {
+    let invalid = || format!("unknown type `{}`", ty);
+    let segments = ty.split('.').collect::<Vec<_>>();
+    let [path @ .., name, major, minor] = &segments[..] else {
+        return Err(invalid());
+    };
+    if !path
+        .iter()
+        .chain([name])
+        .all(|segment| is_identifier(segment))
+    {
+        return Err(invalid());
+    }
+    Ok(TypeName {
+        namespace: match path {
+            [] => namespace.to_vec(),
+            _ => path.iter().map(|segment| segment.to_string()).collect(),
+        },
+        name: name.to_string(),
+        version: (
+            major.parse().map_err(|_| invalid())?,
+            minor.parse().map_err(|_| invalid())?,
+        ),
+    })
+}
+
+/// Looks up a constant of another type, for references like `uavcan.file.Path.2.0.MAX_LENGTH`.
+pub type External<'a> = &'a dyn Fn(&TypeName, &str) -> Option<Value>;
+
+/// Parses the definition in the file at `path`, in `namespace`.
+pub fn parse(
+    path: &Path,
+    namespace: Vec<String>,
+    source: &str,
+    external: External<'_>,
+) -> Result<Definition, Error> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let (port_id, name, version) = parse_file_name(file_name).map_err(|message| Error::Parse {
+        path: path.into(),
+        line: 0,
+        message,
+    })?;
+    let name = TypeName {
+        namespace,
+        name,
+        version,
+    };
+
+    let mut parser = Parser {
+        namespace: &name.namespace,
+        external,
+        composites: vec![Composite::default()],
+        statements: 0,
+        doc: Doc::Header,
+        deprecated: false,
+    };
+    let mut lines = 0;
+    for (index, line) in source.lines().enumerate() {
+        lines = index + 1;
+        parser.line(line, lines).map_err(|message| Error::Parse {
+            path: path.into(),
+            line: lines,
+            message,
+        })?;
+    }
+
+    let deprecated = parser.deprecated;
+    let mut composites = parser.composites;
+    for composite in &mut composites {
+        finish(composite).map_err(|message| Error::Parse {
+            path: path.into(),
+            line: lines,
+            message,
+        })?;
+    }
+    let kind = match <[Composite; 2]>::try_from(composites) {
+        Ok([request, response]) => Kind::Service { request, response },
+        Err(mut composites) => Kind::Message(composites.remove(0)),
+    };
+
+    let max_port_id = match kind {
+        Kind::Message(_) => 8191,
+        Kind::Service { .. } => 511,
+    };
+    if port_id.is_some_and(|port_id| port_id > max_port_id) {
+        return Err(Error::Parse {
+            path: path.into(),
+            line: 0,
+            message: format!("fixed port IDs of this kind go up to {}", max_port_id),
+        });
+    }
+
+    Ok(Definition {
+        path: path.into(),
+        name,
+        port_id,
+        deprecated,
+        kind,
+    })
+}
+
+/// Checks a composite once all of its lines are parsed.
+fn finish(composite: &mut Composite) -> Result<(), String> {
+    if composite.extent.is_none() {
+        return Err("types must be either `@sealed` or have an `@extent`".into());
+    }
+    if composite.union {
+        if composite.fields.len() < 2 {
+            return Err("unions need at least two variants".into());
+        }
+        if composite
+            .fields
+            .iter()
+            .any(|field| matches!(field.kind, FieldKind::Padding(_)))
+        {
+            return Err("unions can't hold padding".into());
+        }
+    }
+    while composite.doc.last().is_some_and(|line| line.is_empty()) {
+        composite.doc.pop();
+    }
+    Ok(())
+}
+
+/// What comment lines document. Comments before the first statement document the type, and
+/// those on the line of an attribute or right below it document the attribute.
+#[derive(Copy, Clone)]
+enum Doc {
+    Header,
+    Field,
+    Constant,
+    Nothing,
+}
+
+struct Parser<'a> {
+    namespace: &'a [String],
+    external: External<'a>,
+    /// The message, or the request and then the response
+    composites: Vec<Composite>,
+    /// Directives and attributes in the current composite so far
+    statements: usize,
+    doc: Doc,
+    deprecated: bool,
+}
+
+impl Parser<'_> {
+    fn composite(&mut self) -> &mut Composite {
+        self.composites.last_mut().unwrap()
+    }
+
+    fn documented(&mut self) -> Option<&mut Vec<String>> {
+        let composite = self.composites.last_mut().unwrap();
+        match self.doc {
+            Doc::Header => Some(&mut composite.doc),
+            Doc::Field => composite.fields.last_mut().map(|field| &mut field.doc),
+            Doc::Constant => composite
+                .constants
+                .last_mut()
+                .map(|constant| &mut constant.doc),
+            Doc::Nothing => None,
+        }
+    }
+
+    fn evaluate(&self, expression: &str) -> Result<Value, String> {
+        let composite = self.composites.last().unwrap();
+        expression::evaluate(expression, &|name| match name.rsplit_once('.') {
+            Some((ty, name)) => (self.external)(&parse_type_name(ty, self.namespace).ok()?, name),
+            None => composite
+                .constants
+                .iter()
+                .find(|constant| constant.name == name)
+                .map(|constant| constant.value.clone()),
+        })
+    }
+
+    fn evaluate_integer(&self, expression: &str) -> Result<u64, String> {
+        self.evaluate(expression)?
+            .integer()
+            .and_then(|value| u64::try_from(value).ok())
+            .ok_or_else(|| format!("`{}` isn't a non-negative integer", expression))
+    }
+
+    fn line(&mut self, line: &str, number: usize) -> Result<(), String> {
+        let (code, comment) = split_comment(line);
+        let code = code.trim();
+        if code.is_empty() {
+            match (comment, self.doc) {
+                (Some(comment), _) => {
+                    if let Some(doc) = self.documented() {
+                        doc.push(comment.into());
+                    }
+                }
+                // Blank lines separate paragraphs of the header, and end the documentation of
+                // an attribute
+                (None, Doc::Header) => {
+                    let doc = &mut self.composite().doc;
+                    if doc.last().is_some_and(|line| !line.is_empty()) {
+                        doc.push(String::new());
+                    }
+                }
+                (None, _) => self.doc = Doc::Nothing,
+            }
+            return Ok(());
+        }
+
+        if code == "---" {
+            if self.composites.len() > 1 {
+                return Err("services have one request and one response".into());
+            }
+            self.composites.push(Composite::default());
+            self.statements = 0;
+            self.doc = Doc::Header;
+            return Ok(());
+        }
+        self.statements += 1;
+        self.doc = Doc::Nothing;
+        match code.strip_prefix('@') {
+            Some(directive) => self.directive(directive),
+            None => {
+                let doc = comment.map(String::from).into_iter().collect();
+                self.attribute(code, doc, number)
+            }
+        }
+    }
+
+    fn directive(&mut self, directive: &str) -> Result<(), String> {
+        let (name, argument) = directive
+            .split_once(char::is_whitespace)
+            .map(|(name, argument)| (name, argument.trim()))
+            .unwrap_or((directive, ""));
+        let no_argument = || match argument {
+            "" => Ok(()),
+            _ => Err(format!("`@{}` takes no argument", name)),
+        };
+        match name {
+            "union" => {
+                no_argument()?;
+                if !self.composite().fields.is_empty() {
+                    return Err("`@union` goes before the fields".into());
+                }
+                self.composite().union = true;
+            }
+            "sealed" | "extent" => {
+                if self.composite().extent.is_some() {
+                    return Err("types are either `@sealed` or have one `@extent`".into());
+                }
+                let extent = match name {
+                    "sealed" => no_argument().map(|_| None)?,
+                    _ => match self.evaluate_integer(argument)? {
+                        bits if bits % 8 == 0 => Some(bits / 8),
+                        _ => return Err("extents are a multiple of 8 bits".into()),
+                    },
+                };
+                self.composite().extent = Some(extent);
+            }
+            "deprecated" => {
+                no_argument()?;
+                if self.composites.len() > 1 || self.statements > 1 {
+                    return Err("`@deprecated` goes before anything else".into());
+                }
+                self.deprecated = true;
+            }
+            // Only checks the definition, which the tools that maintain it already do
+            "assert" | "print" => (),
+            _ => return Err(format!("unknown directive `@{}`", name)),
+        }
+        Ok(())
+    }
+
+    fn attribute(&mut self, code: &str, doc: Vec<String>, line: usize) -> Result<(), String> {
+        let (truncated, code) = match code.split_once(char::is_whitespace) {
+            Some(("saturated", rest)) => (Some(false), rest.trim_start()),
+            Some(("truncated", rest)) => (Some(true), rest.trim_start()),
+            _ => (None, code),
+        };
+
+        let type_end = code
+            .find(|c: char| c.is_whitespace() || c == '[')
+            .unwrap_or(code.len());
+        let (ty, rest) = code.split_at(type_end);
+        let rest = rest.trim_start();
+        let (array, rest) = match rest.strip_prefix('[') {
+            Some(rest) => {
+                let (length, rest) = rest.split_once(']').ok_or("expected `]`")?;
+                (self.array(length.trim())?, rest.trim_start())
+            }
+            None => (Array::None, rest),
+        };
+        let (name, value) = match rest.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (rest, None),
+        };
+
+        let element = match parse_primitive(ty) {
+            Some(Err(bits)) => {
+                if !rest.is_empty() || array != Array::None || truncated.is_some() {
+                    return Err("padding is only `voidN`".into());
+                }
+                self.composite().fields.push(Field {
+                    doc,
+                    line,
+                    kind: FieldKind::Padding(bits),
+                });
+                self.doc = Doc::Field;
+                return Ok(());
+            }
+            Some(Ok(primitive)) => {
+                if truncated.is_some() && primitive == Primitive::Bool {
+                    return Err("`bool` can't be saturated or truncated".into());
+                }
+                // utf8 and byte are truncated uint8, which is the same as saturated
+                Element::Primitive(primitive, truncated.unwrap_or(false))
+            }
+            None if truncated.is_some() => {
+                return Err("only primitives can be saturated or truncated".into());
+            }
+            None => Element::Composite(parse_type_name(ty, self.namespace)?),
+        };
+        if !is_identifier(name) {
+            return Err(format!("`{}` isn't a valid name", name));
+        }
+
+        let Some(value) = value else {
+            self.composite().fields.push(Field {
+                doc,
+                line,
+                kind: FieldKind::Value {
+                    name: name.into(),
+                    element,
+                    array,
+                },
+            });
+            self.doc = Doc::Field;
+            return Ok(());
+        };
+        let (Element::Primitive(ty, _), Array::None) = (element, array) else {
+            return Err("constants are primitives".into());
+        };
+        let value = match (ty, self.evaluate(value)?) {
+            (Primitive::Bool, value @ Value::Bool(_))
+            | (Primitive::Float(_), value @ Value::Rational(_)) => value,
+            (Primitive::Unsigned(bits) | Primitive::Signed(bits), value) => {
+                // Single characters convert to their code
+                let integer = match &value {
+                    Value::String(string) if string.chars().count() == 1 => {
+                        string.chars().next().map(|c| c as i128)
+                    }
+                    value => value.integer(),
+                };
+                let (min, max) = match ty {
+                    Primitive::Signed(_) => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+                    _ => (0, (1i128 << bits) - 1),
+                };
+                match integer {
+                    Some(integer) if (min..=max).contains(&integer) => {
+                        Value::Rational(expression::Rational::new(integer, 1)?)
+                    }
+                    _ => return Err(format!("{} doesn't fit in `{}`", value, ty_name(ty))),
+                }
+            }
+            (_, value) => return Err(format!("{} doesn't fit in `{}`", value, ty_name(ty))),
+        };
+        self.composite().constants.push(Constant {
+            doc,
+            name: name.into(),
+            ty,
+            value,
+        });
+        self.doc = Doc::Constant;
+        Ok(())
+    }
+
+    fn array(&self, length: &str) -> Result<Array, String> {
+        let array = if let Some(capacity) = length.strip_prefix("<=") {
+            Array::Variable(self.evaluate_integer(capacity)?)
+        } else if let Some(capacity) = length.strip_prefix('<') {
+            Array::Variable(self.evaluate_integer(capacity)?.saturating_sub(1))
+        } else {
+            Array::Fixed(self.evaluate_integer(length)?)
+        };
+        match array {
+            Array::Fixed(0) | Array::Variable(0) => Err("arrays can't be empty".into()),
+            array => Ok(array),
+        }
+    }
+}
+
+fn ty_name(ty: Primitive) -> String {
+    match ty {
+        Primitive::Bool => "bool".into(),
+        Primitive::Unsigned(bits) => format!("uint{}", bits),
+        Primitive::Signed(bits) => format!("int{}", bits),
+        Primitive::Float(bits) => format!("float{}", bits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(file_name: &str, source: &str) -> Result<Definition, Error> {
+        parse(
+            Path::new(file_name),
+            vec!["ns".into()],
+            source,
+            &|ty, name| {
+                (ty.to_string() == "ns.Other.1.0" && name == "CAPACITY")
+                    .then(|| Value::Rational(expression::Rational::new(4, 1).unwrap()))
+            },
+        )
+    }
+
+    fn error_line(file_name: &str, source: &str) -> usize {
+        match parse_str(file_name, source) {
+            Err(Error::Parse { line, .. }) => line,
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(
+            parse_file_name("7509.Heartbeat.1.0.dsdl"),
+            Ok((Some(7509), "Heartbeat".into(), (1, 0)))
+        );
+        assert_eq!(
+            parse_file_name("Health.1.12.dsdl"),
+            Ok((None, "Health".into(), (1, 12)))
+        );
+        assert!(parse_file_name("Health.1.dsdl").is_err());
+        assert!(parse_file_name("x.Health.1.0.dsdl").is_err());
+        assert!(parse_file_name("Health.1.0.uavcan").is_err());
+        assert!(parse_file_name("1Health.1.0.dsdl").is_err());
+    }
+
+    #[test]
+    fn message() {
+        let source = "\
+# A message.
+#
+# More about it.
+
+@deprecated
+@extent 8 * (4 + 4)
+uint8 CAPACITY = Other.1.0.CAPACITY - 1  # Capacity of `values`
+float32 HALF = 1 / 2
+uint8 LETTER = 'A'
+
+saturated int12[<=CAPACITY] values
+# The values
+truncated float16 number # In meters
+# above sea level
+void3
+
+# Dropped, as it follows a blank line
+
+ns.sub.Other.1.0[2] others
+@assert _offset_ % 8 == {0}
+Local.0.1 local
+";
+        let definition = parse_str("100.Message.1.2.dsdl", source).unwrap();
+        assert_eq!(definition.port_id, Some(100));
+        assert_eq!(definition.name.to_string(), "ns.Message.1.2");
+        assert!(definition.deprecated);
+        let Kind::Message(composite) = definition.kind else {
+            panic!("not a message");
+        };
+        assert_eq!(composite.doc, ["A message.", "", "More about it."]);
+        assert_eq!(composite.extent, Some(Some(8)));
+        assert!(!composite.union);
+
+        let constants = composite
+            .constants
+            .iter()
+            .map(|constant| (constant.name.as_str(), constant.value.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            constants,
+            [
+                ("CAPACITY", "3".into()),
+                ("HALF", "1/2".into()),
+                ("LETTER", "65".into())
+            ]
+        );
+        assert_eq!(composite.constants[0].doc, ["Capacity of `values`"]);
+
+        let fields = composite
+            .fields
+            .iter()
+            .map(|field| (field.doc.clone(), field.line, field.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields[0],
+            (
+                vec!["The values".into()],
+                11,
+                FieldKind::Value {
+                    name: "values".into(),
+                    element: Element::Primitive(Primitive::Signed(12), false),
+                    array: Array::Variable(3),
+                }
+            )
+        );
+        assert_eq!(
+            fields[1].2,
+            FieldKind::Value {
+                name: "number".into(),
+                element: Element::Primitive(Primitive::Float(16), true),
+                array: Array::None,
+            }
+        );
+        assert_eq!(fields[1].0, ["In meters", "above sea level"]);
+        assert_eq!(fields[2].2, FieldKind::Padding(3));
+        assert_eq!(fields[3].0, Vec::<String>::new());
+        let FieldKind::Value { element, array, .. } = &fields[3].2 else {
+            panic!("not a value");
+        };
+        assert_eq!(
+            element,
+            &Element::Composite(parse_type_name("ns.sub.Other.1.0", &[]).unwrap())
+        );
+        assert_eq!(array, &Array::Fixed(2));
+        let FieldKind::Value { element, .. } = &fields[4].2 else {
+            panic!("not a value");
+        };
+        assert_eq!(
+            element,
+            &Element::Composite(parse_type_name("ns.Local.0.1", &[]).unwrap())
+        );
+    }
+
+    #[test]
+    fn service() {
+        let source = "\
+@union
+uint8 a
+bool b
+@sealed
+---
+# The response
+uint8[<4] c
+@extent 64
+";
+        let definition = parse_str("Service.0.1.dsdl", source).unwrap();
+        let Kind::Service { request, response } = definition.kind else {
+            panic!("not a service");
+        };
+        assert!(request.union);
+        assert_eq!(request.fields.len(), 2);
+        assert_eq!(request.extent, Some(None));
+        assert_eq!(response.doc, ["The response"]);
+        assert_eq!(response.extent, Some(Some(8)));
+        assert_eq!(
+            response.fields[0].kind,
+            FieldKind::Value {
+                name: "c".into(),
+                element: Element::Primitive(Primitive::Unsigned(8), false),
+                array: Array::Variable(3),
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error_line("A.1.0.dsdl", "uint8 a\n"), 1);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\n@extent 8\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@extent 12\n"), 1);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nuint8 X = 256\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nint8 X = 1 / 2\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nuint8[<=N] x\n"), 2);
+        assert_eq!(
+            error_line("A.1.0.dsdl", "@sealed\nuint8[<=Other.1.0.N] x\n"),
+            2
+        );
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nuint8[0] x\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nuint65 x\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\ntruncated bool x\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nvoid3 x\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nuint8 x y\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\n@foo\n"), 2);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\n@union\nuint8 a\n"), 3);
+        assert_eq!(
+            error_line("A.1.0.dsdl", "@sealed\nuint8 a\n@deprecated\n"),
+            3
+        );
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\n---\n@sealed\n---\n"), 4);
+        assert_eq!(error_line("A.1.0.dsdl", "@sealed\nuint8 a\n@union\n"), 3);
+        assert_eq!(error_line("9000.A.1.0.dsdl", "@sealed\n"), 0);
+        assert_eq!(error_line("600.A.1.0.dsdl", "@sealed\n---\n@sealed\n"), 0);
+        assert_eq!(error_line("A.dsdl", "@sealed\n"), 0);
+    }
+}
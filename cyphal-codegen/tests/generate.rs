@@ -0,0 +1,90 @@
+use cyphal::dsdl::{DataType, Deserialize, Serialize};
+use cyphal::service::Service;
+
+include!("golden/demo.rs");
+
+use demo::command_1_0::{Command, CommandRequest, CommandResponse};
+use demo::health_1_0::Health;
+use demo::status_1_0::{self, Status};
+use demo::sub::value_1_0::Value;
+
+#[test]
+fn golden_file_is_up_to_date() {
+    let code = cyphal_codegen::Generator::new()
+        .namespace(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/dsdl/demo"))
+        .generate()
+        .unwrap();
+    assert!(
+        code == include_str!("golden/demo.rs"),
+        "regenerate with `cargo run -p cyphal-codegen -- cyphal-codegen/tests/dsdl/demo -o cyphal-codegen/tests/golden/demo.rs`"
+    );
+}
+
+#[test]
+fn messages() {
+    assert_eq!(status_1_0::SUBJECT_ID, 7000);
+    assert_eq!(Status::NAME_CAPACITY, 8);
+    assert_eq!(Status::MAX_TEMPERATURE, 125.5);
+    assert_eq!(Status::EXTENT, 64);
+    assert_eq!(Health::MAX_SIZE_BITS, 2);
+    assert_eq!(Value::EXTENT, 16);
+    assert_eq!(Value::MAX_SIZE_BYTES, 8);
+
+    let status = Status {
+        uptime: 1,
+        health: Health {
+            value: Health::CAUTION,
+        },
+        temperature: 1.0,
+        _void0: (),
+        trim: [-1, 1],
+        name: heapless_from(b"ab"),
+        values: [
+            Value::Byte(5),
+            Value::Readings(heapless_from(&[0xFFF, 0x123])),
+        ]
+        .into_iter()
+        .collect(),
+        flag: true,
+    };
+    let mut buffer = [0u8; Status::MAX_SIZE_BYTES];
+    let len = status.serialize_to_bytes(&mut buffer);
+    assert_eq!(len, status.size_bytes());
+    assert_eq!(buffer[0..8], [1, 0, 0, 0, 2, 0x00, 0x3C, 0xF0]);
+    assert_eq!(Status::deserialize_from_bytes(&buffer[0..len]), Ok(status));
+
+    let mut buffer = [0u8; Value::MAX_SIZE_BYTES];
+    let len = Value::Health(Health { value: 3 }).serialize_to_bytes(&mut buffer);
+    assert_eq!(buffer[0..len], [1, 3]);
+    assert_eq!(
+        Value::deserialize_from_bytes(&[3, 1]),
+        Ok(Value::Type(true))
+    );
+
+    #[allow(deprecated)]
+    let old = demo::old_0_1::Old { value: 7 };
+    assert_eq!(old.size_bytes(), 1);
+}
+
+#[test]
+fn services() {
+    assert_eq!(Command::SERVICE_ID, 100);
+    assert_eq!(Command::REQUEST_MAX_SIZE, 2 + 1 + 16);
+    assert_eq!(Command::RESPONSE_EXTENT, 1);
+
+    let request = CommandRequest {
+        command: CommandRequest::COMMAND_RESTART,
+        parameter: heapless_from(&[9]),
+    };
+    let mut buffer = [0u8; Command::REQUEST_MAX_SIZE];
+    let len = Command::serialize_request(&request, &mut buffer);
+    assert_eq!(buffer[0..len], [1, 0, 1, 9]);
+    assert_eq!(
+        Command::deserialize_response(&[1]),
+        Some(CommandResponse { ok: true })
+    );
+}
+
+fn heapless_from<T: Clone, const N: usize>(elements: &[T]) -> cyphal::dsdl::heapless::Vec<T, N> {
+    cyphal::dsdl::heapless::Vec::from_slice(elements).unwrap()
+}
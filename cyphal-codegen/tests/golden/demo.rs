@@ -0,0 +1,123 @@
+// Generated by cyphal-codegen from DSDL definitions, do not edit.
+
+#[allow(clippy::all, deprecated, non_camel_case_types, non_snake_case, rustdoc::all)]
+pub mod demo {
+    /// `demo.Command.1.0`
+    pub mod command_1_0 {
+        pub const SERVICE_ID: ::cyphal::types::PortId = 100;
+
+        /// `demo.Command.1.0`
+        pub struct Command;
+
+        impl ::cyphal::service::Service for Command {
+            const SERVICE_ID: ::cyphal::types::PortId = SERVICE_ID;
+            const REQUEST_MAX_SIZE: usize = <CommandRequest as ::cyphal::dsdl::DataType>::MAX_SIZE_BYTES;
+            const RESPONSE_EXTENT: usize = <CommandResponse as ::cyphal::dsdl::DataType>::EXTENT;
+
+            type Request = CommandRequest;
+            type Response = CommandResponse;
+
+            fn serialize_request(request: &CommandRequest, buffer: &mut [u8]) -> usize {
+                ::cyphal::dsdl::Serialize::serialize_to_bytes(request, buffer)
+            }
+
+            fn deserialize_response(buffer: &[u8]) -> Option<CommandResponse> {
+                ::cyphal::dsdl::Deserialize::deserialize_from_bytes(buffer).ok()
+            }
+        }
+
+        /// Runs a command on a node.
+        #[derive(Clone, Debug, PartialEq, ::cyphal::dsdl::DataType)]
+        #[dsdl(crate = ::cyphal, extent = 32)]
+        pub struct CommandRequest {
+            pub command: u16,
+            pub parameter: ::cyphal::dsdl::heapless::Vec<u8, 16>,
+        }
+
+        impl CommandRequest {
+            pub const COMMAND_RESTART: u16 = 1;
+        }
+
+        /// Outcome of the command.
+        #[derive(Clone, Debug, PartialEq, ::cyphal::dsdl::DataType)]
+        #[dsdl(crate = ::cyphal, sealed)]
+        pub struct CommandResponse {
+            pub ok: bool,
+        }
+    }
+
+    /// `demo.Health.1.0`
+    pub mod health_1_0 {
+        /// Health of a node.
+        #[derive(Clone, Debug, PartialEq, ::cyphal::dsdl::DataType)]
+        #[dsdl(crate = ::cyphal, sealed)]
+        pub struct Health {
+            #[dsdl(bits = 2)]
+            pub value: u8,
+        }
+
+        impl Health {
+            pub const NOMINAL: u8 = 0;
+            pub const ADVISORY: u8 = 1;
+            pub const CAUTION: u8 = 2;
+            pub const WARNING: u8 = 3;
+        }
+    }
+
+    /// `demo.Old.0.1`
+    pub mod old_0_1 {
+        /// Replaced by Status.
+        #[deprecated]
+        #[derive(Clone, Debug, PartialEq, ::cyphal::dsdl::DataType)]
+        #[dsdl(crate = ::cyphal, sealed)]
+        pub struct Old {
+            pub value: u8,
+        }
+    }
+
+    /// `demo.Status.1.0`
+    pub mod status_1_0 {
+        pub const SUBJECT_ID: ::cyphal::types::PortId = 7000;
+
+        /// Status published by every demo node.
+        ///
+        /// Extensible: newer versions may add fields at the end.
+        #[derive(Clone, Debug, PartialEq, ::cyphal::dsdl::DataType)]
+        #[dsdl(crate = ::cyphal, extent = 64)]
+        pub struct Status {
+            /// [second]
+            pub uptime: u32,
+            pub health: super::health_1_0::Health,
+            #[dsdl(bits = 16, truncated)]
+            pub temperature: f32,
+            #[dsdl(void = 4)]
+            pub _void0: (),
+            #[dsdl(bits = 3)]
+            pub trim: [i8; 2],
+            pub name: ::cyphal::dsdl::heapless::Vec<u8, 8>,
+            pub values: ::cyphal::dsdl::heapless::Vec<super::sub::value_1_0::Value, 2>,
+            pub flag: bool,
+        }
+
+        impl Status {
+            pub const NAME_CAPACITY: u8 = 8;
+            pub const MAX_TEMPERATURE: f32 = 125.5;
+            pub const FLAG_DEFAULT: bool = true;
+        }
+    }
+
+    pub mod sub {
+        /// `demo.sub.Value.1.0`
+        pub mod value_1_0 {
+            /// A value of one of several kinds.
+            #[derive(Clone, Debug, PartialEq, ::cyphal::dsdl::DataType)]
+            #[dsdl(crate = ::cyphal, extent = 16)]
+            pub enum Value {
+                Byte(u8),
+                Health(super::super::health_1_0::Health),
+                Readings(#[dsdl(bits = 12, capacity = 4)] ::cyphal::dsdl::heapless::Vec<u16, 4>),
+                Type(bool),
+            }
+        }
+    }
+}
@@ -0,0 +1,718 @@
+//! Derives Cyphal v1 DSDL serialization, implementing `cyphal::dsdl::DataType`, `Serialize`
+//! and `Deserialize` for a Rust type.
+//!
+//! Structs map to DSDL structures, with fields serialized in order. Enums map to DSDL unions,
+//! every variant holding exactly one field. The type must be marked either `sealed` or with
+//! its `extent` in bytes, which makes it delimited.
+//!
+//! Fields without attributes are serialized through their own `Serialize` implementation,
+//! which covers `bool`, the standard integer and float types at their natural bit length,
+//! other DSDL types, fixed-length arrays of those and `heapless::Vec` as variable-length
+//! arrays. Field attributes cover the rest:
+//!
+//! - `bits = N` serializes an integer as `uintN`/`intN`, an `f32` as `float16` or an `f64` as
+//!   `float16`/`float32`. On arrays, it applies to the elements.
+//! - `saturated` (the default) clamps values to the range of the serialized type,
+//!   `truncated` drops the bits that don't fit.
+//! - `capacity = N` serializes a `Vec` or `heapless::Vec` as a variable-length array with the
+//!   given capacity.
+//! - `void = N` marks a `()` field as `voidN` padding.
+//!
+//! ```
+//! use cyphal::dsdl::{DataType, Deserialize, Serialize};
+//!
+//! /// `uint3 mode`, `void5`, `float16[2] setpoint`, `uint8[<=16] name`, `@extent 32 * 8`
+//! #[derive(DataType, Debug, Default, PartialEq)]
+//! #[dsdl(extent = 32)]
+//! struct Command {
+//!     #[dsdl(bits = 3)]
+//!     mode: u8,
+//!     #[dsdl(void = 5)]
+//!     _reserved: (),
+//!     #[dsdl(bits = 16)]
+//!     setpoint: [f32; 2],
+//!     name: heapless::Vec<u8, 16>,
+//! }
+//!
+//! assert_eq!(Command::MAX_SIZE_BYTES, 1 + 4 + 1 + 16);
+//! assert_eq!(Command::EXTENT, 32);
+//!
+//! let command = Command { mode: 9, setpoint: [1.5, -2.0], ..Default::default() };
+//! let mut buffer = [0u8; Command::MAX_SIZE_BYTES];
+//! let len = command.serialize_to_bytes(&mut buffer);
+//! // The mode saturates to 7
+//! assert_eq!(buffer[0..len], [7, 0x00, 0x3E, 0x00, 0xC0, 0]);
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Expr, Fields, LitInt, Member, Path, Type};
+
+#[proc_macro_derive(DataType, attributes(dsdl))]
+pub fn derive_data_type(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = ContainerAttrs::parse(input)?;
+    let krate = &attrs.krate;
+    let dsdl = quote!(#krate::dsdl);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = data
+                .fields
+                .iter()
+                .zip(data.fields.members())
+                .map(|(field, member)| Field::parse(field, Some(member)))
+                .collect::<syn::Result<Vec<_>>>()?;
+            expand_struct(&dsdl, &fields)
+        }
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(Error::new(input.span(), "unions need at least one variant"));
+            }
+            let variants = data
+                .variants
+                .iter()
+                .map(|variant| match &variant.fields {
+                    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                        let field = Field::parse(&fields.unnamed[0], None)?;
+                        if let Kind::Void(_) = field.kind {
+                            return Err(Error::new(variant.span(), "union fields can't be void"));
+                        }
+                        Ok((&variant.ident, field))
+                    }
+                    _ => Err(Error::new(
+                        variant.span(),
+                        "union variants must hold exactly one unnamed field",
+                    )),
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            expand_union(&dsdl, &variants)
+        }
+        Data::Union(_) => {
+            return Err(Error::new(
+                input.span(),
+                "DSDL unions are derived for enums, not Rust unions",
+            ));
+        }
+    };
+
+    let Body {
+        max_size_bits,
+        size_bits,
+        serialize,
+        deserialize,
+    } = body;
+    let extent_bytes = match &attrs.extent {
+        Some(extent) => quote!(::core::option::Option::Some(#extent)),
+        None => quote!(::core::option::Option::None),
+    };
+    // Generic types can't be checked outside of an impl
+    let extent_check = match &attrs.extent {
+        Some(_) if input.generics.params.is_empty() => quote! {
+            const _: () = ::core::assert!(
+                <#name as #dsdl::DataType>::EXTENT >= <#name as #dsdl::DataType>::MAX_SIZE_BYTES,
+                "the extent is smaller than the maximum serialized size",
+            );
+        },
+        _ => quote!(),
+    };
+
+    Ok(quote! {
+        impl #impl_generics #dsdl::DataType for #name #ty_generics #where_clause {
+            const EXTENT_BYTES: ::core::option::Option<usize> = #extent_bytes;
+            const MAX_SIZE_BITS: usize = #max_size_bits;
+            const ALIGNMENT_BITS: usize = 8;
+        }
+
+        impl #impl_generics #dsdl::Serialize for #name #ty_generics #where_clause {
+            fn size_bits(&self) -> usize {
+                #size_bits
+            }
+
+            fn serialize(&self, cursor: &mut #dsdl::WriteCursor<'_>) {
+                #serialize
+            }
+        }
+
+        impl #impl_generics #dsdl::Deserialize for #name #ty_generics #where_clause {
+            fn deserialize(
+                cursor: &mut #dsdl::ReadCursor<'_>,
+            ) -> ::core::result::Result<Self, #dsdl::DeserializeError> {
+                #deserialize
+            }
+        }
+
+        #extent_check
+    })
+}
+
+/// Bodies of the generated items.
+struct Body {
+    max_size_bits: TokenStream2,
+    size_bits: TokenStream2,
+    serialize: TokenStream2,
+    deserialize: TokenStream2,
+}
+
+fn expand_struct(dsdl: &TokenStream2, fields: &[Field]) -> Body {
+    let max_size_bits = fields.iter().map(|field| field.max_end_bits(dsdl));
+    let size_bits = fields.iter().map(|field| {
+        let member = field.member.as_ref().unwrap();
+        field.size_bits(dsdl, &quote!((&self.#member)))
+    });
+    let serialize = fields.iter().map(|field| {
+        let member = field.member.as_ref().unwrap();
+        field.serialize(dsdl, &quote!((&self.#member)))
+    });
+    let deserialize = fields.iter().map(|field| {
+        let member = field.member.as_ref().unwrap();
+        let value = field.deserialize(dsdl);
+        quote!(#member: #value)
+    });
+
+    Body {
+        max_size_bits: quote! {{
+            let bits = 0usize;
+            #(let bits = #max_size_bits;)*
+            bits
+        }},
+        size_bits: quote! {
+            let mut bits = 0usize;
+            #(#size_bits)*
+            bits
+        },
+        serialize: quote!(#(#serialize)*),
+        deserialize: quote!(::core::result::Result::Ok(Self { #(#deserialize,)* })),
+    }
+}
+
+fn expand_union(dsdl: &TokenStream2, variants: &[(&syn::Ident, Field)]) -> Body {
+    let count = variants.len();
+    let max_size_bits = variants.iter().map(|(_, field)| field.max_end_bits(dsdl));
+    let size_bits = variants.iter().map(|(ident, field)| {
+        let size_bits = field.size_bits(dsdl, &quote!(value));
+        quote!(Self::#ident(value) => { #size_bits })
+    });
+    let serialize = variants.iter().enumerate().map(|(tag, (ident, field))| {
+        let serialize = field.serialize(dsdl, &quote!(value));
+        quote! {
+            Self::#ident(value) => {
+                cursor.write_union_tag(#tag, #count);
+                #serialize
+            }
+        }
+    });
+    let deserialize = variants.iter().enumerate().map(|(tag, (ident, field))| {
+        let value = field.deserialize(dsdl);
+        // The tag is checked against the number of variants when read
+        let pattern = if tag == count - 1 {
+            quote!(_)
+        } else {
+            quote!(#tag)
+        };
+        quote!(#pattern => Self::#ident(#value))
+    });
+
+    Body {
+        max_size_bits: quote! {{
+            let tag = #dsdl::union_tag_bits(#count) as usize;
+            let mut max = tag;
+            #({
+                let bits = tag;
+                let bits = #max_size_bits;
+                if bits > max {
+                    max = bits;
+                }
+            })*
+            max
+        }},
+        size_bits: quote! {
+            let mut bits = #dsdl::union_tag_bits(#count) as usize;
+            match self {
+                #(#size_bits)*
+            }
+            bits
+        },
+        serialize: quote! {
+            match self {
+                #(#serialize)*
+            }
+        },
+        deserialize: quote! {
+            ::core::result::Result::Ok(match cursor.read_union_tag(#count)? {
+                #(#deserialize,)*
+            })
+        },
+    }
+}
+
+/// Attributes of the type itself.
+struct ContainerAttrs {
+    sealed: bool,
+    extent: Option<Expr>,
+    krate: Path,
+}
+
+impl ContainerAttrs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attrs = ContainerAttrs {
+            sealed: false,
+            extent: None,
+            krate: syn::parse_quote!(::cyphal),
+        };
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("dsdl"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("sealed") {
+                    attrs.sealed = true;
+                } else if meta.path.is_ident("extent") {
+                    attrs.extent = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("crate") {
+                    attrs.krate = meta.value()?.parse()?;
+                } else {
+                    return Err(meta.error("expected `sealed`, `extent` or `crate`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        match (attrs.sealed, &attrs.extent) {
+            (true, Some(extent)) => Err(Error::new(
+                extent.span(),
+                "sealed types don't have an extent",
+            )),
+            (false, None) => Err(Error::new(
+                input.ident.span(),
+                "DSDL types need either `#[dsdl(sealed)]` or `#[dsdl(extent = ...)]`",
+            )),
+            _ => Ok(attrs),
+        }
+    }
+}
+
+/// How values that don't fit the serialized type are handled.
+#[derive(Copy, Clone, PartialEq)]
+enum Cast {
+    Saturated,
+    Truncated,
+}
+
+/// A field of a structure, or of a union variant.
+struct Field {
+    /// `None` for union variants
+    member: Option<Member>,
+    ty: Type,
+    kind: Kind,
+}
+
+enum Kind {
+    /// Padding of the given number of bits
+    Void(usize),
+    /// Serialized through the type's own implementation
+    Nested,
+    Scalar(Scalar),
+    /// Fixed-length array of scalars with the given length
+    FixedArray(Scalar, Expr),
+    /// Variable-length array with the given capacity
+    DynamicArray(Element, Expr),
+}
+
+enum Element {
+    Scalar(Scalar),
+    Nested(Type),
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Class {
+    Bool,
+    Unsigned,
+    Signed,
+    Float,
+}
+
+/// A primitive serialized with a bit length other than its own.
+struct Scalar {
+    ty: Type,
+    /// Bit length of the Rust type
+    width: u32,
+    class: Class,
+    bits: u32,
+    cast: Cast,
+}
+
+impl Field {
+    fn parse(field: &syn::Field, member: Option<Member>) -> syn::Result<Self> {
+        let mut bits = None;
+        let mut cast = None;
+        let mut void = None;
+        let mut capacity = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("dsdl"))
+        {
+            attr.parse_nested_meta(|meta| {
+                let set_cast = |cast: &mut Option<Cast>, value| {
+                    if cast.replace(value).is_some() {
+                        Err(meta.error("only one of `saturated` and `truncated` can be set"))
+                    } else {
+                        Ok(())
+                    }
+                };
+                if meta.path.is_ident("bits") {
+                    bits = Some((meta.value()?.parse::<LitInt>()?, meta.path.span()));
+                } else if meta.path.is_ident("saturated") {
+                    set_cast(&mut cast, Cast::Saturated)?;
+                } else if meta.path.is_ident("truncated") {
+                    set_cast(&mut cast, Cast::Truncated)?;
+                } else if meta.path.is_ident("void") {
+                    void = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<usize>()?);
+                } else if meta.path.is_ident("capacity") {
+                    capacity = Some(meta.value()?.parse::<Expr>()?);
+                } else {
+                    return Err(meta
+                        .error("expected `bits`, `saturated`, `truncated`, `void` or `capacity`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let ty = field.ty.clone();
+        if let Some(void) = void {
+            if bits.is_some() || cast.is_some() || capacity.is_some() {
+                return Err(Error::new(
+                    field.span(),
+                    "void fields can't have other attributes",
+                ));
+            }
+            if !matches!(&ty, Type::Tuple(tuple) if tuple.elems.is_empty()) {
+                return Err(Error::new(ty.span(), "void fields must be of type `()`"));
+            }
+            return Ok(Field {
+                member,
+                ty,
+                kind: Kind::Void(void),
+            });
+        }
+        let bits = bits
+            .map(|(bits, span)| Ok::<_, Error>((bits.base10_parse::<u32>()?, span)))
+            .transpose()?;
+        if cast.is_some() && bits.is_none() {
+            return Err(Error::new(
+                field.span(),
+                "casts only apply to fields with `bits` set",
+            ));
+        }
+        let cast = cast.unwrap_or(Cast::Saturated);
+
+        let kind = match (capacity, bits, &ty) {
+            (Some(capacity), bits, ty) => {
+                let element = element_type(ty)?;
+                let element = match bits {
+                    Some((bits, span)) => Element::Scalar(Scalar::new(element, bits, cast, span)?),
+                    None => Element::Nested(element.clone()),
+                };
+                Kind::DynamicArray(element, capacity)
+            }
+            (None, Some((bits, span)), Type::Array(array)) => Kind::FixedArray(
+                Scalar::new(&array.elem, bits, cast, span)?,
+                array.len.clone(),
+            ),
+            (None, Some((bits, span)), ty) => Kind::Scalar(Scalar::new(ty, bits, cast, span)?),
+            (None, None, _) => Kind::Nested,
+        };
+        Ok(Field { member, ty, kind })
+    }
+
+    /// Expression for the maximum offset the field ends at, starting at `bits` at most.
+    fn max_end_bits(&self, dsdl: &TokenStream2) -> TokenStream2 {
+        let ty = &self.ty;
+        match &self.kind {
+            Kind::Void(void) => quote!(bits + #void),
+            Kind::Nested => quote!(#dsdl::max_field_end_bits::<#ty>(bits)),
+            Kind::Scalar(scalar) => {
+                let bits = scalar.bits as usize;
+                quote!(bits + #bits)
+            }
+            Kind::FixedArray(scalar, len) => {
+                let bits = scalar.bits as usize;
+                quote!(bits + (#len) * #bits)
+            }
+            Kind::DynamicArray(Element::Scalar(scalar), capacity) => {
+                let bits = scalar.bits as usize;
+                quote!(bits + #dsdl::array_length_bits(#capacity) as usize + (#capacity) * #bits)
+            }
+            Kind::DynamicArray(Element::Nested(element), capacity) => quote! {
+                bits.next_multiple_of(<#element as #dsdl::DataType>::ALIGNMENT_BITS)
+                    + #dsdl::array_length_bits(#capacity) as usize
+                    + (#capacity) * #dsdl::max_field_end_bits::<#element>(0)
+            },
+        }
+    }
+
+    /// Statements adding the size of the field at `value`, a reference, to `bits`.
+    fn size_bits(&self, dsdl: &TokenStream2, value: &TokenStream2) -> TokenStream2 {
+        match &self.kind {
+            Kind::Void(void) => quote!(bits += #void;),
+            Kind::Nested => quote!(bits += #dsdl::Serialize::field_size_bits(#value, bits);),
+            Kind::Scalar(scalar) => {
+                let bits = scalar.bits as usize;
+                quote!(bits += #bits;)
+            }
+            Kind::FixedArray(scalar, _) => {
+                let bits = scalar.bits as usize;
+                quote!(bits += #value.len() * #bits;)
+            }
+            Kind::DynamicArray(Element::Scalar(scalar), capacity) => {
+                let bits = scalar.bits as usize;
+                quote! {
+                    bits += #dsdl::array_length_bits(#capacity) as usize
+                        + ::core::cmp::min(#value.len(), #capacity) * #bits;
+                }
+            }
+            Kind::DynamicArray(Element::Nested(element), capacity) => quote! {
+                bits = bits.next_multiple_of(<#element as #dsdl::DataType>::ALIGNMENT_BITS)
+                    + #dsdl::array_length_bits(#capacity) as usize;
+                for element in #value.iter().take(#capacity) {
+                    bits += #dsdl::Serialize::field_size_bits(element, bits);
+                }
+            },
+        }
+    }
+
+    /// Statements serializing the field at `value`, a reference.
+    fn serialize(&self, dsdl: &TokenStream2, value: &TokenStream2) -> TokenStream2 {
+        match &self.kind {
+            Kind::Void(void) => quote!(cursor.write_padding(#void);),
+            Kind::Nested => quote!(#dsdl::Serialize::serialize_field(#value, cursor);),
+            Kind::Scalar(scalar) => scalar.serialize(&quote!(*#value)),
+            Kind::FixedArray(scalar, _) => {
+                let serialize = scalar.serialize(&quote!(*element));
+                quote! {
+                    for element in #value.iter() {
+                        #serialize
+                    }
+                }
+            }
+            Kind::DynamicArray(element, capacity) => {
+                let (align, serialize) = match element {
+                    Element::Scalar(scalar) => (quote!(), scalar.serialize(&quote!(*element))),
+                    Element::Nested(ty) => (
+                        quote! {
+                            if <#ty as #dsdl::DataType>::ALIGNMENT_BITS > 1 {
+                                cursor.align();
+                            }
+                        },
+                        quote!(#dsdl::Serialize::serialize_field(element, cursor);),
+                    ),
+                };
+                quote! {
+                    #align
+                    cursor.write_array_length(::core::cmp::min(#value.len(), #capacity), #capacity);
+                    for element in #value.iter().take(#capacity) {
+                        #serialize
+                    }
+                }
+            }
+        }
+    }
+
+    /// Expression deserializing the field, returning early on errors.
+    fn deserialize(&self, dsdl: &TokenStream2) -> TokenStream2 {
+        let ty = &self.ty;
+        match &self.kind {
+            Kind::Void(void) => quote!({
+                cursor.skip(#void);
+            }),
+            Kind::Nested => quote!(<#ty as #dsdl::Deserialize>::deserialize_field(cursor)?),
+            Kind::Scalar(scalar) => scalar.deserialize(),
+            Kind::FixedArray(scalar, _) => {
+                let deserialize = scalar.deserialize();
+                quote!(::core::array::from_fn(|_| #deserialize))
+            }
+            Kind::DynamicArray(element, capacity) => {
+                let (align, deserialize) = match element {
+                    Element::Scalar(scalar) => (quote!(), scalar.deserialize()),
+                    Element::Nested(element) => (
+                        quote! {
+                            if <#element as #dsdl::DataType>::ALIGNMENT_BITS > 1 {
+                                cursor.align();
+                            }
+                        },
+                        quote!(<#element as #dsdl::Deserialize>::deserialize_field(cursor)?),
+                    ),
+                };
+                quote!({
+                    #align
+                    let len = cursor.read_array_length(#capacity)?;
+                    let mut elements = <#ty as ::core::default::Default>::default();
+                    for _ in 0..len {
+                        ::core::iter::Extend::extend(
+                            &mut elements,
+                            ::core::option::Option::Some(#deserialize),
+                        );
+                    }
+                    elements
+                })
+            }
+        }
+    }
+}
+
+impl Scalar {
+    fn new(ty: &Type, bits: u32, cast: Cast, span: proc_macro2::Span) -> syn::Result<Self> {
+        let name = match ty {
+            Type::Path(path) if path.qself.is_none() => path.path.get_ident(),
+            _ => None,
+        };
+        let (class, width) = match name.map(|name| name.to_string()).as_deref() {
+            Some("bool") => (Class::Bool, 1),
+            Some("u8") => (Class::Unsigned, 8),
+            Some("u16") => (Class::Unsigned, 16),
+            Some("u32") => (Class::Unsigned, 32),
+            Some("u64") => (Class::Unsigned, 64),
+            Some("i8") => (Class::Signed, 8),
+            Some("i16") => (Class::Signed, 16),
+            Some("i32") => (Class::Signed, 32),
+            Some("i64") => (Class::Signed, 64),
+            Some("f32") => (Class::Float, 32),
+            Some("f64") => (Class::Float, 64),
+            _ => {
+                return Err(Error::new(
+                    ty.span(),
+                    "`bits` only applies to bool, integer and float types",
+                ));
+            }
+        };
+
+        let valid = match class {
+            Class::Bool => bits == 1,
+            Class::Unsigned | Class::Signed => (1..=width).contains(&bits),
+            Class::Float => [16, 32, 64].contains(&bits) && bits <= width,
+        };
+        if !valid {
+            return Err(Error::new(
+                span,
+                format!("`{}` can't be serialized with {} bits", quote!(#ty), bits),
+            ));
+        }
+
+        Ok(Scalar {
+            ty: ty.clone(),
+            width,
+            class,
+            bits,
+            cast,
+        })
+    }
+
+    /// Statements writing `value` to `cursor`.
+    fn serialize(&self, value: &TokenStream2) -> TokenStream2 {
+        let bits = self.bits;
+        let saturated = self.cast == Cast::Saturated && bits < self.width;
+        match self.class {
+            Class::Bool => quote!(cursor.write_bool(#value);),
+            Class::Unsigned => {
+                let value = quote!(::core::convert::From::<_>::from(#value));
+                if saturated {
+                    let max = (1u64 << bits) - 1;
+                    quote!(cursor.write_bits(::core::cmp::min::<u64>(#value, #max), #bits);)
+                } else {
+                    quote!(cursor.write_bits(#value, #bits);)
+                }
+            }
+            Class::Signed => {
+                let value = quote!(<i64 as ::core::convert::From<_>>::from(#value));
+                if saturated {
+                    let min = -(1i64 << (bits - 1));
+                    let max = (1i64 << (bits - 1)) - 1;
+                    quote!(cursor.write_signed(::core::cmp::Ord::clamp(#value, #min, #max), #bits);)
+                } else {
+                    quote!(cursor.write_signed(#value, #bits);)
+                }
+            }
+            Class::Float => {
+                let (write, max) = match bits {
+                    16 => (quote!(write_f16), quote!(65504.0)),
+                    32 => (
+                        quote!(write_f32),
+                        quote!(::core::convert::From::from(f32::MAX)),
+                    ),
+                    _ => (quote!(write_f64), quote!(f64::MAX)),
+                };
+                // Only doubles are ever narrowed
+                let cast = if self.width == 64 && bits < 64 {
+                    quote!(as f32)
+                } else {
+                    quote!()
+                };
+                if saturated {
+                    let ty = &self.ty;
+                    quote! {{
+                        let value: #ty = #value;
+                        let max: #ty = #max;
+                        let value = if value.is_finite() {
+                            value.clamp(-max, max)
+                        } else {
+                            value
+                        };
+                        cursor.#write(value #cast);
+                    }}
+                } else {
+                    quote!(cursor.#write(#value #cast);)
+                }
+            }
+        }
+    }
+
+    /// Expression reading a value from `cursor`.
+    fn deserialize(&self) -> TokenStream2 {
+        let bits = self.bits;
+        let ty = &self.ty;
+        // The cursor reads 64-bit integers, and floats up to the serialized size
+        let cast = match self.class {
+            Class::Float if self.width == 64 && bits < 64 => quote!(as f64),
+            Class::Unsigned | Class::Signed if self.width < 64 => quote!(as #ty),
+            _ => quote!(),
+        };
+        match self.class {
+            Class::Bool => quote!(cursor.read_bool()),
+            Class::Unsigned => quote!(cursor.read_bits(#bits) #cast),
+            Class::Signed => quote!(cursor.read_signed(#bits) #cast),
+            Class::Float => {
+                let read = format_ident!("read_f{}", bits);
+                quote!(cursor.#read() #cast)
+            }
+        }
+    }
+}
+
+/// Element type of a `Vec<T>` or `heapless::Vec<T, N>`.
+fn element_type(ty: &Type) -> syn::Result<&Type> {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                if let Some(syn::GenericArgument::Type(element)) = arguments.args.first() {
+                    return Ok(element);
+                }
+            }
+        }
+    }
+    Err(Error::new(
+        ty.span(),
+        "`capacity` only applies to vectors, like `Vec<T>` or `heapless::Vec<T, N>`",
+    ))
+}
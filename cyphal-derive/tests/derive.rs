@@ -0,0 +1,189 @@
+use cyphal::dsdl::{DataType, Deserialize, DeserializeError, Serialize};
+
+fn serialize<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut buffer = vec![0u8; value.size_bytes()];
+    let len = value.serialize_to_bytes(&mut buffer);
+    assert_eq!(len, buffer.len());
+    buffer
+}
+
+/// `uint4 small`, `int5 signed`, `truncated uint4 wrapped`, `void3`, `bool flag`,
+/// `truncated int3 wrapped_signed`, `uint64 wide`
+#[derive(DataType, Clone, Debug, Default, PartialEq)]
+#[dsdl(sealed)]
+struct Scalars {
+    #[dsdl(bits = 4)]
+    small: u8,
+    #[dsdl(bits = 5, saturated)]
+    signed: i16,
+    #[dsdl(bits = 4, truncated)]
+    wrapped: u32,
+    #[dsdl(void = 3)]
+    _reserved: (),
+    flag: bool,
+    #[dsdl(bits = 3, truncated)]
+    wrapped_signed: i8,
+    wide: u64,
+}
+
+#[test]
+fn scalars() {
+    assert_eq!(Scalars::MAX_SIZE_BITS, 4 + 5 + 4 + 3 + 1 + 3 + 64);
+    assert_eq!(Scalars::EXTENT, 11);
+    assert_eq!(Scalars::EXTENT_BYTES, None);
+
+    let value = Scalars {
+        small: 3,
+        signed: -7,
+        wrapped: 5,
+        flag: true,
+        wrapped_signed: -2,
+        wide: u64::MAX,
+        ..Default::default()
+    };
+    let bytes = serialize(&value);
+    assert_eq!(bytes.len(), 11);
+    assert_eq!(Scalars::deserialize_from_bytes(&bytes), Ok(value));
+
+    // Saturation clamps to the serialized range, truncation drops the upper bits
+    let bytes = serialize(&Scalars {
+        small: 200,
+        signed: -100,
+        wrapped: 0x13,
+        wrapped_signed: 5,
+        ..Default::default()
+    });
+    assert_eq!(
+        Scalars::deserialize_from_bytes(&bytes),
+        Ok(Scalars {
+            small: 15,
+            signed: -16,
+            wrapped: 3,
+            wrapped_signed: -3,
+            ..Default::default()
+        })
+    );
+    assert_eq!(bytes[0], 0x0F);
+}
+
+/// `float16 half`, `float32 single`, `truncated float16 truncated`, `float64 double`
+#[derive(DataType, Debug, Default, PartialEq)]
+#[dsdl(sealed)]
+struct Floats {
+    #[dsdl(bits = 16)]
+    half: f32,
+    #[dsdl(bits = 32)]
+    single: f64,
+    #[dsdl(bits = 16, truncated)]
+    truncated: f64,
+    double: f64,
+}
+
+#[test]
+fn floats() {
+    assert_eq!(Floats::MAX_SIZE_BYTES, 2 + 4 + 2 + 8);
+
+    let value = Floats {
+        half: 1e9,
+        single: -1e300,
+        truncated: 1e9,
+        double: 0.1,
+    };
+    assert_eq!(
+        Floats::deserialize_from_bytes(&serialize(&value)),
+        Ok(Floats {
+            half: 65504.0,
+            single: f32::MIN as f64,
+            truncated: f64::INFINITY,
+            double: 0.1,
+        })
+    );
+}
+
+/// `uint12[2] fixed`, `int3[<=5] dynamic`, `Scalars[<=2] nested`
+#[derive(DataType, Debug, Default, PartialEq)]
+#[dsdl(extent = 64)]
+struct Arrays {
+    #[dsdl(bits = 12)]
+    fixed: [u16; 2],
+    #[dsdl(bits = 3, capacity = 5)]
+    dynamic: Vec<i8>,
+    #[dsdl(capacity = 2)]
+    nested: heapless::Vec<Scalars, 2>,
+}
+
+#[test]
+fn arrays() {
+    // The nested array is aligned before its length prefix
+    assert_eq!(Arrays::MAX_SIZE_BITS, 24 + 8 + 15 + 1 + 8 + 2 * 11 * 8);
+    assert_eq!(Arrays::EXTENT, 64);
+
+    let value = Arrays {
+        fixed: [0xABC, 0x123],
+        dynamic: vec![-1, 2, 3],
+        nested: heapless::Vec::from_slice(&[Scalars {
+            small: 1,
+            ..Default::default()
+        }])
+        .unwrap(),
+    };
+    let bytes = serialize(&value);
+    assert_eq!(bytes[0..3], [0xBC, 0x3A, 0x12]);
+    assert_eq!(bytes[3], 3);
+    assert_eq!(bytes[4..7], [0b1101_0111, 0b0000_0000, 1]);
+    assert_eq!(bytes.len(), 7 + 11);
+    assert_eq!(Arrays::deserialize_from_bytes(&bytes), Ok(value));
+
+    // Lengths past the capacity
+    assert_eq!(
+        Arrays::deserialize_from_bytes(&[0, 0, 0, 6]),
+        Err(DeserializeError::ArrayLength)
+    );
+}
+
+/// Union of `uint8 byte`, `Arrays arrays` and `float16 number`
+#[derive(DataType, Debug, PartialEq)]
+#[dsdl(extent = 128)]
+enum Union {
+    Byte(u8),
+    Arrays(Arrays),
+    Number(#[dsdl(bits = 16)] f32),
+}
+
+/// `Union value`, `bool flag`, `Floats floats`
+#[derive(DataType, Debug, PartialEq)]
+#[dsdl(sealed)]
+struct Outer(Union, bool, Floats);
+
+#[test]
+fn composites() {
+    assert_eq!(
+        Union::MAX_SIZE_BITS,
+        8 + 32 + Arrays::MAX_SIZE_BITS.next_multiple_of(8)
+    );
+    assert_eq!(
+        Outer::MAX_SIZE_BITS,
+        32 + Union::MAX_SIZE_BYTES * 8 + 8 + Floats::MAX_SIZE_BITS
+    );
+
+    let value = Outer(Union::Number(-2.0), true, Floats::default());
+    let bytes = serialize(&value);
+    assert_eq!(bytes[0..8], [3, 0, 0, 0, 2, 0x00, 0xC0, 1]);
+    assert_eq!(Outer::deserialize_from_bytes(&bytes), Ok(value));
+
+    let value = Outer(Union::Arrays(Arrays::default()), false, Floats::default());
+    let bytes = serialize(&value);
+    assert_eq!(bytes[0..6], [10, 0, 0, 0, 1, 5]);
+    assert_eq!(Outer::deserialize_from_bytes(&bytes), Ok(value));
+
+    // Delimited types are extensible: unknown bytes are skipped
+    let bytes = [3, 0, 0, 0, 0, 42, 0xFF, 1];
+    assert_eq!(
+        Outer::deserialize_from_bytes(&bytes),
+        Ok(Outer(Union::Byte(42), true, Floats::default()))
+    );
+    assert_eq!(
+        Outer::deserialize_from_bytes(&[1, 0, 0, 0, 3]),
+        Err(DeserializeError::UnionTag)
+    );
+}
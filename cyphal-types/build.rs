@@ -0,0 +1,14 @@
+//! Generates the types from the definitions in `dsdl/`.
+
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=dsdl");
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("uavcan.rs");
+    if let Err(error) = cyphal_codegen::Generator::new()
+        .namespace("dsdl/uavcan")
+        .write(out)
+    {
+        panic!("{}", error);
+    }
+}
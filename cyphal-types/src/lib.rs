@@ -0,0 +1,31 @@
+//! Standard Cyphal data types, generated from the DSDL definitions in `dsdl/` by
+//! `cyphal-codegen`.
+//!
+//! Covers `uavcan.node` (heartbeat, node info and commands), `uavcan.diagnostic`,
+//! `uavcan.primitive` and `uavcan.si`, along with the `uavcan.time` and `uavcan.file` types
+//! they use. Each type lives in a module named after its version:
+//!
+//! ```
+//! use cyphal::dsdl::{Deserialize, Serialize};
+//! use cyphal_types::uavcan::node::health_1_0::Health;
+//! use cyphal_types::uavcan::node::heartbeat_1_0::{self, Heartbeat};
+//! use cyphal_types::uavcan::node::mode_1_0::Mode;
+//!
+//! let heartbeat = Heartbeat {
+//!     uptime: 10,
+//!     health: Health { value: Health::NOMINAL },
+//!     mode: Mode { value: Mode::OPERATIONAL },
+//!     vendor_specific_status_code: 0,
+//! };
+//! let mut buffer = [0u8; 7];
+//! assert_eq!(heartbeat.serialize_to_bytes(&mut buffer), 7);
+//! assert_eq!(Heartbeat::deserialize_from_bytes(&buffer), Ok(heartbeat));
+//! assert_eq!(heartbeat_1_0::SUBJECT_ID, 7509);
+//! ```
+//!
+//! Services with a fixed ID implement `cyphal::service::Service`, so they can be used with
+//! `Node::send_request` directly.
+
+#![no_std]
+
+include!(concat!(env!("OUT_DIR"), "/uavcan.rs"));
@@ -0,0 +1,260 @@
+//! Serialization of the standard types against byte vectors worked out by hand from the
+//! specification, and against the serialization built into `cyphal`.
+
+use cyphal::dsdl::heapless;
+use cyphal::dsdl::{DataType, Deserialize, Serialize};
+use cyphal::service::Service;
+
+use cyphal_types::uavcan::diagnostic::record_1_1::{self, Record};
+use cyphal_types::uavcan::diagnostic::severity_1_0::Severity;
+use cyphal_types::uavcan::node::execute_command_1_1::{
+    ExecuteCommand, ExecuteCommandRequest, ExecuteCommandResponse,
+};
+use cyphal_types::uavcan::node::get_info_1_0::{GetInfo, GetInfoResponse};
+use cyphal_types::uavcan::node::health_1_0::Health;
+use cyphal_types::uavcan::node::heartbeat_1_0::{self, Heartbeat};
+use cyphal_types::uavcan::node::mode_1_0::Mode;
+use cyphal_types::uavcan::node::version_1_0::Version;
+use cyphal_types::uavcan::primitive;
+use cyphal_types::uavcan::si;
+use cyphal_types::uavcan::time::synchronized_timestamp_1_0::SynchronizedTimestamp;
+
+/// Serializes `value`, checks it against `bytes` and deserializes it back.
+fn round_trip<T: Serialize + Deserialize + PartialEq + core::fmt::Debug>(value: T, bytes: &[u8]) {
+    let mut buffer = vec![0u8; T::MAX_SIZE_BYTES];
+    let len = value.serialize_to_bytes(&mut buffer);
+    assert_eq!(len, value.size_bytes());
+    assert_eq!(buffer[..len], *bytes);
+    assert_eq!(T::deserialize_from_bytes(bytes), Ok(value));
+}
+
+fn vec<T: Clone, const N: usize>(elements: &[T]) -> heapless::Vec<T, N> {
+    heapless::Vec::from_slice(elements).unwrap()
+}
+
+#[test]
+fn heartbeat() {
+    assert_eq!(heartbeat_1_0::SUBJECT_ID, 7509);
+    assert_eq!(Heartbeat::MAX_SIZE_BYTES, 7);
+    assert_eq!(Heartbeat::EXTENT, 12);
+
+    let heartbeat = Heartbeat {
+        uptime: 0x12345678,
+        health: Health {
+            value: Health::CAUTION,
+        },
+        mode: Mode {
+            value: Mode::MAINTENANCE,
+        },
+        vendor_specific_status_code: 0xAB,
+    };
+    let bytes = [0x78, 0x56, 0x34, 0x12, 2, 2, 0xAB];
+    round_trip(heartbeat.clone(), &bytes);
+
+    let builtin = cyphal::heartbeat::Heartbeat {
+        uptime: 0x12345678,
+        health: cyphal::heartbeat::Health::Caution,
+        mode: cyphal::heartbeat::Mode::Maintenance,
+        vendor_specific_status_code: 0xAB,
+    };
+    let mut buffer = [0u8; 7];
+    builtin.serialize(&mut buffer);
+    assert_eq!(buffer, bytes);
+
+    // Newer versions may append fields, older ones are zero-extended
+    assert_eq!(
+        Heartbeat::deserialize_from_bytes(&[0x78, 0x56, 0x34, 0x12, 2, 2, 0xAB, 1, 2, 3]),
+        Ok(heartbeat)
+    );
+    assert_eq!(
+        Heartbeat::deserialize_from_bytes(&[1]),
+        Ok(Heartbeat {
+            uptime: 1,
+            health: Health { value: 0 },
+            mode: Mode { value: 0 },
+            vendor_specific_status_code: 0,
+        })
+    );
+}
+
+#[test]
+fn get_info() {
+    assert_eq!(GetInfo::SERVICE_ID, 430);
+    assert_eq!(GetInfo::REQUEST_MAX_SIZE, 0);
+    assert_eq!(GetInfo::RESPONSE_EXTENT, 448);
+    assert_eq!(GetInfoResponse::MAX_SIZE_BYTES, 313);
+
+    let unique_id = core::array::from_fn(|index| index as u8);
+    let response = GetInfoResponse {
+        protocol_version: Version { major: 1, minor: 0 },
+        hardware_version: Version { major: 2, minor: 1 },
+        software_version: Version { major: 3, minor: 4 },
+        software_vcs_revision_id: 0x0102030405060708,
+        unique_id,
+        name: vec(b"org.example"),
+        software_image_crc: vec(&[0xAA]),
+        certificate_of_authenticity: heapless::Vec::new(),
+    };
+    let mut bytes = vec![1, 0, 2, 1, 3, 4, 8, 7, 6, 5, 4, 3, 2, 1];
+    bytes.extend(unique_id);
+    // The name starts at byte 30
+    bytes.push(11);
+    bytes.extend(b"org.example");
+    bytes.extend([1, 0xAA, 0, 0, 0, 0, 0, 0, 0]);
+    bytes.push(0);
+    round_trip(response.clone(), &bytes);
+    assert_eq!(GetInfo::deserialize_response(&bytes), Some(response));
+
+    let mut builtin = cyphal::get_info::NodeInfo::new("org.example".into(), unique_id);
+    builtin.hardware_version = cyphal::get_info::Version { major: 2, minor: 1 };
+    builtin.software_version = cyphal::get_info::Version { major: 3, minor: 4 };
+    builtin.software_vcs_revision_id = 0x0102030405060708;
+    builtin.software_image_crc = Some(0xAA);
+    let mut buffer = [0u8; cyphal::get_info::NodeInfo::MAX_SIZE];
+    let len = builtin.serialize(&mut buffer);
+    assert_eq!(buffer[..len], bytes);
+}
+
+#[test]
+fn execute_command() {
+    assert_eq!(ExecuteCommand::SERVICE_ID, 435);
+    assert_eq!(ExecuteCommand::REQUEST_MAX_SIZE, 2 + 1 + 255);
+    assert_eq!(ExecuteCommand::RESPONSE_EXTENT, 48);
+
+    round_trip(
+        ExecuteCommandRequest {
+            command: ExecuteCommandRequest::COMMAND_RESTART,
+            parameter: heapless::Vec::new(),
+        },
+        &[0xFF, 0xFF, 0],
+    );
+    let request = ExecuteCommandRequest {
+        command: ExecuteCommandRequest::COMMAND_BEGIN_SOFTWARE_UPDATE,
+        parameter: vec(b"/fw.bin"),
+    };
+    let mut buffer = [0u8; ExecuteCommand::REQUEST_MAX_SIZE];
+    let len = ExecuteCommand::serialize_request(&request, &mut buffer);
+    assert_eq!(buffer[..len], *b"\xFD\xFF\x07/fw.bin");
+
+    round_trip(
+        ExecuteCommandResponse {
+            status: ExecuteCommandResponse::STATUS_BAD_COMMAND,
+        },
+        &[3],
+    );
+}
+
+#[test]
+fn diagnostic_record() {
+    assert_eq!(record_1_1::SUBJECT_ID, 8184);
+    assert_eq!(Record::MAX_SIZE_BYTES, 7 + 1 + 1 + 255);
+    assert_eq!(Record::EXTENT, 300);
+
+    round_trip(
+        Record {
+            timestamp: SynchronizedTimestamp {
+                microsecond: 0x123456789ABCDE,
+            },
+            severity: Severity {
+                value: Severity::WARNING,
+            },
+            text: vec(b"hi"),
+        },
+        &[0xDE, 0xBC, 0x9A, 0x78, 0x56, 0x34, 0x12, 4, 2, b'h', b'i'],
+    );
+
+    // The timestamp is truncated to 56 bits
+    let mut buffer = [0u8; 7];
+    SynchronizedTimestamp {
+        microsecond: u64::MAX,
+    }
+    .serialize_to_bytes(&mut buffer);
+    assert_eq!(
+        SynchronizedTimestamp::deserialize_from_bytes(&buffer),
+        Ok(SynchronizedTimestamp {
+            microsecond: (1 << 56) - 1
+        })
+    );
+}
+
+#[test]
+fn primitives() {
+    use primitive::{array, scalar};
+
+    round_trip(primitive::empty_1_0::Empty {}, &[]);
+    round_trip(
+        primitive::string_1_0::String { value: vec(b"hi") },
+        &[2, 0, b'h', b'i'],
+    );
+    round_trip(
+        primitive::unstructured_1_0::Unstructured {
+            value: vec(&[0xAA]),
+        },
+        &[1, 0, 0xAA],
+    );
+
+    round_trip(scalar::bit_1_0::Bit { value: true }, &[1]);
+    round_trip(scalar::integer8_1_0::Integer8 { value: -2 }, &[0xFE]);
+    round_trip(
+        scalar::natural32_1_0::Natural32 { value: 0x01020304 },
+        &[4, 3, 2, 1],
+    );
+    round_trip(scalar::real16_1_0::Real16 { value: 1.0 }, &[0x00, 0x3C]);
+    round_trip(
+        scalar::real64_1_0::Real64 { value: -2.0 },
+        &[0, 0, 0, 0, 0, 0, 0, 0xC0],
+    );
+
+    round_trip(
+        array::bit_1_0::Bit {
+            value: vec(&[true, false, true]),
+        },
+        &[3, 0, 0b101],
+    );
+    round_trip(
+        array::natural16_1_0::Natural16 {
+            value: vec(&[0x1234]),
+        },
+        &[1, 0x34, 0x12],
+    );
+    round_trip(
+        array::integer64_1_0::Integer64 { value: vec(&[-1]) },
+        &[1, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+    );
+
+    // Every array fits in 256 bytes plus its length prefix
+    assert_eq!(array::bit_1_0::Bit::MAX_SIZE_BYTES, 258);
+    assert_eq!(array::natural8_1_0::Natural8::MAX_SIZE_BYTES, 258);
+    assert_eq!(array::real16_1_0::Real16::MAX_SIZE_BYTES, 257);
+    assert_eq!(array::real64_1_0::Real64::MAX_SIZE_BYTES, 257);
+}
+
+#[test]
+fn si() {
+    round_trip(
+        si::sample::length::scalar_1_0::Scalar {
+            timestamp: SynchronizedTimestamp { microsecond: 1 },
+            meter: 1.0,
+        },
+        &[1, 0, 0, 0, 0, 0, 0, 0x00, 0x00, 0x80, 0x3F],
+    );
+    round_trip(
+        si::unit::velocity::vector3_1_0::Vector3 {
+            meter_per_second: [1.0, -2.0, 0.5],
+        },
+        &[0, 0, 0x80, 0x3F, 0, 0, 0, 0xC0, 0, 0, 0, 0x3F],
+    );
+    round_trip(
+        si::unit::duration::wide_scalar_1_0::WideScalar { second: 1.0 },
+        &[0, 0, 0, 0, 0, 0, 0xF0, 0x3F],
+    );
+
+    assert_eq!(
+        si::unit::angle::quaternion_1_0::Quaternion::MAX_SIZE_BYTES,
+        16
+    );
+    assert_eq!(
+        si::sample::temperature::scalar_1_0::Scalar::MAX_SIZE_BYTES,
+        7 + 4
+    );
+}
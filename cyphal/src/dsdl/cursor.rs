@@ -0,0 +1,413 @@
+//! Bit-level cursors over serialized DSDL objects.
+
+use super::DeserializeError;
+
+/// Writes values into a buffer, least significant bit first.
+///
+/// Writing past the end of the buffer panics, callers are expected to size the buffer from
+/// [`super::Serialize::size_bytes`] or the type's maximum size.
+#[derive(Debug)]
+pub struct WriteCursor<'a> {
+    buffer: &'a mut [u8],
+    /// Bits written so far
+    offset: usize,
+}
+
+impl<'a> WriteCursor<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, offset: 0 }
+    }
+
+    /// Number of bits written so far.
+    pub fn offset_bits(&self) -> usize {
+        self.offset
+    }
+
+    /// Number of bytes touched so far, counting a partially written byte.
+    pub fn len_bytes(&self) -> usize {
+        self.offset.div_ceil(8)
+    }
+
+    /// Writes the `bits` least significant bits of `value`, dropping the rest.
+    ///
+    /// # Panics
+    /// If `bits` is over 64.
+    pub fn write_bits(&mut self, mut value: u64, bits: u32) {
+        assert!(bits <= 64);
+        let mut remaining = bits as usize;
+        while remaining > 0 {
+            let byte = self.offset / 8;
+            let shift = self.offset % 8;
+            let chunk = core::cmp::min(8 - shift, remaining);
+            let mask = ((1u16 << chunk) - 1) as u8;
+
+            self.buffer[byte] =
+                (self.buffer[byte] & !(mask << shift)) | ((value as u8 & mask) << shift);
+            value = value.checked_shr(chunk as u32).unwrap_or(0);
+            remaining -= chunk;
+            self.offset += chunk;
+        }
+    }
+
+    /// Writes `bits` zero bits, as for void fields.
+    pub fn write_padding(&mut self, bits: usize) {
+        for _ in 0..bits / 64 {
+            self.write_bits(0, 64);
+        }
+        self.write_bits(0, (bits % 64) as u32);
+    }
+
+    /// Pads up to the next byte boundary.
+    pub fn align(&mut self) {
+        self.write_padding(self.offset.next_multiple_of(8) - self.offset);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bits(value as u64, 1);
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.write_bits(value as u64, 8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_bits(value as u64, 16);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write_bits(value as u64, 32);
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bits(value, 64);
+    }
+
+    /// Writes the `bits` least significant bits of a two's complement value.
+    pub fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write_bits(value as u64, bits);
+    }
+
+    /// Writes a value as a `float16`, rounding to the nearest representable value. Values too
+    /// large for it become infinite.
+    pub fn write_f16(&mut self, value: f32) {
+        self.write_u16(f32_to_f16(value));
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    /// Writes the length prefix of a variable-length array of the given capacity.
+    pub fn write_array_length(&mut self, len: usize, capacity: usize) {
+        debug_assert!(len <= capacity);
+        self.write_bits(len as u64, super::array_length_bits(capacity));
+    }
+
+    /// Writes the tag of a union with the given number of variants.
+    pub fn write_union_tag(&mut self, tag: usize, variants: usize) {
+        debug_assert!(tag < variants);
+        self.write_bits(tag as u64, super::union_tag_bits(variants));
+    }
+}
+
+/// Reads values from a buffer, least significant bit first.
+///
+/// Reading past the end of the buffer yields zeros, which implements the implicit zero
+/// extension of DSDL. Bytes left over once an object is read are ignored, which implements
+/// implicit truncation.
+#[derive(Clone, Debug)]
+pub struct ReadCursor<'a> {
+    buffer: &'a [u8],
+    /// Bits read so far
+    offset: usize,
+}
+
+impl<'a> ReadCursor<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, offset: 0 }
+    }
+
+    /// Number of bits read so far.
+    pub fn offset_bits(&self) -> usize {
+        self.offset
+    }
+
+    /// Reads `bits` bits into the least significant bits of the result.
+    ///
+    /// # Panics
+    /// If `bits` is over 64.
+    pub fn read_bits(&mut self, bits: u32) -> u64 {
+        assert!(bits <= 64);
+        let mut value = 0u64;
+        let mut read = 0;
+        while read < bits as usize {
+            let byte = self.buffer.get(self.offset / 8).copied().unwrap_or(0);
+            let shift = self.offset % 8;
+            let chunk = core::cmp::min(8 - shift, bits as usize - read);
+            let mask = ((1u16 << chunk) - 1) as u8;
+
+            value |= (((byte >> shift) & mask) as u64) << read;
+            read += chunk;
+            self.offset += chunk;
+        }
+        value
+    }
+
+    /// Skips `bits` bits, as for void fields.
+    pub fn skip(&mut self, bits: usize) {
+        self.offset += bits;
+    }
+
+    /// Skips up to the next byte boundary.
+    pub fn align(&mut self) {
+        self.offset = self.offset.next_multiple_of(8);
+    }
+
+    pub fn read_bool(&mut self) -> bool {
+        self.read_bits(1) != 0
+    }
+
+    pub fn read_u8(&mut self) -> u8 {
+        self.read_bits(8) as u8
+    }
+
+    pub fn read_u16(&mut self) -> u16 {
+        self.read_bits(16) as u16
+    }
+
+    pub fn read_u32(&mut self) -> u32 {
+        self.read_bits(32) as u32
+    }
+
+    pub fn read_u64(&mut self) -> u64 {
+        self.read_bits(64)
+    }
+
+    /// Reads a two's complement value of `bits` bits, sign-extending it.
+    pub fn read_signed(&mut self, bits: u32) -> i64 {
+        let value = self.read_bits(bits);
+        match bits {
+            0 => 0,
+            _ => ((value << (64 - bits)) as i64) >> (64 - bits),
+        }
+    }
+
+    /// Reads a `float16`, which `f32` represents exactly.
+    pub fn read_f16(&mut self) -> f32 {
+        f16_to_f32(self.read_u16())
+    }
+
+    pub fn read_f32(&mut self) -> f32 {
+        f32::from_bits(self.read_u32())
+    }
+
+    pub fn read_f64(&mut self) -> f64 {
+        f64::from_bits(self.read_u64())
+    }
+
+    /// Reads the length prefix of a variable-length array of the given capacity.
+    pub fn read_array_length(&mut self, capacity: usize) -> Result<usize, DeserializeError> {
+        let len = self.read_bits(super::array_length_bits(capacity));
+        if len > capacity as u64 {
+            Err(DeserializeError::ArrayLength)
+        } else {
+            Ok(len as usize)
+        }
+    }
+
+    /// Reads the tag of a union with the given number of variants.
+    pub fn read_union_tag(&mut self, variants: usize) -> Result<usize, DeserializeError> {
+        let tag = self.read_bits(super::union_tag_bits(variants));
+        if tag >= variants as u64 {
+            Err(DeserializeError::UnionTag)
+        } else {
+            Ok(tag as usize)
+        }
+    }
+
+    /// Splits off the next `len` bytes into a cursor of their own, for a delimited object.
+    ///
+    /// The cursor must be byte-aligned. Reading past the end of the new cursor yields zeros
+    /// rather than the bytes that follow.
+    pub fn take_bytes(&mut self, len: usize) -> Result<ReadCursor<'a>, DeserializeError> {
+        debug_assert_eq!(self.offset % 8, 0);
+        let start = self.offset / 8;
+        // The length comes off the wire, so it may be anything
+        let bytes = start
+            .checked_add(len)
+            .and_then(|end| self.buffer.get(start..end))
+            .ok_or(DeserializeError::DelimiterHeader)?;
+        self.offset += len * 8;
+        Ok(ReadCursor::new(bytes))
+    }
+}
+
+/// Converts to the bits of a half-precision float, rounding to nearest, ties to even.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exponent == 0xFF {
+        // Infinity, or NaN which keeps a mantissa bit set
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+    // Mantissa with its implicit bit for subnormal results, exponent and mantissa otherwise,
+    // shifted down into place
+    let (full, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        (mantissa | 0x80_0000, (14 - exponent) as u32)
+    } else {
+        (((exponent as u32) << 23) | mantissa, 13)
+    };
+
+    let mut result = full >> shift;
+    let remainder = full & ((1 << shift) - 1);
+    let midpoint = 1 << (shift - 1);
+    if remainder > midpoint || (remainder == midpoint && result & 1 != 0) {
+        // Carries into the exponent as needed, up to infinity
+        result += 1;
+    }
+    sign | result as u16
+}
+
+/// Converts from the bits of a half-precision float.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1F) as i32;
+    let mantissa = (bits & 0x3FF) as u32;
+
+    match exponent {
+        0 => sign * mantissa as f32 / (1u32 << 24) as f32,
+        0x1F if mantissa == 0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => f32::from_bits(
+            (bits as u32 & 0x8000) << 16 | ((exponent + 127 - 15) as u32) << 23 | mantissa << 13,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_packing() {
+        let mut buffer = [0xFFu8; 6];
+        let mut writer = WriteCursor::new(&mut buffer);
+        writer.write_bits(0b101, 3);
+        writer.write_bool(true);
+        writer.write_bits(0x1ABC, 12);
+        writer.write_signed(-2, 5);
+        writer.align();
+        writer.write_u16(0x1234);
+        assert_eq!(writer.offset_bits(), 40);
+        assert_eq!(writer.len_bytes(), 5);
+        // Values are packed least significant bit first, padding is cleared
+        assert_eq!(buffer, [0b1100_1101, 0xAB, 0b0001_1110, 0x34, 0x12, 0xFF]);
+
+        let mut reader = ReadCursor::new(&buffer);
+        assert_eq!(reader.read_bits(3), 0b101);
+        assert!(reader.read_bool());
+        assert_eq!(reader.read_bits(12), 0xABC);
+        assert_eq!(reader.read_signed(5), -2);
+        reader.align();
+        assert_eq!(reader.read_u16(), 0x1234);
+        assert_eq!(reader.read_u8(), 0xFF);
+        // Zero extension
+        assert_eq!(reader.read_u32(), 0);
+        assert_eq!(reader.read_signed(7), 0);
+    }
+
+    #[test]
+    fn unaligned_wide_values() {
+        let mut buffer = [0u8; 17];
+        let mut writer = WriteCursor::new(&mut buffer);
+        writer.write_bits(1, 1);
+        writer.write_u64(0x8123_4567_89AB_CDEF);
+        writer.write_f64(-1.5);
+        writer.write_padding(7);
+        assert_eq!(writer.len_bytes(), 17);
+
+        let mut reader = ReadCursor::new(&buffer);
+        assert_eq!(reader.read_bits(1), 1);
+        assert_eq!(reader.read_u64(), 0x8123_4567_89AB_CDEF);
+        assert_eq!(reader.read_f64(), -1.5);
+    }
+
+    #[test]
+    fn half_precision() {
+        for (value, bits) in [
+            (0.0, 0x0000),
+            (-2.0, 0xC000),
+            (1.0 / 3.0, 0x3555),
+            (65504.0, 0x7BFF),
+            (f32::INFINITY, 0x7C00),
+            // Smallest subnormal
+            (5.960_464_5e-8, 0x0001),
+        ] {
+            let mut buffer = [0u8; 2];
+            WriteCursor::new(&mut buffer).write_f16(value);
+            assert_eq!(u16::from_le_bytes(buffer), bits, "{}", value);
+            if bits != 0x3555 {
+                assert_eq!(ReadCursor::new(&buffer).read_f16(), value);
+            }
+        }
+
+        // Rounding, ties to even, and overflow
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3C00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3C02);
+        assert_eq!(f32_to_f16(65520.0), 0x7C00);
+        assert_eq!(f32_to_f16(-1e-10), 0x8000);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        assert_eq!(f16_to_f32(0x0200), 3.051_757_8e-5);
+    }
+
+    #[test]
+    fn prefixes() {
+        let mut buffer = [0u8; 3];
+        let mut writer = WriteCursor::new(&mut buffer);
+        writer.write_array_length(3, 255);
+        writer.write_array_length(300, 300);
+        assert_eq!(buffer, [3, 0x2C, 0x01]);
+
+        let mut reader = ReadCursor::new(&buffer);
+        assert_eq!(
+            reader.read_array_length(2),
+            Err(DeserializeError::ArrayLength)
+        );
+        assert_eq!(reader.read_array_length(300), Ok(300));
+        assert_eq!(
+            ReadCursor::new(&[5]).read_union_tag(5),
+            Err(DeserializeError::UnionTag)
+        );
+
+        let mut reader = ReadCursor::new(&[1, 2, 3]);
+        reader.skip(8);
+        let mut nested = reader.take_bytes(1).unwrap();
+        assert_eq!(nested.read_u16(), 2);
+        assert_eq!(reader.read_u8(), 3);
+        assert_eq!(
+            reader.take_bytes(1).err(),
+            Some(DeserializeError::DelimiterHeader)
+        );
+        let mut reader = ReadCursor::new(&[1, 2, 3]);
+        reader.skip(8);
+        assert_eq!(
+            reader.take_bytes(usize::MAX).err(),
+            Some(DeserializeError::DelimiterHeader)
+        );
+    }
+}
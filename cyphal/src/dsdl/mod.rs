@@ -0,0 +1,469 @@
+//! Serialization of DSDL objects, following the Cyphal v1 rules.
+//!
+//! Values are packed least significant bit first and multi-byte values are little-endian.
+//! Composite types start on a byte boundary and are padded out to one. Delimited composites,
+//! i.e. those that aren't `@sealed`, carry a 32-bit delimiter header with their size in bytes
+//! when nested in another composite, so that receivers with an older version of the type can
+//! skip fields they don't know about. Top-level objects never carry a delimiter header.
+//!
+//! Receivers zero-extend payloads that are shorter than they expect and ignore bytes past what
+//! they expect, see [`ReadCursor`].
+//!
+//! Objects plug directly into the transfer callbacks:
+//!
+//! ```
+//! use cyphal::dsdl::{Deserialize, Serialize};
+//! use cyphal::transfer::TransferManager;
+//! use cyphal::transport::Transport;
+//! use cyphal::{Node, Priority, TransmissionType};
+//!
+//! type Message = [u16; 3];
+//!
+//! fn publish<M, T, C>(
+//!     node: &mut Node<M, T, C>,
+//!     deadline: embedded_time::Instant<C>,
+//!     message: &Message,
+//! ) -> Option<M::TxTransferToken>
+//! where
+//!     M: TransferManager<C, T>,
+//!     T: Transport<C>,
+//!     C: embedded_time::Clock + Clone,
+//! {
+//!     node.start_tx_transfer(
+//!         message.size_bytes(),
+//!         deadline,
+//!         Priority::Nominal,
+//!         100,
+//!         TransmissionType::Broadcast,
+//!         |buf| Ok::<_, ()>(message.serialize_to_bytes(buf)),
+//!     )
+//!     .ok()
+//! }
+//!
+//! fn receive<M, T, C>(node: &mut Node<M, T, C>, token: M::RxTransferToken) -> Option<Message>
+//! where
+//!     M: TransferManager<C, T>,
+//!     T: Transport<C>,
+//!     C: embedded_time::Clock + Clone,
+//! {
+//!     let mut message = None;
+//!     node.transfer_manager
+//!         .with_rx_transfer(token, |_, payload| {
+//!             message = Message::deserialize_from_bytes(payload).ok();
+//!         })
+//!         .ok()?;
+//!     message
+//! }
+//! ```
+
+mod cursor;
+mod primitive;
+
+pub use cursor::{ReadCursor, WriteCursor};
+/// Derives [`DataType`], [`Serialize`] and [`Deserialize`], see the `cyphal-derive` crate.
+#[cfg(feature = "derive")]
+pub use cyphal_derive::DataType;
+/// Variable-length arrays are `heapless::Vec`s, re-exported for generated code.
+pub use heapless;
+
+/// Errors from deserializing an object that no valid object serializes to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DeserializeError {
+    /// A variable-length array is longer than its capacity
+    ArrayLength,
+    /// A union tag is past the last variant
+    UnionTag,
+    /// A delimiter header gives more bytes than are left in the payload
+    DelimiterHeader,
+}
+
+/// Properties of a DSDL type shared by serialization and deserialization.
+pub trait DataType {
+    /// Extent in bytes of a delimited composite type, `None` for sealed types and for
+    /// primitives and arrays, which are never delimited.
+    const EXTENT_BYTES: Option<usize>;
+
+    /// Maximum size of a serialized object in bits, without a delimiter header or trailing
+    /// padding. For arrays, this is the size when starting at their alignment.
+    const MAX_SIZE_BITS: usize;
+
+    /// Alignment in bits: 8 for composite types, 1 for primitives and that of their elements
+    /// for arrays.
+    const ALIGNMENT_BITS: usize;
+
+    /// Maximum size of a serialized object in bytes, to size buffers.
+    const MAX_SIZE_BYTES: usize = Self::MAX_SIZE_BITS.div_ceil(8);
+
+    /// Bytes of a received object that are kept, to use as the extent of subscriptions. For
+    /// sealed types this is their maximum size.
+    const EXTENT: usize = match Self::EXTENT_BYTES {
+        Some(extent) => extent,
+        None => Self::MAX_SIZE_BYTES,
+    };
+}
+
+/// Maximum offset in bits at which a field of type `T` ends, when it starts at `offset_bits`
+/// at most. Used to compute [`DataType::MAX_SIZE_BITS`] of composite types.
+pub const fn max_field_end_bits<T: DataType>(offset_bits: usize) -> usize {
+    let start = offset_bits.next_multiple_of(T::ALIGNMENT_BITS);
+    if T::EXTENT_BYTES.is_some() {
+        start + 32 + T::MAX_SIZE_BITS.next_multiple_of(8)
+    } else {
+        start + T::MAX_SIZE_BITS.next_multiple_of(T::ALIGNMENT_BITS)
+    }
+}
+
+/// A DSDL object that can be serialized.
+///
+/// Composite types implement [`Serialize::serialize`] and [`Serialize::size_bits`]; the field
+/// methods add the alignment and delimiter header a composite needs when nested in another one.
+/// Primitives and arrays override the field methods, as they are neither aligned nor delimited.
+pub trait Serialize: DataType {
+    /// Size of the serialized object in bits, without a delimiter header or trailing padding.
+    fn size_bits(&self) -> usize;
+
+    /// Writes the object at the cursor.
+    fn serialize(&self, cursor: &mut WriteCursor<'_>);
+
+    /// Size in bits of the object serialized as a field starting at `offset_bits`, including
+    /// padding and delimiter header.
+    fn field_size_bits(&self, offset_bits: usize) -> usize {
+        let header = if Self::EXTENT_BYTES.is_some() { 32 } else { 0 };
+        let padding = offset_bits.next_multiple_of(8) - offset_bits;
+        padding + header + self.size_bits().next_multiple_of(8)
+    }
+
+    /// Writes the object as a field of a composite.
+    fn serialize_field(&self, cursor: &mut WriteCursor<'_>) {
+        cursor.align();
+        if Self::EXTENT_BYTES.is_some() {
+            cursor.write_u32(self.size_bits().div_ceil(8) as u32);
+        }
+        self.serialize(cursor);
+        cursor.align();
+    }
+
+    /// Size of the serialized object in bytes, to size the buffer for
+    /// [`Serialize::serialize_to_bytes`].
+    fn size_bytes(&self) -> usize {
+        self.size_bits().div_ceil(8)
+    }
+
+    /// Serializes the object as the payload of a transfer into the start of `buffer`,
+    /// returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`Serialize::size_bytes`].
+    fn serialize_to_bytes(&self, buffer: &mut [u8]) -> usize {
+        let mut cursor = WriteCursor::new(buffer);
+        self.serialize(&mut cursor);
+        cursor.align();
+        cursor.len_bytes()
+    }
+}
+
+/// A DSDL object that can be deserialized.
+///
+/// As with [`Serialize`], composite types implement [`Deserialize::deserialize`] and
+/// primitives and arrays override [`Deserialize::deserialize_field`].
+pub trait Deserialize: DataType + Sized {
+    /// Reads the object at the cursor.
+    fn deserialize(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError>;
+
+    /// Reads the object as a field of a composite.
+    ///
+    /// The delimiter header of a delimited type bounds it, so fields added in a newer version
+    /// of the type are skipped and fields missing from an older version are zero-extended.
+    fn deserialize_field(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+        cursor.align();
+        if Self::EXTENT_BYTES.is_some() {
+            let len = usize::try_from(cursor.read_u32())
+                .map_err(|_| DeserializeError::DelimiterHeader)?;
+            Self::deserialize(&mut cursor.take_bytes(len)?)
+        } else {
+            let value = Self::deserialize(cursor)?;
+            cursor.align();
+            Ok(value)
+        }
+    }
+
+    /// Deserializes the object from the payload of a transfer.
+    fn deserialize_from_bytes(buffer: &[u8]) -> Result<Self, DeserializeError> {
+        Self::deserialize(&mut ReadCursor::new(buffer))
+    }
+}
+
+/// Bits in the length prefix of a variable-length array with the given capacity.
+pub const fn array_length_bits(capacity: usize) -> u32 {
+    standard_bits(capacity as u64)
+}
+
+/// Bits in the tag of a union with the given number of variants.
+pub const fn union_tag_bits(variants: usize) -> u32 {
+    standard_bits(variants.saturating_sub(1) as u64)
+}
+
+/// Smallest of the standard 8, 16, 32 and 64 bit widths that fits `max`.
+const fn standard_bits(max: u64) -> u32 {
+    match u64::BITS - max.leading_zeros() {
+        0..=8 => 8,
+        9..=16 => 16,
+        17..=32 => 32,
+        _ => 64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `uint3 a`, `int12 b`, `bool[<=3] c`, sealed
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct Sealed {
+        a: u8,
+        b: i16,
+        c: heapless::Vec<bool, 3>,
+    }
+
+    impl DataType for Sealed {
+        const EXTENT_BYTES: Option<usize> = None;
+        const MAX_SIZE_BITS: usize = max_field_end_bits::<heapless::Vec<bool, 3>>(15);
+        const ALIGNMENT_BITS: usize = 8;
+    }
+
+    impl Serialize for Sealed {
+        fn size_bits(&self) -> usize {
+            3 + 12 + self.c.field_size_bits(15)
+        }
+
+        fn serialize(&self, cursor: &mut WriteCursor<'_>) {
+            cursor.write_bits(self.a as u64, 3);
+            cursor.write_signed(self.b as i64, 12);
+            self.c.serialize_field(cursor);
+        }
+    }
+
+    impl Deserialize for Sealed {
+        fn deserialize(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+            Ok(Self {
+                a: cursor.read_bits(3) as u8,
+                b: cursor.read_signed(12) as i16,
+                c: Deserialize::deserialize_field(cursor)?,
+            })
+        }
+    }
+
+    /// Union of `uint16 number` and `Sealed nested`, delimited with `@extent 16 * 8`
+    #[derive(Clone, Debug, PartialEq)]
+    enum Delimited {
+        Number(u16),
+        Nested(Sealed),
+    }
+
+    impl DataType for Delimited {
+        const EXTENT_BYTES: Option<usize> = Some(16);
+        const MAX_SIZE_BITS: usize = 8 + Sealed::MAX_SIZE_BITS.next_multiple_of(8);
+        const ALIGNMENT_BITS: usize = 8;
+    }
+
+    impl Serialize for Delimited {
+        fn size_bits(&self) -> usize {
+            8 + match self {
+                Delimited::Number(number) => number.field_size_bits(8),
+                Delimited::Nested(nested) => nested.field_size_bits(8),
+            }
+        }
+
+        fn serialize(&self, cursor: &mut WriteCursor<'_>) {
+            match self {
+                Delimited::Number(number) => {
+                    cursor.write_union_tag(0, 2);
+                    number.serialize_field(cursor);
+                }
+                Delimited::Nested(nested) => {
+                    cursor.write_union_tag(1, 2);
+                    nested.serialize_field(cursor);
+                }
+            }
+        }
+    }
+
+    impl Deserialize for Delimited {
+        fn deserialize(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+            Ok(match cursor.read_union_tag(2)? {
+                0 => Delimited::Number(Deserialize::deserialize_field(cursor)?),
+                _ => Delimited::Nested(Deserialize::deserialize_field(cursor)?),
+            })
+        }
+    }
+
+    /// `Delimited[2] pair`, `bool flag`
+    #[derive(Clone, Debug, PartialEq)]
+    struct Outer {
+        pair: [Delimited; 2],
+        flag: bool,
+    }
+
+    impl DataType for Outer {
+        const EXTENT_BYTES: Option<usize> = None;
+        const MAX_SIZE_BITS: usize =
+            max_field_end_bits::<bool>(max_field_end_bits::<[Delimited; 2]>(0));
+        const ALIGNMENT_BITS: usize = 8;
+    }
+
+    impl Serialize for Outer {
+        fn size_bits(&self) -> usize {
+            let bits = self.pair.field_size_bits(0);
+            bits + self.flag.field_size_bits(bits)
+        }
+
+        fn serialize(&self, cursor: &mut WriteCursor<'_>) {
+            self.pair.serialize_field(cursor);
+            self.flag.serialize_field(cursor);
+        }
+    }
+
+    impl Deserialize for Outer {
+        fn deserialize(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+            Ok(Self {
+                pair: Deserialize::deserialize_field(cursor)?,
+                flag: Deserialize::deserialize_field(cursor)?,
+            })
+        }
+    }
+
+    #[test]
+    fn composites() {
+        let sealed = Sealed {
+            a: 5,
+            b: -3,
+            c: heapless::Vec::from_slice(&[true, false, true]).unwrap(),
+        };
+        let mut buffer = [0u8; 32];
+        assert_eq!(sealed.size_bytes(), 4);
+        assert_eq!(sealed.serialize_to_bytes(&mut buffer), 4);
+        // The array starts right after the 15 bits before it, the length prefix isn't aligned
+        assert_eq!(
+            buffer[0..4],
+            [0b1110_1101, 0b1111_1111, 0b1000_0001, 0b0000_0010]
+        );
+        assert_eq!(
+            Sealed::deserialize_from_bytes(&buffer[0..4]),
+            Ok(sealed.clone())
+        );
+
+        let outer = Outer {
+            pair: [Delimited::Number(0x1234), Delimited::Nested(sealed.clone())],
+            flag: true,
+        };
+        let len = outer.serialize_to_bytes(&mut buffer);
+        assert_eq!(len, outer.size_bytes());
+        assert_eq!(
+            buffer[0..len],
+            [
+                3,
+                0,
+                0,
+                0,
+                0,
+                0x34,
+                0x12, // Delimiter header, tag, number
+                5,
+                0,
+                0,
+                0,
+                1,
+                0b1110_1101,
+                0b1111_1111,
+                0b1000_0001,
+                0b0000_0010, // Nested
+                1,           // Flag
+            ]
+        );
+        assert_eq!(Outer::deserialize_from_bytes(&buffer[0..len]), Ok(outer));
+
+        // Arrays of composites are aligned before their length prefix
+        let elements = heapless::Vec::<Sealed, 2>::from_slice(&[sealed]).unwrap();
+        let mut cursor = WriteCursor::new(&mut buffer);
+        true.serialize_field(&mut cursor);
+        elements.serialize_field(&mut cursor);
+        assert_eq!(elements.field_size_bits(1), 7 + 8 + 32);
+        assert_eq!(cursor.offset_bits(), 48);
+        assert_eq!(buffer[0..3], [1, 1, 0b1110_1101]);
+        let mut cursor = ReadCursor::new(&buffer);
+        assert_eq!(bool::deserialize_field(&mut cursor), Ok(true));
+        assert_eq!(Deserialize::deserialize_field(&mut cursor), Ok(elements));
+
+        assert_eq!(Sealed::MAX_SIZE_BITS, 26);
+        assert_eq!(Sealed::EXTENT, 4);
+        assert_eq!(Delimited::EXTENT, 16);
+        assert_eq!(<[Delimited; 2]>::MAX_SIZE_BITS, 2 * (32 + 40));
+        assert_eq!(Outer::MAX_SIZE_BYTES, 2 * (4 + 5) + 1);
+    }
+
+    #[test]
+    fn extensibility() {
+        // A newer version of the first element adds a byte, which is skipped
+        let payload = [
+            4, 0, 0, 0, 0, 0x34, 0x12, 0xAA, 3, 0, 0, 0, 0, 0x78, 0x56, 1,
+        ];
+        assert_eq!(
+            Outer::deserialize_from_bytes(&payload),
+            Ok(Outer {
+                pair: [Delimited::Number(0x1234), Delimited::Number(0x5678)],
+                flag: true,
+            })
+        );
+
+        // An older version of the first element lacks a byte, which is zero-extended
+        let payload = [2, 0, 0, 0, 0, 0x34, 3, 0, 0, 0, 0, 0x78, 0x56, 1];
+        assert_eq!(
+            Outer::deserialize_from_bytes(&payload),
+            Ok(Outer {
+                pair: [Delimited::Number(0x34), Delimited::Number(0x5678)],
+                flag: true,
+            })
+        );
+
+        // Truncated payloads are zero-extended, excess bytes are ignored
+        assert_eq!(
+            Sealed::deserialize_from_bytes(&[0b1110_1101]),
+            Ok(Sealed {
+                a: 5,
+                b: 0x1D,
+                c: heapless::Vec::new(),
+            })
+        );
+        assert_eq!(
+            Sealed::deserialize_from_bytes(&[0, 0, 0, 0, 0xFF]),
+            Ok(Sealed::default())
+        );
+
+        // Malformed payloads
+        let payload = [4, 0, 0, 0, 0, 0x34, 0x12];
+        assert_eq!(
+            Outer::deserialize_from_bytes(&payload),
+            Err(DeserializeError::DelimiterHeader)
+        );
+        let payload = [1, 0, 0, 0, 2];
+        assert_eq!(
+            Outer::deserialize_from_bytes(&payload),
+            Err(DeserializeError::UnionTag)
+        );
+        assert_eq!(
+            Sealed::deserialize_from_bytes(&[0, 0, 0b0000_0010]),
+            Err(DeserializeError::ArrayLength)
+        );
+    }
+
+    #[test]
+    fn prefix_widths() {
+        assert_eq!(array_length_bits(0), 8);
+        assert_eq!(array_length_bits(255), 8);
+        assert_eq!(array_length_bits(256), 16);
+        assert_eq!(array_length_bits(65536), 32);
+        assert_eq!(union_tag_bits(2), 8);
+        assert_eq!(union_tag_bits(256), 8);
+        assert_eq!(union_tag_bits(257), 16);
+    }
+}
@@ -0,0 +1,214 @@
+//! Implementations for primitive types, at their natural bit length, and arrays.
+//!
+//! Fields with other bit lengths, like `uint3`, are written through the cursor directly.
+
+use super::{DataType, Deserialize, DeserializeError, ReadCursor, Serialize, WriteCursor};
+
+macro_rules! primitive {
+    ($type:ty, $bits:expr, $write:ident, $read:ident) => {
+        primitive!(
+            $type,
+            $bits,
+            |value, cursor| cursor.$write(value),
+            |cursor| cursor.$read()
+        );
+    };
+    ($type:ty, $bits:expr, signed) => {
+        primitive!(
+            $type,
+            $bits,
+            |value, cursor| cursor.write_signed(value as i64, $bits),
+            |cursor| cursor.read_signed($bits) as $type
+        );
+    };
+    ($type:ty, $bits:expr, |$value:ident, $cursor:ident| $write:expr, |$read_cursor:ident| $read:expr) => {
+        impl DataType for $type {
+            const EXTENT_BYTES: Option<usize> = None;
+            const MAX_SIZE_BITS: usize = $bits;
+            const ALIGNMENT_BITS: usize = 1;
+        }
+
+        impl Serialize for $type {
+            fn size_bits(&self) -> usize {
+                $bits
+            }
+
+            fn serialize(&self, $cursor: &mut WriteCursor<'_>) {
+                let $value = *self;
+                $write
+            }
+
+            fn field_size_bits(&self, _offset_bits: usize) -> usize {
+                $bits
+            }
+
+            fn serialize_field(&self, cursor: &mut WriteCursor<'_>) {
+                self.serialize(cursor)
+            }
+        }
+
+        impl Deserialize for $type {
+            fn deserialize($read_cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+                Ok($read)
+            }
+
+            fn deserialize_field(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+                Self::deserialize(cursor)
+            }
+        }
+    };
+}
+
+primitive!(bool, 1, write_bool, read_bool);
+primitive!(u8, 8, write_u8, read_u8);
+primitive!(u16, 16, write_u16, read_u16);
+primitive!(u32, 32, write_u32, read_u32);
+primitive!(u64, 64, write_u64, read_u64);
+primitive!(i8, 8, signed);
+primitive!(i16, 16, signed);
+primitive!(i32, 32, signed);
+primitive!(i64, 64, signed);
+primitive!(f32, 32, write_f32, read_f32);
+primitive!(f64, 64, write_f64, read_f64);
+
+/// Size of the elements of an array, as fields starting at `offset_bits`.
+fn elements_size_bits<T: Serialize>(elements: &[T], offset_bits: usize) -> usize {
+    elements.iter().fold(0, |bits, element| {
+        bits + element.field_size_bits(offset_bits + bits)
+    })
+}
+
+/// Fixed-length arrays.
+impl<T: DataType, const N: usize> DataType for [T; N] {
+    const EXTENT_BYTES: Option<usize> = None;
+    const MAX_SIZE_BITS: usize = N * super::max_field_end_bits::<T>(0);
+    const ALIGNMENT_BITS: usize = T::ALIGNMENT_BITS;
+}
+
+impl<T: Serialize, const N: usize> Serialize for [T; N] {
+    fn size_bits(&self) -> usize {
+        elements_size_bits(self, 0)
+    }
+
+    fn serialize(&self, cursor: &mut WriteCursor<'_>) {
+        for element in self {
+            element.serialize_field(cursor);
+        }
+    }
+
+    fn field_size_bits(&self, offset_bits: usize) -> usize {
+        elements_size_bits(self, offset_bits)
+    }
+
+    fn serialize_field(&self, cursor: &mut WriteCursor<'_>) {
+        self.serialize(cursor)
+    }
+}
+
+impl<T: Deserialize, const N: usize> Deserialize for [T; N] {
+    fn deserialize(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+        let mut elements = heapless::Vec::<T, N>::new();
+        for _ in 0..N {
+            // Can't fail, there are only N elements
+            let _ = elements.push(T::deserialize_field(cursor)?);
+        }
+        elements.into_array().map_err(|_| unreachable!())
+    }
+
+    fn deserialize_field(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+        Self::deserialize(cursor)
+    }
+}
+
+/// Variable-length arrays, with the capacity as their maximum length.
+///
+/// The length prefix is a multiple of 8 bits, so arrays of composites are aligned before it
+/// rather than between it and the first element.
+impl<T: DataType, const N: usize> DataType for heapless::Vec<T, N> {
+    const EXTENT_BYTES: Option<usize> = None;
+    const MAX_SIZE_BITS: usize =
+        super::array_length_bits(N) as usize + N * super::max_field_end_bits::<T>(0);
+    const ALIGNMENT_BITS: usize = T::ALIGNMENT_BITS;
+}
+
+impl<T: Serialize, const N: usize> Serialize for heapless::Vec<T, N> {
+    fn size_bits(&self) -> usize {
+        self.field_size_bits(0)
+    }
+
+    fn serialize(&self, cursor: &mut WriteCursor<'_>) {
+        if T::ALIGNMENT_BITS > 1 {
+            cursor.align();
+        }
+        cursor.write_array_length(self.len(), N);
+        for element in self {
+            element.serialize_field(cursor);
+        }
+    }
+
+    fn field_size_bits(&self, offset_bits: usize) -> usize {
+        let start = offset_bits.next_multiple_of(T::ALIGNMENT_BITS);
+        let prefix = super::array_length_bits(N) as usize;
+        start - offset_bits + prefix + elements_size_bits(self, start + prefix)
+    }
+
+    fn serialize_field(&self, cursor: &mut WriteCursor<'_>) {
+        self.serialize(cursor)
+    }
+}
+
+impl<T: Deserialize, const N: usize> Deserialize for heapless::Vec<T, N> {
+    fn deserialize(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+        if T::ALIGNMENT_BITS > 1 {
+            cursor.align();
+        }
+        let len = cursor.read_array_length(N)?;
+        let mut elements = heapless::Vec::new();
+        for _ in 0..len {
+            // Can't fail, the length is at most the capacity
+            let _ = elements.push(T::deserialize_field(cursor)?);
+        }
+        Ok(elements)
+    }
+
+    fn deserialize_field(cursor: &mut ReadCursor<'_>) -> Result<Self, DeserializeError> {
+        Self::deserialize(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_and_arrays() {
+        let mut buffer = [0u8; 16];
+        let mut cursor = WriteCursor::new(&mut buffer);
+        true.serialize_field(&mut cursor);
+        (-2i16).serialize_field(&mut cursor);
+        [1u8, 2].serialize_field(&mut cursor);
+        heapless::Vec::<u16, 2>::from_slice(&[0x0102])
+            .unwrap()
+            .serialize_field(&mut cursor);
+        assert_eq!(cursor.offset_bits(), 1 + 16 + 16 + 8 + 16);
+        assert_eq!(
+            buffer[0..8],
+            [0xFD, 0xFF, 0x03, 0x04, 0x02, 0x04, 0x02, 0x00]
+        );
+
+        let mut cursor = ReadCursor::new(&buffer);
+        assert!(bool::deserialize_field(&mut cursor).unwrap());
+        assert_eq!(i16::deserialize_field(&mut cursor), Ok(-2));
+        assert_eq!(<[u8; 2]>::deserialize_field(&mut cursor), Ok([1, 2]));
+        assert_eq!(
+            heapless::Vec::<u16, 2>::deserialize_field(&mut cursor).unwrap(),
+            [0x0102]
+        );
+
+        assert_eq!([1.5f32; 3].size_bits(), 96);
+        assert_eq!(heapless::Vec::<bool, 300>::new().size_bits(), 16);
+        assert_eq!(<heapless::Vec<bool, 300>>::MAX_SIZE_BITS, 316);
+        assert_eq!(<[i16; 3]>::MAX_SIZE_BYTES, 6);
+        assert_eq!(<heapless::Vec<u8, 4>>::EXTENT, 5);
+    }
+}
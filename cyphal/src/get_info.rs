@@ -0,0 +1,222 @@
+//! `uavcan.node.GetInfo.1.0` service.
+//!
+//! Lets tools on the bus identify a node. Once the node is given its [`NodeInfo`] through
+//! [`crate::Node::set_info`], requests are answered through [`crate::Node::poll`].
+
+use crate::service::Service;
+use crate::types::PortId;
+
+/// Fixed service ID of `uavcan.node.GetInfo.1.0`.
+pub const SERVICE_ID: PortId = 430;
+
+/// Extent of the request, which is empty.
+pub const REQUEST_EXTENT: usize = 0;
+
+/// Extent of the response.
+pub const RESPONSE_EXTENT: usize = 448;
+
+/// Maximum length of the node name.
+pub const NAME_CAPACITY: usize = 50;
+
+/// `uavcan.node.Version.1.0`
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+/// Version of the Cyphal specification implemented by this crate.
+pub const PROTOCOL_VERSION: Version = Version { major: 1, minor: 0 };
+
+/// Response to `uavcan.node.GetInfo.1.0`, describing the node.
+///
+/// The certificate of authenticity is not supported, it is always sent empty and skipped over
+/// when deserializing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeInfo {
+    pub protocol_version: Version,
+    pub hardware_version: Version,
+    pub software_version: Version,
+    /// Version control revision the software was built from, zero if unknown
+    pub software_vcs_revision_id: u64,
+    /// Globally unique ID of the node's hardware, must not change
+    pub unique_id: [u8; 16],
+    /// Reverse-domain name of the node, e.g. `org.example.product`
+    pub name: heapless::String<NAME_CAPACITY>,
+    /// CRC-64-WE of the software image, if known
+    pub software_image_crc: Option<u64>,
+}
+
+impl NodeInfo {
+    /// Maximum serialized size in bytes, with an empty certificate of authenticity.
+    pub const MAX_SIZE: usize = 2 * 3 + 8 + 16 + 1 + NAME_CAPACITY + 1 + 8 + 1;
+
+    /// Info for a node implementing [`PROTOCOL_VERSION`], with every version left at zero.
+    pub fn new(name: heapless::String<NAME_CAPACITY>, unique_id: [u8; 16]) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            hardware_version: Version::default(),
+            software_version: Version::default(),
+            software_vcs_revision_id: 0,
+            unique_id,
+            name,
+            software_image_crc: None,
+        }
+    }
+
+    /// Serializes the info into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than the serialized info, [`NodeInfo::MAX_SIZE`] is always enough.
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        let mut len = 0;
+        let mut write = |bytes: &[u8]| {
+            buffer[len..len + bytes.len()].copy_from_slice(bytes);
+            len += bytes.len();
+        };
+
+        for version in [
+            self.protocol_version,
+            self.hardware_version,
+            self.software_version,
+        ] {
+            write(&[version.major, version.minor]);
+        }
+        write(&self.software_vcs_revision_id.to_le_bytes());
+        write(&self.unique_id);
+        write(&[self.name.len() as u8]);
+        write(self.name.as_bytes());
+        match self.software_image_crc {
+            Some(crc) => {
+                write(&[1]);
+                write(&crc.to_le_bytes());
+            }
+            None => write(&[0]),
+        }
+        // Empty certificate of authenticity
+        write(&[0]);
+
+        len
+    }
+
+    /// Deserializes a received response.
+    ///
+    /// Missing bytes are taken as zero, as required for DSDL types. Array lengths beyond their
+    /// capacity and names that aren't valid UTF-8 are rejected.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        let mut offset = 0;
+        let mut read = |bytes: &mut [u8]| {
+            // Implicit zero extension
+            let start = core::cmp::min(offset, buffer.len());
+            let end = core::cmp::min(offset + bytes.len(), buffer.len());
+            bytes.fill(0);
+            bytes[0..end - start].copy_from_slice(&buffer[start..end]);
+            offset += bytes.len();
+        };
+        let mut read_version = || {
+            let mut bytes = [0u8; 2];
+            read(&mut bytes);
+            Version {
+                major: bytes[0],
+                minor: bytes[1],
+            }
+        };
+
+        let protocol_version = read_version();
+        let hardware_version = read_version();
+        let software_version = read_version();
+
+        let mut software_vcs_revision_id = [0u8; 8];
+        read(&mut software_vcs_revision_id);
+        let mut unique_id = [0u8; 16];
+        read(&mut unique_id);
+
+        let mut name_len = [0u8];
+        read(&mut name_len);
+        let mut name_bytes = heapless::Vec::<u8, NAME_CAPACITY>::new();
+        name_bytes.resize(name_len[0] as usize, 0).ok()?;
+        read(&mut name_bytes);
+        let mut name = heapless::String::new();
+        name.push_str(core::str::from_utf8(&name_bytes).ok()?)
+            .ok()?;
+
+        let mut crc_len = [0u8];
+        read(&mut crc_len);
+        let software_image_crc = match crc_len[0] {
+            0 => None,
+            1 => {
+                let mut crc = [0u8; 8];
+                read(&mut crc);
+                Some(u64::from_le_bytes(crc))
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            protocol_version,
+            hardware_version,
+            software_version,
+            software_vcs_revision_id: u64::from_le_bytes(software_vcs_revision_id),
+            unique_id,
+            name,
+            software_image_crc,
+        })
+    }
+}
+
+/// `uavcan.node.GetInfo.1.0`, for asking other nodes for their info through
+/// [`crate::Node::send_request`].
+#[derive(Copy, Clone, Debug)]
+pub struct GetInfo;
+
+impl Service for GetInfo {
+    const SERVICE_ID: PortId = SERVICE_ID;
+    const REQUEST_MAX_SIZE: usize = REQUEST_EXTENT;
+    const RESPONSE_EXTENT: usize = RESPONSE_EXTENT;
+
+    type Request = ();
+    type Response = NodeInfo;
+
+    fn serialize_request(_request: &(), _buffer: &mut [u8]) -> usize {
+        0
+    }
+
+    fn deserialize_response(buffer: &[u8]) -> Option<NodeInfo> {
+        NodeInfo::deserialize(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialization() {
+        let mut info = NodeInfo::new("org.example.node".into(), [0xAA; 16]);
+        info.hardware_version = Version { major: 2, minor: 1 };
+        info.software_version = Version { major: 0, minor: 3 };
+        info.software_vcs_revision_id = 0x0123456789ABCDEF;
+        info.software_image_crc = Some(0x1122334455667788);
+
+        let mut buffer = [0u8; NodeInfo::MAX_SIZE];
+        let len = info.serialize(&mut buffer);
+        assert_eq!(len, 6 + 8 + 16 + 1 + 16 + 1 + 8 + 1);
+        assert_eq!(buffer[0..6], [1, 0, 2, 1, 0, 3]);
+        assert_eq!(buffer[6..14], 0x0123456789ABCDEFu64.to_le_bytes());
+        assert_eq!(buffer[30], 16);
+        assert_eq!(&buffer[31..47], b"org.example.node");
+        assert_eq!(buffer[47], 1);
+        assert_eq!(buffer[len - 1], 0);
+        assert_eq!(NodeInfo::deserialize(&buffer[0..len]), Some(info.clone()));
+
+        // Without a CRC, and with the trailing empty arrays cut off
+        info.software_image_crc = None;
+        let len = info.serialize(&mut buffer);
+        assert_eq!(buffer[len - 2..len], [0, 0]);
+        assert_eq!(NodeInfo::deserialize(&buffer[0..len - 2]), Some(info));
+
+        // Name longer than its capacity
+        buffer[30] = NAME_CAPACITY as u8 + 1;
+        assert_eq!(NodeInfo::deserialize(&buffer), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! `uavcan.node.Heartbeat.1.0` publication.
+//!
+//! Every non-anonymous node has to publish a heartbeat once per second. [`crate::Node::poll`]
+//! takes care of that, this module holds the message itself and the state behind it.
+
+use embedded_time::duration::Seconds;
+
+use crate::Priority;
+use crate::time::Timestamp;
+use crate::types::PortId;
+
+/// Fixed subject ID of `uavcan.node.Heartbeat.1.0`.
+pub const SUBJECT_ID: PortId = 7509;
+
+/// Priority heartbeats are published at.
+pub const PRIORITY: Priority = Priority::Nominal;
+
+/// `uavcan.node.Health.1.0`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, FromPrimitive)]
+pub enum Health {
+    /// The node is functioning properly
+    Nominal = 0,
+    /// A critical parameter went out of range or the node encountered a minor failure
+    Advisory = 1,
+    /// The node encountered a major failure and is performing in a degraded mode
+    Caution = 2,
+    /// The node suffered a fatal malfunction and is unable to perform its intended function
+    Warning = 3,
+}
+
+/// `uavcan.node.Mode.1.0`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, FromPrimitive)]
+pub enum Mode {
+    /// Normal operating mode
+    Operational = 0,
+    /// Initialization is in progress, this mode is entered immediately after startup
+    Initialization = 1,
+    /// Calibration, self-test, etc.
+    Maintenance = 2,
+    /// New software/firmware is being loaded or the bootloader is running
+    SoftwareUpdate = 3,
+}
+
+/// `uavcan.node.Heartbeat.1.0`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Heartbeat {
+    /// Seconds since the node started
+    pub uptime: u32,
+    pub health: Health,
+    pub mode: Mode,
+    pub vendor_specific_status_code: u8,
+}
+
+impl Heartbeat {
+    /// Serialized size in bytes. Health and mode are both padded out to a whole byte.
+    pub const SIZE: usize = 7;
+
+    /// Serializes the heartbeat into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`Heartbeat::SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        buffer[0..4].copy_from_slice(&self.uptime.to_le_bytes());
+        buffer[4] = self.health as u8;
+        buffer[5] = self.mode as u8;
+        buffer[6] = self.vendor_specific_status_code;
+        Self::SIZE
+    }
+
+    /// Deserializes a received heartbeat.
+    ///
+    /// Missing bytes are taken as zero and anything past [`Heartbeat::SIZE`] is ignored, as
+    /// required for DSDL types. Mode values reserved by the standard are rejected.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        let mut bytes = [0u8; Self::SIZE];
+        let len = core::cmp::min(buffer.len(), Self::SIZE);
+        bytes[0..len].copy_from_slice(&buffer[0..len]);
+
+        Some(Self {
+            uptime: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            // Only the low bits are part of the value, the rest is padding
+            health: num_traits::FromPrimitive::from_u8(bytes[4] & 0x03)?,
+            mode: num_traits::FromPrimitive::from_u8(bytes[5] & 0x07)?,
+            vendor_specific_status_code: bytes[6],
+        })
+    }
+}
+
+/// State behind a node's heartbeat, with the status it reports.
+#[derive(Debug)]
+pub struct HeartbeatPublisher<C: embedded_time::Clock> {
+    pub health: Health,
+    pub mode: Mode,
+    pub vendor_specific_status_code: u8,
+
+    uptime: u32,
+    /// Unset until the first poll, which is taken as the node's start time
+    next_publication: Option<Timestamp<C>>,
+}
+
+impl<C: embedded_time::Clock> HeartbeatPublisher<C> {
+    pub fn new() -> Self {
+        Self {
+            health: Health::Nominal,
+            mode: Mode::Initialization,
+            vendor_specific_status_code: 0,
+            uptime: 0,
+            next_publication: None,
+        }
+    }
+
+    /// Returns the heartbeat to publish if one is due.
+    ///
+    /// The first call publishes straight away, after that one is due at every whole second
+    /// since. Missed publications aren't made up for, but the uptime still counts them.
+    pub fn poll(&mut self, now: Timestamp<C>) -> Option<Heartbeat> {
+        match self.next_publication {
+            Some(mut next) => {
+                if now < next {
+                    return None;
+                }
+                while now >= next {
+                    self.uptime = self.uptime.saturating_add(1);
+                    next = next + Seconds(1u32);
+                }
+                self.next_publication = Some(next);
+            }
+            None => self.next_publication = Some(now + Seconds(1u32)),
+        }
+
+        Some(Heartbeat {
+            uptime: self.uptime,
+            health: self.health,
+            mode: self.mode,
+            vendor_specific_status_code: self.vendor_specific_status_code,
+        })
+    }
+}
+
+impl<C: embedded_time::Clock> Default for HeartbeatPublisher<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: embedded_time::Clock> Clone for HeartbeatPublisher<C> {
+    fn clone(&self) -> Self {
+        Self {
+            health: self.health,
+            mode: self.mode,
+            vendor_specific_status_code: self.vendor_specific_status_code,
+            uptime: self.uptime,
+            next_publication: self.next_publication,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialization() {
+        let heartbeat = Heartbeat {
+            uptime: 0x12345678,
+            health: Health::Caution,
+            mode: Mode::Maintenance,
+            vendor_specific_status_code: 0xAB,
+        };
+
+        let mut buffer = [0u8; Heartbeat::SIZE];
+        assert_eq!(heartbeat.serialize(&mut buffer), Heartbeat::SIZE);
+        assert_eq!(buffer, [0x78, 0x56, 0x34, 0x12, 2, 2, 0xAB]);
+        assert_eq!(Heartbeat::deserialize(&buffer), Some(heartbeat));
+
+        // Implicit zero extension
+        let truncated = Heartbeat::deserialize(&buffer[0..4]).unwrap();
+        assert_eq!(truncated.uptime, 0x12345678);
+        assert_eq!(truncated.health, Health::Nominal);
+        assert_eq!(truncated.mode, Mode::Operational);
+        assert_eq!(truncated.vendor_specific_status_code, 0);
+
+        // Reserved mode
+        assert_eq!(Heartbeat::deserialize(&[0, 0, 0, 0, 0, 7, 0]), None);
+    }
+}
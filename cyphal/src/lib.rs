@@ -25,18 +25,23 @@
 //#![deny(warnings)]
 
 #[allow(unused_imports)]
-#[cfg(feature = "std")]
+#[cfg(any(test, feature = "std"))]
 #[macro_use]
 extern crate std;
 
-#[cfg(test)]
-extern crate test;
-
 #[macro_use]
 extern crate num_derive;
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
+pub mod dsdl;
+pub mod get_info;
+pub mod heartbeat;
+pub mod pnp;
+#[cfg(feature = "alloc")]
+pub mod register;
+pub mod service;
 pub mod time;
 
 //mod crc16;
@@ -44,7 +49,7 @@ pub mod transfer;
 pub mod transport;
 pub mod types;
 
-pub use node::{Node, TransmissionType};
+pub use node::{Node, TransmissionType, TransmitFrameError};
 use time::Duration;
 pub use transfer::TransferKind;
 
@@ -79,6 +84,9 @@ pub enum RxError {
 
     /// Transport implementation has incorrectly assigned a remote node id to a message
     MessageWithRemoteId,
+
+    /// Transport-level frame header is malformed or failed its own integrity check
+    InvalidHeader,
 }
 
 /// Errors that can be caused by incorrect parameters for transmission
@@ -111,8 +119,7 @@ pub enum Priority {
 }
 
 /// Simple subscription type to
-// TODO remove this allow
-#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
 pub struct Subscription {
     transfer_kind: TransferKind,
     port_id: PortId,
@@ -141,3 +148,14 @@ impl PartialEq for Subscription {
         self.transfer_kind == other.transfer_kind && self.port_id == other.port_id
     }
 }
+
+/// Errors from managing subscriptions.
+#[derive(Copy, Clone, Debug)]
+pub enum SubscriptionError {
+    /// A subscription for this port already exists
+    SubscriptionExists,
+    /// No subscription for this port exists
+    SubscriptionDoesNotExist,
+    /// No space left to store the subscription
+    NoSpace,
+}
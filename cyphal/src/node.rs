@@ -2,18 +2,93 @@ use core::marker::PhantomData;
 
 use core::clone::Clone;
 
+use crate::get_info::{self, NodeInfo};
+use crate::heartbeat::{self, Health, Heartbeat, HeartbeatPublisher, Mode};
+use crate::pnp::{AllocationClient, AllocationDataV2, ProtocolVersion};
+#[cfg(feature = "alloc")]
+use crate::pnp::{AllocationStorage, Allocator};
+#[cfg(feature = "alloc")]
+use crate::register::{self, AccessRequest, AccessResponse, ListRequest, Registry};
+use crate::service::{ClientEvent, PendingRequest, RequestId, Service};
 use crate::transfer::manager::{
     CreateTransferError, InternalOrUserError, TokenAccessError, UpdateTransferError,
 };
 use crate::transfer::{TransferManager, TransferMetadata};
 use crate::transport::Transport;
-use crate::{RxError, TransferKind, TxError, types::*};
+use crate::{Priority, RxError, Subscription, SubscriptionError, TransferKind, TxError, types::*};
 
 /// Node implementation. Generic across session managers and transport types.
-#[derive(Debug, Clone, Copy)]
-pub struct Node<M: TransferManager<C, T>, T: Transport<C>, C: embedded_time::Clock> {
+///
+/// Everything the node keeps track of itself has a fixed capacity, so it can be used without
+/// an allocator along with [`crate::transfer::StaticTransferManager`]:
+/// - `PORTS`: maximum number of ports (and destinations, for requests) whose next transfer ID
+///   is kept, and of services used through [`Node::send_request`]
+/// - `REQUESTS`: maximum number of requests sent through [`Node::send_request`] waiting for a
+///   response, or for [`Node::poll_client`] to hand it out
+/// - `QUEUE`: maximum number of frames queued through [`Node::enqueue`]
+/// - `RESPONSE`: maximum response extent of services used through [`Node::send_request`], the
+///   default fits `uavcan.node.GetInfo`
+///
+/// Services the node answers itself queue up to a handful of requests each, anything beyond
+/// that is dropped.
+#[derive(Debug, Clone)]
+pub struct Node<
+    M: TransferManager<C, T>,
+    T: Transport<C>,
+    C: embedded_time::Clock,
+    const PORTS: usize = 16,
+    const REQUESTS: usize = 8,
+    const QUEUE: usize = 64,
+    const RESPONSE: usize = 448,
+> {
     id: Option<NodeId>,
 
+    /// Next transfer ID to send on each subject, and on each service for each destination
+    transfer_ids: heapless::LinearMap<(TransferKind, PortId, Option<NodeId>), TransferId, PORTS>,
+
+    heartbeat: HeartbeatPublisher<C>,
+
+    /// Node-ID allocation in progress, see [`Node::start_allocation`]
+    allocation: Option<AllocationClient<C>>,
+    /// Version of allocation requests taken in, see [`Node::serve_allocations`]
+    serve_allocations: Option<ProtocolVersion>,
+    /// Payloads of allocation requests still to be handled
+    pending_allocation_requests: heapless::Deque<
+        heapless::Vec<u8, { AllocationDataV2::SIZE }>,
+        MAX_PENDING_ALLOCATION_REQUESTS,
+    >,
+
+    /// Answer to GetInfo requests, which are only handled once this is set
+    info: Option<NodeInfo>,
+    /// GetInfo requests still to be answered: client, transfer ID and priority
+    pending_info_responses:
+        heapless::Deque<(NodeId, TransferId, Priority), MAX_PENDING_INFO_RESPONSES>,
+
+    /// Whether register requests are taken in, see [`Node::serve_registers`]
+    #[cfg(feature = "alloc")]
+    serve_registers: bool,
+    /// Register requests still to be answered: client, transfer ID, priority and the request
+    #[cfg(feature = "alloc")]
+    pending_register_requests: heapless::Deque<
+        (NodeId, TransferId, Priority, RegisterRequest),
+        MAX_PENDING_REGISTER_REQUESTS,
+    >,
+
+    /// Requests sent through [`Node::send_request`] still waiting for a response, with their
+    /// deadlines
+    outstanding_requests: heapless::LinearMap<RequestId, embedded_time::Instant<C>, REQUESTS>,
+    /// Services whose responses are matched against outstanding requests
+    client_services: heapless::Vec<PortId, PORTS>,
+    /// Responses not yet handed out by [`Node::poll_client`], copied out of the transfer
+    /// manager as they arrive
+    client_events: heapless::Deque<(RequestId, heapless::Vec<u8, RESPONSE>), REQUESTS>,
+
+    /// Frames queued through [`Node::enqueue`] with their deadlines, keyed by
+    /// [`Transport::transmit_order`] and then by the order they were queued in
+    tx_queue: heapless::Vec<QueuedFrame<T::Frame, C>, QUEUE>,
+    /// Number of frames queued so far
+    tx_queued: u64,
+
     /// Session manager. Made public so it could be managed by implementation.
     ///
     /// Instead of being public, could be placed behind a `with_session_manager` fn
@@ -24,10 +99,35 @@ pub struct Node<M: TransferManager<C, T>, T: Transport<C>, C: embedded_time::Clo
     _transport: PhantomData<T>,
 }
 
+/// Register requests are answered in order, requests beyond this many are dropped.
+#[cfg(feature = "alloc")]
+const MAX_PENDING_REGISTER_REQUESTS: usize = 4;
+
+/// Allocation requests are handled in order, requests beyond this many are dropped.
+const MAX_PENDING_ALLOCATION_REQUESTS: usize = 4;
+
+/// GetInfo requests are answered in order, requests beyond this many are dropped.
+const MAX_PENDING_INFO_RESPONSES: usize = 4;
+
+/// Transfers the node creates itself are dropped if they haven't gone out this long after
+/// being created. Heartbeats are superseded by then, and clients will have retried.
+const TX_TIMEOUT: crate::time::Duration = embedded_time::duration::Milliseconds(1000);
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+enum RegisterRequest {
+    Access(AccessRequest),
+    List(ListRequest),
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum TransmitFrameError {
     TokenError(TokenAccessError),
     TxError(TxError),
+    /// The transfer's deadline passed before the frame went out, so the transfer was dropped
+    Expired,
+    /// The TX queue has no room for every frame of the transfer, so the transfer was dropped
+    QueueFull,
     /// This indicates an error with the transfer manager implementation,
     /// when there is no access erro but the callback has not been called
     InvalidHandling,
@@ -35,12 +135,16 @@ pub enum TransmitFrameError {
 
 #[derive(Debug, Clone, Copy)]
 pub enum TransmissionType {
+    /// Service request to the given node
     Request(crate::NodeId),
-    Response(crate::NodeId),
+    /// Service response to the given node, reusing the transfer ID of the request
+    Response(crate::NodeId, TransferId),
+    /// Message on a subject
     Broadcast,
 }
 
-impl<'a, M, T, C> Node<M, T, C>
+impl<M, T, C, const PORTS: usize, const REQUESTS: usize, const QUEUE: usize, const RESPONSE: usize>
+    Node<M, T, C, PORTS, REQUESTS, QUEUE, RESPONSE>
 where
     M: TransferManager<C, T>,
     T: Transport<C>,
@@ -49,14 +153,425 @@ where
     pub fn new(id: Option<NodeId>, session_manager: M) -> Self {
         Self {
             id,
+            transfer_ids: heapless::LinearMap::new(),
+            heartbeat: HeartbeatPublisher::new(),
+            allocation: None,
+            serve_allocations: None,
+            pending_allocation_requests: heapless::Deque::new(),
+            info: None,
+            pending_info_responses: heapless::Deque::new(),
+            #[cfg(feature = "alloc")]
+            serve_registers: false,
+            #[cfg(feature = "alloc")]
+            pending_register_requests: heapless::Deque::new(),
+            outstanding_requests: heapless::LinearMap::new(),
+            client_services: heapless::Vec::new(),
+            client_events: heapless::Deque::new(),
+            tx_queue: heapless::Vec::new(),
+            tx_queued: 0,
             transfer_manager: session_manager,
             _clock: PhantomData,
             _transport: PhantomData,
         }
     }
 
+    /// The node's ID, `None` while anonymous.
+    pub fn id(&self) -> Option<NodeId> {
+        self.id
+    }
+
+    /// Start receiving transfers on a port.
+    ///
+    /// Frames for ports without a subscription are dropped by [`Node::try_receive_frame`].
+    pub fn subscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        self.transfer_manager.subscribe(subscription)
+    }
+
+    /// Stop receiving transfers on a port.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        self.transfer_manager.unsubscribe(subscription)
+    }
+
+    /// Health reported in the node's heartbeat.
+    pub fn set_health(&mut self, health: Health) {
+        self.heartbeat.health = health;
+    }
+
+    /// Mode reported in the node's heartbeat. Nodes start out in [`Mode::Initialization`].
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.heartbeat.mode = mode;
+    }
+
+    /// Vendor-specific status code reported in the node's heartbeat.
+    pub fn set_vendor_specific_status_code(&mut self, code: u8) {
+        self.heartbeat.vendor_specific_status_code = code;
+    }
+
+    /// Start obtaining a node ID through plug-and-play allocation. Nodes that already have an ID
+    /// are left alone.
+    ///
+    /// Requests are published through [`Node::poll`], and responses taken in by
+    /// [`Node::try_receive_frame`]. Once an allocator hands out an ID the node stops being
+    /// anonymous, which can be checked through [`Node::id`].
+    pub fn start_allocation(
+        &mut self,
+        allocation: AllocationClient<C>,
+    ) -> Result<(), SubscriptionError> {
+        if self.id.is_some() || self.allocation.is_some() {
+            return Ok(());
+        }
+
+        let version = allocation.version();
+        self.transfer_manager.subscribe(Subscription::new(
+            TransferKind::Message,
+            version.subject_id(),
+            version.extent(),
+            embedded_time::duration::Milliseconds(1000),
+        ))?;
+        self.allocation = Some(allocation);
+        Ok(())
+    }
+
+    /// Start allocating node IDs to anonymous nodes requesting one, with the given version of
+    /// the protocol.
+    ///
+    /// Requests are taken in by [`Node::try_receive_frame`] and answered through
+    /// [`Node::poll_allocator`], which is handed the allocator. The node needs an ID of its own
+    /// to publish responses under.
+    #[cfg(feature = "alloc")]
+    pub fn serve_allocations(&mut self, version: ProtocolVersion) -> Result<(), SubscriptionError> {
+        if self.serve_allocations.is_some() {
+            return Ok(());
+        }
+
+        self.transfer_manager.subscribe(Subscription::new(
+            TransferKind::Message,
+            version.subject_id(),
+            version.extent(),
+            embedded_time::duration::Milliseconds(1000),
+        ))?;
+        self.serve_allocations = Some(version);
+        Ok(())
+    }
+
+    /// Handles the next pending allocation request with `allocator`, returning the response for
+    /// the user to transmit through [`Node::transmit_frame`].
+    ///
+    /// Should be called until it returns `None`, alongside [`Node::poll`]. Errors from storing
+    /// allocations are returned as user errors, in which case no response is sent and the
+    /// request stays queued, to be handled again by the next call.
+    #[cfg(feature = "alloc")]
+    pub fn poll_allocator<S: AllocationStorage>(
+        &mut self,
+        now: embedded_time::Instant<C>,
+        allocator: &mut Allocator<S>,
+    ) -> Result<Option<M::TxTransferToken>, InternalOrUserError<CreateTransferError, S::Error>>
+    {
+        let Some(version) = self.serve_allocations else {
+            return Ok(None);
+        };
+        if let Some(id) = self.id {
+            allocator.reserve(id);
+        }
+
+        while let Some(request) = self.pending_allocation_requests.front() {
+            let response = allocator
+                .handle_message(version, None, request)
+                .map_err(InternalOrUserError::UserError)?;
+            self.pending_allocation_requests.pop_front();
+            let Some(response) = response else {
+                continue;
+            };
+
+            let mut payload = [0u8; AllocationDataV2::SIZE];
+            let len = response.serialize(&mut payload);
+            return self
+                .start_tx_transfer(
+                    len,
+                    now + TX_TIMEOUT,
+                    Priority::Nominal,
+                    version.subject_id(),
+                    TransmissionType::Broadcast,
+                    |buf| {
+                        buf.copy_from_slice(&payload[0..len]);
+                        Ok(len)
+                    },
+                )
+                .map(Some)
+                .map_err(|e| InternalOrUserError::InternalError(internal_error(e)));
+        }
+
+        Ok(None)
+    }
+
+    /// Start answering `uavcan.node.GetInfo` requests with `info`.
+    ///
+    /// Requests are taken in by [`Node::try_receive_frame`] and answered through [`Node::poll`].
+    /// Calling this again replaces the info.
+    pub fn set_info(&mut self, info: NodeInfo) -> Result<(), SubscriptionError> {
+        if self.info.is_none() {
+            self.transfer_manager.subscribe(Subscription::new(
+                TransferKind::Request,
+                get_info::SERVICE_ID,
+                get_info::REQUEST_EXTENT,
+                // Recommended transfer-ID timeout
+                embedded_time::duration::Milliseconds(2000),
+            ))?;
+        }
+        self.info = Some(info);
+        Ok(())
+    }
+
+    /// Start answering `uavcan.register.Access` and `uavcan.register.List` requests.
+    ///
+    /// Requests are taken in by [`Node::try_receive_frame`] and answered through
+    /// [`Node::poll_registers`], which is handed the registry.
+    #[cfg(feature = "alloc")]
+    pub fn serve_registers(&mut self) -> Result<(), SubscriptionError> {
+        if self.serve_registers {
+            return Ok(());
+        }
+
+        for (port_id, extent) in [
+            (register::ACCESS_SERVICE_ID, AccessRequest::MAX_SIZE),
+            (register::LIST_SERVICE_ID, ListRequest::SIZE),
+        ] {
+            self.transfer_manager.subscribe(Subscription::new(
+                TransferKind::Request,
+                port_id,
+                extent,
+                // Recommended transfer-ID timeout
+                embedded_time::duration::Milliseconds(2000),
+            ))?;
+        }
+        self.serve_registers = true;
+        Ok(())
+    }
+
+    /// Answers the next pending register request against `registry`, returning the response
+    /// for the user to transmit through [`Node::transmit_frame`].
+    ///
+    /// Should be called until it returns `None`, alongside [`Node::poll`]. As with GetInfo, a
+    /// response that can't be created is dropped.
+    #[cfg(feature = "alloc")]
+    pub fn poll_registers(
+        &mut self,
+        now: embedded_time::Instant<C>,
+        registry: &mut impl Registry,
+    ) -> Result<Option<M::TxTransferToken>, CreateTransferError> {
+        let Some((client, transfer_id, priority, request)) =
+            self.pending_register_requests.pop_front()
+        else {
+            return Ok(None);
+        };
+
+        // The larger of the two responses
+        let mut response = [0u8; AccessResponse::MAX_SIZE];
+        let (port_id, len) = match request {
+            RegisterRequest::Access(request) => (
+                register::ACCESS_SERVICE_ID,
+                request.handle(registry).serialize(&mut response),
+            ),
+            RegisterRequest::List(request) => (
+                register::LIST_SERVICE_ID,
+                request.handle(registry).serialize(&mut response),
+            ),
+        };
+        self.respond(
+            now,
+            port_id,
+            client,
+            transfer_id,
+            priority,
+            &response[0..len],
+        )
+        .map(Some)
+    }
+
+    /// Periodic housekeeping, to be called at least once per second.
+    ///
+    /// Returns transfers generated by the node itself for the user to transmit through
+    /// [`Node::transmit_frame`], one at a time, so this should be called until it returns `None`:
+    /// - The heartbeat, when it's due. The first call is taken as the node's start time.
+    ///   Anonymous nodes don't publish heartbeats.
+    /// - Anonymous node-ID allocation requests, while allocation is in progress.
+    /// - Responses to GetInfo requests. A response that can't be created is dropped, it's up
+    ///   to the client to retry.
+    pub fn poll(
+        &mut self,
+        now: embedded_time::Instant<C>,
+    ) -> Result<Option<M::TxTransferToken>, CreateTransferError> {
+        // Keep counting uptime even while anonymous
+        if let (Some(heartbeat), Some(_)) = (self.heartbeat.poll(now), self.id) {
+            return self
+                .start_tx_transfer(
+                    Heartbeat::SIZE,
+                    now + TX_TIMEOUT,
+                    heartbeat::PRIORITY,
+                    heartbeat::SUBJECT_ID,
+                    TransmissionType::Broadcast,
+                    |buf| Ok(heartbeat.serialize(buf)),
+                )
+                .map(Some)
+                .map_err(internal_error);
+        }
+
+        if let Some(allocation) = &mut self.allocation {
+            if let Some(request) = allocation.poll(now) {
+                let subject_id = allocation.subject_id();
+                return self
+                    .start_tx_transfer(
+                        request.len(),
+                        now + TX_TIMEOUT,
+                        Priority::Nominal,
+                        subject_id,
+                        TransmissionType::Broadcast,
+                        |buf| {
+                            buf.copy_from_slice(&request);
+                            Ok(request.len())
+                        },
+                    )
+                    .map(Some)
+                    .map_err(internal_error);
+            }
+        }
+
+        if let Some(info) = &self.info {
+            if let Some((client, transfer_id, priority)) = self.pending_info_responses.pop_front() {
+                let mut response = [0u8; NodeInfo::MAX_SIZE];
+                let len = info.serialize(&mut response);
+                return self
+                    .respond(
+                        now,
+                        get_info::SERVICE_ID,
+                        client,
+                        transfer_id,
+                        priority,
+                        &response[0..len],
+                    )
+                    .map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Sends a request to `server_node_id`, returning the transfer for the user to transmit
+    /// through [`Node::transmit_frame`] along with a handle to the request.
+    ///
+    /// The response, or the request timing out if there is none within `timeout`, is reported
+    /// by [`Node::poll_client`]. The first request on a service subscribes to its responses,
+    /// which are then only delivered that way. Responses nobody is waiting for are dropped.
+    ///
+    /// Fails with [`CreateTransferError::NoSpace`] if `REQUESTS` requests are already waiting
+    /// for a response or for it to be handed out, if `PORTS` services are already in use, or if
+    /// the service's response extent is larger than `RESPONSE`.
+    pub fn send_request<S: Service>(
+        &mut self,
+        now: embedded_time::Instant<C>,
+        server_node_id: NodeId,
+        priority: Priority,
+        request: &S::Request,
+        timeout: crate::time::Duration,
+    ) -> Result<(M::TxTransferToken, PendingRequest<S>), CreateTransferError> {
+        // Leaves room to keep a response to every outstanding request
+        if self.outstanding_requests.len() + self.client_events.len() >= REQUESTS
+            || S::RESPONSE_EXTENT > RESPONSE
+        {
+            return Err(CreateTransferError::NoSpace);
+        }
+        if !self.client_services.contains(&S::SERVICE_ID) {
+            if self.client_services.is_full() {
+                return Err(CreateTransferError::NoSpace);
+            }
+            match self.transfer_manager.subscribe(Subscription::new(
+                TransferKind::Response,
+                S::SERVICE_ID,
+                S::RESPONSE_EXTENT,
+                // Recommended transfer-ID timeout
+                embedded_time::duration::Milliseconds(2000),
+            )) {
+                Ok(()) | Err(SubscriptionError::SubscriptionExists) => {}
+                Err(_) => return Err(CreateTransferError::NoSpace),
+            }
+            // Can't fail, checked above
+            let _ = self.client_services.push(S::SERVICE_ID);
+        }
+
+        let id = RequestId {
+            service_id: S::SERVICE_ID,
+            server_node_id,
+            transfer_id: self.next_transfer_id(
+                TransferKind::Request,
+                S::SERVICE_ID,
+                Some(server_node_id),
+            ),
+        };
+        let token = self
+            .start_tx_transfer(
+                S::REQUEST_MAX_SIZE,
+                now + timeout,
+                priority,
+                S::SERVICE_ID,
+                TransmissionType::Request(server_node_id),
+                |buf| Ok(S::serialize_request(request, buf)),
+            )
+            .map_err(internal_error)?;
+
+        // Can't fail, there was room above and a request with the same ID is replaced
+        let _ = self.outstanding_requests.insert(id, now + timeout);
+        Ok((token, PendingRequest::new(id)))
+    }
+
+    /// Hands the next outcome of a request sent through [`Node::send_request`] to `cb`, either
+    /// its response or it timing out, returning what `cb` does. Should be called until it
+    /// returns `None`.
+    ///
+    /// Responses are handed out in the order they arrived, before any timeouts.
+    pub fn poll_client<R>(
+        &mut self,
+        now: embedded_time::Instant<C>,
+        cb: impl FnOnce(ClientEvent) -> R,
+    ) -> Option<R> {
+        if let Some((id, response)) = self.client_events.pop_front() {
+            return Some(cb(ClientEvent::Response(id, &response)));
+        }
+
+        let id = self
+            .outstanding_requests
+            .iter()
+            .find(|(_, deadline)| now >= **deadline)
+            .map(|(id, _)| *id)?;
+        self.outstanding_requests.remove(&id);
+        Some(cb(ClientEvent::Timeout(id)))
+    }
+
+    /// Creates the response to a request the node answers itself.
+    fn respond(
+        &mut self,
+        now: embedded_time::Instant<C>,
+        port_id: PortId,
+        client: NodeId,
+        transfer_id: TransferId,
+        priority: Priority,
+        response: &[u8],
+    ) -> Result<M::TxTransferToken, CreateTransferError> {
+        self.start_tx_transfer(
+            response.len(),
+            now + TX_TIMEOUT,
+            priority,
+            port_id,
+            TransmissionType::Response(client, transfer_id),
+            |buf| {
+                buf[0..response.len()].copy_from_slice(response);
+                Ok(response.len())
+            },
+        )
+        .map_err(internal_error)
+    }
+
     pub fn try_receive_frame(
-        self: &mut Self,
+        &mut self,
         frame: &T::Frame,
     ) -> Result<Option<M::RxTransferToken>, RxError> {
         let (frame, metadata) = T::rx_process_frame(frame)?;
@@ -84,101 +599,400 @@ where
             }
         }
 
-        // TODO check subscriptions
+        // Drop anything we haven't subscribed to before it takes up any space
+        if self
+            .transfer_manager
+            .subscription(frame.metadata.transfer_kind, frame.metadata.port_id)
+            .is_none()
+        {
+            return Ok(None);
+        }
 
-        println!("Port ID: {}", frame.metadata.port_id);
-        match self.transfer_manager.append_frame(&frame, metadata) {
-            Ok(tok) => {
-                println!("Frame appended");
-                Ok(tok)
-            }
+        let token = match self.transfer_manager.append_frame(&frame, metadata) {
+            Ok(tok) => tok,
             Err(UpdateTransferError::NoSpace) => {
                 // TODO should I handle this error explicitly? yes
-                println!("Out of space");
-                Ok(None)
+                None
             }
             Err(UpdateTransferError::DoesNotExist) => {
-                println!("Frame not appended?");
                 if !frame.first_frame {
-                    println!("New session no start....");
                     return Err(RxError::NewSessionNoStart);
                 }
 
                 match self.transfer_manager.new_transfer(&frame, metadata) {
-                    Ok(tok) => {
-                        println!("New transfer made");
-                        Ok(tok)
-                    }
+                    Ok(tok) => tok,
                     Err(CreateTransferError::AlreadyExists) => {
                         // This is theoretically unreachable
                         // TODO handle error
-                        println!("Transferx exists!");
-                        Ok(None)
+                        None
                     }
+                    // Already received, e.g. over a redundant interface
+                    Err(CreateTransferError::Duplicate) => None,
                     Err(CreateTransferError::NoSpace) => {
                         // TODO handle error
-                        println!("new transfer Out of space");
-                        Ok(None)
-                    }
-                    Err(err) => {
-                        println!("Error: {:?}", err);
-                        Ok(None)
+                        None
                     }
+                    Err(CreateTransferError::RxError(e)) => return Err(e),
+                }
+            }
+            Err(UpdateTransferError::RxError(e)) => return Err(e),
+            Err(UpdateTransferError::TimedOut) => return Err(RxError::Timeout),
+        };
+
+        // Services the node answers itself never make it to the user
+        match token {
+            Some(token) if frame.metadata.transfer_kind == TransferKind::Request => {
+                Ok(self.take_request(&frame.metadata, token))
+            }
+            Some(token)
+                if frame.metadata.transfer_kind == TransferKind::Response
+                    && self.client_services.contains(&frame.metadata.port_id) =>
+            {
+                self.take_response(&frame.metadata, token);
+                Ok(None)
+            }
+            Some(token)
+                if frame.metadata.transfer_kind == TransferKind::Message
+                    && self
+                        .allocation
+                        .as_ref()
+                        .is_some_and(|a| a.subject_id() == frame.metadata.port_id) =>
+            {
+                self.take_allocation(token);
+                Ok(None)
+            }
+            Some(token)
+                if frame.metadata.transfer_kind == TransferKind::Message
+                    && self
+                        .serve_allocations
+                        .is_some_and(|v| v.subject_id() == frame.metadata.port_id) =>
+            {
+                self.take_allocation_request(token);
+                Ok(None)
+            }
+            token => Ok(token),
+        }
+    }
+
+    /// Takes in a response to a service used through [`Node::send_request`], keeping a copy of
+    /// it if a request is waiting for it.
+    ///
+    /// The transfer manager may drop or replace the transfer before [`Node::poll_client`] is
+    /// called, so it's copied out right away.
+    fn take_response(&mut self, metadata: &TransferMetadata<C>, token: M::RxTransferToken) {
+        let waiting = metadata.source_node_id.and_then(|server_node_id| {
+            let id = RequestId {
+                service_id: metadata.port_id,
+                server_node_id,
+                transfer_id: metadata.transfer_id,
+            };
+            self.outstanding_requests.remove(&id).map(|_| id)
+        });
+        let Some(id) = waiting else {
+            // The token was just handed out, so it's valid
+            let _ = self.transfer_manager.cancel_rx_transfer(token);
+            return;
+        };
+
+        let client_events = &mut self.client_events;
+        // The token was just handed out, so it's valid
+        let _ = self.transfer_manager.with_rx_transfer(token, |_, payload| {
+            // Can't fail: send_request leaves room for a response to every outstanding request,
+            // and the length is capped to the capacity. Anything beyond RESPONSE is past the
+            // service's extent.
+            let len = core::cmp::min(payload.len(), RESPONSE);
+            let _ =
+                client_events.push_back((id, heapless::Vec::from_slice(&payload[0..len]).unwrap()));
+        });
+    }
+
+    /// Takes in a message on the allocation subject, taking on the node ID if it was allocated
+    /// to this node.
+    fn take_allocation(&mut self, token: M::RxTransferToken) {
+        let Some(allocation) = &self.allocation else {
+            return;
+        };
+
+        let mut node_id = None;
+        // The token was just handed out, so it's valid
+        let _ = self
+            .transfer_manager
+            .with_rx_transfer(token, |metadata, payload| {
+                node_id = allocation.handle_message(metadata.source_node_id, payload);
+            });
+
+        if node_id.is_some() {
+            self.id = node_id;
+            let version = allocation.version();
+            self.allocation = None;
+            let _ = self.transfer_manager.unsubscribe(Subscription::new(
+                TransferKind::Message,
+                version.subject_id(),
+                version.extent(),
+                embedded_time::duration::Milliseconds(1000),
+            ));
+        }
+    }
+
+    /// Takes in a message on the allocator's subject, keeping it if it's a request.
+    fn take_allocation_request(&mut self, token: M::RxTransferToken) {
+        let pending_allocation_requests = &mut self.pending_allocation_requests;
+        // The token was just handed out, so it's valid
+        let _ = self
+            .transfer_manager
+            .with_rx_transfer(token, |metadata, payload| {
+                // Anything not anonymous is a response from another allocator
+                if metadata.source_node_id.is_none() {
+                    let len = core::cmp::min(payload.len(), AllocationDataV2::SIZE);
+                    // Can't fail, the length is capped to the capacity. Requests beyond
+                    // MAX_PENDING_ALLOCATION_REQUESTS are dropped.
+                    let _ = pending_allocation_requests
+                        .push_back(heapless::Vec::from_slice(&payload[0..len]).unwrap());
+                }
+            });
+    }
+
+    /// Takes in a received request if the node answers it itself, handing the token back if not.
+    fn take_request(
+        &mut self,
+        metadata: &TransferMetadata<C>,
+        token: M::RxTransferToken,
+    ) -> Option<M::RxTransferToken> {
+        match metadata.port_id {
+            get_info::SERVICE_ID if self.info.is_some() => {
+                // Service transfers can't be anonymous
+                if let Some(client) = metadata.source_node_id {
+                    // Requests beyond MAX_PENDING_INFO_RESPONSES are dropped, the client will
+                    // retry
+                    let _ = self.pending_info_responses.push_back((
+                        client,
+                        metadata.transfer_id,
+                        metadata.priority,
+                    ));
                 }
+                // The request has no payload. The token was just handed out, so it's valid.
+                let _ = self.transfer_manager.cancel_rx_transfer(token);
+                None
+            }
+            #[cfg(feature = "alloc")]
+            register::ACCESS_SERVICE_ID | register::LIST_SERVICE_ID if self.serve_registers => {
+                self.take_register_request(token);
+                None
             }
-            Err(UpdateTransferError::RxError(e)) => Err(e),
-            Err(UpdateTransferError::TimedOut) => Err(RxError::Timeout),
+            _ => Some(token),
         }
     }
 
-    // TODO implement
-    // This needs to take: data, metadata, timestamp
+    /// Takes in a register request, keeping it to be answered by [`Node::poll_registers`].
+    #[cfg(feature = "alloc")]
+    fn take_register_request(&mut self, token: M::RxTransferToken) {
+        let pending_register_requests = &mut self.pending_register_requests;
+        // The token was just handed out, so it's valid
+        let _ = self
+            .transfer_manager
+            .with_rx_transfer(token, |metadata, payload| {
+                // Service transfers can't be anonymous
+                let Some(client) = metadata.source_node_id else {
+                    return;
+                };
+
+                let request = match metadata.port_id {
+                    register::ACCESS_SERVICE_ID => {
+                        AccessRequest::deserialize(payload).map(RegisterRequest::Access)
+                    }
+                    _ => ListRequest::deserialize(payload).map(RegisterRequest::List),
+                };
+                if let Some(request) = request {
+                    // Requests beyond MAX_PENDING_REGISTER_REQUESTS are dropped
+                    let _ = pending_register_requests.push_back((
+                        client,
+                        metadata.transfer_id,
+                        metadata.priority,
+                        request,
+                    ));
+                }
+            });
+    }
+
+    /// Transfer ID the next transfer of this kind will use on a port.
+    ///
+    /// Messages count per subject, and requests per service and destination.
+    pub fn next_transfer_id(
+        &self,
+        transfer_kind: TransferKind,
+        port_id: PortId,
+        destination_node_id: Option<NodeId>,
+    ) -> TransferId {
+        self.transfer_ids
+            .get(&(transfer_kind, port_id, destination_node_id))
+            .copied()
+            .unwrap_or(0)
+    }
+
     // Generally I think the API around starting a transfer needs a bit of thought
+    /// Creates a new TX transfer, filling its payload in through `cb`.
+    ///
+    /// The transfer ID is picked by the node, which keeps a counter for every port (see
+    /// [`Node::next_transfer_id`]). Responses reuse the request's transfer ID instead. Once
+    /// `PORTS` ports are counted, transfers on any other port fail with
+    /// [`CreateTransferError::NoSpace`].
+    ///
+    /// Frames of the transfer that haven't gone out by `deadline` are dropped, which
+    /// [`Node::transmit_frame`] and [`Node::pop_frame`] report as
+    /// [`TransmitFrameError::Expired`]. The transfer manager may drop it through
+    /// [`TransferManager::update_transfers`] as well.
     pub fn start_tx_transfer<E>(
         &mut self,
         requested_buffer_size: usize,
-        timestamp: embedded_time::Instant<C>,
+        deadline: embedded_time::Instant<C>,
         priority: crate::Priority,
         port_id: crate::PortId,
         tx_kind: TransmissionType,
-        transfer_id: TransferId,
         cb: impl FnOnce(&mut [u8]) -> Result<usize, E>,
     ) -> Result<M::TxTransferToken, InternalOrUserError<CreateTransferError, E>> {
+        let (transfer_kind, destination_node_id) = match tx_kind {
+            TransmissionType::Response(id, _) => (TransferKind::Response, Some(id)),
+            TransmissionType::Request(id) => (TransferKind::Request, Some(id)),
+            TransmissionType::Broadcast => (TransferKind::Message, None),
+        };
+        let transfer_id = match tx_kind {
+            TransmissionType::Response(_, transfer_id) => transfer_id,
+            _ => self.next_transfer_id(transfer_kind, port_id, destination_node_id),
+        };
+        let key = (transfer_kind, port_id, destination_node_id);
+        // Forgetting a counter would risk reusing transfer IDs the receivers still remember
+        if transfer_kind != TransferKind::Response
+            && !self.transfer_ids.contains_key(&key)
+            && self.transfer_ids.len() == PORTS
+        {
+            return Err(InternalOrUserError::InternalError(
+                CreateTransferError::NoSpace,
+            ));
+        }
+
         let metadata = TransferMetadata {
-            timestamp: timestamp,
-            priority: priority,
-            transfer_kind: match tx_kind {
-                TransmissionType::Response(_) => TransferKind::Response,
-                TransmissionType::Request(_) => TransferKind::Request,
-                TransmissionType::Broadcast => TransferKind::Message,
-            },
-            port_id: port_id,
-            // TODO make psuedorandom if anon
+            timestamp: deadline,
+            priority,
+            transfer_kind,
+            port_id,
+            // The transport picks a pseudo-random source ID for anonymous transfers
             source_node_id: self.id,
-            destination_node_id: match tx_kind {
-                TransmissionType::Response(id) | TransmissionType::Request(id) => Some(id),
-                TransmissionType::Broadcast => None,
-            },
-            transfer_id: transfer_id,
+            destination_node_id,
+            transfer_id,
         };
-        self.transfer_manager
-            .create_transmission(requested_buffer_size, &metadata, cb)
+        let token =
+            self.transfer_manager
+                .create_transmission(requested_buffer_size, &metadata, cb)?;
+
+        // Only count transfers that actually go out
+        if transfer_kind != TransferKind::Response {
+            // Can't fail, checked above
+            let _ = self
+                .transfer_ids
+                .insert(key, T::next_transfer_id(transfer_id));
+        }
+
+        Ok(token)
     }
 
     // TODO users may want a variant of this function that preserves the token
     // so they can peek the transfer metadata for logging
     /// Creates a new frame for the provided transport to provide.
+    ///
+    /// This leaves the interleaving of concurrent transfers up to the caller, see
+    /// [`Node::enqueue`] to have them sent by priority instead. Transfers past their deadline
+    /// are dropped with [`TransmitFrameError::Expired`].
     pub fn transmit_frame(
         &mut self,
         token: M::TxTransferToken,
         // TODO node should hold a clock instance
         timestamp: embedded_time::Instant<C>,
     ) -> Result<(T::Frame, Option<M::TxTransferToken>), TransmitFrameError> {
+        self.next_frame(token, timestamp)
+            .map(|(frame, _, token)| (frame, token))
+    }
+
+    /// Queues every frame of a transfer, to be handed out by [`Node::pop_frame`].
+    ///
+    /// On errors the transfer is dropped without queueing any of its frames, which includes the
+    /// queue not having room for all of them ([`TransmitFrameError::QueueFull`]).
+    pub fn enqueue(
+        &mut self,
+        mut token: M::TxTransferToken,
+        now: embedded_time::Instant<C>,
+    ) -> Result<(), TransmitFrameError> {
+        let queued = self.tx_queue.len();
+        loop {
+            let (frame, metadata, next) = match self.next_frame(token, now) {
+                Ok(next) => next,
+                Err(e) => {
+                    self.tx_queue.truncate(queued);
+                    return Err(e);
+                }
+            };
+
+            let key = (T::transmit_order(&metadata), self.tx_queued);
+            if self
+                .tx_queue
+                .push((key, frame, metadata.timestamp))
+                .is_err()
+            {
+                if let Some(next) = next {
+                    // Dropping any returned error here, the token was just handed out
+                    let _ = self.transfer_manager.cancel_tx_transfer(next);
+                }
+                self.tx_queue.truncate(queued);
+                return Err(TransmitFrameError::QueueFull);
+            }
+            self.tx_queued += 1;
+
+            match next {
+                Some(next) => token = next,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns the next queued frame for the driver to transmit, or `None` once the queue is
+    /// empty.
+    ///
+    /// Frames go out by [`Transport::transmit_order`], which for CAN is their arbitration ID,
+    /// so a large transfer doesn't hold up anything of a higher priority queued after it.
+    /// Frames whose transfer's deadline is before `now` are dropped, each one returning
+    /// [`TransmitFrameError::Expired`] so they can be counted.
+    pub fn pop_frame(
+        &mut self,
+        now: embedded_time::Instant<C>,
+    ) -> Option<Result<T::Frame, TransmitFrameError>> {
+        // The queue is small enough to search through
+        let (index, _) = self
+            .tx_queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (key, _, _))| *key)?;
+        let (_, frame, deadline) = self.tx_queue.swap_remove(index);
+        if now > deadline {
+            return Some(Err(TransmitFrameError::Expired));
+        }
+        Some(Ok(frame))
+    }
+
+    /// Creates the next frame of a transfer, dropping the transfer if its deadline has passed.
+    fn next_frame(
+        &mut self,
+        token: M::TxTransferToken,
+        timestamp: embedded_time::Instant<C>,
+    ) -> Result<NextFrame<M, T, C>, TransmitFrameError> {
         let mut frame_out = Err(TransmitFrameError::InvalidHandling);
         let res = M::transmit(
             &mut self.transfer_manager,
             token,
             |transfer_metadata, transport_metadata, data| {
+                if timestamp > transfer_metadata.timestamp {
+                    frame_out = Err(TransmitFrameError::Expired);
+                    return 0;
+                }
+
                 let frame = T::transmit_frame(
                     transfer_metadata,
                     transport_metadata,
@@ -188,7 +1002,7 @@ where
                 );
                 match frame {
                     Ok((frame, consumed)) => {
-                        frame_out = Ok(frame);
+                        frame_out = Ok((frame, *transfer_metadata));
                         consumed
                     }
 
@@ -203,22 +1017,979 @@ where
         match res {
             Ok(token) => {
                 match frame_out {
-                    Ok(frame) => Ok((frame, token)),
-                    // Some TxError occurred, so we can't continue sending things,
-                    // clean up.
-                    Err(TransmitFrameError::TxError(e)) => {
+                    Ok((frame, metadata)) => Ok((frame, metadata, token)),
+                    // Some TxError occurred or the deadline passed, so we can't continue
+                    // sending things, clean up.
+                    Err(e @ (TransmitFrameError::TxError(_) | TransmitFrameError::Expired)) => {
                         if let Some(token) = token {
                             // Dropping any returned error here, the token should be correct
                             // from the fact we got a transmit error
                             let _ = self.transfer_manager.cancel_tx_transfer(token);
                         }
-                        Err(TransmitFrameError::TxError(e))
+                        Err(e)
                     }
                     // Generic error, just return it and move on
                     Err(e) => Err(e),
                 }
             }
+            // The transfer manager dropped it in `update_transfers`
+            Err(TokenAccessError::TransferTimeout) => Err(TransmitFrameError::Expired),
             Err(e) => Err(TransmitFrameError::TokenError(e)),
         }
     }
 }
+
+/// Frame of a transfer, the transfer's metadata and the token for the rest of the transfer if
+/// there is more to it.
+type NextFrame<M, T, C> = (
+    <T as Transport<C>>::Frame,
+    TransferMetadata<C>,
+    Option<<M as TransferManager<C, T>>::TxTransferToken>,
+);
+
+/// Frame queued through [`Node::enqueue`], with its place in the queue and its transfer's
+/// deadline.
+type QueuedFrame<F, C> = ((u32, u64), F, embedded_time::Instant<C>);
+
+/// Unwraps errors from creating transfers whose payload can't fail to serialize.
+fn internal_error<E>(e: InternalOrUserError<E, core::convert::Infallible>) -> E {
+    match e {
+        InternalOrUserError::InternalError(e) => e,
+        InternalOrUserError::UserError(e) => match e {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use crate::time::TestClock;
+    use crate::transfer::HeapTransferManager;
+    use crate::transport::can::{Can, CanFrame};
+
+    use alloc::vec::Vec;
+    use embedded_time::Clock;
+    use embedded_time::duration::Milliseconds;
+
+    type TestNode = Node<HeapTransferManager<TestClock, Can>, Can, TestClock>;
+
+    fn publish(
+        node: &mut TestNode,
+        clock: &TestClock,
+        port_id: PortId,
+        tx_kind: TransmissionType,
+        payload: &[u8],
+    ) -> Vec<CanFrame<TestClock>> {
+        let deadline = clock.try_now().unwrap();
+        let token = start(node, deadline, Priority::Nominal, port_id, tx_kind, payload);
+        transmit_all(node, clock, token)
+    }
+
+    fn start(
+        node: &mut TestNode,
+        deadline: embedded_time::Instant<TestClock>,
+        priority: Priority,
+        port_id: PortId,
+        tx_kind: TransmissionType,
+        payload: &[u8],
+    ) -> <HeapTransferManager<TestClock, Can> as TransferManager<TestClock, Can>>::TxTransferToken
+    {
+        node.start_tx_transfer(payload.len(), deadline, priority, port_id, tx_kind, |buf| {
+            buf.copy_from_slice(payload);
+            Ok::<usize, ()>(payload.len())
+        })
+        .unwrap()
+    }
+
+    fn transmit_all(
+        node: &mut TestNode,
+        clock: &TestClock,
+        mut token: <HeapTransferManager<TestClock, Can> as TransferManager<TestClock, Can>>::TxTransferToken,
+    ) -> Vec<CanFrame<TestClock>> {
+        let mut frames = Vec::new();
+        loop {
+            let (frame, next) = node
+                .transmit_frame(token, clock.try_now().unwrap())
+                .unwrap();
+            frames.push(frame);
+            match next {
+                Some(next) => token = next,
+                None => break,
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn subscriptions_filter_frames() {
+        let clock = TestClock::default();
+        let mut publisher = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut node = TestNode::new(Some(2), HeapTransferManager::new());
+        let subscription = Subscription::new(TransferKind::Message, 100, 64, Milliseconds(1000));
+
+        // Nothing is received before subscribing
+        let frames = publish(
+            &mut publisher,
+            &clock,
+            100,
+            TransmissionType::Broadcast,
+            b"hello",
+        );
+        assert!(matches!(node.try_receive_frame(&frames[0]), Ok(None)));
+
+        node.subscribe(subscription).unwrap();
+        assert!(matches!(
+            node.subscribe(subscription),
+            Err(SubscriptionError::SubscriptionExists)
+        ));
+
+        let frames = publish(
+            &mut publisher,
+            &clock,
+            100,
+            TransmissionType::Broadcast,
+            b"hello",
+        );
+        let token = node.try_receive_frame(&frames[0]).unwrap().unwrap();
+        node.transfer_manager
+            .with_rx_transfer(token, |metadata, payload| {
+                assert_eq!(metadata.port_id, 100);
+                assert_eq!(payload, b"hello");
+            })
+            .unwrap();
+
+        // Other ports are still dropped, even when not starting a transfer
+        let frames = publish(
+            &mut publisher,
+            &clock,
+            101,
+            TransmissionType::Broadcast,
+            &[0u8; 19],
+        );
+        assert!(matches!(node.try_receive_frame(&frames[0]), Ok(None)));
+        assert!(matches!(node.try_receive_frame(&frames[1]), Ok(None)));
+
+        // Ongoing transfers are dropped along with the subscription
+        let frames = publish(
+            &mut publisher,
+            &clock,
+            100,
+            TransmissionType::Broadcast,
+            &[0u8; 19],
+        );
+        assert!(matches!(node.try_receive_frame(&frames[0]), Ok(None)));
+        node.unsubscribe(subscription).unwrap();
+        assert!(matches!(node.try_receive_frame(&frames[1]), Ok(None)));
+        assert!(matches!(node.try_receive_frame(&frames[2]), Ok(None)));
+        assert!(matches!(
+            node.unsubscribe(subscription),
+            Err(SubscriptionError::SubscriptionDoesNotExist)
+        ));
+    }
+
+    /// Service transfers to other nodes can safely be ignored.
+    #[test]
+    fn discard_misguided_service_frames() {
+        let clock = TestClock::default();
+        let mut client = TestNode::new(Some(41), HeapTransferManager::new());
+        let mut node = TestNode::new(Some(42), HeapTransferManager::new());
+        let mut anonymous = TestNode::new(None, HeapTransferManager::new());
+        for kind in [TransferKind::Request, TransferKind::Response] {
+            let subscription = Subscription::new(kind, 100, 64, Milliseconds(1000));
+            node.subscribe(subscription).unwrap();
+            anonymous.subscribe(subscription).unwrap();
+        }
+
+        for tx_kind in [
+            TransmissionType::Request(43),
+            TransmissionType::Response(43, 0),
+        ] {
+            let frames = publish(&mut client, &clock, 100, tx_kind, b"hello");
+            assert!(
+                matches!(node.try_receive_frame(&frames[0]), Ok(None)),
+                "Didn't discard misguided {:?}",
+                tx_kind
+            );
+            assert!(
+                matches!(anonymous.try_receive_frame(&frames[0]), Ok(None)),
+                "Didn't discard {:?} with an anonymous node",
+                tx_kind
+            );
+        }
+
+        // The same request does reach the node it is addressed to
+        let frames = publish(
+            &mut client,
+            &clock,
+            100,
+            TransmissionType::Request(42),
+            b"hello",
+        );
+        let token = node.try_receive_frame(&frames[0]).unwrap().unwrap();
+        node.transfer_manager
+            .with_rx_transfer(token, |metadata, payload| {
+                assert_eq!(metadata.source_node_id, Some(41));
+                assert_eq!(payload, b"hello");
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn transfer_ids_per_port() {
+        let clock = TestClock::default();
+        let mut node = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut send = |port_id, tx_kind| {
+            let frames = publish(&mut node, &clock, port_id, tx_kind, b"hello");
+            let (frame, _) = Can::rx_process_frame(&frames[0]).unwrap();
+            frame.metadata.transfer_id
+        };
+
+        // Subjects count independently, and wrap around at CAN's 5 bits
+        for transfer_id in 0..32 {
+            assert_eq!(send(100, TransmissionType::Broadcast), transfer_id);
+        }
+        assert_eq!(send(100, TransmissionType::Broadcast), 0);
+        assert_eq!(send(101, TransmissionType::Broadcast), 0);
+
+        // Requests count per destination, responses reuse the request's ID
+        assert_eq!(send(430, TransmissionType::Request(2)), 0);
+        assert_eq!(send(430, TransmissionType::Request(2)), 1);
+        assert_eq!(send(430, TransmissionType::Request(3)), 0);
+        assert_eq!(send(430, TransmissionType::Response(2, 17)), 17);
+        assert_eq!(send(430, TransmissionType::Request(2)), 2);
+
+        assert_eq!(node.next_transfer_id(TransferKind::Message, 100, None), 1);
+    }
+
+    #[test]
+    fn heartbeat_published() {
+        let mut clock = TestClock::default();
+        let mut node = TestNode::new(Some(1), HeapTransferManager::new());
+        node.set_health(Health::Advisory);
+        node.set_vendor_specific_status_code(0x42);
+
+        fn poll(node: &mut TestNode, clock: &TestClock) -> Option<Heartbeat> {
+            let token = node.poll(clock.try_now().unwrap()).unwrap()?;
+            let frames = transmit_all(node, clock, token);
+            assert_eq!(frames.len(), 1);
+            let (frame, _) = Can::rx_process_frame(&frames[0]).unwrap();
+            assert_eq!(frame.metadata.port_id, heartbeat::SUBJECT_ID);
+            assert_eq!(frame.metadata.transfer_kind, TransferKind::Message);
+            Heartbeat::deserialize(frame.payload)
+        }
+
+        // Published straight away, and then once a second
+        let heartbeat = poll(&mut node, &clock).unwrap();
+        assert_eq!(heartbeat.uptime, 0);
+        assert_eq!(heartbeat.health, Health::Advisory);
+        assert_eq!(heartbeat.mode, Mode::Initialization);
+        assert_eq!(heartbeat.vendor_specific_status_code, 0x42);
+
+        clock.add_duration(&Milliseconds(999u32)).unwrap();
+        assert!(poll(&mut node, &clock).is_none());
+        clock.add_duration(&Milliseconds(1u32)).unwrap();
+        node.set_mode(Mode::Operational);
+        let heartbeat = poll(&mut node, &clock).unwrap();
+        assert_eq!(heartbeat.uptime, 1);
+        assert_eq!(heartbeat.mode, Mode::Operational);
+        assert!(poll(&mut node, &clock).is_none());
+
+        // Missed publications still count towards uptime
+        clock.add_duration(&Milliseconds(2500u32)).unwrap();
+        assert_eq!(poll(&mut node, &clock).unwrap().uptime, 3);
+        clock.add_duration(&Milliseconds(500u32)).unwrap();
+        assert_eq!(poll(&mut node, &clock).unwrap().uptime, 4);
+    }
+
+    #[test]
+    fn anonymous_heartbeat() {
+        let clock = TestClock::default();
+        let mut node = TestNode::new(None, HeapTransferManager::new());
+        assert!(node.poll(clock.try_now().unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn get_info_answered() {
+        let clock = TestClock::default();
+        let mut server = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut client = TestNode::new(Some(2), HeapTransferManager::new());
+        client
+            .subscribe(Subscription::new(
+                TransferKind::Response,
+                get_info::SERVICE_ID,
+                get_info::RESPONSE_EXTENT,
+                Milliseconds(1000),
+            ))
+            .unwrap();
+
+        let mut info = NodeInfo::new("org.example.server".into(), [0x5A; 16]);
+        info.software_image_crc = Some(0xDEADBEEF);
+        server.set_info(info.clone()).unwrap();
+
+        // Get the heartbeat out of the way
+        assert!(server.poll(clock.try_now().unwrap()).unwrap().is_some());
+        assert!(server.poll(clock.try_now().unwrap()).unwrap().is_none());
+
+        // Requests are taken in by the node, answered on the next poll
+        for _ in 0..3 {
+            publish(&mut client, &clock, 430, TransmissionType::Request(1), &[]);
+        }
+        let frames = publish(&mut client, &clock, 430, TransmissionType::Request(1), &[]);
+        assert!(matches!(server.try_receive_frame(&frames[0]), Ok(None)));
+
+        // Each request gets its own response, even from the same client
+        let frames = publish(&mut client, &clock, 430, TransmissionType::Request(1), &[]);
+        assert!(matches!(server.try_receive_frame(&frames[0]), Ok(None)));
+
+        for transfer_id in [3, 4] {
+            let token = server.poll(clock.try_now().unwrap()).unwrap().unwrap();
+            let frames = transmit_all(&mut server, &clock, token);
+            assert!(frames.len() > 1);
+
+            let mut token = None;
+            for frame in &frames {
+                token = client.try_receive_frame(frame).unwrap();
+            }
+            client
+                .transfer_manager
+                .with_rx_transfer(token.unwrap(), |metadata, payload| {
+                    assert_eq!(metadata.transfer_kind, TransferKind::Response);
+                    assert_eq!(metadata.source_node_id, Some(1));
+                    assert_eq!(metadata.destination_node_id, Some(2));
+                    assert_eq!(metadata.transfer_id, transfer_id);
+                    assert_eq!(NodeInfo::deserialize(payload), Some(info.clone()));
+                })
+                .unwrap();
+        }
+        assert!(server.poll(clock.try_now().unwrap()).unwrap().is_none());
+
+        // Without info, requests are left to the user
+        let mut server = TestNode::new(Some(1), HeapTransferManager::new());
+        let frames = publish(&mut client, &clock, 430, TransmissionType::Request(1), &[]);
+        assert!(matches!(server.try_receive_frame(&frames[0]), Ok(None)));
+        assert!(server.poll(clock.try_now().unwrap()).unwrap().is_some());
+        assert!(server.poll(clock.try_now().unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn service_client() {
+        use crate::get_info::GetInfo;
+
+        let mut clock = TestClock::default();
+        let mut server = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut client = TestNode::new(Some(2), HeapTransferManager::new());
+        let info = NodeInfo::new("org.example.server".into(), [0x5A; 16]);
+        server.set_info(info.clone()).unwrap();
+        assert!(server.poll(clock.try_now().unwrap()).unwrap().is_some());
+
+        let (token, request) = client
+            .send_request::<GetInfo>(
+                clock.try_now().unwrap(),
+                1,
+                Priority::Nominal,
+                &(),
+                Milliseconds(500),
+            )
+            .unwrap();
+        assert_eq!(request.id().transfer_id, 0);
+        for frame in transmit_all(&mut client, &clock, token) {
+            assert!(matches!(server.try_receive_frame(&frame), Ok(None)));
+        }
+        assert!(
+            client
+                .poll_client(clock.try_now().unwrap(), |_| ())
+                .is_none()
+        );
+
+        // The response is matched to the request and handed out once
+        let token = server.poll(clock.try_now().unwrap()).unwrap().unwrap();
+        for frame in transmit_all(&mut server, &clock, token) {
+            assert!(matches!(client.try_receive_frame(&frame), Ok(None)));
+        }
+        let response = client.poll_client(clock.try_now().unwrap(), |event| {
+            assert_eq!(event.request_id(), request.id());
+            assert!(!request.timed_out(&event));
+            request.response(&event)
+        });
+        assert_eq!(response, Some(Some(info)));
+        assert!(
+            client
+                .poll_client(clock.try_now().unwrap(), |_| ())
+                .is_none()
+        );
+
+        // Without a response, the request times out once its deadline passes
+        let (token, request) = client
+            .send_request::<GetInfo>(
+                clock.try_now().unwrap(),
+                1,
+                Priority::Nominal,
+                &(),
+                Milliseconds(500),
+            )
+            .unwrap();
+        assert_eq!(request.id().transfer_id, 1);
+        for frame in transmit_all(&mut client, &clock, token) {
+            assert!(matches!(server.try_receive_frame(&frame), Ok(None)));
+        }
+        clock.add_duration(&Milliseconds(499u32)).unwrap();
+        assert!(
+            client
+                .poll_client(clock.try_now().unwrap(), |_| ())
+                .is_none()
+        );
+        clock.add_duration(&Milliseconds(1u32)).unwrap();
+        let timed_out = client.poll_client(clock.try_now().unwrap(), |event| {
+            assert_eq!(request.response(&event), None);
+            request.timed_out(&event)
+        });
+        assert_eq!(timed_out, Some(true));
+
+        // A late response is dropped
+        let token = server.poll(clock.try_now().unwrap()).unwrap().unwrap();
+        for frame in transmit_all(&mut server, &clock, token) {
+            assert!(matches!(client.try_receive_frame(&frame), Ok(None)));
+        }
+        assert!(
+            client
+                .poll_client(clock.try_now().unwrap(), |_| ())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn client_responses_kept() {
+        use crate::get_info::GetInfo;
+
+        type Client = Node<HeapTransferManager<TestClock, Can>, Can, TestClock, 16, 2>;
+
+        let clock = TestClock::default();
+        let now = clock.try_now().unwrap();
+        let mut server = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut client = Client::new(Some(2), HeapTransferManager::new());
+        let info = NodeInfo::new("org.example.server".into(), [0x5A; 16]);
+        server.set_info(info.clone()).unwrap();
+
+        // Both responses come in on the same session before either is handed out
+        let mut requests = Vec::new();
+        for _ in 0..2 {
+            let (token, request) = client
+                .send_request::<GetInfo>(now, 1, Priority::Nominal, &(), Milliseconds(500))
+                .unwrap();
+            let (frame, next) = client.transmit_frame(token, now).unwrap();
+            assert!(next.is_none());
+            assert!(matches!(server.try_receive_frame(&frame), Ok(None)));
+            requests.push(request);
+        }
+        while let Some(token) = server.poll(now).unwrap() {
+            for frame in transmit_all(&mut server, &clock, token) {
+                assert!(matches!(client.try_receive_frame(&frame), Ok(None)));
+            }
+        }
+
+        // Responses waiting to be handed out hold up new requests
+        assert!(matches!(
+            client.send_request::<GetInfo>(now, 1, Priority::Nominal, &(), Milliseconds(500)),
+            Err(CreateTransferError::NoSpace)
+        ));
+        for request in &requests {
+            let response = client.poll_client(now, |event| request.response(&event));
+            assert_eq!(response, Some(Some(info.clone())));
+        }
+        assert!(client.poll_client(now, |_| ()).is_none());
+        assert!(
+            client
+                .send_request::<GetInfo>(now, 1, Priority::Nominal, &(), Milliseconds(500))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn registers_served() {
+        use crate::register::{ListResponse, MemoryRegistry, PortDirection, Value};
+        use alloc::vec;
+
+        let clock = TestClock::default();
+        let mut server = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut client = TestNode::new(Some(2), HeapTransferManager::new());
+        for (port_id, extent) in [
+            (register::ACCESS_SERVICE_ID, AccessResponse::MAX_SIZE),
+            (register::LIST_SERVICE_ID, ListResponse::MAX_SIZE),
+        ] {
+            client
+                .subscribe(Subscription::new(
+                    TransferKind::Response,
+                    port_id,
+                    extent,
+                    Milliseconds(1000),
+                ))
+                .unwrap();
+        }
+
+        let mut registry = MemoryRegistry::new();
+        registry.insert_port(
+            PortDirection::Publication,
+            "hello",
+            "example.Hello.1.0",
+            Some(100),
+        );
+        server.serve_registers().unwrap();
+
+        // Round trip of a request through the server, returning the response payload
+        let mut call = |port_id, request: &[u8]| {
+            let frames = publish(
+                &mut client,
+                &clock,
+                port_id,
+                TransmissionType::Request(1),
+                request,
+            );
+            for frame in &frames {
+                assert!(matches!(server.try_receive_frame(frame), Ok(None)));
+            }
+
+            let now = clock.try_now().unwrap();
+            let response = server.poll_registers(now, &mut registry).unwrap().unwrap();
+            assert!(server.poll_registers(now, &mut registry).unwrap().is_none());
+
+            let mut token = None;
+            for frame in &transmit_all(&mut server, &clock, response) {
+                token = client.try_receive_frame(frame).unwrap();
+            }
+            let mut response = Vec::new();
+            client
+                .transfer_manager
+                .with_rx_transfer(token.unwrap(), |metadata, payload| {
+                    assert_eq!(metadata.port_id, port_id);
+                    assert_eq!(metadata.destination_node_id, Some(2));
+                    response.extend_from_slice(payload);
+                })
+                .unwrap();
+            response
+        };
+
+        let mut buffer = [0u8; AccessRequest::MAX_SIZE];
+        let len = ListRequest { index: 0 }.serialize(&mut buffer);
+        let response = ListResponse::deserialize(&call(register::LIST_SERVICE_ID, &buffer[0..len]));
+        assert_eq!(response.unwrap().name, "uavcan.pub.hello.id");
+
+        let request = AccessRequest {
+            name: "uavcan.pub.hello.id".into(),
+            value: Value::Natural16(vec![200]),
+        };
+        let len = request.serialize(&mut buffer);
+        let response =
+            AccessResponse::deserialize(&call(register::ACCESS_SERVICE_ID, &buffer[0..len]))
+                .unwrap();
+        assert_eq!(response.value, Value::Natural16(vec![200]));
+        assert!(response.mutable);
+
+        assert_eq!(
+            register::port_id(&registry, PortDirection::Publication, "hello"),
+            Some(200)
+        );
+    }
+
+    #[test]
+    fn node_id_allocated() {
+        use crate::pnp::{self, AllocationDataV1, ProtocolVersion};
+
+        let mut clock = TestClock::default();
+        let mut allocator = TestNode::new(Some(127), HeapTransferManager::new());
+        let mut node = TestNode::new(None, HeapTransferManager::new());
+        let unique_id = [0x17; 16];
+        node.start_allocation(pnp::AllocationClient::new(
+            ProtocolVersion::V1,
+            unique_id,
+            None,
+        ))
+        .unwrap();
+
+        // Wait out the random delay before the first request
+        let frames = loop {
+            if let Some(token) = node.poll(clock.try_now().unwrap()).unwrap() {
+                break transmit_all(&mut node, &clock, token);
+            }
+            clock.add_duration(&Milliseconds(10u32)).unwrap();
+        };
+        assert_eq!(frames.len(), 1);
+        let (frame, _) = Can::rx_process_frame(&frames[0]).unwrap();
+        assert_eq!(frame.metadata.port_id, pnp::SUBJECT_ID_V1);
+        assert_eq!(frame.metadata.source_node_id, None);
+        let request = AllocationDataV1::deserialize(frame.payload).unwrap();
+        assert_eq!(request.unique_id_hash, pnp::unique_id_hash(&unique_id));
+
+        // Responses for other nodes are ignored
+        let mut response = [0u8; AllocationDataV1::MAX_SIZE];
+        let mut respond = |allocator: &mut TestNode, unique_id_hash| {
+            AllocationDataV1 {
+                unique_id_hash,
+                allocated_node_id: Some(42),
+            }
+            .serialize(&mut response);
+            publish(
+                allocator,
+                &clock,
+                pnp::SUBJECT_ID_V1,
+                TransmissionType::Broadcast,
+                &response,
+            )
+        };
+        for frame in respond(&mut allocator, request.unique_id_hash ^ 1) {
+            assert!(matches!(node.try_receive_frame(&frame), Ok(None)));
+        }
+        assert_eq!(node.id(), None);
+
+        for frame in respond(&mut allocator, request.unique_id_hash) {
+            assert!(matches!(node.try_receive_frame(&frame), Ok(None)));
+        }
+        assert_eq!(node.id(), Some(42));
+
+        // Allocation is over, the node now publishes heartbeats under its new ID. Uptime kept
+        // counting while anonymous, so the first one is due on the next whole second.
+        assert!(node.poll(clock.try_now().unwrap()).unwrap().is_none());
+        clock.add_duration(&Milliseconds(1000u32)).unwrap();
+        let token = node.poll(clock.try_now().unwrap()).unwrap().unwrap();
+        let frames = transmit_all(&mut node, &clock, token);
+        let (frame, _) = Can::rx_process_frame(&frames[0]).unwrap();
+        assert_eq!(frame.metadata.port_id, heartbeat::SUBJECT_ID);
+        assert_eq!(frame.metadata.source_node_id, Some(42));
+    }
+
+    /// Version 1 requests go out in a single frame even with a preferred node ID, under a
+    /// pseudo-random source ID that differs between nodes.
+    #[test]
+    fn allocation_requests_anonymous() {
+        use crate::pnp::{self, AllocationDataV1, ProtocolVersion};
+        use crate::transport::can::CanMessageId;
+
+        let mut clock = TestClock::default();
+        let mut requests = Vec::new();
+        for unique_id in [[0x17; 16], [0x18; 16]] {
+            let mut node = TestNode::new(None, HeapTransferManager::new());
+            node.start_allocation(pnp::AllocationClient::new(
+                ProtocolVersion::V1,
+                unique_id,
+                Some(42),
+            ))
+            .unwrap();
+
+            let frames = loop {
+                if let Some(token) = node.poll(clock.try_now().unwrap()).unwrap() {
+                    break transmit_all(&mut node, &clock, token);
+                }
+                clock.add_duration(&Milliseconds(10u32)).unwrap();
+            };
+            assert_eq!(frames.len(), 1);
+            let (frame, _) = Can::rx_process_frame(&frames[0]).unwrap();
+            assert_eq!(frame.metadata.source_node_id, None);
+            let request = AllocationDataV1::deserialize(frame.payload).unwrap();
+            assert_eq!(request.unique_id_hash, pnp::unique_id_hash(&unique_id));
+            assert_eq!(request.allocated_node_id, None);
+            requests.push(CanMessageId::from(frames[0].id).source_id());
+        }
+        assert_ne!(requests[0], requests[1]);
+    }
+
+    #[test]
+    fn allocations_served() {
+        use crate::pnp::{self, Allocation, Allocator, MemoryStorage};
+
+        /// Fails to store the first allocation.
+        struct FlakyStorage {
+            failed: bool,
+            storage: MemoryStorage,
+        }
+
+        impl AllocationStorage for FlakyStorage {
+            type Error = ();
+
+            fn load(&mut self) -> Result<Vec<Allocation>, ()> {
+                Ok(Vec::new())
+            }
+
+            fn store(&mut self, allocation: &Allocation) -> Result<(), ()> {
+                if !self.failed {
+                    self.failed = true;
+                    return Err(());
+                }
+                let _ = self.storage.store(allocation);
+                Ok(())
+            }
+        }
+
+        let mut clock = TestClock::default();
+        let mut server = TestNode::new(Some(125), HeapTransferManager::new());
+        let mut node = TestNode::new(None, HeapTransferManager::new());
+        let storage = FlakyStorage {
+            failed: false,
+            storage: MemoryStorage::default(),
+        };
+        let mut allocator = Allocator::new(storage, 125).unwrap();
+        server.serve_allocations(ProtocolVersion::V1).unwrap();
+        node.start_allocation(pnp::AllocationClient::new(
+            ProtocolVersion::V1,
+            [0x99; 16],
+            None,
+        ))
+        .unwrap();
+
+        let frames = loop {
+            if let Some(token) = node.poll(clock.try_now().unwrap()).unwrap() {
+                break transmit_all(&mut node, &clock, token);
+            }
+            clock.add_duration(&Milliseconds(10u32)).unwrap();
+        };
+        assert!(matches!(server.try_receive_frame(&frames[0]), Ok(None)));
+
+        // A request that couldn't be stored is handled again. The server's own ID is never
+        // handed out.
+        let now = clock.try_now().unwrap();
+        assert!(matches!(
+            server.poll_allocator(now, &mut allocator),
+            Err(InternalOrUserError::UserError(()))
+        ));
+        let token = server.poll_allocator(now, &mut allocator).unwrap().unwrap();
+        assert!(
+            server
+                .poll_allocator(now, &mut allocator)
+                .unwrap()
+                .is_none()
+        );
+        for frame in transmit_all(&mut server, &clock, token) {
+            assert!(matches!(node.try_receive_frame(&frame), Ok(None)));
+        }
+        assert_eq!(node.id(), Some(124));
+        assert_eq!(allocator.allocations().count(), 1);
+    }
+
+    #[test]
+    fn tx_queue_ordered_by_priority() {
+        use crate::transport::can::{CanMessageId, CanServiceId};
+
+        let clock = TestClock::default();
+        let mut node = TestNode::new(Some(1), HeapTransferManager::new());
+        let now = clock.try_now().unwrap();
+
+        // A low priority transfer of 3 frames is queued first
+        let token = start(
+            &mut node,
+            now,
+            Priority::Low,
+            100,
+            TransmissionType::Broadcast,
+            &[0u8; 16],
+        );
+        node.enqueue(token, now).unwrap();
+        for (priority, port_id, tx_kind) in [
+            (Priority::High, 200, TransmissionType::Request(2)),
+            (Priority::High, 300, TransmissionType::Broadcast),
+            (Priority::High, 200, TransmissionType::Response(2, 0)),
+            (Priority::Low, 50, TransmissionType::Broadcast),
+        ] {
+            let token = start(&mut node, now, priority, port_id, tx_kind, b"hi");
+            node.enqueue(token, now).unwrap();
+        }
+
+        let mut frames = Vec::new();
+        while let Some(frame) = node.pop_frame(now) {
+            frames.push(frame.unwrap());
+        }
+        let ids: Vec<_> = frames
+            .iter()
+            .map(|frame| {
+                let id = CanMessageId::from(frame.id);
+                if id.is_message() {
+                    (id.priority(), id.subject_id(), None)
+                } else {
+                    let id = CanServiceId::from(frame.id);
+                    (id.priority(), id.service_id(), Some(id.is_req()))
+                }
+            })
+            .collect();
+        // Messages go ahead of responses ahead of requests, all else being equal
+        let high = Priority::High as u8;
+        let low = Priority::Low as u8;
+        assert_eq!(
+            ids,
+            [
+                (high, 300, None),
+                (high, 200, Some(false)),
+                (high, 200, Some(true)),
+                (low, 50, None),
+                (low, 100, None),
+                (low, 100, None),
+                (low, 100, None),
+            ]
+        );
+        // Frames of the same transfer stay in order, from start to end of transfer
+        let tail_byte = |frame: &CanFrame<TestClock>| *frame.payload.last().unwrap();
+        assert_eq!(tail_byte(&frames[4]) & 0xC0, 0x80);
+        assert_eq!(tail_byte(&frames[5]) & 0xC0, 0x00);
+        assert_eq!(tail_byte(&frames[6]) & 0xC0, 0x40);
+    }
+
+    #[test]
+    fn tx_deadlines() {
+        use crate::transport::can::CanMessageId;
+
+        let mut clock = TestClock::default();
+        let mut node = TestNode::new(Some(1), HeapTransferManager::new());
+        let now = clock.try_now().unwrap();
+        let deadline = now + Milliseconds(100u32);
+
+        // Transfers are dropped once their deadline passes, part way through or not
+        let first = start(
+            &mut node,
+            deadline,
+            Priority::Nominal,
+            100,
+            TransmissionType::Broadcast,
+            &[0u8; 16],
+        );
+        let (_, first) = node.transmit_frame(first, now).unwrap();
+        let second = start(
+            &mut node,
+            deadline,
+            Priority::Nominal,
+            101,
+            TransmissionType::Broadcast,
+            b"hi",
+        );
+        clock.add_duration(&Milliseconds(101u32)).unwrap();
+        let late = clock.try_now().unwrap();
+        assert!(matches!(
+            node.transmit_frame(first.unwrap(), late),
+            Err(TransmitFrameError::Expired)
+        ));
+        assert!(matches!(
+            node.transmit_frame(second, late),
+            Err(TransmitFrameError::Expired)
+        ));
+
+        // The same goes for transfers the transfer manager timed out
+        let token = start(
+            &mut node,
+            late,
+            Priority::Nominal,
+            100,
+            TransmissionType::Broadcast,
+            b"hi",
+        );
+        clock.add_duration(&Milliseconds(1u32)).unwrap();
+        node.transfer_manager
+            .update_transfers(clock.try_now().unwrap());
+        assert!(matches!(
+            node.transmit_frame(token, late),
+            Err(TransmitFrameError::Expired)
+        ));
+
+        // Queued frames report being dropped one by one
+        let now = clock.try_now().unwrap();
+        let token = start(
+            &mut node,
+            now + Milliseconds(100u32),
+            Priority::Nominal,
+            100,
+            TransmissionType::Broadcast,
+            &[0u8; 16],
+        );
+        node.enqueue(token, now).unwrap();
+        let token = start(
+            &mut node,
+            now + Milliseconds(200u32),
+            Priority::Slow,
+            100,
+            TransmissionType::Broadcast,
+            b"hi",
+        );
+        node.enqueue(token, now).unwrap();
+        clock.add_duration(&Milliseconds(150u32)).unwrap();
+        let now = clock.try_now().unwrap();
+        for _ in 0..3 {
+            assert!(matches!(
+                node.pop_frame(now),
+                Some(Err(TransmitFrameError::Expired))
+            ));
+        }
+        let frame = node.pop_frame(now).unwrap().unwrap();
+        assert_eq!(
+            CanMessageId::from(frame.id).priority(),
+            Priority::Slow as u8
+        );
+        assert!(node.pop_frame(now).is_none());
+
+        // Expired transfers aren't queued at all
+        let token = start(
+            &mut node,
+            now,
+            Priority::Nominal,
+            100,
+            TransmissionType::Broadcast,
+            b"hi",
+        );
+        clock.add_duration(&Milliseconds(1u32)).unwrap();
+        assert!(matches!(
+            node.enqueue(token, clock.try_now().unwrap()),
+            Err(TransmitFrameError::Expired)
+        ));
+        assert!(node.pop_frame(now).is_none());
+    }
+
+    /// Nodes on a static transfer manager work within fixed limits, refusing anything beyond
+    /// them.
+    #[test]
+    fn static_capacities() {
+        use crate::get_info::GetInfo;
+        use crate::transfer::StaticTransferManager;
+
+        type StaticNode =
+            Node<StaticTransferManager<TestClock, Can, 2, 4, 64, 4, 4>, Can, TestClock, 2, 1, 2>;
+
+        let clock = TestClock::default();
+        let now = clock.try_now().unwrap();
+        let mut node = StaticNode::new(Some(1), StaticTransferManager::new());
+        let start = |node: &mut StaticNode, port_id, payload: &[u8]| {
+            node.start_tx_transfer(
+                payload.len(),
+                now,
+                Priority::Nominal,
+                port_id,
+                TransmissionType::Broadcast,
+                |buf| {
+                    buf.copy_from_slice(payload);
+                    Ok::<usize, ()>(payload.len())
+                },
+            )
+        };
+
+        // Only one request can wait for a response
+        node.send_request::<GetInfo>(now, 2, Priority::Nominal, &(), Milliseconds(500))
+            .unwrap();
+        assert!(matches!(
+            node.send_request::<GetInfo>(now, 2, Priority::Nominal, &(), Milliseconds(500)),
+            Err(CreateTransferError::NoSpace)
+        ));
+
+        // The request took up one of the two transfer-ID counters
+        let token = start(&mut node, 100, &[0u8; 16]).unwrap();
+        assert!(matches!(
+            start(&mut node, 101, b"hi"),
+            Err(InternalOrUserError::InternalError(
+                CreateTransferError::NoSpace
+            ))
+        ));
+        assert_eq!(node.next_transfer_id(TransferKind::Message, 101, None), 0);
+
+        // Transfers that don't fit the queue aren't queued at all
+        assert!(matches!(
+            node.enqueue(token, now),
+            Err(TransmitFrameError::QueueFull)
+        ));
+        assert!(node.pop_frame(now).is_none());
+        let token = start(&mut node, 100, b"hi").unwrap();
+        node.enqueue(token, now).unwrap();
+        assert!(matches!(node.pop_frame(now), Some(Ok(_))));
+        assert!(node.pop_frame(now).is_none());
+    }
+}
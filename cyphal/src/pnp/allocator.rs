@@ -0,0 +1,325 @@
+//! Allocating side of plug-and-play allocation.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::types::NodeId;
+
+use super::{AllocationDataV1, AllocationDataV2, ProtocolVersion, unique_id_hash};
+
+/// A node ID handed out to a node, identified by its unique ID.
+///
+/// Nodes allocated through version 1 are only known by the hash of their unique ID, which is
+/// stored in place of it, see [`Allocation::from_hash`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Allocation {
+    pub node_id: NodeId,
+    pub unique_id: [u8; 16],
+}
+
+impl Allocation {
+    /// Allocation for a node only known by its [`unique_id_hash`]. The hash takes up the first
+    /// six bytes of the unique ID, little-endian, and the rest is zero.
+    pub fn from_hash(node_id: NodeId, unique_id_hash: u64) -> Self {
+        let mut unique_id = [0u8; 16];
+        unique_id[0..6].copy_from_slice(&unique_id_hash.to_le_bytes()[0..6]);
+        Self { node_id, unique_id }
+    }
+
+    /// Whether this allocation belongs to the node with the given hash.
+    fn matches_hash(&self, hash: u64) -> bool {
+        *self == Self::from_hash(self.node_id, hash) || unique_id_hash(&self.unique_id) == hash
+    }
+}
+
+/// Persistent storage for an allocator's table.
+///
+/// Allocations must survive restarts, otherwise an ID could be handed out twice.
+pub trait AllocationStorage {
+    type Error;
+
+    /// Every allocation stored so far, read once when the allocator is created.
+    fn load(&mut self) -> Result<Vec<Allocation>, Self::Error>;
+
+    /// Stores a new allocation. It must be durable once this returns, as the response goes out
+    /// right after.
+    fn store(&mut self, allocation: &Allocation) -> Result<(), Self::Error>;
+}
+
+/// Storage held in RAM, for testing or for allocators that can't keep any state.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    pub allocations: Vec<Allocation>,
+}
+
+impl AllocationStorage for MemoryStorage {
+    type Error = core::convert::Infallible;
+
+    fn load(&mut self) -> Result<Vec<Allocation>, Self::Error> {
+        Ok(self.allocations.clone())
+    }
+
+    fn store(&mut self, allocation: &Allocation) -> Result<(), Self::Error> {
+        self.allocations.push(*allocation);
+        Ok(())
+    }
+}
+
+/// Response of the allocator, to publish on the version's subject.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AllocationResponse {
+    V1(AllocationDataV1),
+    V2(AllocationDataV2),
+}
+
+impl AllocationResponse {
+    /// Serializes the response into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`AllocationDataV2::SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        match self {
+            AllocationResponse::V1(response) => response.serialize(buffer),
+            AllocationResponse::V2(response) => response.serialize(buffer),
+        }
+    }
+}
+
+/// Non-redundant node-ID allocator, keeping an allocation table keyed by unique ID.
+///
+/// Nodes that were allocated an ID before always get the same one back. New nodes get their
+/// preferred ID if it's free, otherwise the closest free one below it, or above it if there is
+/// none below. Nodes without a preference count as preferring the highest ID.
+#[derive(Debug)]
+pub struct Allocator<S: AllocationStorage> {
+    storage: S,
+    /// Allocations by node ID
+    table: BTreeMap<NodeId, [u8; 16]>,
+    /// Never handed out, e.g. the allocator's own ID
+    reserved: Vec<NodeId>,
+    max_node_id: NodeId,
+}
+
+impl<S: AllocationStorage> Allocator<S> {
+    /// Creates an allocator handing out IDs up to `max_node_id`, loading the table from
+    /// `storage`.
+    ///
+    /// On CAN, 125 is the highest ID to hand out, as 126 and 127 are reserved for tools.
+    pub fn new(mut storage: S, max_node_id: NodeId) -> Result<Self, S::Error> {
+        let table = storage
+            .load()?
+            .into_iter()
+            .map(|allocation| (allocation.node_id, allocation.unique_id))
+            .collect();
+
+        Ok(Self {
+            storage,
+            table,
+            reserved: Vec::new(),
+            max_node_id,
+        })
+    }
+
+    /// Keeps an ID from being handed out, for nodes with a statically configured ID. The
+    /// allocator's own ID is reserved by [`crate::Node::poll_allocator`].
+    pub fn reserve(&mut self, node_id: NodeId) {
+        if !self.reserved.contains(&node_id) {
+            self.reserved.push(node_id);
+        }
+    }
+
+    /// Every allocation made so far.
+    pub fn allocations(&self) -> impl Iterator<Item = Allocation> + '_ {
+        self.table
+            .iter()
+            .map(|(&node_id, &unique_id)| Allocation { node_id, unique_id })
+    }
+
+    /// Handles a message received on the version's subject, returning the response to publish.
+    ///
+    /// Only anonymous messages are requests, anything else is a response from an allocator and
+    /// is ignored. New allocations are stored before returning, if that fails no response
+    /// should be sent.
+    pub fn handle_message(
+        &mut self,
+        version: ProtocolVersion,
+        source_node_id: Option<NodeId>,
+        payload: &[u8],
+    ) -> Result<Option<AllocationResponse>, S::Error> {
+        if source_node_id.is_some() {
+            return Ok(None);
+        }
+
+        match version {
+            ProtocolVersion::V1 => {
+                let Some(request) = AllocationDataV1::deserialize(payload) else {
+                    return Ok(None);
+                };
+                let hash = request.unique_id_hash;
+                let existing = self.allocations().find(|a| a.matches_hash(hash));
+                let node_id = match existing {
+                    Some(allocation) => Some(allocation.node_id),
+                    None => self.allocate(
+                        request.allocated_node_id,
+                        Allocation::from_hash(0, hash).unique_id,
+                    )?,
+                };
+
+                Ok(node_id.map(|node_id| {
+                    AllocationResponse::V1(AllocationDataV1 {
+                        unique_id_hash: hash,
+                        allocated_node_id: Some(node_id),
+                    })
+                }))
+            }
+            ProtocolVersion::V2 => {
+                let Some(request) = AllocationDataV2::deserialize(payload) else {
+                    return Ok(None);
+                };
+                let existing = self
+                    .allocations()
+                    .find(|a| a.unique_id == request.unique_id);
+                let node_id = match existing {
+                    Some(allocation) => Some(allocation.node_id),
+                    None => self.allocate(Some(request.node_id), request.unique_id)?,
+                };
+
+                Ok(node_id.map(|node_id| {
+                    AllocationResponse::V2(AllocationDataV2 {
+                        node_id,
+                        unique_id: request.unique_id,
+                    })
+                }))
+            }
+        }
+    }
+
+    /// Allocates and stores a free ID, returning `None` if every ID is taken.
+    fn allocate(
+        &mut self,
+        preferred_node_id: Option<NodeId>,
+        unique_id: [u8; 16],
+    ) -> Result<Option<NodeId>, S::Error> {
+        let preferred = match preferred_node_id {
+            Some(node_id) if node_id <= self.max_node_id => node_id,
+            _ => self.max_node_id,
+        };
+        let free = |node_id: &NodeId| {
+            !self.table.contains_key(node_id) && !self.reserved.contains(node_id)
+        };
+        let node_id = (0..=preferred)
+            .rev()
+            .find(free)
+            .or_else(|| (preferred..=self.max_node_id).find(free));
+
+        let Some(node_id) = node_id else {
+            return Ok(None);
+        };
+        self.storage.store(&Allocation { node_id, unique_id })?;
+        self.table.insert(node_id, unique_id);
+        Ok(Some(node_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn request_v1(
+        allocator: &mut Allocator<MemoryStorage>,
+        hash: u64,
+        preferred: Option<NodeId>,
+    ) -> Option<NodeId> {
+        let mut buffer = [0u8; AllocationDataV1::MAX_SIZE];
+        let len = AllocationDataV1 {
+            unique_id_hash: hash,
+            allocated_node_id: preferred,
+        }
+        .serialize(&mut buffer);
+
+        match allocator
+            .handle_message(ProtocolVersion::V1, None, &buffer[0..len])
+            .unwrap()?
+        {
+            AllocationResponse::V1(response) => {
+                assert_eq!(response.unique_id_hash, hash);
+                response.allocated_node_id
+            }
+            AllocationResponse::V2(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn allocation() {
+        let mut allocator = Allocator::new(MemoryStorage::default(), 10).unwrap();
+        allocator.reserve(10);
+
+        // Preferred if free, otherwise the closest below, then above
+        assert_eq!(request_v1(&mut allocator, 1, None), Some(9));
+        assert_eq!(request_v1(&mut allocator, 2, Some(5)), Some(5));
+        assert_eq!(request_v1(&mut allocator, 3, Some(5)), Some(4));
+        assert_eq!(request_v1(&mut allocator, 4, Some(200)), Some(8));
+
+        // Known nodes get the same ID back
+        assert_eq!(request_v1(&mut allocator, 2, Some(7)), Some(5));
+
+        // Responses from other allocators aren't requests
+        let mut buffer = [0u8; AllocationDataV1::MAX_SIZE];
+        AllocationDataV1 {
+            unique_id_hash: 5,
+            allocated_node_id: Some(1),
+        }
+        .serialize(&mut buffer);
+        assert_eq!(
+            allocator.handle_message(ProtocolVersion::V1, Some(3), &buffer),
+            Ok(None)
+        );
+
+        // Running out of IDs
+        let mut allocator = Allocator::new(MemoryStorage::default(), 1).unwrap();
+        assert_eq!(request_v1(&mut allocator, 1, Some(0)), Some(0));
+        assert_eq!(request_v1(&mut allocator, 2, Some(0)), Some(1));
+        assert_eq!(request_v1(&mut allocator, 3, Some(0)), None);
+    }
+
+    #[test]
+    fn table_persisted() {
+        let mut allocator = Allocator::new(MemoryStorage::default(), 125).unwrap();
+        let mut buffer = [0u8; AllocationDataV2::SIZE];
+        let unique_id = [0x3C; 16];
+        AllocationDataV2 {
+            node_id: 20,
+            unique_id,
+        }
+        .serialize(&mut buffer);
+        assert_eq!(
+            allocator.handle_message(ProtocolVersion::V2, None, &buffer),
+            Ok(Some(AllocationResponse::V2(AllocationDataV2 {
+                node_id: 20,
+                unique_id
+            })))
+        );
+        assert_eq!(request_v1(&mut allocator, 0xABCD, None), Some(125));
+
+        // A new allocator over the same storage knows both nodes, by unique ID or its hash
+        let storage = allocator.storage;
+        assert_eq!(
+            storage.allocations,
+            vec![
+                Allocation {
+                    node_id: 20,
+                    unique_id
+                },
+                Allocation::from_hash(125, 0xABCD)
+            ]
+        );
+        let mut allocator = Allocator::new(storage, 125).unwrap();
+        assert_eq!(
+            request_v1(&mut allocator, unique_id_hash(&unique_id), None),
+            Some(20)
+        );
+        assert_eq!(request_v1(&mut allocator, 0xABCD, Some(3)), Some(125));
+        assert_eq!(allocator.allocations().count(), 2);
+    }
+}
@@ -0,0 +1,220 @@
+//! Requesting side of plug-and-play allocation.
+
+use embedded_time::duration::Milliseconds;
+
+use crate::time::{Duration, Timestamp};
+use crate::types::{NodeId, PortId};
+
+use super::{AllocationDataV1, AllocationDataV2, ProtocolVersion, unique_id_hash};
+
+/// Upper bound of the delay before the first request, which doubles after every request.
+pub const INITIAL_BACKOFF: Duration = Milliseconds(1000);
+
+/// Upper bound the delay between requests stops growing at.
+pub const MAX_BACKOFF: Duration = Milliseconds(8000);
+
+/// Requests a node ID from an allocator until one is handed out.
+///
+/// Requests are spaced out by a random delay, growing between requests, so that nodes starting
+/// up together don't keep colliding. The randomness is seeded from the unique ID, which differs
+/// between nodes by definition.
+#[derive(Debug)]
+pub struct AllocationClient<C: embedded_time::Clock> {
+    version: ProtocolVersion,
+    unique_id: [u8; 16],
+    preferred_node_id: Option<NodeId>,
+
+    /// Xorshift state
+    rng: u64,
+    backoff: Duration,
+    /// Unset until the first poll
+    next_request: Option<Timestamp<C>>,
+}
+
+impl<C: embedded_time::Clock> AllocationClient<C> {
+    /// Client for a node with the given unique ID, optionally asking for a specific node ID.
+    /// Allocators are free to hand out another one.
+    ///
+    /// The preference only goes out with version 2. Version 1 requests have to fit in a single
+    /// classic CAN frame, which leaves no room for it.
+    pub fn new(
+        version: ProtocolVersion,
+        unique_id: [u8; 16],
+        preferred_node_id: Option<NodeId>,
+    ) -> Self {
+        let mut crc = crc_any::CRCu64::crc64we();
+        crc.digest(&unique_id);
+
+        Self {
+            version,
+            unique_id,
+            preferred_node_id,
+            // Xorshift gets stuck at zero
+            rng: crc.get_crc() | 1,
+            backoff: INITIAL_BACKOFF,
+            next_request: None,
+        }
+    }
+
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Subject requests are published on, and responses received from.
+    pub fn subject_id(&self) -> PortId {
+        self.version.subject_id()
+    }
+
+    /// Returns the payload of the request to publish anonymously, if one is due.
+    pub fn poll(
+        &mut self,
+        now: Timestamp<C>,
+    ) -> Option<heapless::Vec<u8, { AllocationDataV2::SIZE }>> {
+        match self.next_request {
+            Some(next) if now >= next => {}
+            Some(_) => return None,
+            None => {
+                self.next_request = Some(now + self.random_delay(0));
+                return None;
+            }
+        }
+
+        self.backoff = core::cmp::min(MAX_BACKOFF, Milliseconds(self.backoff.0 * 2));
+        self.next_request = Some(now + self.random_delay(self.backoff.0 / 2));
+
+        let mut payload = heapless::Vec::new();
+        // Can't fail, the capacity fits either version
+        let _ = payload.resize(AllocationDataV2::SIZE, 0);
+        let len = match self.version {
+            ProtocolVersion::V1 => AllocationDataV1 {
+                unique_id_hash: unique_id_hash(&self.unique_id),
+                allocated_node_id: None,
+            }
+            .serialize(&mut payload),
+            ProtocolVersion::V2 => AllocationDataV2 {
+                node_id: self
+                    .preferred_node_id
+                    .unwrap_or(AllocationDataV2::NO_PREFERENCE),
+                unique_id: self.unique_id,
+            }
+            .serialize(&mut payload),
+        };
+        payload.truncate(len);
+        Some(payload)
+    }
+
+    /// Checks a message received on [`AllocationClient::subject_id`], returning the node ID
+    /// allocated to this node if it's the response to our request.
+    ///
+    /// Only responses, which are published by the allocator under its own node ID, count.
+    /// Requests from other anonymous nodes are ignored.
+    pub fn handle_message(&self, source_node_id: Option<NodeId>, payload: &[u8]) -> Option<NodeId> {
+        source_node_id?;
+
+        match self.version {
+            ProtocolVersion::V1 => {
+                let response = AllocationDataV1::deserialize(payload)?;
+                if response.unique_id_hash == unique_id_hash(&self.unique_id) {
+                    response.allocated_node_id
+                } else {
+                    None
+                }
+            }
+            ProtocolVersion::V2 => {
+                let response = AllocationDataV2::deserialize(payload)?;
+                if response.unique_id == self.unique_id {
+                    Some(response.node_id)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Random delay between `min` milliseconds and the current back-off.
+    fn random_delay(&mut self, min: u32) -> Duration {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        let range = self.backoff.0 - min;
+        Milliseconds(min + (self.rng % range as u64) as u32)
+    }
+}
+
+impl<C: embedded_time::Clock> Clone for AllocationClient<C> {
+    fn clone(&self) -> Self {
+        Self {
+            version: self.version,
+            unique_id: self.unique_id,
+            preferred_node_id: self.preferred_node_id,
+            rng: self.rng,
+            backoff: self.backoff,
+            next_request: self.next_request,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::TestClock;
+    use embedded_time::Clock;
+
+    #[test]
+    fn randomized_backoff() {
+        let mut clock = TestClock::default();
+        let mut client = AllocationClient::<TestClock>::new(ProtocolVersion::V1, [7; 16], Some(5));
+
+        // Requests go out after a random delay, growing up to the maximum back-off
+        let mut last_request = clock.try_now().unwrap();
+        let mut intervals = alloc::vec::Vec::new();
+        for _ in 0..60_000 {
+            let now = clock.try_now().unwrap();
+            if let Some(payload) = client.poll(now) {
+                let request = AllocationDataV1::deserialize(&payload).unwrap();
+                assert_eq!(request.unique_id_hash, unique_id_hash(&[7; 16]));
+                // No room for the preferred node ID in version 1
+                assert_eq!(request.allocated_node_id, None);
+
+                let interval = Milliseconds::<u32>::try_from(now - last_request).unwrap();
+                intervals.push(interval.0);
+                last_request = now;
+            }
+            clock.add_duration(&Milliseconds(1u32)).unwrap();
+        }
+
+        assert!(intervals.len() > 5);
+        assert!(intervals[0] < INITIAL_BACKOFF.0);
+        for (i, &interval) in intervals.iter().enumerate().skip(1) {
+            let backoff = core::cmp::min(MAX_BACKOFF.0, INITIAL_BACKOFF.0 << i);
+            assert!(
+                interval >= backoff / 2 && interval <= backoff,
+                "{} {}",
+                i,
+                interval
+            );
+        }
+        // Not just the lower bound every time
+        assert!(intervals.iter().skip(4).any(|&i| i != intervals[4]));
+    }
+
+    #[test]
+    fn responses() {
+        let client = AllocationClient::<TestClock>::new(ProtocolVersion::V2, [7; 16], None);
+        let mut buffer = [0u8; AllocationDataV2::SIZE];
+
+        let mut response = AllocationDataV2 {
+            node_id: 42,
+            unique_id: [7; 16],
+        };
+        response.serialize(&mut buffer);
+        assert_eq!(client.handle_message(Some(1), &buffer), Some(42));
+        // Anonymous messages are requests from other nodes
+        assert_eq!(client.handle_message(None, &buffer), None);
+
+        response.unique_id[15] = 8;
+        response.serialize(&mut buffer);
+        assert_eq!(client.handle_message(Some(1), &buffer), None);
+    }
+}
@@ -0,0 +1,208 @@
+//! Plug-and-play node-ID allocation, through `uavcan.pnp.NodeIDAllocationData`.
+//!
+//! Nodes without an ID publish anonymous allocation requests carrying their unique ID, which
+//! an allocator on the bus answers with a free node ID. Version 1 of the message fits in a
+//! single classic CAN frame by only carrying a 48-bit hash of the unique ID, version 2 carries
+//! the whole unique ID and is meant for transports with a larger MTU.
+//!
+//! [`AllocationClient`] implements the requesting side, see [`crate::Node::start_allocation`],
+//! and [`Allocator`] the allocating side, see [`crate::Node::serve_allocations`].
+
+#[cfg(feature = "alloc")]
+mod allocator;
+mod client;
+
+#[cfg(feature = "alloc")]
+pub use allocator::*;
+pub use client::*;
+
+use crate::types::{NodeId, PortId};
+
+/// Fixed subject ID of `uavcan.pnp.NodeIDAllocationData.1.0`.
+pub const SUBJECT_ID_V1: PortId = 8166;
+
+/// Fixed subject ID of `uavcan.pnp.NodeIDAllocationData.2.0`.
+pub const SUBJECT_ID_V2: PortId = 8165;
+
+/// Version of `uavcan.pnp.NodeIDAllocationData` used for allocation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProtocolVersion {
+    /// For classic CAN, identifying nodes by a hash of their unique ID
+    V1,
+    /// For transports that can fit the whole unique ID in a single frame
+    V2,
+}
+
+impl ProtocolVersion {
+    /// Subject the version's messages are published on.
+    pub fn subject_id(self) -> PortId {
+        match self {
+            ProtocolVersion::V1 => SUBJECT_ID_V1,
+            ProtocolVersion::V2 => SUBJECT_ID_V2,
+        }
+    }
+
+    /// Extent of the version's message.
+    pub fn extent(self) -> usize {
+        match self {
+            ProtocolVersion::V1 => AllocationDataV1::MAX_SIZE,
+            ProtocolVersion::V2 => AllocationDataV2::SIZE,
+        }
+    }
+}
+
+/// Hash identifying a unique ID in version 1 requests: the CRC-64-WE of the unique ID,
+/// truncated to 48 bits.
+pub fn unique_id_hash(unique_id: &[u8; 16]) -> u64 {
+    let mut crc = crc_any::CRCu64::crc64we();
+    crc.digest(unique_id);
+    crc.get_crc() & AllocationDataV1::HASH_MASK
+}
+
+/// `uavcan.pnp.NodeIDAllocationData.1.0`
+///
+/// Requests leave the node ID unset, or set it to the node's preferred ID. Responses have it set
+/// to the allocated ID.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AllocationDataV1 {
+    /// See [`unique_id_hash`]
+    pub unique_id_hash: u64,
+    pub allocated_node_id: Option<NodeId>,
+}
+
+impl AllocationDataV1 {
+    /// Maximum serialized size in bytes, which is also the extent.
+    pub const MAX_SIZE: usize = 6 + 1 + 2;
+
+    /// Bits of the hash that are carried by the message.
+    pub const HASH_MASK: u64 = (1 << 48) - 1;
+
+    /// Serializes the message into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`AllocationDataV1::MAX_SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        buffer[0..6].copy_from_slice(&self.unique_id_hash.to_le_bytes()[0..6]);
+        match self.allocated_node_id {
+            Some(node_id) => {
+                buffer[6] = 1;
+                buffer[7..9].copy_from_slice(&node_id.to_le_bytes());
+                Self::MAX_SIZE
+            }
+            None => {
+                buffer[6] = 0;
+                7
+            }
+        }
+    }
+
+    /// Deserializes a received message. Missing bytes are taken as zero.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        let mut bytes = [0u8; Self::MAX_SIZE];
+        let len = core::cmp::min(buffer.len(), Self::MAX_SIZE);
+        bytes[0..len].copy_from_slice(&buffer[0..len]);
+
+        let mut hash = [0u8; 8];
+        hash[0..6].copy_from_slice(&bytes[0..6]);
+        let allocated_node_id = match bytes[6] {
+            0 => None,
+            1 => Some(NodeId::from_le_bytes([bytes[7], bytes[8]])),
+            _ => return None,
+        };
+
+        Some(Self {
+            unique_id_hash: u64::from_le_bytes(hash),
+            allocated_node_id,
+        })
+    }
+}
+
+/// `uavcan.pnp.NodeIDAllocationData.2.0`
+///
+/// Requests set the node ID to the node's preferred ID, or [`AllocationDataV2::NO_PREFERENCE`].
+/// Responses have it set to the allocated ID.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AllocationDataV2 {
+    pub node_id: NodeId,
+    pub unique_id: [u8; 16],
+}
+
+impl AllocationDataV2 {
+    /// Serialized size in bytes, which is also the extent.
+    pub const SIZE: usize = 2 + 16;
+
+    /// Node ID of requests without a preferred ID.
+    pub const NO_PREFERENCE: NodeId = NodeId::MAX;
+
+    /// Serializes the message into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`AllocationDataV2::SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        buffer[0..2].copy_from_slice(&self.node_id.to_le_bytes());
+        buffer[2..Self::SIZE].copy_from_slice(&self.unique_id);
+        Self::SIZE
+    }
+
+    /// Deserializes a received message. Missing bytes are taken as zero.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        let mut bytes = [0u8; Self::SIZE];
+        let len = core::cmp::min(buffer.len(), Self::SIZE);
+        bytes[0..len].copy_from_slice(&buffer[0..len]);
+
+        let mut unique_id = [0u8; 16];
+        unique_id.copy_from_slice(&bytes[2..Self::SIZE]);
+        Some(Self {
+            node_id: NodeId::from_le_bytes([bytes[0], bytes[1]]),
+            unique_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialization() {
+        let mut buffer = [0u8; AllocationDataV2::SIZE];
+
+        let request = AllocationDataV1 {
+            unique_id_hash: 0x0000_BEEF_CAFE_1234,
+            allocated_node_id: None,
+        };
+        assert_eq!(request.serialize(&mut buffer), 7);
+        assert_eq!(buffer[0..7], [0x34, 0x12, 0xFE, 0xCA, 0xEF, 0xBE, 0]);
+        assert_eq!(AllocationDataV1::deserialize(&buffer[0..7]), Some(request));
+
+        let response = AllocationDataV1 {
+            allocated_node_id: Some(125),
+            ..request
+        };
+        assert_eq!(response.serialize(&mut buffer), AllocationDataV1::MAX_SIZE);
+        assert_eq!(buffer[6..9], [1, 125, 0]);
+        assert_eq!(AllocationDataV1::deserialize(&buffer[0..9]), Some(response));
+
+        let response = AllocationDataV2 {
+            node_id: 0x0102,
+            unique_id: [0xA5; 16],
+        };
+        assert_eq!(response.serialize(&mut buffer), AllocationDataV2::SIZE);
+        assert_eq!(buffer[0..3], [0x02, 0x01, 0xA5]);
+        assert_eq!(AllocationDataV2::deserialize(&buffer), Some(response));
+    }
+
+    #[test]
+    fn hash() {
+        let mut unique_id = [0u8; 16];
+        unique_id[0..9].copy_from_slice(b"123456789");
+        let hash = unique_id_hash(&unique_id);
+        assert_eq!(hash & !AllocationDataV1::HASH_MASK, 0);
+        assert_ne!(hash, unique_id_hash(&[0u8; 16]));
+
+        // CRC-64-WE check value
+        let mut crc = crc_any::CRCu64::crc64we();
+        crc.digest(b"123456789");
+        assert_eq!(crc.get_crc(), 0x62EC_59E3_F1A4_F00A);
+    }
+}
@@ -0,0 +1,423 @@
+//! Registers, and the `uavcan.register.Access.1.0` and `uavcan.register.List.1.0` services.
+//!
+//! Registers are named, typed values used for a node's configuration. They are kept in a
+//! [`Registry`] supplied by the user, which [`crate::Node::poll_registers`] serves to the bus
+//! once enabled through [`crate::Node::serve_registers`].
+//!
+//! Port IDs are configured through the standard `uavcan.pub.<name>.id` and
+//! `uavcan.sub.<name>.id` registers, see [`port_id`].
+
+mod registry;
+mod value;
+
+pub use registry::*;
+pub use value::Value;
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::service::Service;
+use crate::types::PortId;
+use value::{Reader, Writer};
+
+/// Fixed service ID of `uavcan.register.Access.1.0`.
+pub const ACCESS_SERVICE_ID: PortId = 384;
+
+/// Fixed service ID of `uavcan.register.List.1.0`.
+pub const LIST_SERVICE_ID: PortId = 385;
+
+/// Maximum length of a register name.
+pub const NAME_CAPACITY: usize = 255;
+
+/// Value of a port ID register when the port isn't configured.
+pub const UNSET_PORT_ID: u16 = 0xFFFF;
+
+/// Whether a port is published or subscribed to, which decides its registers' prefix.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PortDirection {
+    Publication,
+    Subscription,
+}
+
+impl PortDirection {
+    fn prefix(self) -> &'static str {
+        match self {
+            PortDirection::Publication => "uavcan.pub",
+            PortDirection::Subscription => "uavcan.sub",
+        }
+    }
+}
+
+/// Port ID configured in the `uavcan.pub.<name>.id` or `uavcan.sub.<name>.id` register.
+///
+/// Returns `None` if the register doesn't exist, isn't a single `natural16`, or is set to
+/// [`UNSET_PORT_ID`]. Port IDs are only read this way, so changes to them take effect once the
+/// user reads them again, usually on restart.
+pub fn port_id(registry: &impl Registry, direction: PortDirection, name: &str) -> Option<PortId> {
+    let register = registry.read(&format!("{}.{}.id", direction.prefix(), name))?;
+    match register.value {
+        Value::Natural16(value) if value.len() == 1 && value[0] != UNSET_PORT_ID => Some(value[0]),
+        _ => None,
+    }
+}
+
+fn write_name(writer: &mut Writer, name: &str) {
+    let name = &name.as_bytes()[0..core::cmp::min(name.len(), NAME_CAPACITY)];
+    writer.write(&[name.len() as u8]);
+    writer.write(name);
+}
+
+fn read_name(reader: &mut Reader) -> Option<String> {
+    let len = reader.read_u8() as usize;
+    let name = (0..len).map(|_| reader.read_u8()).collect();
+    String::from_utf8(name).ok()
+}
+
+/// Request of `uavcan.register.Access.1.0`, reading a register and optionally writing it first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessRequest {
+    pub name: String,
+    /// Value to write, [`Value::Empty`] to only read
+    pub value: Value,
+}
+
+impl AccessRequest {
+    /// Maximum serialized size in bytes, which is also the extent.
+    pub const MAX_SIZE: usize = 1 + NAME_CAPACITY + Value::MAX_SIZE;
+
+    /// Serializes the request into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`AccessRequest::MAX_SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        let mut writer = Writer::new(buffer);
+        write_name(&mut writer, &self.name);
+        self.value.write(&mut writer);
+        writer.len()
+    }
+
+    /// Deserializes a received request. Missing bytes are taken as zero.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(buffer);
+        Some(Self {
+            name: read_name(&mut reader)?,
+            value: Value::read(&mut reader)?,
+        })
+    }
+
+    /// Handles the request against `registry`.
+    ///
+    /// Non-empty values are written first, if that fails the register is left as it is. The
+    /// response holds the register's resulting value, or an empty value if it doesn't exist.
+    pub fn handle(self, registry: &mut impl Registry) -> AccessResponse {
+        if !self.value.is_empty() {
+            // Failed writes are reported through the value sent back
+            let _ = registry.write(&self.name, self.value);
+        }
+
+        match registry.read(&self.name) {
+            Some(register) => AccessResponse {
+                timestamp: 0,
+                mutable: register.mutable,
+                persistent: register.persistent,
+                value: register.value,
+            },
+            None => AccessResponse {
+                timestamp: 0,
+                mutable: false,
+                persistent: false,
+                value: Value::Empty,
+            },
+        }
+    }
+}
+
+/// Response of `uavcan.register.Access.1.0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessResponse {
+    /// Microseconds of synchronized time the value was sampled at, zero if unknown
+    pub timestamp: u64,
+    pub mutable: bool,
+    pub persistent: bool,
+    pub value: Value,
+}
+
+impl AccessResponse {
+    /// Maximum serialized size in bytes, which is also the extent.
+    pub const MAX_SIZE: usize = 7 + 1 + Value::MAX_SIZE;
+
+    /// Serializes the response into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`AccessResponse::MAX_SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        let mut writer = Writer::new(buffer);
+        // 56 bit timestamp
+        writer.write(&self.timestamp.to_le_bytes()[0..7]);
+        writer.write(&[self.mutable as u8 | (self.persistent as u8) << 1]);
+        self.value.write(&mut writer);
+        writer.len()
+    }
+
+    /// Deserializes a received response. Missing bytes are taken as zero.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(buffer);
+        let timestamp = reader.read::<7>();
+        let flags = reader.read_u8();
+        Some(Self {
+            timestamp: timestamp
+                .iter()
+                .rev()
+                .fold(0, |timestamp, &byte| (timestamp << 8) | byte as u64),
+            mutable: flags & 0x01 != 0,
+            persistent: flags & 0x02 != 0,
+            value: Value::read(&mut reader)?,
+        })
+    }
+}
+
+/// Request of `uavcan.register.List.1.0`, asking for the name of the register at an index.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ListRequest {
+    pub index: u16,
+}
+
+impl ListRequest {
+    /// Serialized size in bytes, which is also the extent.
+    pub const SIZE: usize = 2;
+
+    /// Serializes the request into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`ListRequest::SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        buffer[0..Self::SIZE].copy_from_slice(&self.index.to_le_bytes());
+        Self::SIZE
+    }
+
+    /// Deserializes a received request. Missing bytes are taken as zero.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        Some(Self {
+            index: Reader::new(buffer).read_u16(),
+        })
+    }
+
+    /// Handles the request against `registry`. Indices past the last register get an empty
+    /// name back.
+    pub fn handle(self, registry: &impl Registry) -> ListResponse {
+        ListResponse {
+            name: registry.name(self.index as usize).unwrap_or_default(),
+        }
+    }
+}
+
+/// Response of `uavcan.register.List.1.0`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListResponse {
+    /// Name of the register, empty if there is none at the requested index
+    pub name: String,
+}
+
+impl ListResponse {
+    /// Maximum serialized size in bytes, which is also the extent.
+    pub const MAX_SIZE: usize = 1 + NAME_CAPACITY;
+
+    /// Serializes the response into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than [`ListResponse::MAX_SIZE`].
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        let mut writer = Writer::new(buffer);
+        write_name(&mut writer, &self.name);
+        writer.len()
+    }
+
+    /// Deserializes a received response. Missing bytes are taken as zero.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        Some(Self {
+            name: read_name(&mut Reader::new(buffer))?,
+        })
+    }
+}
+
+/// `uavcan.register.Access.1.0`, for accessing other nodes' registers through
+/// [`crate::Node::send_request`].
+#[derive(Copy, Clone, Debug)]
+pub struct Access;
+
+impl Service for Access {
+    const SERVICE_ID: PortId = ACCESS_SERVICE_ID;
+    const REQUEST_MAX_SIZE: usize = AccessRequest::MAX_SIZE;
+    const RESPONSE_EXTENT: usize = AccessResponse::MAX_SIZE;
+
+    type Request = AccessRequest;
+    type Response = AccessResponse;
+
+    fn serialize_request(request: &AccessRequest, buffer: &mut [u8]) -> usize {
+        request.serialize(buffer)
+    }
+
+    fn deserialize_response(buffer: &[u8]) -> Option<AccessResponse> {
+        AccessResponse::deserialize(buffer)
+    }
+}
+
+/// `uavcan.register.List.1.0`, for listing other nodes' registers through
+/// [`crate::Node::send_request`].
+#[derive(Copy, Clone, Debug)]
+pub struct List;
+
+impl Service for List {
+    const SERVICE_ID: PortId = LIST_SERVICE_ID;
+    const REQUEST_MAX_SIZE: usize = ListRequest::SIZE;
+    const RESPONSE_EXTENT: usize = ListResponse::MAX_SIZE;
+
+    type Request = ListRequest;
+    type Response = ListResponse;
+
+    fn serialize_request(request: &ListRequest, buffer: &mut [u8]) -> usize {
+        request.serialize(buffer)
+    }
+
+    fn deserialize_response(buffer: &[u8]) -> Option<ListResponse> {
+        ListResponse::deserialize(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn registry() -> MemoryRegistry {
+        let mut registry = MemoryRegistry::new();
+        registry.insert(
+            "example.gain",
+            Register {
+                value: Value::Real32(vec![1.5]),
+                mutable: true,
+                persistent: false,
+            },
+        );
+        registry.insert(
+            "example.serial",
+            Register {
+                value: Value::Natural32(vec![1234]),
+                mutable: false,
+                persistent: true,
+            },
+        );
+        registry.insert_port(
+            PortDirection::Publication,
+            "status",
+            "uavcan.node.Heartbeat.1.0",
+            None,
+        );
+        registry
+    }
+
+    fn access(registry: &mut MemoryRegistry, name: &str, value: Value) -> AccessResponse {
+        let request = AccessRequest {
+            name: name.into(),
+            value,
+        };
+        let mut buffer = [0u8; AccessRequest::MAX_SIZE];
+        let len = request.serialize(&mut buffer);
+        let request = AccessRequest::deserialize(&buffer[0..len]).unwrap();
+
+        let response = request.handle(registry);
+        let mut buffer = [0u8; AccessResponse::MAX_SIZE];
+        let len = response.serialize(&mut buffer);
+        assert_eq!(
+            AccessResponse::deserialize(&buffer[0..len]),
+            Some(response.clone())
+        );
+        response
+    }
+
+    #[test]
+    fn access_registers() {
+        let mut registry = registry();
+
+        let response = access(&mut registry, "example.gain", Value::Empty);
+        assert_eq!(response.value, Value::Real32(vec![1.5]));
+        assert!(response.mutable);
+        assert!(!response.persistent);
+
+        let response = access(&mut registry, "example.gain", Value::Real32(vec![2.0]));
+        assert_eq!(response.value, Value::Real32(vec![2.0]));
+
+        // Writes of the wrong type, and to immutable registers, are ignored
+        let response = access(&mut registry, "example.gain", Value::Natural8(vec![1]));
+        assert_eq!(response.value, Value::Real32(vec![2.0]));
+        let response = access(&mut registry, "example.serial", Value::Natural32(vec![1]));
+        assert_eq!(response.value, Value::Natural32(vec![1234]));
+        assert!(!response.mutable);
+        assert!(response.persistent);
+
+        let response = access(&mut registry, "example.missing", Value::Natural8(vec![1]));
+        assert_eq!(response.value, Value::Empty);
+    }
+
+    #[test]
+    fn list_registers() {
+        let registry = registry();
+        let names: alloc::vec::Vec<_> = (0..)
+            .map(|index| ListRequest { index }.handle(&registry).name)
+            .take_while(|name| !name.is_empty())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "example.gain",
+                "example.serial",
+                "uavcan.pub.status.id",
+                "uavcan.pub.status.type"
+            ]
+        );
+
+        let response = ListRequest { index: 1 }.handle(&registry);
+        let mut buffer = [0u8; ListResponse::MAX_SIZE];
+        let len = response.serialize(&mut buffer);
+        assert_eq!(&buffer[0..len], b"\x0eexample.serial");
+        assert_eq!(ListResponse::deserialize(&buffer[0..len]), Some(response));
+    }
+
+    #[test]
+    fn port_ids() {
+        let mut registry = registry();
+        assert_eq!(
+            port_id(&registry, PortDirection::Publication, "status"),
+            None
+        );
+        assert_eq!(
+            port_id(&registry, PortDirection::Subscription, "status"),
+            None
+        );
+
+        access(
+            &mut registry,
+            "uavcan.pub.status.id",
+            Value::Natural16(vec![1000]),
+        );
+        assert_eq!(
+            port_id(&registry, PortDirection::Publication, "status"),
+            Some(1000)
+        );
+
+        registry.insert_port(
+            PortDirection::Subscription,
+            "setpoint",
+            "example.Setpoint.1.0",
+            Some(42),
+        );
+        assert_eq!(
+            port_id(&registry, PortDirection::Subscription, "setpoint"),
+            Some(42)
+        );
+        assert_eq!(
+            registry.read("uavcan.sub.setpoint.type").unwrap().value,
+            Value::String("example.Setpoint.1.0".into())
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Storage behind the register services.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+
+use super::{PortDirection, UNSET_PORT_ID, Value};
+use crate::types::PortId;
+
+/// A register's value along with its flags.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Register {
+    pub value: Value,
+    /// Whether the register can be written through `uavcan.register.Access`
+    pub mutable: bool,
+    /// Whether the value is retained across restarts
+    pub persistent: bool,
+}
+
+/// Errors from writing a register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RegisterError {
+    /// No register with this name exists
+    DoesNotExist,
+    /// The register can't be written
+    Immutable,
+    /// The value isn't of the register's type
+    TypeMismatch,
+}
+
+/// Storage for a node's registers.
+///
+/// Implement this to back registers with RAM, flash, a file or anything else. Persistent
+/// registers are expected to be written through to storage by [`Registry::write`].
+pub trait Registry {
+    /// Reads the register with the given name.
+    fn read(&self, name: &str) -> Option<Register>;
+
+    /// Writes the register with the given name.
+    ///
+    /// Implementations should only accept values of the same type as the register's current
+    /// value (see [`Value::same_type`]), and never write immutable registers.
+    fn write(&mut self, name: &str, value: Value) -> Result<(), RegisterError>;
+
+    /// Name of the register at `index`, used to list the registers.
+    ///
+    /// Indices must be contiguous from zero, and the order must not change while the node is
+    /// running.
+    fn name(&self, index: usize) -> Option<String>;
+}
+
+/// Registry held in RAM, with registers kept in alphabetical order.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryRegistry {
+    registers: BTreeMap<String, Register>,
+}
+
+impl MemoryRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a register, replacing any register with the same name.
+    pub fn insert(&mut self, name: &str, register: Register) {
+        self.registers.insert(name.into(), register);
+    }
+
+    /// Removes a register, returning it if it existed.
+    pub fn remove(&mut self, name: &str) -> Option<Register> {
+        self.registers.remove(name)
+    }
+
+    /// Adds the standard registers of a port: its mutable ID register, unset if `default` is
+    /// `None`, and the immutable register holding its DSDL type name.
+    ///
+    /// `name` is the port's name without the `uavcan.pub.` or `uavcan.sub.` prefix, see
+    /// [`super::port_id`] for reading the ID back.
+    pub fn insert_port(
+        &mut self,
+        direction: PortDirection,
+        name: &str,
+        type_name: &str,
+        default: Option<PortId>,
+    ) {
+        let prefix = direction.prefix();
+        self.insert(
+            &format!("{}.{}.id", prefix, name),
+            Register {
+                value: Value::Natural16(vec![default.unwrap_or(UNSET_PORT_ID)]),
+                mutable: true,
+                persistent: true,
+            },
+        );
+        self.insert(
+            &format!("{}.{}.type", prefix, name),
+            Register {
+                value: Value::String(type_name.into()),
+                mutable: false,
+                persistent: true,
+            },
+        );
+    }
+}
+
+impl Registry for MemoryRegistry {
+    fn read(&self, name: &str) -> Option<Register> {
+        self.registers.get(name).cloned()
+    }
+
+    fn write(&mut self, name: &str, value: Value) -> Result<(), RegisterError> {
+        let register = self
+            .registers
+            .get_mut(name)
+            .ok_or(RegisterError::DoesNotExist)?;
+
+        if !register.mutable {
+            return Err(RegisterError::Immutable);
+        }
+        if !register.value.same_type(&value) {
+            return Err(RegisterError::TypeMismatch);
+        }
+
+        register.value = value;
+        Ok(())
+    }
+
+    fn name(&self, index: usize) -> Option<String> {
+        self.registers.keys().nth(index).cloned()
+    }
+}
@@ -0,0 +1,326 @@
+//! `uavcan.register.Value.1.0` and its serialization.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// `uavcan.register.Value.1.0`, the value of a register.
+///
+/// Every variant other than [`Value::Empty`] is an array, with the capacity given by
+/// [`Value::capacity`]. Arrays beyond their capacity are truncated when serialized.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Empty,
+    String(String),
+    Unstructured(Vec<u8>),
+    Bit(Vec<bool>),
+    Integer64(Vec<i64>),
+    Integer32(Vec<i32>),
+    Integer16(Vec<i16>),
+    Integer8(Vec<i8>),
+    Natural64(Vec<u64>),
+    Natural32(Vec<u32>),
+    Natural16(Vec<u16>),
+    Natural8(Vec<u8>),
+    Real64(Vec<f64>),
+    Real32(Vec<f32>),
+    /// IEEE 754 binary16 values, as their bit patterns
+    Real16(Vec<u16>),
+}
+
+impl Value {
+    /// Maximum serialized size in bytes.
+    pub const MAX_SIZE: usize = 1 + 2 + 256;
+
+    /// Union tag of the variant.
+    pub fn tag(&self) -> u8 {
+        match self {
+            Value::Empty => 0,
+            Value::String(_) => 1,
+            Value::Unstructured(_) => 2,
+            Value::Bit(_) => 3,
+            Value::Integer64(_) => 4,
+            Value::Integer32(_) => 5,
+            Value::Integer16(_) => 6,
+            Value::Integer8(_) => 7,
+            Value::Natural64(_) => 8,
+            Value::Natural32(_) => 9,
+            Value::Natural16(_) => 10,
+            Value::Natural8(_) => 11,
+            Value::Real64(_) => 12,
+            Value::Real32(_) => 13,
+            Value::Real16(_) => 14,
+        }
+    }
+
+    /// Maximum number of elements of the variant's array, zero for [`Value::Empty`].
+    pub fn capacity(&self) -> usize {
+        capacity(self.tag())
+    }
+
+    /// Whether both values are of the same variant, regardless of their contents.
+    pub fn same_type(&self, other: &Value) -> bool {
+        self.tag() == other.tag()
+    }
+
+    /// Whether this is [`Value::Empty`].
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Value::Empty)
+    }
+
+    /// Serializes the value into the start of `buffer`, returning the number of bytes used.
+    ///
+    /// # Panics
+    /// If `buffer` is shorter than the serialized value, [`Value::MAX_SIZE`] is always enough.
+    pub fn serialize(&self, buffer: &mut [u8]) -> usize {
+        let mut writer = Writer::new(buffer);
+        self.write(&mut writer);
+        writer.len()
+    }
+
+    /// Deserializes a value. Missing bytes are taken as zero, as required for DSDL types.
+    ///
+    /// Returns `None` for unknown tags and arrays longer than their capacity.
+    pub fn deserialize(buffer: &[u8]) -> Option<Self> {
+        Self::read(&mut Reader::new(buffer))
+    }
+
+    pub(super) fn write(&self, writer: &mut Writer) {
+        let capacity = self.capacity();
+        writer.write(&[self.tag()]);
+        match self {
+            Value::Empty => {}
+            Value::String(value) => {
+                write_array(writer, value.as_bytes(), capacity, u8::to_le_bytes)
+            }
+            Value::Unstructured(value) => write_array(writer, value, capacity, u8::to_le_bytes),
+            Value::Bit(value) => {
+                let value = &value[0..core::cmp::min(value.len(), capacity)];
+                write_length(writer, value.len(), capacity);
+                // Packed least significant bit first
+                for bits in value.chunks(8) {
+                    let byte = bits
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |byte, (i, &bit)| byte | ((bit as u8) << i));
+                    writer.write(&[byte]);
+                }
+            }
+            Value::Integer64(value) => write_array(writer, value, capacity, i64::to_le_bytes),
+            Value::Integer32(value) => write_array(writer, value, capacity, i32::to_le_bytes),
+            Value::Integer16(value) => write_array(writer, value, capacity, i16::to_le_bytes),
+            Value::Integer8(value) => write_array(writer, value, capacity, i8::to_le_bytes),
+            Value::Natural64(value) => write_array(writer, value, capacity, u64::to_le_bytes),
+            Value::Natural32(value) => write_array(writer, value, capacity, u32::to_le_bytes),
+            Value::Natural16(value) => write_array(writer, value, capacity, u16::to_le_bytes),
+            Value::Natural8(value) => write_array(writer, value, capacity, u8::to_le_bytes),
+            Value::Real64(value) => write_array(writer, value, capacity, f64::to_le_bytes),
+            Value::Real32(value) => write_array(writer, value, capacity, f32::to_le_bytes),
+            Value::Real16(value) => write_array(writer, value, capacity, u16::to_le_bytes),
+        }
+    }
+
+    pub(super) fn read(reader: &mut Reader) -> Option<Self> {
+        let tag = reader.read_u8();
+        let capacity = capacity(tag);
+        Some(match tag {
+            0 => Value::Empty,
+            1 => Value::String(
+                String::from_utf8(read_array(reader, capacity, u8::from_le_bytes)?).ok()?,
+            ),
+            2 => Value::Unstructured(read_array(reader, capacity, u8::from_le_bytes)?),
+            3 => {
+                let len = read_length(reader, capacity)?;
+                let mut value = Vec::with_capacity(len);
+                let mut byte = 0;
+                for i in 0..len {
+                    if i % 8 == 0 {
+                        byte = reader.read_u8();
+                    }
+                    value.push(byte & (1 << (i % 8)) != 0);
+                }
+                Value::Bit(value)
+            }
+            4 => Value::Integer64(read_array(reader, capacity, i64::from_le_bytes)?),
+            5 => Value::Integer32(read_array(reader, capacity, i32::from_le_bytes)?),
+            6 => Value::Integer16(read_array(reader, capacity, i16::from_le_bytes)?),
+            7 => Value::Integer8(read_array(reader, capacity, i8::from_le_bytes)?),
+            8 => Value::Natural64(read_array(reader, capacity, u64::from_le_bytes)?),
+            9 => Value::Natural32(read_array(reader, capacity, u32::from_le_bytes)?),
+            10 => Value::Natural16(read_array(reader, capacity, u16::from_le_bytes)?),
+            11 => Value::Natural8(read_array(reader, capacity, u8::from_le_bytes)?),
+            12 => Value::Real64(read_array(reader, capacity, f64::from_le_bytes)?),
+            13 => Value::Real32(read_array(reader, capacity, f32::from_le_bytes)?),
+            14 => Value::Real16(read_array(reader, capacity, u16::from_le_bytes)?),
+            _ => return None,
+        })
+    }
+}
+
+/// Array capacity of each variant, by tag. Every array variant is 256 bytes at most, except
+/// for the bit array.
+fn capacity(tag: u8) -> usize {
+    match tag {
+        1 | 2 | 7 | 11 => 256,
+        3 => 2048,
+        4 | 8 | 12 => 32,
+        5 | 9 | 13 => 64,
+        6 | 10 | 14 => 128,
+        _ => 0,
+    }
+}
+
+/// Writes an array length prefix, which is 16 bits wide for arrays that don't fit in 8.
+fn write_length(writer: &mut Writer, len: usize, capacity: usize) {
+    if capacity > u8::MAX as usize {
+        writer.write(&(len as u16).to_le_bytes());
+    } else {
+        writer.write(&[len as u8]);
+    }
+}
+
+fn read_length(reader: &mut Reader, capacity: usize) -> Option<usize> {
+    let len = if capacity > u8::MAX as usize {
+        reader.read_u16() as usize
+    } else {
+        reader.read_u8() as usize
+    };
+
+    if len > capacity { None } else { Some(len) }
+}
+
+fn write_array<T: Copy, const N: usize>(
+    writer: &mut Writer,
+    values: &[T],
+    capacity: usize,
+    to_bytes: fn(T) -> [u8; N],
+) {
+    let values = &values[0..core::cmp::min(values.len(), capacity)];
+    write_length(writer, values.len(), capacity);
+    for &value in values {
+        writer.write(&to_bytes(value));
+    }
+}
+
+fn read_array<T, const N: usize>(
+    reader: &mut Reader,
+    capacity: usize,
+    from_bytes: fn([u8; N]) -> T,
+) -> Option<Vec<T>> {
+    let len = read_length(reader, capacity)?;
+    Some((0..len).map(|_| from_bytes(reader.read())).collect())
+}
+
+/// Serializes into a buffer, panicking if it runs out of space.
+pub(super) struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Deserializes from a buffer. Reading past its end yields zeros, as DSDL requires implicit
+/// zero extension of truncated payloads.
+pub(super) struct Reader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, offset: 0 }
+    }
+
+    pub fn read<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        let start = core::cmp::min(self.offset, self.buffer.len());
+        let end = core::cmp::min(self.offset + N, self.buffer.len());
+        bytes[0..end - start].copy_from_slice(&self.buffer[start..end]);
+        self.offset += N;
+        bytes
+    }
+
+    pub fn read_u8(&mut self) -> u8 {
+        self.read::<1>()[0]
+    }
+
+    pub fn read_u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.read())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn round_trip(value: Value) -> Vec<u8> {
+        let mut buffer = [0u8; Value::MAX_SIZE];
+        let len = value.serialize(&mut buffer);
+        assert_eq!(Value::deserialize(&buffer[0..len]), Some(value));
+        buffer[0..len].to_vec()
+    }
+
+    #[test]
+    fn serialization() {
+        assert_eq!(round_trip(Value::Empty), [0]);
+        assert_eq!(
+            round_trip(Value::String("abc".into())),
+            [1, 3, 0, b'a', b'b', b'c']
+        );
+        assert_eq!(
+            round_trip(Value::Natural16(vec![0x1234, 7509])),
+            [10, 2, 0x34, 0x12, 0x55, 0x1D]
+        );
+        assert_eq!(round_trip(Value::Integer8(vec![-1])), [7, 1, 0, 0xFF]);
+        assert_eq!(
+            round_trip(Value::Bit(vec![
+                true, false, true, true, false, false, false, false, true
+            ])),
+            [3, 9, 0, 0b0000_1101, 0b0000_0001]
+        );
+        assert_eq!(
+            round_trip(Value::Real32(vec![1.0])),
+            [13, 1, 0x00, 0x00, 0x80, 0x3F]
+        );
+        round_trip(Value::Unstructured(vec![0xAB; 256]));
+        round_trip(Value::Integer64(vec![i64::MIN; 32]));
+        round_trip(Value::Real64(vec![-0.5, 1e100]));
+        round_trip(Value::Real16(vec![0x3C00]));
+
+        // Truncated at the capacity
+        let mut buffer = [0u8; Value::MAX_SIZE];
+        let len = Value::Natural64(vec![1; 33]).serialize(&mut buffer);
+        assert_eq!(
+            Value::deserialize(&buffer[0..len]),
+            Some(Value::Natural64(vec![1; 32]))
+        );
+    }
+
+    #[test]
+    fn deserialization_errors() {
+        // Implicit zero extension
+        assert_eq!(Value::deserialize(&[]), Some(Value::Empty));
+        assert_eq!(
+            Value::deserialize(&[9, 2, 1]),
+            Some(Value::Natural32(vec![1, 0]))
+        );
+
+        // Unknown tag, and lengths beyond capacity
+        assert_eq!(Value::deserialize(&[15]), None);
+        assert_eq!(Value::deserialize(&[8, 33]), None);
+        assert_eq!(Value::deserialize(&[3, 0x01, 0x08]), None);
+    }
+}
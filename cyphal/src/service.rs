@@ -0,0 +1,98 @@
+//! Typed service calls.
+//!
+//! Requests are sent through [`crate::Node::send_request`], which keeps track of them until the
+//! matching response arrives or their deadline passes. Both outcomes are handed out by
+//! [`crate::Node::poll_client`].
+
+use core::marker::PhantomData;
+
+use crate::types::{NodeId, PortId, TransferId};
+
+/// A service type, with its request and response serialization.
+pub trait Service {
+    /// Fixed service ID
+    const SERVICE_ID: PortId;
+    /// Maximum serialized size of the request
+    const REQUEST_MAX_SIZE: usize;
+    /// Extent of the response, used to receive it
+    const RESPONSE_EXTENT: usize;
+
+    type Request;
+    type Response;
+
+    /// Serializes a request into the start of `buffer`, returning the number of bytes used.
+    /// `buffer` is at least [`Service::REQUEST_MAX_SIZE`] long.
+    fn serialize_request(request: &Self::Request, buffer: &mut [u8]) -> usize;
+
+    /// Deserializes a received response.
+    fn deserialize_response(buffer: &[u8]) -> Option<Self::Response>;
+}
+
+/// Identifies a request: responses come from the server it was sent to, on the same service,
+/// with the same transfer ID.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RequestId {
+    pub service_id: PortId,
+    pub server_node_id: NodeId,
+    pub transfer_id: TransferId,
+}
+
+/// Outcome of a request sent through [`crate::Node::send_request`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ClientEvent<'a> {
+    /// The response arrived, with its payload
+    Response(RequestId, &'a [u8]),
+    /// No response arrived before the deadline, any response after it is dropped
+    Timeout(RequestId),
+}
+
+impl ClientEvent<'_> {
+    /// Request the event is about.
+    pub fn request_id(&self) -> RequestId {
+        match self {
+            ClientEvent::Response(id, _) | ClientEvent::Timeout(id) => *id,
+        }
+    }
+}
+
+/// Handle to a request in flight, to pick its outcome out of [`ClientEvent`]s.
+#[derive(Debug)]
+pub struct PendingRequest<S: Service> {
+    id: RequestId,
+    _service: PhantomData<S>,
+}
+
+impl<S: Service> PendingRequest<S> {
+    pub(crate) fn new(id: RequestId) -> Self {
+        Self {
+            id,
+            _service: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    /// The response to this request, if `event` carries it and it deserializes.
+    pub fn response(&self, event: &ClientEvent<'_>) -> Option<S::Response> {
+        match event {
+            ClientEvent::Response(id, payload) if *id == self.id => {
+                S::deserialize_response(payload)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `event` reports this request timing out.
+    pub fn timed_out(&self, event: &ClientEvent<'_>) -> bool {
+        *event == ClientEvent::Timeout(self.id)
+    }
+}
+
+impl<S: Service> Copy for PendingRequest<S> {}
+impl<S: Service> Clone for PendingRequest<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
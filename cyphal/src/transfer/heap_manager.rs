@@ -1,255 +1,789 @@
 //! Alloc-based transfer manager. Intended for embedded applications with a specialized allocation implementation.
 //!
+//! Only requires `alloc`, so it can be used on `no_std` targets that provide a global allocator.
 
-use embedded_time::Clock;
+use crate::time::{Duration, Timestamp};
+use crate::transport::Transport;
+use crate::types::{NodeId, PortId, TransferId};
+use crate::{Subscription, SubscriptionError, TransferKind};
 
-use crate::transfer::*;
-use crate::types::NodeId;
-use crate::transfer::RefTransfer;
+use super::{
+    Frame, TransferMetadata,
+    manager::{
+        CreateTransferError, InternalOrUserError, SessionKey, SessionState, TokenAccessError,
+        TransferManager, UpdateTransferError, timestamp_expired, within_extent, without_crc,
+    },
+};
 
-use alloc::{collections::BTreeMap, vec::Vec};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
 
-/// Internal session object.
-#[derive(Clone, Debug)]
-struct Session<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
-    // Timestamp of first frame
-    pub timestamp: Option<Timestamp<C>>,
-    pub payload: Vec<u8>,
-    pub transfer_id: TransferId,
+/// Progress of the last transfer on a session.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Progress {
+    Receiving,
+    /// Waiting to be read by the user
+    Complete,
+    /// Read, cancelled or failed, the session is only kept around to reject duplicates
+    Consumed,
+}
+
+/// Internal session object, holding the last transfer of a session.
+struct Session<C: embedded_time::Clock, T: Transport<C>> {
+    transfer_metadata: TransferMetadata<C>,
+    transport_metadata: T::RxMetadata,
+    payload: Vec<u8>,
+    /// Total bytes received, including anything truncated and the transport CRC
+    received: usize,
+    progress: Progress,
+    /// Last transfer accepted on the session, to reject duplicates of it
+    accepted: Option<SessionState<C>>,
 
-    pub md: T,
+    // Copied from the subscription, so it doesn't need to be looked up for every frame
+    extent: usize,
+    timeout: Duration,
 }
 
-impl<T, C> Session<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
-    pub fn new(transfer_id: TransferId, known_max_payload_size: Option<usize>) -> Self {
-        Self {
-            timestamp: None,
-            payload: Vec::with_capacity(known_max_payload_size.unwrap_or(10)),
-            transfer_id,
-            md: T::new(),
-        }
+impl<C: embedded_time::Clock, T: Transport<C>> Session<C, T> {
+    /// Frees the payload, keeping the last accepted transfer ID around to reject duplicates.
+    fn consume(&mut self) {
+        self.progress = Progress::Consumed;
+        self.payload = Vec::new();
     }
 
-    pub fn reset(&mut self) {
-        self.payload.clear();
-        self.timestamp = None;
-        self.md = T::new()
+    /// Marks the transfer as complete, making its transfer ID the one duplicates are checked
+    /// against.
+    fn complete(&mut self) {
+        self.progress = Progress::Complete;
+        self.accepted = Some(SessionState::new(&self.transfer_metadata, self.timeout));
     }
 
-    pub fn reset_to_new_transfer_id(&mut self, transfer_id: TransferId) {
-        self.reset();
-        self.transfer_id = transfer_id;
+    /// Appends frame data, silently dropping anything beyond the subscription's extent.
+    ///
+    /// `crc_size` is the number of trailing transport CRC bytes to strip once complete.
+    fn extend_payload(&mut self, data: &[u8], crc_size: usize) {
+        self.payload
+            .extend_from_slice(within_extent(self.extent, self.payload.len(), data));
+        self.received += data.len();
+        if crc_size > 0 {
+            let len = without_crc(self.payload.len(), self.received, crc_size);
+            self.payload.truncate(len);
+        }
     }
 }
 
-/// Internal subscription object. Contains hash map of sessions.
-struct Subscription<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
-    sub: crate::Subscription,
-    sessions: BTreeMap<NodeId, Session<T, C>>,
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct TxKey {
+    transfer_kind: TransferKind,
+    port_id: PortId,
+    destination_node_id: Option<NodeId>,
+    transfer_id: TransferId,
 }
 
-impl<T, C> Subscription<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
-    pub fn new(sub: crate::Subscription) -> Self {
+impl TxKey {
+    fn from_metadata<C: embedded_time::Clock>(metadata: &TransferMetadata<C>) -> Self {
         Self {
-            sub,
-            sessions: BTreeMap::new(),
-        }
-    }
-
-    /// Update subscription with incoming frame
-    fn update<'a>(&'a mut self, frame: InternalRxFrame<C>) -> Result<Option<RefTransfer<'a, C>>, SessionError> {
-        // TODO maybe some of the logic here can be skipped with anon transfers.
-        let session_id = frame.source_node_id.unwrap();
-
-        let extent = self.sub.extent;
-        let session = self.sessions.get_mut(&session_id);
-        match session {
-            // error if session not exists and not start of transfer
-            None if !frame.start_of_transfer => return Err(SessionError::NewSessionNoStart),
-            // create new session if not exists (start of transfer)
-            None => {
-                self.sessions
-                    .insert(session_id, Session::new(frame.transfer_id, Some(extent)));
-            }
-            // session already exists and check for wrong transfer_id
-            Some(session) if session.transfer_id != frame.transfer_id => {
-                session.reset_to_new_transfer_id(frame.transfer_id);
-            }
-            // session already exists and check for timeout
-            Some(session)
-                if timestamp_expired(self.sub.timeout, frame.timestamp, session.timestamp) =>
-            {
-                session.reset();
-                return Err(SessionError::Timeout);
-            }
-            _ => (),
+            transfer_kind: metadata.transfer_kind,
+            port_id: metadata.port_id,
+            destination_node_id: metadata.destination_node_id,
+            transfer_id: metadata.transfer_id,
         }
-
-        self.accept_frame(session_id, frame)
     }
+}
 
-    fn accept_frame<'a>(
-        &'a mut self,
-        session: NodeId,
-        frame: InternalRxFrame<C>,
-    ) -> Result<Option<RefTransfer<'a, C>>, SessionError> {
-        let mut session = self.sessions.get_mut(&session).unwrap();
-
-        if frame.start_of_transfer {
-            session.timestamp = Some(frame.timestamp);
-        }
+struct TxTransfer<C: embedded_time::Clock, T: Transport<C>> {
+    transfer_metadata: TransferMetadata<C>,
+    transport_metadata: T::TxMetadata,
+    consumed: usize,
+    payload: Vec<u8>,
+    timed_out: bool,
+}
 
-        if let Some(len) = session.md.update(&frame) {
-            // Truncate payload if subscription extent is less than the incoming data
-            // TODO not working.
-            let payload_to_copy = if session.payload.len() + len > self.sub.extent {
-                session.payload.len() + len - self.sub.extent
-            } else {
-                len
-            };
-            session.payload.extend(&frame.payload[0..payload_to_copy]);
-
-            if frame.end_of_transfer {
-                if session.md.is_valid(&frame) {
-                    Ok(Some(RefTransfer::from_frame(
-                        frame,
-                        session.timestamp.unwrap(),
-                        &session.payload,
-                    )))
-                } else {
-                    Err(SessionError::BadMetadata)
-                }
-            } else {
-                Ok(None)
-            }
-        } else {
-            Err(SessionError::BadMetadata)
-        }
-    }
+/// Token referring to a completed RX transfer.
+#[derive(Debug, Eq, PartialEq)]
+pub struct HeapRxToken {
+    session: SessionKey,
+    transfer_id: TransferId,
 }
 
-/// SessionManager based on full std support. Meant to be lowest
-/// barrier to entry and greatest flexibility at the cost of resource usage
-/// and not being no_std.
-pub struct HeapSessionManager<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
-    subscriptions: Vec<Subscription<T, C>>,
+#[derive(Debug, Eq, PartialEq)]
+pub struct HeapTxToken(TxKey);
+
+/// Transfer manager using `alloc` collections, keyed by session.
+///
+/// Only transfers matching a subscription are accepted, and every session holds at most one
+/// ongoing transfer. Each subscription's extent limits the size of its payloads (anything
+/// beyond it is implicitly truncated), and its timeout limits the time between the first and
+/// last frame of a transfer. The timeout doubles as the transfer-ID timeout: sessions are kept
+/// for that long after their last transfer, to reject duplicates of it.
+pub struct HeapTransferManager<C: embedded_time::Clock, T: Transport<C>> {
+    subscriptions: Vec<Subscription>,
+    sessions: BTreeMap<SessionKey, Session<C, T>>,
+    tx_transfers: BTreeMap<TxKey, TxTransfer<C, T>>,
 }
 
-impl<T, C> HeapSessionManager<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
+impl<C: embedded_time::Clock, T: Transport<C>> HeapTransferManager<C, T> {
     // TODO add param to pre-allocate some values (can reduce fragmentation if
     // everything gets allocated early and we don't touch it later)
     pub fn new() -> Self {
         Self {
             subscriptions: Vec::new(),
+            sessions: BTreeMap::new(),
+            tx_transfers: BTreeMap::new(),
         }
     }
 
-    /// Modify subscription in place, creating a new one if not found.
+    /// Modify the extent and timeout of an existing subscription.
+    ///
+    /// Only affects transfers started after this call.
     pub fn edit_subscription(
         &mut self,
-        subscription: crate::Subscription,
+        subscription: Subscription,
     ) -> Result<(), SubscriptionError> {
-        match self
+        let existing = self
             .subscriptions
-            .iter()
-            .position(|s| s.sub == subscription)
-        {
-            Some(pos) => {
-                self.subscriptions[pos] = Subscription::new(subscription);
-                Ok(())
-            }
-            None => Err(SubscriptionError::SubscriptionDoesNotExist),
-        }
+            .iter_mut()
+            .find(|s| **s == subscription)
+            .ok_or(SubscriptionError::SubscriptionDoesNotExist)?;
+        *existing = subscription;
+        Ok(())
     }
 }
 
-impl<T, C> Default for HeapSessionManager<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
+impl<C: embedded_time::Clock, T: Transport<C>> Default for HeapTransferManager<C, T> {
     fn default() -> Self {
-        Self {
-            subscriptions: Default::default(),
-        }
+        Self::new()
     }
 }
 
-// TODO maybe we want an API to send a ManagedTransfer or something, where it "leaks" the transfer
-// as a new type (w/ unsafe function?) that implements Drop. Drop could signal back somehow that it's
-// finished, and then some update management function on the HeapSessionManager can clean it up.
-impl<T, C> SessionManager<C> for HeapSessionManager<T, C>
-where
-    T: crate::transport::SessionMetadata<C>,
-    C: Clock,
-{
-    fn subscribe(&mut self, subscription: crate::Subscription) -> Result<(), SubscriptionError> {
-        if self.subscriptions.iter().any(|s| s.sub == subscription) {
+impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for HeapTransferManager<C, T> {
+    type RxTransferToken = HeapRxToken;
+    type TxTransferToken = HeapTxToken;
+
+    fn subscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        if self.subscriptions.contains(&subscription) {
             return Err(SubscriptionError::SubscriptionExists);
         }
 
-        self.subscriptions.push(Subscription::new(subscription));
+        self.subscriptions.push(subscription);
         Ok(())
     }
 
-    fn unsubscribe(&mut self, subscription: crate::Subscription) -> Result<(), SubscriptionError> {
-        match self
+    // Ongoing transfers are dropped along with the subscription
+    fn unsubscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        let pos = self
             .subscriptions
             .iter()
-            .position(|x| x.sub == subscription)
+            .position(|s| *s == subscription)
+            .ok_or(SubscriptionError::SubscriptionDoesNotExist)?;
+        self.subscriptions.remove(pos);
+
+        self.sessions.retain(|key, _| {
+            key.transfer_kind != subscription.transfer_kind || key.port_id != subscription.port_id
+        });
+        Ok(())
+    }
+
+    fn subscription(&self, transfer_kind: TransferKind, port_id: PortId) -> Option<&Subscription> {
+        self.subscriptions
+            .iter()
+            .find(|s| s.transfer_kind == transfer_kind && s.port_id == port_id)
+    }
+
+    fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    fn append_frame(
+        &mut self,
+        frame: &Frame<C>,
+        metadata: T::FrameMetadata,
+    ) -> Result<Option<Self::RxTransferToken>, UpdateTransferError> {
+        let key = SessionKey::from_metadata(&frame.metadata);
+        let session = match self.sessions.get_mut(&key) {
+            Some(session)
+                if session.progress == Progress::Receiving
+                    && session.transfer_metadata.transfer_id == frame.metadata.transfer_id =>
+            {
+                session
+            }
+            // Any other transfer ID is a new transfer, replacing the current one
+            _ => return Err(UpdateTransferError::DoesNotExist),
+        };
+
+        // Failed transfers are dropped, but the session still rejects duplicates of the last
+        // accepted one
+        if timestamp_expired(
+            session.timeout,
+            frame.metadata.timestamp,
+            Some(session.transfer_metadata.timestamp),
+        ) {
+            session.consume();
+            return Err(UpdateTransferError::TimedOut);
+        }
+
+        let crc_size = match T::update_rx_metadata(&mut session.transport_metadata, metadata, frame)
         {
-            Some(pos) => {
-                self.subscriptions.remove(pos);
+            Ok(crc_size) => crc_size,
+            Err(e) => {
+                session.consume();
+                return Err(UpdateTransferError::RxError(e));
+            }
+        };
+
+        session.extend_payload(frame.payload, crc_size);
+
+        if frame.last_frame {
+            session.complete();
+            Ok(Some(HeapRxToken {
+                session: key,
+                transfer_id: frame.metadata.transfer_id,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn new_transfer(
+        &mut self,
+        frame: &Frame<C>,
+        metadata: T::FrameMetadata,
+    ) -> Result<Option<Self::RxTransferToken>, CreateTransferError> {
+        let (extent, timeout) =
+            match self.subscription(frame.metadata.transfer_kind, frame.metadata.port_id) {
+                Some(subscription) => (subscription.extent, subscription.timeout),
+                // Not subscribed, not interested
+                None => return Ok(None),
+            };
+
+        let key = SessionKey::from_metadata(&frame.metadata);
+        let mut accepted = None;
+        if let Some(session) = self.sessions.get(&key) {
+            if session.progress == Progress::Receiving
+                && session.transfer_metadata.transfer_id == frame.metadata.transfer_id
+            {
+                return Err(CreateTransferError::AlreadyExists);
+            }
+            if session
+                .accepted
+                .is_some_and(|state| state.is_duplicate(&frame.metadata))
+            {
+                return Err(CreateTransferError::Duplicate);
+            }
+            accepted = session.accepted;
+        }
+
+        let mut transport_metadata = T::RxMetadata::default();
+        let crc_size = T::update_rx_metadata(&mut transport_metadata, metadata, frame)
+            .map_err(CreateTransferError::RxError)?;
+
+        let mut session = Session {
+            transfer_metadata: frame.metadata,
+            transport_metadata,
+            payload: Vec::with_capacity(core::cmp::min(extent, frame.payload.len())),
+            received: 0,
+            progress: Progress::Receiving,
+            accepted,
+            extent,
+            timeout,
+        };
+        session.extend_payload(frame.payload, crc_size);
+        if frame.last_frame {
+            session.complete();
+        }
+        self.sessions.insert(key, session);
+
+        if frame.last_frame {
+            Ok(Some(HeapRxToken {
+                session: key,
+                transfer_id: frame.metadata.transfer_id,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn with_rx_transfer(
+        &mut self,
+        token: Self::RxTransferToken,
+        cb: impl FnOnce(&TransferMetadata<C>, &[u8]),
+    ) -> Result<(), TokenAccessError> {
+        match self.sessions.get_mut(&token.session) {
+            Some(session)
+                if session.progress == Progress::Complete
+                    && session.transfer_metadata.transfer_id == token.transfer_id =>
+            {
+                cb(&session.transfer_metadata, &session.payload);
+                session.consume();
                 Ok(())
             }
-            None => Err(SubscriptionError::SubscriptionDoesNotExist),
+            _ => Err(TokenAccessError::InvalidToken),
         }
     }
 
-    fn ingest<'a>(&'a mut self, frame: InternalRxFrame<C>) -> Result<Option<RefTransfer<'a, C>>, SessionError> {
-        match self
-            .subscriptions
-            .iter_mut()
-            .find(|sub| Self::matches_sub(&sub.sub, &frame))
-        {
-            Some(subscription) => subscription.update(frame),
-            None => Ok(None),
+    fn cancel_rx_transfer(&mut self, token: Self::RxTransferToken) -> Result<(), TokenAccessError> {
+        match self.sessions.get_mut(&token.session) {
+            Some(session)
+                if session.progress != Progress::Consumed
+                    && session.transfer_metadata.transfer_id == token.transfer_id =>
+            {
+                session.consume();
+                Ok(())
+            }
+            _ => Err(TokenAccessError::InvalidToken),
+        }
+    }
+
+    fn create_transmission<E>(
+        &mut self,
+        requested_buffer_size: usize,
+        metadata: &TransferMetadata<C>,
+        cb: impl FnOnce(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<Self::TxTransferToken, InternalOrUserError<CreateTransferError, E>> {
+        let key = TxKey::from_metadata(metadata);
+
+        if self.tx_transfers.contains_key(&key) {
+            return Err(InternalOrUserError::InternalError(
+                CreateTransferError::AlreadyExists,
+            ));
+        }
+
+        let mut buf = vec![0u8; T::get_crc_padded_size(requested_buffer_size)];
+
+        let consumed =
+            cb(&mut buf[0..requested_buffer_size]).map_err(InternalOrUserError::UserError)?;
+        // Don't let the user screw this up for us
+        let consumed = core::cmp::min(requested_buffer_size, consumed);
+
+        // Process transport CRC + padding and get the actual payload length
+        let real_len = T::process_tx_crc(&mut buf, consumed);
+        buf.truncate(real_len);
+
+        self.tx_transfers.insert(
+            key,
+            TxTransfer {
+                transfer_metadata: *metadata,
+                transport_metadata: T::TxMetadata::default(),
+                consumed: 0,
+                payload: buf,
+                timed_out: false,
+            },
+        );
+
+        Ok(HeapTxToken(key))
+    }
+
+    fn transmit(
+        &mut self,
+        token: Self::TxTransferToken,
+        cb: impl FnOnce(&TransferMetadata<C>, &mut T::TxMetadata, &[u8]) -> usize,
+    ) -> Result<Option<Self::TxTransferToken>, TokenAccessError> {
+        let transfer = self
+            .tx_transfers
+            .get_mut(&token.0)
+            .ok_or(TokenAccessError::InvalidToken)?;
+
+        if transfer.timed_out {
+            self.tx_transfers.remove(&token.0);
+            return Err(TokenAccessError::TransferTimeout);
         }
+
+        let consumed = cb(
+            &transfer.transfer_metadata,
+            &mut transfer.transport_metadata,
+            &transfer.payload[transfer.consumed..],
+        );
+        transfer.consumed += consumed;
+
+        if transfer.consumed >= transfer.payload.len() {
+            // Transfer complete
+            self.tx_transfers.remove(&token.0);
+            Ok(None)
+        } else {
+            Ok(Some(token))
+        }
+    }
+
+    fn cancel_tx_transfer(&mut self, token: Self::TxTransferToken) -> Result<(), TokenAccessError> {
+        self.tx_transfers
+            .remove(&token.0)
+            .ok_or(TokenAccessError::InvalidToken)
+            .map(|_| ())
     }
 
-    fn update_sessions(&mut self, timestamp: Timestamp<C>) {
-        for sub in &mut self.subscriptions {
-            let extent = sub.sub.extent;
-            for session in sub.sessions.values_mut() {
-                if timestamp_expired(sub.sub.timeout, timestamp, session.timestamp) {
-                    let transfer_id = session.transfer_id;
-                    *session = Session::new(transfer_id, Some(extent));
-                }
+    fn update_transfers(&mut self, timestamp: Timestamp<C>) {
+        for transfer in self.tx_transfers.values_mut() {
+            if timestamp > transfer.transfer_metadata.timestamp {
+                transfer.timed_out = true;
             }
         }
+
+        // RX sessions use their subscription's timeout instead, both for receiving the transfer and
+        // as the transfer-ID timeout. Completed transfers are kept around until they are consumed.
+        self.sessions.retain(|_, session| {
+            if session.progress == Progress::Receiving
+                && timestamp_expired(
+                    session.timeout,
+                    timestamp,
+                    Some(session.transfer_metadata.timestamp),
+                )
+            {
+                session.consume();
+            }
+            session.progress != Progress::Consumed
+                || session
+                    .accepted
+                    .is_some_and(|state| !state.expired(timestamp))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use crate::time::TestClock;
+    use crate::transport::can::{Can, CanFrame};
+
+    use alloc::vec::Vec;
+    use embedded_time::Clock;
+    use embedded_time::duration::Milliseconds;
+
+    type Manager = HeapTransferManager<TestClock, Can>;
+
+    fn make_metadata(
+        clock: &TestClock,
+        port_id: PortId,
+        transfer_id: u64,
+    ) -> TransferMetadata<TestClock> {
+        TransferMetadata {
+            timestamp: clock.try_now().unwrap(),
+            priority: Priority::Nominal,
+            transfer_kind: TransferKind::Message,
+            port_id,
+            source_node_id: Some(1),
+            destination_node_id: None,
+            transfer_id,
+        }
+    }
+
+    /// Transmits a whole transfer through the manager, returning the CAN frames.
+    fn transmit(
+        manager: &mut Manager,
+        metadata: &TransferMetadata<TestClock>,
+        payload: &[u8],
+    ) -> Vec<CanFrame<TestClock>> {
+        let mut token = manager
+            .create_transmission(payload.len(), metadata, |buf| {
+                buf.copy_from_slice(payload);
+                Ok::<usize, ()>(payload.len())
+            })
+            .unwrap();
+
+        let mut frames = Vec::new();
+        loop {
+            let res = manager
+                .transmit(token, |transfer_metadata, tx_metadata, data| {
+                    let (frame, consumed) = Can::transmit_frame(
+                        transfer_metadata,
+                        tx_metadata,
+                        data,
+                        transfer_metadata.source_node_id,
+                        transfer_metadata.timestamp,
+                    )
+                    .unwrap();
+                    frames.push(frame);
+                    consumed
+                })
+                .unwrap();
+            match res {
+                Some(next) => token = next,
+                None => break,
+            }
+        }
+
+        frames
+    }
+
+    /// Feeds frames into the manager the same way the node does.
+    fn receive(
+        manager: &mut Manager,
+        frame: &CanFrame<TestClock>,
+    ) -> Result<Option<HeapRxToken>, UpdateTransferError> {
+        let (frame, metadata) = Can::rx_process_frame(frame).unwrap();
+        match manager.append_frame(&frame, metadata) {
+            Err(UpdateTransferError::DoesNotExist) if frame.first_frame => manager
+                .new_transfer(&frame, metadata)
+                .map_err(|_| UpdateTransferError::NoSpace),
+            res => res,
+        }
+    }
+
+    fn read(manager: &mut Manager, token: HeapRxToken) -> Vec<u8> {
+        let mut payload = Vec::new();
+        manager
+            .with_rx_transfer(token, |_, data| payload.extend_from_slice(data))
+            .unwrap();
+        payload
+    }
+
+    #[test]
+    fn subscriptions() {
+        let clock = TestClock::default();
+        let mut manager = Manager::new();
+        let sub = Subscription::new(TransferKind::Message, 100, 64, Milliseconds(1000));
+
+        manager.subscribe(sub).unwrap();
+        assert!(matches!(
+            manager.subscribe(sub),
+            Err(SubscriptionError::SubscriptionExists)
+        ));
+        assert_eq!(manager.subscriptions(), [sub]);
+
+        let mut tx = Manager::new();
+        let frames = transmit(&mut tx, &make_metadata(&clock, 100, 0), b"hello");
+        assert!(matches!(receive(&mut manager, &frames[0]), Ok(Some(_))));
+
+        // Unsubscribed ports are ignored
+        let frames = transmit(&mut tx, &make_metadata(&clock, 101, 0), b"hello");
+        assert!(matches!(receive(&mut manager, &frames[0]), Ok(None)));
+
+        manager.unsubscribe(sub).unwrap();
+        assert!(matches!(
+            manager.unsubscribe(sub),
+            Err(SubscriptionError::SubscriptionDoesNotExist)
+        ));
+        assert!(manager.subscriptions().is_empty());
+        let frames = transmit(&mut tx, &make_metadata(&clock, 100, 1), b"hello");
+        assert!(matches!(receive(&mut manager, &frames[0]), Ok(None)));
+    }
+
+    #[test]
+    fn multi_frame_round_trip() {
+        let clock = TestClock::default();
+        let mut manager = Manager::new();
+        manager
+            .subscribe(Subscription::new(
+                TransferKind::Message,
+                100,
+                64,
+                Milliseconds(1000),
+            ))
+            .unwrap();
+
+        let mut tx = Manager::new();
+        // CRC is split across the last two frames
+        let payload: Vec<u8> = (0..20).collect();
+        let frames = transmit(&mut tx, &make_metadata(&clock, 100, 5), &payload);
+        assert_eq!(frames.len(), 4);
+
+        for frame in frames[0..3].iter() {
+            assert!(matches!(receive(&mut manager, frame), Ok(None)));
+        }
+        let token = receive(&mut manager, &frames[3]).unwrap().unwrap();
+        assert_eq!(read(&mut manager, token), payload);
+    }
+
+    #[test]
+    fn extent_truncates() {
+        let clock = TestClock::default();
+        let mut manager = Manager::new();
+        manager
+            .subscribe(Subscription::new(
+                TransferKind::Message,
+                100,
+                10,
+                Milliseconds(1000),
+            ))
+            .unwrap();
+
+        let mut tx = Manager::new();
+        let payload: Vec<u8> = (0..19).collect();
+        let mut token = None;
+        for frame in transmit(&mut tx, &make_metadata(&clock, 100, 5), &payload).iter() {
+            token = receive(&mut manager, frame).unwrap();
+        }
+        assert_eq!(read(&mut manager, token.unwrap()), &payload[0..10]);
+
+        // An extent reaching into the CRC doesn't deliver any of it
+        manager
+            .edit_subscription(Subscription::new(
+                TransferKind::Message,
+                100,
+                21,
+                Milliseconds(1000),
+            ))
+            .unwrap();
+        let mut token = None;
+        for frame in transmit(&mut tx, &make_metadata(&clock, 100, 6), &payload).iter() {
+            token = receive(&mut manager, frame).unwrap();
+        }
+        assert_eq!(read(&mut manager, token.unwrap()), payload);
+    }
+
+    #[test]
+    fn subscription_timeout() {
+        let mut clock = TestClock::default();
+        let mut manager = Manager::new();
+        manager
+            .subscribe(Subscription::new(
+                TransferKind::Message,
+                100,
+                64,
+                Milliseconds(10),
+            ))
+            .unwrap();
+
+        let mut tx = Manager::new();
+        let frames = transmit(&mut tx, &make_metadata(&clock, 100, 5), &[0u8; 19]);
+        receive(&mut manager, &frames[0]).unwrap();
+
+        // Frames carry their reception time, so rebuild the rest with a later timestamp
+        clock.add_duration(&Milliseconds(20u32)).unwrap();
+        let late = CanFrame::new(
+            clock.try_now().unwrap(),
+            frames[1].id.as_raw(),
+            &frames[1].payload,
+        );
+        assert!(matches!(
+            receive(&mut manager, &late),
+            Err(UpdateTransferError::TimedOut)
+        ));
+
+        // Stale sessions are cleaned up by the housekeeping call
+        receive(&mut manager, &frames[0]).unwrap();
+        manager.update_transfers(clock.try_now().unwrap());
+        assert!(matches!(
+            receive(&mut manager, &frames[1]),
+            Err(UpdateTransferError::DoesNotExist)
+        ));
+
+        // Frames timestamped before the transfer started haven't timed out
+        let frames = transmit(&mut tx, &make_metadata(&clock, 100, 6), &[0u8; 19]);
+        receive(&mut manager, &frames[0]).unwrap();
+        let early = CanFrame::new(
+            TestClock::default().try_now().unwrap(),
+            frames[1].id.as_raw(),
+            &frames[1].payload,
+        );
+        assert!(matches!(receive(&mut manager, &early), Ok(None)));
+    }
+
+    #[test]
+    fn tx_deadline() {
+        let mut clock = TestClock::default();
+        let mut manager = Manager::new();
+        let metadata = make_metadata(&clock, 100, 0);
+        let create = |manager: &mut Manager| {
+            manager.create_transmission(8, &metadata, |_| Ok::<usize, ()>(8))
+        };
+
+        // Transfers time out once past their deadline, and are dropped on the next access
+        let token = create(&mut manager).unwrap();
+        clock.add_duration(&Milliseconds(1u32)).unwrap();
+        manager.update_transfers(clock.try_now().unwrap());
+        assert!(matches!(
+            manager.transmit(token, |_, _, _| unreachable!()),
+            Err(TokenAccessError::TransferTimeout)
+        ));
+        create(&mut manager).unwrap();
+    }
+
+    /// Repeated transfer IDs are rejected within the transfer-ID timeout, across CAN's 5-bit
+    /// transfer ID wraparound.
+    #[test]
+    fn duplicates_rejected() {
+        let mut clock = TestClock::default();
+        let mut manager = Manager::new();
+        manager
+            .subscribe(Subscription::new(
+                TransferKind::Message,
+                100,
+                64,
+                Milliseconds(100),
+            ))
+            .unwrap();
+
+        let mut tx = Manager::new();
+        let mut start = |manager: &mut Manager, metadata: &TransferMetadata<TestClock>| {
+            let frames = transmit(&mut tx, metadata, b"hello");
+            let (frame, frame_metadata) = Can::rx_process_frame(&frames[0]).unwrap();
+            manager.new_transfer(&frame, frame_metadata)
+        };
+
+        // 32 and 33 go out as 0 and 1
+        for transfer_id in 30..34 {
+            let token = start(&mut manager, &make_metadata(&clock, 100, transfer_id));
+            assert_eq!(read(&mut manager, token.unwrap().unwrap()), b"hello");
+        }
+        for transfer_id in [1, 33] {
+            assert!(matches!(
+                start(&mut manager, &make_metadata(&clock, 100, transfer_id)),
+                Err(CreateTransferError::Duplicate)
+            ));
+        }
+
+        // Other sources have their own sessions, and anonymous transfers have none
+        let mut metadata = make_metadata(&clock, 100, 1);
+        metadata.source_node_id = Some(2);
+        assert!(matches!(start(&mut manager, &metadata), Ok(Some(_))));
+        metadata.source_node_id = None;
+        assert!(matches!(start(&mut manager, &metadata), Ok(Some(_))));
+        assert!(matches!(start(&mut manager, &metadata), Ok(Some(_))));
+
+        // The same transfer ID is fine again once the timeout passes
+        clock.add_duration(&Milliseconds(150u32)).unwrap();
+        assert!(matches!(
+            start(&mut manager, &make_metadata(&clock, 100, 1)),
+            Ok(Some(_))
+        ));
+    }
+
+    /// A transfer failing its CRC isn't accepted, so it can be sent again with the same ID.
+    #[test]
+    fn resent_after_failed_crc() {
+        let clock = TestClock::default();
+        let mut manager = Manager::new();
+        manager
+            .subscribe(Subscription::new(
+                TransferKind::Message,
+                100,
+                64,
+                Milliseconds(100),
+            ))
+            .unwrap();
+        let mut tx = Manager::new();
+        let payload: Vec<u8> = (0..20).collect();
+
+        let frames = transmit(&mut tx, &make_metadata(&clock, 100, 4), &payload);
+        let token = frames
+            .iter()
+            .fold(None, |_, frame| receive(&mut manager, frame).unwrap());
+        assert_eq!(read(&mut manager, token.unwrap()), payload);
+
+        let frames = transmit(&mut tx, &make_metadata(&clock, 100, 5), &payload);
+        let mut corrupted = frames.clone();
+        corrupted[1].payload[0] ^= 1;
+        let (last, rest) = corrupted.split_last().unwrap();
+        for frame in rest {
+            assert!(matches!(receive(&mut manager, frame), Ok(None)));
+        }
+        assert!(matches!(
+            receive(&mut manager, last),
+            Err(UpdateTransferError::RxError(_))
+        ));
+
+        // The previous transfer is still a duplicate, but the failed one isn't
+        assert!(
+            receive(
+                &mut manager,
+                &transmit(&mut tx, &make_metadata(&clock, 100, 4), &payload)[0]
+            )
+            .is_err()
+        );
+        let token = frames
+            .iter()
+            .fold(None, |_, frame| receive(&mut manager, frame).unwrap());
+        assert_eq!(read(&mut manager, token.unwrap()), payload);
+        assert!(receive(&mut manager, &frames[0]).is_err());
     }
 }
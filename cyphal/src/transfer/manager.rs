@@ -1,7 +1,8 @@
-use crate::RxError;
 use crate::time::{Duration, Timestamp};
 use crate::transfer::Frame;
 use crate::transport::Transport;
+use crate::types::{NodeId, PortId, TransferId};
+use crate::{RxError, Subscription, SubscriptionError, TransferKind};
 
 use embedded_time::fixed_point::FixedPoint;
 
@@ -35,6 +36,8 @@ pub enum CreateTransferError {
     NoSpace,
     /// A transfer with the same metadata already exists
     AlreadyExists,
+    /// The transfer ID was already received on this session within the transfer-ID timeout
+    Duplicate,
     RxError(RxError),
 }
 
@@ -42,8 +45,7 @@ pub enum CreateTransferError {
 pub enum TokenAccessError {
     /// Token does not reference an existing transfer
     InvalidToken,
-    /// Transfer timed out
-    // TODO can this even be distinguished?
+    /// Transfer timed out, for TX transfers this means their deadline passed
     TransferTimeout,
 }
 
@@ -67,6 +69,18 @@ pub trait TransferManager<C: embedded_time::Clock, T: Transport<C>> {
 
     // TODO now that I am dependent on Transport, I can store transport-specific metadata
 
+    /// Start accepting transfers on the subscription's port.
+    fn subscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError>;
+
+    /// Stop accepting transfers on the subscription's port. Ongoing transfers on it may be dropped.
+    fn unsubscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError>;
+
+    /// Find the subscription covering transfers of the given kind on a port, if there is one.
+    fn subscription(&self, transfer_kind: TransferKind, port_id: PortId) -> Option<&Subscription>;
+
+    /// All the subscriptions, in no particular order.
+    fn subscriptions(&self) -> &[Subscription];
+
     /// Attempt to append a new frame onto an existing transfer, optionally returning a transfer token
     /// if it's the final frame of a multi-frame transfer.
     ///
@@ -86,7 +100,8 @@ pub trait TransferManager<C: embedded_time::Clock, T: Transport<C>> {
     /// - Expected by the user, and
     /// - the first frame
     ///
-    /// So implementations do not need to check for the existance of a transfer.
+    /// So implementations do not need to check for the existance of a transfer. They are expected to
+    /// reject duplicate transfers with [`CreateTransferError::Duplicate`], see [`SessionState`].
     fn new_transfer(
         &mut self,
         frame: &Frame<C>,
@@ -139,9 +154,82 @@ pub trait TransferManager<C: embedded_time::Clock, T: Transport<C>> {
 
     /// Housekeeping function called to clean up timed-out transfers
     ///
+    /// TX transfers time out once `timestamp` is past their deadline, which is the timestamp in
+    /// their metadata. Transmitting them afterwards fails with
+    /// [`TokenAccessError::TransferTimeout`] and drops them. RX transfers time out according to
+    /// their subscription.
+    ///
     /// Note: an implementation is expected to also clean up complete transfers after some period,
     /// or it will be possible for the user to not clear out a transfer via usage.
-    fn update_transfers(&mut self, timestamp: Timestamp<C>, timeout: Duration);
+    fn update_transfers(&mut self, timestamp: Timestamp<C>);
+}
+
+/// Identifies an RX session, the stream of transfers from a single source on a single port.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SessionKey {
+    pub transfer_kind: TransferKind,
+    pub port_id: PortId,
+    pub source_node_id: Option<NodeId>,
+}
+
+impl SessionKey {
+    pub fn from_metadata<C: embedded_time::Clock>(metadata: &TransferMetadata<C>) -> Self {
+        Self {
+            transfer_kind: metadata.transfer_kind,
+            port_id: metadata.port_id,
+            source_node_id: metadata.source_node_id,
+        }
+    }
+}
+
+/// Transfer-ID state of an RX session, used to reject duplicate transfers.
+///
+/// A transfer is a duplicate if it has the same transfer ID as the last transfer accepted on its
+/// session, and starts within the transfer-ID timeout of it. Only equality is checked, so this
+/// holds up when the transfer ID wraps around, whatever the transport's modulo is.
+///
+/// Anonymous transfers don't belong to a session, so they are never considered duplicates.
+#[derive(Debug)]
+pub struct SessionState<C: embedded_time::Clock> {
+    transfer_id: TransferId,
+    timestamp: Timestamp<C>,
+    timeout: Duration,
+}
+
+impl<C: embedded_time::Clock> SessionState<C> {
+    /// Starts tracking a session from an accepted transfer, using the subscription's timeout as
+    /// the transfer-ID timeout.
+    pub fn new(metadata: &TransferMetadata<C>, timeout: Duration) -> Self {
+        Self {
+            transfer_id: metadata.transfer_id,
+            timestamp: metadata.timestamp,
+            timeout,
+        }
+    }
+
+    /// Whether a transfer starting with this metadata is a duplicate of the last one.
+    pub fn is_duplicate(&self, metadata: &TransferMetadata<C>) -> bool {
+        metadata.source_node_id.is_some()
+            && metadata.transfer_id == self.transfer_id
+            && !self.expired(metadata.timestamp)
+    }
+
+    /// Whether the transfer-ID timeout has passed, after which the state can be forgotten.
+    pub fn expired(&self, now: Timestamp<C>) -> bool {
+        timestamp_expired(self.timeout, now, Some(self.timestamp))
+    }
+
+    /// Timestamp of the last accepted transfer.
+    pub fn timestamp(&self) -> Timestamp<C> {
+        self.timestamp
+    }
+}
+
+impl<C: embedded_time::Clock> Copy for SessionState<C> {}
+impl<C: embedded_time::Clock> Clone for SessionState<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
 pub fn timestamp_expired<C: embedded_time::Clock, D>(
@@ -153,11 +241,34 @@ where
     D: embedded_time::duration::Duration + FixedPoint,
     <C as embedded_time::Clock>::T: From<<D as FixedPoint>::T>,
 {
-    if let Some(then) = then {
-        if now - then > timeout.to_generic(C::SCALING_FACTOR).unwrap() {
-            return true;
-        }
-    }
+    let Some(then) = then else {
+        return false;
+    };
+    // Both durations share the clock's scaling factor, so compare raw ticks. Comparing the
+    // Generic durations directly converts them to whole seconds first.
+    let timeout = timeout.to_generic::<C::T>(C::SCALING_FACTOR).unwrap();
+    // `now` may be before `then`, e.g. for frames timestamped by hardware or reordered across
+    // interfaces, and nothing has expired then
+    now.checked_duration_since(&then)
+        .is_some_and(|elapsed| elapsed.integer() > timeout.integer())
+}
 
-    false
+/// Part of a frame's payload that still fits within a subscription's extent, given the number of
+/// payload bytes already received.
+///
+/// Anything beyond the extent is implicitly truncated, as required by the specification. The frame
+/// must already have gone through [`Transport::update_rx_metadata`], so that the transfer CRC is
+/// still checked over the full payload.
+pub fn within_extent(extent: usize, received: usize, data: &[u8]) -> &[u8] {
+    let remaining = extent.saturating_sub(received);
+    &data[0..core::cmp::min(remaining, data.len())]
+}
+
+/// Length of a completed transfer's stored payload once the transport CRC is stripped.
+///
+/// `received` is the total number of bytes received for the transfer and `crc_size` the value
+/// returned by [`Transport::update_rx_metadata`] for the last frame. The CRC may already have been
+/// cut off, fully or in part, by truncating at the extent.
+pub fn without_crc(stored: usize, received: usize, crc_size: usize) -> usize {
+    core::cmp::min(stored, received.saturating_sub(crc_size))
 }
@@ -1,10 +1,13 @@
+use crate::time::Duration;
 use crate::transport::Transport;
+use crate::types::PortId;
+use crate::{Subscription, SubscriptionError, TransferKind};
 
 use super::{
     Frame, TransferMetadata,
     manager::{
-        CreateTransferError, InternalOrUserError, TokenAccessError, TransferManager,
-        UpdateTransferError, timestamp_expired,
+        CreateTransferError, InternalOrUserError, SessionKey, SessionState, TokenAccessError,
+        TransferManager, UpdateTransferError, timestamp_expired, within_extent, without_crc,
     },
 };
 
@@ -20,6 +23,10 @@ struct RxTransfer<C: embedded_time::Clock, T: Transport<C>> {
     transfer_metadata: TransferMetadata<C>,
     transport_metadata: T::RxMetadata,
     payload: Vec<u8>,
+    /// Total bytes received, including anything truncated and the transport CRC
+    received: usize,
+    extent: usize,
+    timeout: Duration,
 }
 
 struct TxTransfer<C: embedded_time::Clock, T: Transport<C>> {
@@ -30,6 +37,9 @@ struct TxTransfer<C: embedded_time::Clock, T: Transport<C>> {
 }
 
 pub struct MapTransferManager<C: embedded_time::Clock, T: Transport<C>> {
+    subscriptions: Vec<Subscription>,
+    /// Last transfer accepted on each RX session, to reject duplicates
+    sessions: HashMap<SessionKey, SessionState<C>>,
     rx_transfers: HashMap<RxToken, TransferStatus<RxTransfer<C, T>>>,
     tx_transfers: HashMap<TxToken, TransferStatus<TxTransfer<C, T>>>,
 }
@@ -37,12 +47,20 @@ pub struct MapTransferManager<C: embedded_time::Clock, T: Transport<C>> {
 impl<C: embedded_time::Clock, T: Transport<C>> MapTransferManager<C, T> {
     pub fn new() -> Self {
         Self {
+            subscriptions: Vec::new(),
+            sessions: HashMap::new(),
             rx_transfers: HashMap::new(),
             tx_transfers: HashMap::new(),
         }
     }
 }
 
+impl<C: embedded_time::Clock, T: Transport<C>> Default for MapTransferManager<C, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Eq, PartialEq, Hash)]
 pub struct RxToken(u64);
 
@@ -61,6 +79,47 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
     type RxTransferToken = RxToken;
     type TxTransferToken = TxToken;
 
+    fn subscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        if self.subscriptions.contains(&subscription) {
+            return Err(SubscriptionError::SubscriptionExists);
+        }
+
+        self.subscriptions.push(subscription);
+        Ok(())
+    }
+
+    fn unsubscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        let pos = self
+            .subscriptions
+            .iter()
+            .position(|s| *s == subscription)
+            .ok_or(SubscriptionError::SubscriptionDoesNotExist)?;
+        self.subscriptions.remove(pos);
+
+        self.sessions.retain(|key, _| {
+            key.transfer_kind != subscription.transfer_kind || key.port_id != subscription.port_id
+        });
+        self.rx_transfers.retain(|_, transfer| {
+            let metadata = match transfer {
+                TransferStatus::Active(transfer) => &transfer.transfer_metadata,
+                TransferStatus::TimedOut => return true,
+            };
+            metadata.transfer_kind != subscription.transfer_kind
+                || metadata.port_id != subscription.port_id
+        });
+        Ok(())
+    }
+
+    fn subscription(&self, transfer_kind: TransferKind, port_id: PortId) -> Option<&Subscription> {
+        self.subscriptions
+            .iter()
+            .find(|s| s.transfer_kind == transfer_kind && s.port_id == port_id)
+    }
+
+    fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
     fn append_frame(
         &mut self,
         frame: &Frame<C>,
@@ -71,16 +130,34 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
         match self.rx_transfers.get_mut(&token) {
             Some(TransferStatus::TimedOut) => Err(UpdateTransferError::TimedOut),
             Some(TransferStatus::Active(rx_transfer)) => {
-                println!("Active transfer found");
-                if let Err(e) =
-                    T::update_rx_metadata(&mut rx_transfer.transport_metadata, metadata, frame)
-                {
-                    return Err(UpdateTransferError::RxError(e));
-                }
-
-                rx_transfer.payload.extend_from_slice(frame.payload);
+                let crc_size = match T::update_rx_metadata(
+                    &mut rx_transfer.transport_metadata,
+                    metadata,
+                    frame,
+                ) {
+                    Ok(crc_size) => crc_size,
+                    Err(e) => {
+                        // Drop the transfer, so it can be sent again
+                        self.rx_transfers.remove(&token);
+                        return Err(UpdateTransferError::RxError(e));
+                    }
+                };
+
+                // Anything past the extent is dropped, but still goes through the CRC above
+                let data =
+                    within_extent(rx_transfer.extent, rx_transfer.payload.len(), frame.payload);
+                rx_transfer.payload.extend_from_slice(data);
+                rx_transfer.received += frame.payload.len();
+                let len = without_crc(rx_transfer.payload.len(), rx_transfer.received, crc_size);
+                rx_transfer.payload.truncate(len);
 
                 if frame.last_frame {
+                    if frame.metadata.source_node_id.is_some() {
+                        self.sessions.insert(
+                            SessionKey::from_metadata(&frame.metadata),
+                            SessionState::new(&rx_transfer.transfer_metadata, rx_transfer.timeout),
+                        );
+                    }
                     // Return token on completion of transfer
                     Ok(Some(token))
                 } else {
@@ -96,22 +173,47 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
         frame: &Frame<C>,
         metadata: T::FrameMetadata,
     ) -> Result<Option<Self::RxTransferToken>, CreateTransferError> {
+        let (extent, timeout) =
+            match self.subscription(frame.metadata.transfer_kind, frame.metadata.port_id) {
+                Some(subscription) => (subscription.extent, subscription.timeout),
+                // Not subscribed, not interested
+                None => return Ok(None),
+            };
+
         let token = RxToken(hash_metadata(&frame.metadata));
 
-        if let Some(_) = self.rx_transfers.get(&token) {
+        if self.rx_transfers.contains_key(&token) {
             return Err(CreateTransferError::AlreadyExists);
         }
 
+        let key = SessionKey::from_metadata(&frame.metadata);
+        if let Some(state) = self.sessions.get(&key) {
+            if state.is_duplicate(&frame.metadata) {
+                return Err(CreateTransferError::Duplicate);
+            }
+        }
+
         let mut transport_metadata = T::RxMetadata::default();
-        T::update_rx_metadata(&mut transport_metadata, metadata, &frame)
-            .map_err(|e| CreateTransferError::RxError(e))?;
+        let crc_size = T::update_rx_metadata(&mut transport_metadata, metadata, frame)
+            .map_err(CreateTransferError::RxError)?;
+
+        let mut payload = Vec::from(within_extent(extent, 0, frame.payload));
+        payload.truncate(without_crc(payload.len(), frame.payload.len(), crc_size));
+        // Only complete transfers count, so a failed one can be sent again with the same ID
+        if frame.last_frame && key.source_node_id.is_some() {
+            self.sessions
+                .insert(key, SessionState::new(&frame.metadata, timeout));
+        }
 
         self.rx_transfers.insert(
             token,
             TransferStatus::Active(RxTransfer {
                 transfer_metadata: frame.metadata,
-                transport_metadata: transport_metadata,
-                payload: Vec::from(frame.payload),
+                transport_metadata,
+                payload,
+                received: frame.payload.len(),
+                extent,
+                timeout,
             }),
         );
 
@@ -161,7 +263,7 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
     ) -> Result<Self::TxTransferToken, InternalOrUserError<CreateTransferError, E>> {
         let token = TxToken(hash_metadata(metadata));
 
-        if let Some(_) = self.tx_transfers.get(&token) {
+        if self.tx_transfers.contains_key(&token) {
             return Err(InternalOrUserError::InternalError(
                 CreateTransferError::AlreadyExists,
             ));
@@ -169,8 +271,7 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
 
         let final_buf_size = T::get_crc_padded_size(requested_buffer_size);
 
-        let mut buf = Vec::new();
-        buf.resize(final_buf_size, 0u8);
+        let mut buf = vec![0u8; final_buf_size];
 
         match cb(&mut buf[0..requested_buffer_size]) {
             Ok(mut consumed) => {
@@ -188,7 +289,7 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
                 let _ = self.tx_transfers.insert(
                     token,
                     TransferStatus::Active(TxTransfer {
-                        transfer_metadata: metadata.clone(),
+                        transfer_metadata: *metadata,
                         transport_metadata: T::TxMetadata::default(),
                         consumed: 0usize,
                         payload: buf,
@@ -208,7 +309,6 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
         token: Self::TxTransferToken,
         cb: impl FnOnce(&TransferMetadata<C>, &mut T::TxMetadata, &[u8]) -> usize,
     ) -> Result<Option<Self::TxTransferToken>, TokenAccessError> {
-        // TODO distinguish timeouts from lack of access, and handle timeouts
         let transfer = self
             .tx_transfers
             .get_mut(&token)
@@ -216,13 +316,16 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
 
         let transfer = match transfer {
             TransferStatus::Active(transfer) => transfer,
-            TransferStatus::TimedOut => return Err(TokenAccessError::TransferTimeout),
+            TransferStatus::TimedOut => {
+                self.tx_transfers.remove(&token);
+                return Err(TokenAccessError::TransferTimeout);
+            }
         };
 
         let consumed = cb(
             &transfer.transfer_metadata,
             &mut transfer.transport_metadata,
-            &mut transfer.payload[transfer.consumed..],
+            &transfer.payload[transfer.consumed..],
         );
         transfer.consumed += consumed;
 
@@ -235,19 +338,10 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
         }
     }
 
-    fn update_transfers(
-        &mut self,
-        timestamp: crate::time::Timestamp<C>,
-        timeout: crate::time::Duration,
-    ) {
+    fn update_transfers(&mut self, timestamp: crate::time::Timestamp<C>) {
         for (_token, transfer) in self.tx_transfers.iter_mut() {
             let expired = if let TransferStatus::Active(transfer) = transfer {
-                // TODO why Some here?
-                timestamp_expired(
-                    timeout,
-                    timestamp,
-                    Some(transfer.transfer_metadata.timestamp),
-                )
+                timestamp > transfer.transfer_metadata.timestamp
             } else {
                 false
             };
@@ -257,11 +351,14 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
             }
         }
 
+        // RX transfers use their subscription's timeout instead, which is also the transfer-ID
+        // timeout
+        self.sessions.retain(|_, state| !state.expired(timestamp));
         for (_token, transfer) in self.rx_transfers.iter_mut() {
             let expired = if let TransferStatus::Active(transfer) = transfer {
                 // TODO why Some here?
                 timestamp_expired(
-                    timeout,
+                    transfer.timeout,
                     timestamp,
                     Some(transfer.transfer_metadata.timestamp),
                 )
@@ -275,3 +372,154 @@ impl<C: embedded_time::Clock, T: Transport<C>> TransferManager<C, T> for MapTran
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use crate::time::TestClock;
+    use crate::transport::can::{Can, CanFrame, CanMessageId};
+
+    use embedded_time::Clock;
+    use embedded_time::duration::Milliseconds;
+
+    #[test]
+    fn extent_truncates() {
+        let clock = TestClock::default();
+        let metadata = TransferMetadata {
+            timestamp: clock.try_now().unwrap(),
+            priority: Priority::Nominal,
+            transfer_kind: TransferKind::Message,
+            port_id: 100,
+            source_node_id: Some(1),
+            destination_node_id: None,
+            transfer_id: 0,
+        };
+        let payload: Vec<u8> = (0..19).collect();
+
+        let mut tx = MapTransferManager::<TestClock, Can>::new();
+        let mut token = tx
+            .create_transmission(payload.len(), &metadata, |buf| {
+                buf.copy_from_slice(&payload);
+                Ok::<usize, ()>(payload.len())
+            })
+            .unwrap();
+        let mut frames = Vec::new();
+        loop {
+            let next = tx
+                .transmit(token, |transfer_metadata, tx_metadata, data| {
+                    let (frame, consumed) = Can::transmit_frame(
+                        transfer_metadata,
+                        tx_metadata,
+                        data,
+                        Some(1),
+                        clock.try_now().unwrap(),
+                    )
+                    .unwrap();
+                    frames.push(frame);
+                    consumed
+                })
+                .unwrap();
+            match next {
+                Some(next) => token = next,
+                None => break,
+            }
+        }
+
+        let mut rx = MapTransferManager::<TestClock, Can>::new();
+        rx.subscribe(Subscription::new(
+            TransferKind::Message,
+            100,
+            10,
+            Milliseconds(1000),
+        ))
+        .unwrap();
+
+        let (frame, frame_metadata) = Can::rx_process_frame(&frames[0]).unwrap();
+        assert!(rx.new_transfer(&frame, frame_metadata).unwrap().is_none());
+        let mut token = None;
+        for frame in frames[1..].iter() {
+            let (frame, frame_metadata) = Can::rx_process_frame(frame).unwrap();
+            token = rx.append_frame(&frame, frame_metadata).unwrap();
+        }
+
+        rx.with_rx_transfer(token.unwrap(), |_, data| assert_eq!(data, &payload[0..10]))
+            .unwrap();
+    }
+
+    #[test]
+    fn duplicates_rejected() {
+        let clock = TestClock::default();
+        let mut rx = MapTransferManager::<TestClock, Can>::new();
+        rx.subscribe(Subscription::new(
+            TransferKind::Message,
+            100,
+            64,
+            Milliseconds(1000),
+        ))
+        .unwrap();
+
+        let mut receive = |transfer_id: u8| {
+            // Single-frame transfer, tail byte with start, end and toggle set
+            let frame = CanFrame::new(
+                clock.try_now().unwrap(),
+                CanMessageId::new(Priority::Nominal, 100, Some(1)).as_raw(),
+                &[1, 2, 3, 0xE0 | transfer_id],
+            );
+            let (frame, frame_metadata) = Can::rx_process_frame(&frame).unwrap();
+            rx.new_transfer(&frame, frame_metadata)
+                .map(|token| rx.cancel_rx_transfer(token.unwrap()).unwrap())
+        };
+
+        assert!(receive(0).is_ok());
+        assert!(matches!(receive(0), Err(CreateTransferError::Duplicate)));
+        assert!(receive(1).is_ok());
+        assert!(receive(0).is_ok());
+    }
+
+    /// A transfer failing its CRC isn't accepted, so it can be sent again with the same ID.
+    #[test]
+    fn resent_after_failed_crc() {
+        let clock = TestClock::default();
+        let mut rx = MapTransferManager::<TestClock, Can>::new();
+        rx.subscribe(Subscription::new(
+            TransferKind::Message,
+            100,
+            64,
+            Milliseconds(1000),
+        ))
+        .unwrap();
+
+        // Two-frame transfer with ID 5, the CRC of [0..7] across the second frame
+        let id = CanMessageId::new(Priority::Nominal, 100, Some(1)).as_raw();
+        let frames = [
+            CanFrame::new(clock.try_now().unwrap(), id, &[0, 1, 2, 3, 4, 5, 6, 0xA5]),
+            CanFrame::new(clock.try_now().unwrap(), id, &[0x28, 0xC2, 0x45]),
+        ];
+        fn receive(
+            rx: &mut MapTransferManager<TestClock, Can>,
+            frame: &CanFrame<TestClock>,
+        ) -> Result<Option<RxToken>, UpdateTransferError> {
+            let (frame, frame_metadata) = Can::rx_process_frame(frame).unwrap();
+            match rx.append_frame(&frame, frame_metadata) {
+                Err(UpdateTransferError::DoesNotExist) => rx
+                    .new_transfer(&frame, frame_metadata)
+                    .map_err(|_| UpdateTransferError::NoSpace),
+                res => res,
+            }
+        }
+
+        let mut corrupted = frames.clone();
+        corrupted[0].payload[0] ^= 1;
+        assert!(matches!(receive(&mut rx, &corrupted[0]), Ok(None)));
+        assert!(matches!(
+            receive(&mut rx, &corrupted[1]),
+            Err(UpdateTransferError::RxError(_))
+        ));
+
+        assert!(matches!(receive(&mut rx, &frames[0]), Ok(None)));
+        let token = receive(&mut rx, &frames[1]).unwrap().unwrap();
+        rx.cancel_rx_transfer(token).unwrap();
+        assert!(receive(&mut rx, &frames[0]).is_err());
+    }
+}
@@ -7,12 +7,18 @@ use crate::types::*;
 
 use crate::Priority;
 
+#[cfg(feature = "alloc")]
+pub mod heap_manager;
 pub mod manager;
+pub mod static_manager;
 
 #[cfg(feature = "std")]
 pub mod map_manager;
 
+#[cfg(feature = "alloc")]
+pub use heap_manager::HeapTransferManager;
 pub use manager::TransferManager;
+pub use static_manager::StaticTransferManager;
 
 /// Protocol-level transfer types.
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
@@ -25,7 +31,7 @@ pub enum TransferKind {
 /// Metadata describing a transfer. This metadata is transport-agnostic.
 #[derive(Debug)]
 pub struct TransferMetadata<C: embedded_time::Clock> {
-    // for tx -> transmission_timeout
+    /// Time of the first frame for RX transfers, transmission deadline for TX transfers
     pub timestamp: Timestamp<C>,
     pub priority: Priority,
     pub transfer_kind: TransferKind,
@@ -56,7 +62,7 @@ impl<C: embedded_time::Clock> Hash for TransferMetadata<C> {
             state.write_u16(remote_node_id);
         }
         state.write_u16(self.port_id);
-        state.write_u8(self.transfer_id);
+        state.write_u64(self.transfer_id);
     }
 }
 
@@ -82,6 +88,7 @@ pub enum TransferError {
     BadMetadata,
 }
 
+#[derive(Debug)]
 pub struct Frame<'a, C: embedded_time::Clock> {
     pub metadata: TransferMetadata<C>,
     pub payload: &'a [u8],
@@ -0,0 +1,861 @@
+//! Transfer manager backed entirely by static storage.
+//!
+//! Requires neither `std` nor an allocator, all limits are set through const generics.
+
+use crate::time::{Duration, Timestamp};
+use crate::transport::Transport;
+use crate::types::PortId;
+use crate::{Subscription, SubscriptionError, TransferKind};
+
+use super::{
+    Frame, TransferMetadata,
+    manager::{
+        CreateTransferError, InternalOrUserError, SessionKey, SessionState, TokenAccessError,
+        TransferManager, UpdateTransferError, timestamp_expired, within_extent, without_crc,
+    },
+};
+
+enum TransferStatus<D> {
+    Active(D),
+    TimedOut(D),
+}
+
+impl<D> TransferStatus<D> {
+    fn inner(&self) -> &D {
+        match self {
+            Self::Active(transfer) | Self::TimedOut(transfer) => transfer,
+        }
+    }
+}
+
+struct RxTransfer<C: embedded_time::Clock, T: Transport<C>, const BUF: usize> {
+    transfer_metadata: TransferMetadata<C>,
+    transport_metadata: T::RxMetadata,
+    payload: heapless::Vec<u8, BUF>,
+    /// Total bytes received, including anything truncated and the transport CRC
+    received: usize,
+    extent: usize,
+    timeout: Duration,
+}
+
+struct TxTransfer<C: embedded_time::Clock, T: Transport<C>, const BUF: usize> {
+    transfer_metadata: TransferMetadata<C>,
+    transport_metadata: T::TxMetadata,
+    consumed: usize,
+    payload: heapless::Vec<u8, BUF>,
+}
+
+/// Storage for a single transfer.
+///
+/// The generation is bumped every time the slot is reused, so tokens
+/// referring to a previous occupant are rejected.
+struct Slot<D> {
+    generation: u32,
+    status: Option<TransferStatus<D>>,
+}
+
+impl<D> Slot<D> {
+    fn empty() -> Self {
+        Self {
+            generation: 0,
+            status: None,
+        }
+    }
+
+    fn get(&self, generation: u32) -> Option<&TransferStatus<D>> {
+        if self.generation == generation {
+            self.status.as_ref()
+        } else {
+            None
+        }
+    }
+
+    fn get_mut(&mut self, generation: u32) -> Option<&mut TransferStatus<D>> {
+        if self.generation == generation {
+            self.status.as_mut()
+        } else {
+            None
+        }
+    }
+
+    fn take(&mut self, generation: u32) -> Option<TransferStatus<D>> {
+        if self.generation == generation {
+            self.status.take()
+        } else {
+            None
+        }
+    }
+
+    fn fill(&mut self, transfer: D) -> u32 {
+        self.generation = self.generation.wrapping_add(1);
+        self.status = Some(TransferStatus::Active(transfer));
+        self.generation
+    }
+}
+
+/// Transfer manager with a fixed capacity, for targets without an allocator.
+///
+/// - `RX`: maximum number of concurrent RX transfers
+/// - `TX`: maximum number of concurrent TX transfers
+/// - `BUF`: maximum payload size of a single transfer, including any transport CRC and padding
+/// - `SUBS`: maximum number of subscriptions
+/// - `SESSIONS`: maximum number of RX sessions (port and source node pairs) whose last transfer
+///   ID is remembered to reject duplicates. When full, the least recently active one is forgotten.
+///
+/// When any of the limits are reached, `NoSpace` errors are returned. RX transfers that grow
+/// past `BUF` are dropped. Timed out transfers are kept around until their slot is
+/// needed by a new transfer.
+///
+/// Every slot holds a full `BUF` sized buffer, so this can get large quickly. It is intended
+/// to be placed in a `static`, not on the stack.
+pub struct StaticTransferManager<
+    C: embedded_time::Clock,
+    T: Transport<C>,
+    const RX: usize,
+    const TX: usize,
+    const BUF: usize,
+    const SUBS: usize,
+    const SESSIONS: usize,
+> {
+    subscriptions: heapless::Vec<Subscription, SUBS>,
+    sessions: heapless::Vec<(SessionKey, SessionState<C>), SESSIONS>,
+    rx_transfers: [Slot<RxTransfer<C, T, BUF>>; RX],
+    tx_transfers: [Slot<TxTransfer<C, T, BUF>>; TX],
+}
+
+impl<
+    C: embedded_time::Clock,
+    T: Transport<C>,
+    const RX: usize,
+    const TX: usize,
+    const BUF: usize,
+    const SUBS: usize,
+    const SESSIONS: usize,
+> StaticTransferManager<C, T, RX, TX, BUF, SUBS, SESSIONS>
+{
+    pub fn new() -> Self {
+        Self {
+            subscriptions: heapless::Vec::new(),
+            sessions: heapless::Vec::new(),
+            rx_transfers: core::array::from_fn(|_| Slot::empty()),
+            tx_transfers: core::array::from_fn(|_| Slot::empty()),
+        }
+    }
+
+    /// Records the last transfer accepted on a session, forgetting the least recently active
+    /// session if there's no space left.
+    fn track_session(&mut self, key: SessionKey, state: SessionState<C>) {
+        // Anonymous transfers can't be told apart, so there's nothing to track
+        if key.source_node_id.is_none() {
+            return;
+        }
+
+        if let Some((_, existing)) = self.sessions.iter_mut().find(|(k, _)| *k == key) {
+            *existing = state;
+        } else if self.sessions.push((key, state)).is_err() {
+            if let Some(oldest) = self
+                .sessions
+                .iter_mut()
+                .min_by_key(|(_, state)| state.timestamp())
+            {
+                *oldest = (key, state);
+            }
+        }
+    }
+}
+
+impl<
+    C: embedded_time::Clock,
+    T: Transport<C>,
+    const RX: usize,
+    const TX: usize,
+    const BUF: usize,
+    const SUBS: usize,
+    const SESSIONS: usize,
+> Default for StaticTransferManager<C, T, RX, TX, BUF, SUBS, SESSIONS>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct StaticRxToken {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct StaticTxToken {
+    index: usize,
+    generation: u32,
+}
+
+/// Checks if two sets of metadata belong to the same transfer, ignoring timestamps.
+fn same_transfer<C: embedded_time::Clock>(
+    a: &TransferMetadata<C>,
+    b: &TransferMetadata<C>,
+) -> bool {
+    a.priority == b.priority
+        && a.transfer_kind == b.transfer_kind
+        && a.port_id == b.port_id
+        && a.source_node_id == b.source_node_id
+        && a.destination_node_id == b.destination_node_id
+        && a.transfer_id == b.transfer_id
+}
+
+/// Finds a slot for a new transfer, preferring empty slots over ones holding timed out transfers.
+fn free_slot<D>(slots: &[Slot<D>]) -> Option<usize> {
+    slots
+        .iter()
+        .position(|slot| slot.status.is_none())
+        .or_else(|| {
+            slots
+                .iter()
+                .position(|slot| matches!(slot.status, Some(TransferStatus::TimedOut(_))))
+        })
+}
+
+impl<
+    C: embedded_time::Clock,
+    T: Transport<C>,
+    const RX: usize,
+    const TX: usize,
+    const BUF: usize,
+    const SUBS: usize,
+    const SESSIONS: usize,
+> TransferManager<C, T> for StaticTransferManager<C, T, RX, TX, BUF, SUBS, SESSIONS>
+{
+    type RxTransferToken = StaticRxToken;
+    type TxTransferToken = StaticTxToken;
+
+    fn subscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        if self.subscriptions.contains(&subscription) {
+            return Err(SubscriptionError::SubscriptionExists);
+        }
+
+        self.subscriptions
+            .push(subscription)
+            .map_err(|_| SubscriptionError::NoSpace)
+    }
+
+    fn unsubscribe(&mut self, subscription: Subscription) -> Result<(), SubscriptionError> {
+        let pos = self
+            .subscriptions
+            .iter()
+            .position(|s| *s == subscription)
+            .ok_or(SubscriptionError::SubscriptionDoesNotExist)?;
+        self.subscriptions.swap_remove(pos);
+
+        self.sessions.retain(|(key, _)| {
+            key.transfer_kind != subscription.transfer_kind || key.port_id != subscription.port_id
+        });
+        for slot in self.rx_transfers.iter_mut() {
+            let subscribed = match &slot.status {
+                Some(status) => {
+                    let metadata = &status.inner().transfer_metadata;
+                    metadata.transfer_kind == subscription.transfer_kind
+                        && metadata.port_id == subscription.port_id
+                }
+                None => false,
+            };
+            if subscribed {
+                slot.status = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn subscription(&self, transfer_kind: TransferKind, port_id: PortId) -> Option<&Subscription> {
+        self.subscriptions
+            .iter()
+            .find(|s| s.transfer_kind == transfer_kind && s.port_id == port_id)
+    }
+
+    fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    fn append_frame(
+        &mut self,
+        frame: &Frame<C>,
+        metadata: T::FrameMetadata,
+    ) -> Result<Option<Self::RxTransferToken>, UpdateTransferError> {
+        let index = self
+            .rx_transfers
+            .iter()
+            .position(|slot| match &slot.status {
+                Some(status) => same_transfer(&status.inner().transfer_metadata, &frame.metadata),
+                None => false,
+            })
+            .ok_or(UpdateTransferError::DoesNotExist)?;
+
+        let slot = &mut self.rx_transfers[index];
+        let rx_transfer = match &mut slot.status {
+            Some(TransferStatus::Active(rx_transfer)) => rx_transfer,
+            Some(TransferStatus::TimedOut(_)) => {
+                if frame.first_frame {
+                    // Start of a new transfer reusing the timed out one's metadata
+                    slot.status = None;
+                    return Err(UpdateTransferError::DoesNotExist);
+                }
+                return Err(UpdateTransferError::TimedOut);
+            }
+            None => unreachable!(),
+        };
+
+        let crc_size =
+            match T::update_rx_metadata(&mut rx_transfer.transport_metadata, metadata, frame) {
+                Ok(crc_size) => crc_size,
+                Err(e) => {
+                    // Free the slot up, so the transfer can be sent again
+                    slot.status = None;
+                    return Err(UpdateTransferError::RxError(e));
+                }
+            };
+
+        // Anything past the extent is dropped, but still goes through the CRC above
+        let data = within_extent(rx_transfer.extent, rx_transfer.payload.len(), frame.payload);
+        if rx_transfer.payload.extend_from_slice(data).is_err() {
+            // Can't ever complete this transfer, free the slot up
+            slot.status = None;
+            return Err(UpdateTransferError::NoSpace);
+        }
+        rx_transfer.received += frame.payload.len();
+        let len = without_crc(rx_transfer.payload.len(), rx_transfer.received, crc_size);
+        rx_transfer.payload.truncate(len);
+
+        if frame.last_frame {
+            let state = SessionState::new(&rx_transfer.transfer_metadata, rx_transfer.timeout);
+            let generation = slot.generation;
+            self.track_session(SessionKey::from_metadata(&frame.metadata), state);
+            // Return token on completion of transfer
+            Ok(Some(StaticRxToken { index, generation }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn new_transfer(
+        &mut self,
+        frame: &Frame<C>,
+        metadata: T::FrameMetadata,
+    ) -> Result<Option<Self::RxTransferToken>, CreateTransferError> {
+        let (extent, timeout) =
+            match self.subscription(frame.metadata.transfer_kind, frame.metadata.port_id) {
+                Some(subscription) => (subscription.extent, subscription.timeout),
+                // Not subscribed, not interested
+                None => return Ok(None),
+            };
+
+        let exists = self.rx_transfers.iter().any(|slot| match &slot.status {
+            Some(TransferStatus::Active(transfer)) => {
+                same_transfer(&transfer.transfer_metadata, &frame.metadata)
+            }
+            _ => false,
+        });
+        if exists {
+            return Err(CreateTransferError::AlreadyExists);
+        }
+
+        let key = SessionKey::from_metadata(&frame.metadata);
+        if self
+            .sessions
+            .iter()
+            .any(|(k, state)| *k == key && state.is_duplicate(&frame.metadata))
+        {
+            return Err(CreateTransferError::Duplicate);
+        }
+
+        let index = free_slot(&self.rx_transfers).ok_or(CreateTransferError::NoSpace)?;
+        let mut payload: heapless::Vec<u8, BUF> =
+            heapless::Vec::from_slice(within_extent(extent, 0, frame.payload))
+                .map_err(|_| CreateTransferError::NoSpace)?;
+
+        let mut transport_metadata = T::RxMetadata::default();
+        let crc_size = T::update_rx_metadata(&mut transport_metadata, metadata, frame)
+            .map_err(CreateTransferError::RxError)?;
+        payload.truncate(without_crc(payload.len(), frame.payload.len(), crc_size));
+        // Only complete transfers count, so a failed one can be sent again with the same ID
+        if frame.last_frame {
+            self.track_session(key, SessionState::new(&frame.metadata, timeout));
+        }
+
+        let generation = self.rx_transfers[index].fill(RxTransfer {
+            transfer_metadata: frame.metadata,
+            transport_metadata,
+            payload,
+            received: frame.payload.len(),
+            extent,
+            timeout,
+        });
+
+        if frame.last_frame {
+            Ok(Some(StaticRxToken { index, generation }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn with_rx_transfer(
+        &mut self,
+        token: Self::RxTransferToken,
+        cb: impl FnOnce(&TransferMetadata<C>, &[u8]),
+    ) -> Result<(), TokenAccessError> {
+        let slot = self
+            .rx_transfers
+            .get_mut(token.index)
+            .ok_or(TokenAccessError::InvalidToken)?;
+
+        match slot.get(token.generation) {
+            Some(TransferStatus::TimedOut(_)) => Err(TokenAccessError::TransferTimeout),
+            Some(TransferStatus::Active(transfer)) => {
+                cb(&transfer.transfer_metadata, &transfer.payload);
+                // Token is consumed, so the slot can be reused
+                slot.status = None;
+                Ok(())
+            }
+            None => Err(TokenAccessError::InvalidToken),
+        }
+    }
+
+    fn cancel_rx_transfer(&mut self, token: Self::RxTransferToken) -> Result<(), TokenAccessError> {
+        self.rx_transfers
+            .get_mut(token.index)
+            .and_then(|slot| slot.take(token.generation))
+            .ok_or(TokenAccessError::InvalidToken)
+            .map(|_| ())
+    }
+
+    fn create_transmission<E>(
+        &mut self,
+        requested_buffer_size: usize,
+        metadata: &TransferMetadata<C>,
+        cb: impl FnOnce(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<Self::TxTransferToken, InternalOrUserError<CreateTransferError, E>> {
+        let exists = self.tx_transfers.iter().any(|slot| match &slot.status {
+            Some(TransferStatus::Active(transfer)) => {
+                same_transfer(&transfer.transfer_metadata, metadata)
+            }
+            _ => false,
+        });
+        if exists {
+            return Err(InternalOrUserError::InternalError(
+                CreateTransferError::AlreadyExists,
+            ));
+        }
+
+        let index = free_slot(&self.tx_transfers).ok_or(InternalOrUserError::InternalError(
+            CreateTransferError::NoSpace,
+        ))?;
+
+        let final_buf_size = T::get_crc_padded_size(requested_buffer_size);
+        let mut buf = heapless::Vec::<u8, BUF>::new();
+        buf.resize(final_buf_size, 0)
+            .map_err(|_| InternalOrUserError::InternalError(CreateTransferError::NoSpace))?;
+
+        let consumed =
+            cb(&mut buf[0..requested_buffer_size]).map_err(InternalOrUserError::UserError)?;
+        // Don't let the user screw this up for us
+        let consumed = core::cmp::min(requested_buffer_size, consumed);
+
+        // Process transport CRC + padding and get the actual payload length
+        let real_len = T::process_tx_crc(&mut buf, consumed);
+        buf.truncate(real_len);
+
+        let generation = self.tx_transfers[index].fill(TxTransfer {
+            transfer_metadata: *metadata,
+            transport_metadata: T::TxMetadata::default(),
+            consumed: 0,
+            payload: buf,
+        });
+
+        Ok(StaticTxToken { index, generation })
+    }
+
+    fn transmit(
+        &mut self,
+        token: Self::TxTransferToken,
+        cb: impl FnOnce(&TransferMetadata<C>, &mut T::TxMetadata, &[u8]) -> usize,
+    ) -> Result<Option<Self::TxTransferToken>, TokenAccessError> {
+        let slot = self
+            .tx_transfers
+            .get_mut(token.index)
+            .ok_or(TokenAccessError::InvalidToken)?;
+
+        let transfer = match slot.get_mut(token.generation) {
+            Some(TransferStatus::Active(transfer)) => transfer,
+            Some(TransferStatus::TimedOut(_)) => {
+                slot.status = None;
+                return Err(TokenAccessError::TransferTimeout);
+            }
+            None => return Err(TokenAccessError::InvalidToken),
+        };
+
+        let consumed = cb(
+            &transfer.transfer_metadata,
+            &mut transfer.transport_metadata,
+            &transfer.payload[transfer.consumed..],
+        );
+        transfer.consumed += consumed;
+
+        if transfer.consumed >= transfer.payload.len() {
+            // Transfer complete
+            slot.status = None;
+            Ok(None)
+        } else {
+            Ok(Some(token))
+        }
+    }
+
+    fn cancel_tx_transfer(&mut self, token: Self::TxTransferToken) -> Result<(), TokenAccessError> {
+        self.tx_transfers
+            .get_mut(token.index)
+            .and_then(|slot| slot.take(token.generation))
+            .ok_or(TokenAccessError::InvalidToken)
+            .map(|_| ())
+    }
+
+    fn update_transfers(&mut self, timestamp: Timestamp<C>) {
+        for slot in self.tx_transfers.iter_mut() {
+            if let Some(TransferStatus::Active(transfer)) = &slot.status {
+                if timestamp > transfer.transfer_metadata.timestamp {
+                    if let Some(TransferStatus::Active(transfer)) = slot.status.take() {
+                        slot.status = Some(TransferStatus::TimedOut(transfer));
+                    }
+                }
+            }
+        }
+
+        // RX transfers use their subscription's timeout instead, which is also the transfer-ID
+        // timeout
+        self.sessions.retain(|(_, state)| !state.expired(timestamp));
+        for slot in self.rx_transfers.iter_mut() {
+            if let Some(TransferStatus::Active(transfer)) = &slot.status {
+                if timestamp_expired(
+                    transfer.timeout,
+                    timestamp,
+                    Some(transfer.transfer_metadata.timestamp),
+                ) {
+                    if let Some(TransferStatus::Active(transfer)) = slot.status.take() {
+                        slot.status = Some(TransferStatus::TimedOut(transfer));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::TestClock;
+    use crate::transport::can::{Can, CanFrame};
+    use crate::{Priority, TransferKind};
+
+    use embedded_time::Clock;
+    use embedded_time::duration::Milliseconds;
+
+    type Manager<const RX: usize, const TX: usize, const BUF: usize> =
+        StaticTransferManager<TestClock, Can, RX, TX, BUF, 1, 4>;
+
+    /// Creates a manager subscribed to the port used by every test.
+    fn receiver<const RX: usize, const BUF: usize>(extent: usize) -> Manager<RX, 0, BUF> {
+        let mut manager = Manager::new();
+        manager
+            .subscribe(Subscription::new(
+                TransferKind::Message,
+                100,
+                extent,
+                Milliseconds(1000),
+            ))
+            .unwrap();
+        manager
+    }
+
+    fn make_metadata(clock: &TestClock, transfer_id: u64) -> TransferMetadata<TestClock> {
+        TransferMetadata {
+            timestamp: clock.try_now().unwrap(),
+            priority: Priority::Nominal,
+            transfer_kind: TransferKind::Message,
+            port_id: 100,
+            source_node_id: Some(1),
+            destination_node_id: None,
+            transfer_id,
+        }
+    }
+
+    /// Transmits a whole transfer through the manager, returning the CAN frames.
+    fn transmit<M: TransferManager<TestClock, Can, TxTransferToken = StaticTxToken>>(
+        manager: &mut M,
+        metadata: &TransferMetadata<TestClock>,
+        payload: &[u8],
+    ) -> alloc::vec::Vec<CanFrame<TestClock>> {
+        let mut token = manager
+            .create_transmission(payload.len(), metadata, |buf| {
+                buf.copy_from_slice(payload);
+                Ok::<usize, ()>(payload.len())
+            })
+            .unwrap();
+
+        let mut frames = alloc::vec::Vec::new();
+        loop {
+            let res = manager
+                .transmit(token, |transfer_metadata, tx_metadata, data| {
+                    let (frame, consumed) = Can::transmit_frame(
+                        transfer_metadata,
+                        tx_metadata,
+                        data,
+                        Some(1),
+                        transfer_metadata.timestamp,
+                    )
+                    .unwrap();
+                    frames.push(frame);
+                    consumed
+                })
+                .unwrap();
+            match res {
+                Some(next) => token = next,
+                None => break,
+            }
+        }
+
+        frames
+    }
+
+    fn receive<M: TransferManager<TestClock, Can>>(
+        manager: &mut M,
+        frame: &CanFrame<TestClock>,
+    ) -> Result<Option<M::RxTransferToken>, CreateTransferError> {
+        let (frame, metadata) = Can::rx_process_frame(frame).unwrap();
+        match manager.append_frame(&frame, metadata) {
+            Ok(token) => Ok(token),
+            Err(UpdateTransferError::DoesNotExist) => manager.new_transfer(&frame, metadata),
+            Err(UpdateTransferError::NoSpace) => Err(CreateTransferError::NoSpace),
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let clock = TestClock::default();
+        let mut tx = Manager::<1, 1, 64>::new();
+        let mut rx = receiver::<1, 64>(64);
+
+        // CRC is split across the last two frames
+        let payload: alloc::vec::Vec<u8> = (0..20).collect();
+        let frames = transmit(&mut tx, &make_metadata(&clock, 3), &payload);
+        assert_eq!(frames.len(), 4);
+
+        let mut token = None;
+        for frame in frames.iter() {
+            token = receive(&mut rx, frame).unwrap();
+        }
+
+        let mut received = alloc::vec::Vec::new();
+        rx.with_rx_transfer(token.unwrap(), |metadata, data| {
+            assert_eq!(metadata.transfer_id, 3);
+            received.extend_from_slice(data);
+        })
+        .unwrap();
+        assert_eq!(received, payload);
+
+        // Slots are freed after use
+        let frames = transmit(&mut tx, &make_metadata(&clock, 4), &payload);
+        for frame in frames.iter() {
+            receive(&mut rx, frame).unwrap();
+        }
+    }
+
+    #[test]
+    fn rx_no_space() {
+        let clock = TestClock::default();
+        let mut tx = Manager::<0, 3, 64>::new();
+        let mut rx = receiver::<2, 64>(64);
+
+        let payload = [0u8; 19];
+        let first = transmit(&mut tx, &make_metadata(&clock, 0), &payload);
+        let second = transmit(&mut tx, &make_metadata(&clock, 1), &payload);
+        let third = transmit(&mut tx, &make_metadata(&clock, 2), &payload);
+
+        assert!(matches!(receive(&mut rx, &first[0]), Ok(None)));
+        assert!(matches!(receive(&mut rx, &second[0]), Ok(None)));
+        assert!(matches!(
+            receive(&mut rx, &third[0]),
+            Err(CreateTransferError::NoSpace)
+        ));
+
+        // Ongoing transfers aren't affected
+        for frame in first[1..].iter() {
+            receive(&mut rx, frame).unwrap();
+        }
+    }
+
+    #[test]
+    fn rx_buffer_overflow() {
+        let clock = TestClock::default();
+        let mut tx = Manager::<0, 1, 64>::new();
+        let mut rx = receiver::<1, 16>(64);
+
+        let frames = transmit(&mut tx, &make_metadata(&clock, 0), &[0u8; 19]);
+        assert!(matches!(receive(&mut rx, &frames[0]), Ok(None)));
+        assert!(matches!(receive(&mut rx, &frames[1]), Ok(None)));
+        assert!(matches!(
+            receive(&mut rx, &frames[2]),
+            Err(CreateTransferError::NoSpace)
+        ));
+
+        // Transfer was dropped, freeing the slot
+        let frames = transmit(&mut tx, &make_metadata(&clock, 1), &[0u8; 5]);
+        assert!(matches!(receive(&mut rx, &frames[0]), Ok(Some(_))));
+    }
+
+    /// Payload past the extent is dropped, but still has to pass the CRC check.
+    #[test]
+    fn extent_truncates() {
+        let clock = TestClock::default();
+        let mut tx = Manager::<0, 1, 64>::new();
+        let mut rx = receiver::<1, 64>(10);
+
+        let payload: alloc::vec::Vec<u8> = (0..19).collect();
+        let frames = transmit(&mut tx, &make_metadata(&clock, 0), &payload);
+        let mut token = None;
+        for frame in frames.iter() {
+            token = receive(&mut rx, frame).unwrap();
+        }
+        rx.with_rx_transfer(token.unwrap(), |_, data| assert_eq!(data, &payload[0..10]))
+            .unwrap();
+
+        // Corrupt data that's beyond the extent
+        let mut frames = transmit(&mut tx, &make_metadata(&clock, 1), &payload);
+        frames[2].payload[0] ^= 0xFF;
+        receive(&mut rx, &frames[0]).unwrap();
+        receive(&mut rx, &frames[1]).unwrap();
+        let (frame, metadata) = Can::rx_process_frame(&frames[2]).unwrap();
+        assert!(matches!(
+            rx.append_frame(&frame, metadata),
+            Err(UpdateTransferError::RxError(crate::RxError::CrcError))
+        ));
+    }
+
+    #[test]
+    fn tx_no_space() {
+        let clock = TestClock::default();
+        let mut tx = Manager::<0, 1, 16>::new();
+
+        // Doesn't fit once the CRC is added
+        let res = tx.create_transmission(15, &make_metadata(&clock, 0), |_| Ok::<usize, ()>(15));
+        assert!(matches!(
+            res,
+            Err(InternalOrUserError::InternalError(
+                CreateTransferError::NoSpace
+            ))
+        ));
+
+        let token = tx
+            .create_transmission(8, &make_metadata(&clock, 0), |_| Ok::<usize, ()>(8))
+            .unwrap();
+        let res = tx.create_transmission(8, &make_metadata(&clock, 1), |_| Ok::<usize, ()>(8));
+        assert!(matches!(
+            res,
+            Err(InternalOrUserError::InternalError(
+                CreateTransferError::NoSpace
+            ))
+        ));
+
+        tx.cancel_tx_transfer(token).unwrap();
+        tx.create_transmission(8, &make_metadata(&clock, 1), |_| Ok::<usize, ()>(8))
+            .unwrap();
+    }
+
+    #[test]
+    fn timed_out_slots_reused() {
+        let mut clock = TestClock::default();
+        let mut tx = Manager::<0, 2, 64>::new();
+        let mut rx = receiver::<1, 64>(64);
+
+        let first = transmit(&mut tx, &make_metadata(&clock, 0), &[0u8; 19]);
+        receive(&mut rx, &first[0]).unwrap();
+
+        clock.add_duration(&Milliseconds(2000u32)).unwrap();
+        rx.update_transfers(clock.try_now().unwrap());
+
+        let (frame, metadata) = Can::rx_process_frame(&first[1]).unwrap();
+        assert!(matches!(
+            rx.append_frame(&frame, metadata),
+            Err(UpdateTransferError::TimedOut)
+        ));
+
+        let second = transmit(&mut tx, &make_metadata(&clock, 1), &[0u8; 5]);
+        assert!(matches!(receive(&mut rx, &second[0]), Ok(Some(_))));
+    }
+
+    #[test]
+    fn duplicates_rejected() {
+        let mut clock = TestClock::default();
+        let mut tx = Manager::<0, 1, 64>::new();
+        let mut rx = receiver::<1, 64>(64);
+
+        let frames = transmit(&mut tx, &make_metadata(&clock, 31), &[0u8; 5]);
+        let token = receive(&mut rx, &frames[0]).unwrap().unwrap();
+        rx.cancel_rx_transfer(token).unwrap();
+        assert!(matches!(
+            receive(&mut rx, &frames[0]),
+            Err(CreateTransferError::Duplicate)
+        ));
+
+        // Transfer ID wraps around to 0
+        let frames = transmit(&mut tx, &make_metadata(&clock, 32), &[0u8; 5]);
+        let token = receive(&mut rx, &frames[0]).unwrap().unwrap();
+        rx.cancel_rx_transfer(token).unwrap();
+
+        // Session state is dropped after the transfer-ID timeout
+        clock.add_duration(&Milliseconds(2000u32)).unwrap();
+        rx.update_transfers(clock.try_now().unwrap());
+        let frames = transmit(&mut tx, &make_metadata(&clock, 32), &[0u8; 5]);
+        assert!(matches!(receive(&mut rx, &frames[0]), Ok(Some(_))));
+    }
+
+    /// A transfer failing its CRC isn't accepted, so it can be sent again with the same ID.
+    #[test]
+    fn resent_after_failed_crc() {
+        let clock = TestClock::default();
+        let mut tx = Manager::<0, 1, 64>::new();
+        let mut rx = receiver::<1, 64>(64);
+        let payload: alloc::vec::Vec<u8> = (0..20).collect();
+
+        let frames = transmit(&mut tx, &make_metadata(&clock, 4), &payload);
+        let token = frames
+            .iter()
+            .fold(None, |_, frame| receive(&mut rx, frame).unwrap());
+        rx.cancel_rx_transfer(token.unwrap()).unwrap();
+
+        let frames = transmit(&mut tx, &make_metadata(&clock, 5), &payload);
+        let mut corrupted = frames.clone();
+        corrupted[1].payload[0] ^= 1;
+        let (last, rest) = corrupted.split_last().unwrap();
+        for frame in rest {
+            assert!(matches!(receive(&mut rx, frame), Ok(None)));
+        }
+        let (last, metadata) = Can::rx_process_frame(last).unwrap();
+        assert!(matches!(
+            rx.append_frame(&last, metadata),
+            Err(UpdateTransferError::RxError(_))
+        ));
+
+        // The previous transfer is still a duplicate, but the failed one isn't
+        let duplicate = transmit(&mut tx, &make_metadata(&clock, 4), &payload);
+        assert!(matches!(
+            receive(&mut rx, &duplicate[0]),
+            Err(CreateTransferError::Duplicate)
+        ));
+        let token = frames
+            .iter()
+            .fold(None, |_, frame| receive(&mut rx, frame).unwrap());
+        rx.cancel_rx_transfer(token.unwrap()).unwrap();
+        assert!(matches!(
+            receive(&mut rx, &frames[0]),
+            Err(CreateTransferError::Duplicate)
+        ));
+    }
+}
@@ -36,12 +36,31 @@ bitfield! {
 }
 
 impl CanMessageId {
+    /// ID of a message from `source_id`, or of an anonymous message with a source ID of 0 if
+    /// `None`. See [`CanMessageId::anonymous`] to pick the source ID of anonymous messages.
     // TODO bounds checks (can these be auto-implemented?)
     #[allow(clippy::new_ret_no_self)]
     pub fn new(priority: Priority, subject_id: PortId, source_id: Option<NodeId>) -> ExtendedId {
-        let is_anon = source_id.is_none();
-        // TODO do better than XKCD 221
-        let source_id = source_id.unwrap_or(4);
+        match source_id {
+            Some(source_id) => Self::build(priority, subject_id, source_id, false),
+            None => Self::anonymous(priority, subject_id, 0),
+        }
+    }
+
+    /// ID of an anonymous message, carrying `pseudo_id` as its source ID.
+    ///
+    /// The pseudo ID should differ between nodes, so that anonymous messages published at the
+    /// same time by different nodes don't end up with the same CAN ID.
+    pub fn anonymous(priority: Priority, subject_id: PortId, pseudo_id: NodeId) -> ExtendedId {
+        Self::build(priority, subject_id, pseudo_id, true)
+    }
+
+    fn build(
+        priority: Priority,
+        subject_id: PortId,
+        source_id: NodeId,
+        is_anon: bool,
+    ) -> ExtendedId {
         let mut id = CanMessageId(0);
         id.set_priority(priority.to_u8().unwrap());
         id.set_svc(false);
@@ -147,16 +166,25 @@ bitfield! {
     /// Toggle bit to ensure messages are received in order.
     pub bool, toggle, set_toggle: 5;
     /// Transfer ID to ensure the correct messages are being received.
-    pub TransferId, transfer_id, set_transfer_id: 4, 0;
+    pub u8, raw_transfer_id, set_raw_transfer_id: 4, 0;
 }
 
 impl TailByte {
-    pub fn new(is_start: bool, is_end: bool, toggle: bool, transfer_id: u8) -> Self {
+    /// Number of bits available for the transfer ID.
+    pub const TRANSFER_ID_BITS: u32 = 5;
+
+    /// Creates a tail byte. Only the low 5 bits of the transfer ID are used.
+    pub fn new(is_start: bool, is_end: bool, toggle: bool, transfer_id: TransferId) -> Self {
         let mut byte = TailByte(0);
         byte.set_start_of_transfer(is_start);
         byte.set_end_of_transfer(is_end);
         byte.set_toggle(toggle);
-        byte.set_transfer_id(transfer_id);
+        byte.set_raw_transfer_id((transfer_id & ((1 << Self::TRANSFER_ID_BITS) - 1)) as u8);
         byte
     }
+
+    /// Transfer ID to ensure the correct messages are being received.
+    pub fn transfer_id(&self) -> TransferId {
+        self.raw_transfer_id() as TransferId
+    }
 }
@@ -0,0 +1,557 @@
+//! Glue between a node on the classic CAN transport and any [`embedded_can`] driver.
+//!
+//! Received frames are converted with [`CanFrame::from_frame`], timestamped on arrival and fed
+//! to [`Node::try_receive_frame`]. The frames queued through [`Node::enqueue`] are converted
+//! with [`CanFrame::to_frame`] and written out by priority, through [`Pump`] for non-blocking
+//! drivers or [`transmit_blocking`] for blocking ones.
+//!
+//! ```
+//! use cyphal::transfer::TransferManager;
+//! use cyphal::transport::can::{self, Can, Pump};
+//! use cyphal::Node;
+//!
+//! fn spin<M, C, D>(node: &mut Node<M, Can, C>, driver: &mut D, pump: &mut Pump<D::Frame>, clock: &C)
+//! where
+//!     M: TransferManager<C, Can>,
+//!     C: embedded_time::Clock + Clone,
+//!     D: embedded_can::nb::Can,
+//! {
+//!     while let Ok(token) = can::receive(node, driver, clock) {
+//!         if let Some(_token) = token {
+//!             // Handle the transfer through node.transfer_manager
+//!         }
+//!     }
+//!     pump.transmit(node, driver, clock).ok();
+//! }
+//! ```
+
+use embedded_can::{Frame, blocking, nb as nb_can};
+
+use super::{Can, CanFrame};
+use crate::node::TransmitFrameError;
+use crate::transfer::TransferManager;
+use crate::{Node, RxError};
+
+/// Errors from moving frames between a node and an [`embedded_can`] driver.
+#[derive(Debug)]
+pub enum DriverError<E> {
+    /// The driver failed
+    Driver(E),
+    /// The node rejected a received frame
+    Rx(RxError),
+    /// The driver's frame type can't hold a frame from the node, which is dropped
+    Unsupported,
+    /// The clock couldn't be read
+    Clock(embedded_time::clock::Error),
+}
+
+/// Frames handled by a call to [`Pump::transmit`], [`transmit_blocking`] or
+/// `SocketCan::flush`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Flushed {
+    /// Frames handed to the driver
+    pub sent: usize,
+    /// Frames dropped because their transfer's deadline passed
+    pub expired: usize,
+}
+
+/// Receives a frame from a non-blocking driver and feeds it to `node`, returning the transfer
+/// it completes, if any.
+///
+/// Returns [`nb::Error::WouldBlock`] once the driver has nothing left, and `Ok(None)` for
+/// frames Cyphal doesn't use, like standard ID ones.
+pub fn receive<
+    M,
+    C,
+    D,
+    const PORTS: usize,
+    const REQUESTS: usize,
+    const QUEUE: usize,
+    const RESPONSE: usize,
+>(
+    node: &mut Node<M, Can, C, PORTS, REQUESTS, QUEUE, RESPONSE>,
+    driver: &mut D,
+    clock: &C,
+) -> nb::Result<Option<M::RxTransferToken>, DriverError<D::Error>>
+where
+    M: TransferManager<C, Can>,
+    C: embedded_time::Clock + Clone,
+    D: nb_can::Can,
+{
+    let frame = driver
+        .receive()
+        .map_err(|error| error.map(DriverError::Driver))?;
+    let timestamp = clock.try_now().map_err(DriverError::Clock)?;
+    let Some(frame) = CanFrame::from_frame(timestamp, &frame) else {
+        return Ok(None);
+    };
+    Ok(node.try_receive_frame(&frame).map_err(DriverError::Rx)?)
+}
+
+/// Blocks until the driver receives an extended data frame and feeds it to `node`, returning
+/// the transfer it completes, if any.
+pub fn receive_blocking<
+    M,
+    C,
+    D,
+    const PORTS: usize,
+    const REQUESTS: usize,
+    const QUEUE: usize,
+    const RESPONSE: usize,
+>(
+    node: &mut Node<M, Can, C, PORTS, REQUESTS, QUEUE, RESPONSE>,
+    driver: &mut D,
+    clock: &C,
+) -> Result<Option<M::RxTransferToken>, DriverError<D::Error>>
+where
+    M: TransferManager<C, Can>,
+    C: embedded_time::Clock + Clone,
+    D: blocking::Can,
+{
+    loop {
+        let frame = driver.receive().map_err(DriverError::Driver)?;
+        let timestamp = clock.try_now().map_err(DriverError::Clock)?;
+        if let Some(frame) = CanFrame::from_frame(timestamp, &frame) {
+            return node.try_receive_frame(&frame).map_err(DriverError::Rx);
+        }
+    }
+}
+
+/// Writes out every frame queued in `node` through [`Node::enqueue`] to a blocking driver,
+/// highest priority first.
+///
+/// Stops at the first frame the driver fails to write, which is then lost along with the rest
+/// of its transfer.
+pub fn transmit_blocking<
+    M,
+    C,
+    D,
+    const PORTS: usize,
+    const REQUESTS: usize,
+    const QUEUE: usize,
+    const RESPONSE: usize,
+>(
+    node: &mut Node<M, Can, C, PORTS, REQUESTS, QUEUE, RESPONSE>,
+    driver: &mut D,
+    clock: &C,
+) -> Result<Flushed, DriverError<D::Error>>
+where
+    M: TransferManager<C, Can>,
+    C: embedded_time::Clock + Clone,
+    D: blocking::Can,
+{
+    let mut flushed = Flushed::default();
+    while let Some(frame) = pop_frame(node, clock, &mut flushed)? {
+        driver.transmit(&frame).map_err(DriverError::Driver)?;
+        flushed.sent += 1;
+    }
+    Ok(flushed)
+}
+
+/// Feeds the frames queued in a node to a non-blocking driver.
+///
+/// Holds on to the frame the driver had no room for, or handed back to make room for a higher
+/// priority one, until the next call.
+#[derive(Debug)]
+pub struct Pump<F> {
+    pending: Option<F>,
+}
+
+impl<F: Frame> Default for Pump<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Frame> Pump<F> {
+    pub const fn new() -> Self {
+        Self { pending: None }
+    }
+
+    /// Whether every frame taken from the node has been handed to the driver.
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    /// Writes frames queued in `node` through [`Node::enqueue`] to the driver, highest priority
+    /// first, until the queue is empty or the driver is full.
+    ///
+    /// Call it again once the driver has room, e.g. from its transmit interrupt. The held back
+    /// frame goes out first and isn't checked against its transfer's deadline again.
+    pub fn transmit<
+        M,
+        C,
+        D,
+        const PORTS: usize,
+        const REQUESTS: usize,
+        const QUEUE: usize,
+        const RESPONSE: usize,
+    >(
+        &mut self,
+        node: &mut Node<M, Can, C, PORTS, REQUESTS, QUEUE, RESPONSE>,
+        driver: &mut D,
+        clock: &C,
+    ) -> Result<Flushed, DriverError<D::Error>>
+    where
+        M: TransferManager<C, Can>,
+        C: embedded_time::Clock + Clone,
+        D: nb_can::Can<Frame = F>,
+    {
+        let mut flushed = Flushed::default();
+        loop {
+            let frame = match self.pending.take() {
+                Some(frame) => frame,
+                None => match pop_frame(node, clock, &mut flushed)? {
+                    Some(frame) => frame,
+                    None => return Ok(flushed),
+                },
+            };
+            match driver.transmit(&frame) {
+                Ok(replaced) => {
+                    flushed.sent += 1;
+                    self.pending = replaced;
+                }
+                Err(nb::Error::WouldBlock) => {
+                    self.pending = Some(frame);
+                    return Ok(flushed);
+                }
+                Err(nb::Error::Other(error)) => {
+                    self.pending = Some(frame);
+                    return Err(DriverError::Driver(error));
+                }
+            }
+        }
+    }
+}
+
+/// Takes the next frame to go out from the node's queue, counting the expired ones skipped on
+/// the way.
+fn pop_frame<
+    M,
+    C,
+    F,
+    E,
+    const PORTS: usize,
+    const REQUESTS: usize,
+    const QUEUE: usize,
+    const RESPONSE: usize,
+>(
+    node: &mut Node<M, Can, C, PORTS, REQUESTS, QUEUE, RESPONSE>,
+    clock: &C,
+    flushed: &mut Flushed,
+) -> Result<Option<F>, DriverError<E>>
+where
+    M: TransferManager<C, Can>,
+    C: embedded_time::Clock + Clone,
+    F: Frame,
+{
+    loop {
+        let now = clock.try_now().map_err(DriverError::Clock)?;
+        match node.pop_frame(now) {
+            Some(Ok(frame)) => return frame.to_frame().ok_or(DriverError::Unsupported).map(Some),
+            Some(Err(TransmitFrameError::Expired)) => flushed.expired += 1,
+            // Nothing else comes out of the queue
+            Some(Err(_)) => {}
+            None => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+
+    use arrayvec::ArrayVec;
+    use embedded_can::{ErrorKind, ExtendedId, Id, StandardId};
+    use embedded_time::Clock;
+    use embedded_time::duration::Milliseconds;
+
+    use super::*;
+    use crate::time::TestClock;
+    use crate::transfer::HeapTransferManager;
+    use crate::{Priority, Subscription, TransferKind, TransmissionType};
+
+    type TestNode = Node<HeapTransferManager<TestClock, Can>, Can, TestClock>;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestFrame {
+        id: Id,
+        remote: bool,
+        data: ArrayVec<[u8; 8]>,
+    }
+
+    impl Frame for TestFrame {
+        fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+            if data.len() > 8 {
+                return None;
+            }
+            Some(Self {
+                id: id.into(),
+                remote: false,
+                data: data.iter().copied().collect(),
+            })
+        }
+
+        fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+            let mut frame = Self::new(id, &[0; 8][..dlc])?;
+            frame.remote = true;
+            Some(frame)
+        }
+
+        fn is_extended(&self) -> bool {
+            matches!(self.id, Id::Extended(_))
+        }
+
+        fn is_remote_frame(&self) -> bool {
+            self.remote
+        }
+
+        fn id(&self) -> Id {
+            self.id
+        }
+
+        fn dlc(&self) -> usize {
+            self.data.len()
+        }
+
+        fn data(&self) -> &[u8] {
+            &self.data
+        }
+    }
+
+    /// Controller with a few transmit mailboxes, swapping out the lowest priority frame like
+    /// `embedded_can::nb::Can` allows.
+    #[derive(Default)]
+    struct Mailboxes {
+        capacity: usize,
+        tx: Vec<TestFrame>,
+        rx: VecDeque<TestFrame>,
+    }
+
+    impl nb_can::Can for Mailboxes {
+        type Frame = TestFrame;
+        type Error = ErrorKind;
+
+        fn transmit(&mut self, frame: &TestFrame) -> nb::Result<Option<TestFrame>, ErrorKind> {
+            if self.tx.len() < self.capacity {
+                self.tx.push(frame.clone());
+                return Ok(None);
+            }
+            let raw = |frame: &TestFrame| match frame.id {
+                Id::Extended(id) => id.as_raw(),
+                Id::Standard(_) => 0,
+            };
+            let lowest = (0..self.tx.len())
+                .max_by_key(|&index| raw(&self.tx[index]))
+                .ok_or(nb::Error::WouldBlock)?;
+            if raw(&self.tx[lowest]) <= raw(frame) {
+                return Err(nb::Error::WouldBlock);
+            }
+            Ok(Some(core::mem::replace(
+                &mut self.tx[lowest],
+                frame.clone(),
+            )))
+        }
+
+        fn receive(&mut self) -> nb::Result<TestFrame, ErrorKind> {
+            self.rx.pop_front().ok_or(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Bus that never fails or fills up.
+    #[derive(Default)]
+    struct Bus {
+        tx: Vec<TestFrame>,
+        rx: VecDeque<TestFrame>,
+    }
+
+    impl blocking::Can for Bus {
+        type Frame = TestFrame;
+        type Error = ErrorKind;
+
+        fn transmit(&mut self, frame: &TestFrame) -> Result<(), ErrorKind> {
+            self.tx.push(frame.clone());
+            Ok(())
+        }
+
+        fn receive(&mut self) -> Result<TestFrame, ErrorKind> {
+            self.rx.pop_front().ok_or(ErrorKind::Other)
+        }
+    }
+
+    fn publish(
+        node: &mut TestNode,
+        clock: &TestClock,
+        priority: Priority,
+        port_id: u16,
+        payload: &[u8],
+    ) {
+        let now = clock.try_now().unwrap();
+        let token = node
+            .start_tx_transfer(
+                payload.len(),
+                now + Milliseconds(100u32),
+                priority,
+                port_id,
+                TransmissionType::Broadcast,
+                |buf| {
+                    buf.copy_from_slice(payload);
+                    Ok::<usize, ()>(payload.len())
+                },
+            )
+            .unwrap();
+        node.enqueue(token, now).unwrap();
+    }
+
+    fn subscriber(port_id: u16) -> TestNode {
+        let mut node = TestNode::new(Some(2), HeapTransferManager::new());
+        node.subscribe(Subscription::new(
+            TransferKind::Message,
+            port_id,
+            64,
+            Milliseconds(1000),
+        ))
+        .unwrap();
+        node
+    }
+
+    fn payload(
+        node: &mut TestNode,
+        token: <HeapTransferManager<TestClock, Can> as TransferManager<TestClock, Can>>::RxTransferToken,
+    ) -> Vec<u8> {
+        let mut received = Vec::new();
+        node.transfer_manager
+            .with_rx_transfer(token, |_, payload| received.extend_from_slice(payload))
+            .unwrap();
+        received
+    }
+
+    #[test]
+    fn frame_conversions() {
+        let clock = TestClock::default();
+        let now = clock.try_now().unwrap();
+        let id = ExtendedId::new(0x1234567).unwrap();
+
+        let frame = CanFrame::new(now, id.as_raw(), &[1, 2, 3]);
+        let converted: TestFrame = frame.to_frame().unwrap();
+        assert_eq!(converted.id, Id::Extended(id));
+        assert_eq!(converted.data(), [1, 2, 3]);
+
+        let back = CanFrame::from_frame(now, &converted).unwrap();
+        assert_eq!(back.id, id);
+        assert_eq!(back.payload, frame.payload);
+        assert_eq!(back.timestamp, now);
+
+        let standard = TestFrame::new(StandardId::new(0x123).unwrap(), &[1]).unwrap();
+        assert!(CanFrame::from_frame(now, &standard).is_none());
+        let remote = TestFrame::new_remote(id, 1).unwrap();
+        assert!(CanFrame::from_frame(now, &remote).is_none());
+    }
+
+    #[test]
+    fn blocking_round_trip() {
+        let clock = TestClock::default();
+        let mut publisher = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut subscriber = subscriber(100);
+        let mut bus = Bus::default();
+
+        publish(
+            &mut publisher,
+            &clock,
+            Priority::Nominal,
+            100,
+            b"hello over embedded-can",
+        );
+        let flushed = transmit_blocking(&mut publisher, &mut bus, &clock).unwrap();
+        assert_eq!(
+            flushed,
+            Flushed {
+                sent: 4,
+                expired: 0
+            }
+        );
+
+        // Standard ID frames are skipped
+        bus.rx
+            .push_back(TestFrame::new(StandardId::new(1).unwrap(), &[]).unwrap());
+        bus.rx.extend(bus.tx.drain(..));
+        let token = loop {
+            if let Some(token) = receive_blocking(&mut subscriber, &mut bus, &clock).unwrap() {
+                break token;
+            }
+        };
+        assert_eq!(payload(&mut subscriber, token), b"hello over embedded-can");
+        assert!(matches!(
+            receive_blocking(&mut subscriber, &mut bus, &clock),
+            Err(DriverError::Driver(ErrorKind::Other))
+        ));
+    }
+
+    #[test]
+    fn pump() {
+        let mut clock = TestClock::default();
+        let mut publisher = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut subscriber = subscriber(100);
+        let mut mailboxes = Mailboxes {
+            capacity: 2,
+            ..Default::default()
+        };
+        let mut pump = Pump::new();
+
+        publish(&mut publisher, &clock, Priority::Slow, 100, b"slow");
+        assert_eq!(
+            pump.transmit(&mut publisher, &mut mailboxes, &clock)
+                .unwrap(),
+            Flushed {
+                sent: 1,
+                expired: 0
+            }
+        );
+        assert!(pump.is_idle());
+
+        // Two more frames than fit, the slow one gets swapped out and held back with them
+        publish(&mut publisher, &clock, Priority::Fast, 101, b"0123456789");
+        let flushed = pump
+            .transmit(&mut publisher, &mut mailboxes, &clock)
+            .unwrap();
+        assert_eq!(flushed.sent, 2);
+        assert!(!pump.is_idle());
+        assert!(mailboxes.tx.iter().all(|frame| {
+            CanFrame::<TestClock>::from_frame(clock.try_now().unwrap(), frame)
+                .is_some_and(|frame| frame.id.as_raw() >> 26 == Priority::Fast as u32)
+        }));
+
+        // Once the mailboxes are empty the slow frame goes out again, before anything expires
+        let sent: Vec<_> = mailboxes.tx.drain(..).collect();
+        pump.transmit(&mut publisher, &mut mailboxes, &clock)
+            .unwrap();
+        assert!(pump.is_idle());
+        assert_eq!(mailboxes.tx.len(), 1);
+        mailboxes.rx.extend(mailboxes.tx.drain(..));
+        mailboxes.rx.extend(sent);
+        let mut received = Vec::new();
+        loop {
+            match receive(&mut subscriber, &mut mailboxes, &clock) {
+                Ok(Some(token)) => received.push(payload(&mut subscriber, token)),
+                Ok(None) => {}
+                Err(nb::Error::WouldBlock) => break,
+                Err(error) => panic!("{:?}", error),
+            }
+        }
+        assert_eq!(received, [b"slow".to_vec()]);
+
+        // Frames past their deadline never reach the driver
+        publish(&mut publisher, &clock, Priority::Nominal, 100, b"late");
+        clock.add_duration(&Milliseconds(200u32)).unwrap();
+        assert_eq!(
+            pump.transmit(&mut publisher, &mut mailboxes, &clock)
+                .unwrap(),
+            Flushed {
+                sent: 0,
+                expired: 1
+            }
+        );
+        assert!(mailboxes.tx.is_empty());
+    }
+}
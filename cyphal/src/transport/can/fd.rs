@@ -1,390 +1,197 @@
-//! UAVCAN/CAN-FD transport implementation WIP
+//! UAVCAN/CAN-FD transport implementation.
+//!
+//! Framing is shared with classic CAN (see `legacy.rs`), the differences
+//! being the 64 byte MTU and the restricted set of frame lengths CAN FD allows. Any
+//! frame that would not land on a valid DLC length gets zero padding inserted before
+//! the tail byte (and before the CRC for multi-frame transfers, so the padding is
+//! covered by the transfer CRC).
 
 use arrayvec::ArrayVec;
-use embedded_hal::can::ExtendedId;
-use embedded_time::Clock;
-use num_traits::FromPrimitive;
+use embedded_can::ExtendedId;
 
-use super::bitfields::*;
-use crate::crc16::Crc16;
-use crate::internal::InternalRxFrame;
+use super::legacy::{Can, FrameMetadata, RxMetadata, TxMetadata, next_frame_header, process_frame};
 use crate::time::Timestamp;
+use crate::transfer::TransferMetadata;
 use crate::transport::Transport;
-use crate::StreamingIterator;
-use crate::{NodeId, Priority, RxError, TransferKind, TxError};
-use crate::transfer::Transfer;
+use crate::{NodeId, RxError, TransferId, TxError};
+
+use crc_any::CRCu16;
 
 /// Unit struct for declaring transport type
 #[derive(Copy, Clone, Debug)]
 pub struct FdCan;
 
-impl<C: embedded_time::Clock + 'static> Transport<C> for FdCan {
-    type Frame = FdCanFrame<C>;
-    type FrameIter<'a, X: Transfer<'a, C>> = FdCanIter<'a, C, X> where X: 'a;
-
-    const MTU_SIZE: usize = 64;
-
-    fn rx_process_frame<'a>(
-        node_id: &Option<NodeId>,
-        frame: &'a Self::Frame,
-    ) -> Result<Option<InternalRxFrame<'a, C>>, RxError> {
-        // Frames cannot be empty. They must at least have a tail byte.
-        // NOTE: libcanard specifies this as only for multi-frame transfers but uses
-        // this logic.
-        if frame.payload.is_empty() {
-            return Err(RxError::FrameEmpty);
-        }
-
-        // Pull tail byte from payload
-        let tail_byte = TailByte(*frame.payload.last().unwrap());
-
-        // Protocol version states SOT must have toggle set
-        if tail_byte.start_of_transfer() && !tail_byte.toggle() {
-            return Err(RxError::TransferStartMissingToggle);
-        }
-        // Non-last frames must use the MTU fully
-        if !tail_byte.end_of_transfer() && frame.payload.len() < <Self as Transport<C>>::MTU_SIZE {
-            return Err(RxError::NonLastUnderUtilization);
-        }
-
-        if CanServiceId(frame.id.as_raw()).is_svc() {
-            // Handle services
-            let id = CanServiceId(frame.id.as_raw());
+/// Frame lengths indexed by DLC.
+const DLC_TO_LEN: [usize; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
 
-            // Ignore invalid frames
-            if !id.valid() {
-                return Err(RxError::InvalidCanId);
-            }
-
-            // Ignore frames not meant for us
-            if node_id.is_none() || id.destination_id() != node_id.unwrap() {
-                return Ok(None);
-            }
-
-            let transfer_kind = if id.is_req() {
-                TransferKind::Request
-            } else {
-                TransferKind::Response
-            };
-
-            return Ok(Some(InternalRxFrame::as_service(
-                frame.timestamp,
-                Priority::from_u8(id.priority()).unwrap(),
-                transfer_kind,
-                id.service_id(),
-                id.source_id(),
-                id.destination_id(),
-                tail_byte.transfer_id(),
-                tail_byte.start_of_transfer(),
-                tail_byte.end_of_transfer(),
-                &frame.payload,
-            )));
-        } else {
-            // Handle messages
-            let id = CanMessageId(frame.id.as_raw());
-
-            // We can ignore ID in anonymous transfers
-            let source_node_id = if id.is_anon() {
-                // Anonymous transfers can only be single-frame transfers
-                if !(tail_byte.start_of_transfer() && tail_byte.end_of_transfer()) {
-                    return Err(RxError::AnonNotSingleFrame);
-                }
-
-                None
-            } else {
-                Some(id.source_id())
-            };
-
-            if !id.valid() {
-                return Err(RxError::InvalidCanId);
-            }
-
-            return Ok(Some(InternalRxFrame::as_message(
-                frame.timestamp,
-                Priority::from_u8(id.priority()).unwrap(),
-                id.subject_id(),
-                source_node_id,
-                tail_byte.transfer_id(),
-                tail_byte.start_of_transfer(),
-                tail_byte.end_of_transfer(),
-                &frame.payload,
-            )));
-        }
-    }
-
-    fn transmit<'a, X: Transfer<'a, C>>(
-        transfer: &X,
-    ) -> Result<Self::FrameIter<'a>, TxError> {
-        FdCanIter::new(transfer, Some(1))
-    }
+/// Converts a CAN FD DLC into the number of bytes in the frame.
+///
+/// DLC values above 15 are clamped to the maximum frame length.
+pub fn dlc_to_len(dlc: u8) -> usize {
+    DLC_TO_LEN[core::cmp::min(dlc as usize, 15)]
 }
 
-/// Iterator type to transmit a transfer.
+/// Converts a frame length into the smallest DLC able to hold it.
 ///
-/// By splitting transmission into an iterator I can easily `.collect()` it for a handy
-/// array, store it in another object, or just bulk transfer it all at once, without
-/// having to commit to any proper memory model.
-#[derive(Debug)]
-pub struct FdCanIter<'a, C: embedded_time::Clock, X: Transfer<'a, C>> {
-    transfer: &'a X,
-    frame_id: ExtendedId,
-    payload_offset: usize,
-    crc: Crc16,
-    crc_left: u8,
-    toggle: bool,
-    is_start: bool,
-    can_frame: Option<FdCanFrame<C>>,
+/// Lengths above 64 are clamped to the maximum DLC.
+pub fn len_to_dlc(len: usize) -> u8 {
+    DLC_TO_LEN
+        .iter()
+        .position(|&l| l >= len)
+        .unwrap_or(DLC_TO_LEN.len() - 1) as u8
 }
 
-// TODO there must be a way to link the MTU sizes into here? I tried with const generics but that got complicated and unstable
-impl<'a, C: embedded_time::Clock, X: Transfer<'a, C>> FdCanIter<'a, C, X> {
-    pub fn new(
-        transfer: &'a X,
-        node_id: Option<NodeId>,
-    ) -> Result<Self, TxError> {
-        let frame_id = match transfer.transfer_kind() {
-            TransferKind::Message => {
-                if node_id.is_none() && transfer.payload().len() > 63 {
-                    return Err(TxError::AnonNotSingleFrame);
-                }
+/// Rounds a frame length up to the next length CAN FD can represent.
+pub fn round_up_frame_len(len: usize) -> usize {
+    dlc_to_len(len_to_dlc(len))
+}
 
-                CanMessageId::new(transfer.priority(), transfer.port_id(), node_id)
-            }
-            TransferKind::Request => {
-                // These runtime checks should be removed via proper typing further up but we'll
-                // leave it as is for now.
-                let source = node_id.ok_or(TxError::ServiceNoSourceID)?;
-                let destination = transfer
-                    .remote_node_id()
-                    .ok_or(TxError::ServiceNoDestinationID)?;
-                CanServiceId::new(
-                    transfer.priority(),
-                    true,
-                    transfer.port_id(),
-                    destination,
-                    source,
-                )
-            }
-            TransferKind::Response => {
-                let source = node_id.ok_or(TxError::ServiceNoSourceID)?;
-                let destination = transfer
-                    .remote_node_id()
-                    .ok_or(TxError::ServiceNoDestinationID)?;
-                CanServiceId::new(
-                    transfer.priority(),
-                    false,
-                    transfer.port_id(),
-                    destination,
-                    source,
-                )
-            }
-        };
+/// Number of payload bytes per frame, the last byte is always reserved for the tail byte.
+const FRAME_DATA_SIZE: usize = 63;
 
-        Ok(Self {
-            transfer,
-            frame_id,
-            payload_offset: 0,
-            crc: Crc16::init(),
-            crc_left: 2,
-            toggle: true,
-            is_start: true,
-            can_frame: None,
-        })
+/// Returns the number of zero bytes that must be inserted after `data_size` bytes of
+/// transfer payload so that the final frame lands on a valid CAN FD length.
+fn padding_size(data_size: usize) -> usize {
+    if data_size <= FRAME_DATA_SIZE {
+        // Single frame, padding goes between the payload and the tail byte
+        return round_up_frame_len(data_size + 1) - (data_size + 1);
     }
-}
 
-impl<'a, C: Clock, X: Transfer<'a, C>> StreamingIterator for FdCanIter<'a, C, X> {
-    type Item = FdCanFrame<C>;
+    // Multi-frame, padding goes between the payload and the CRC
+    let total = data_size + 2;
+    let last_frame_data = total - FRAME_DATA_SIZE * ((total - 1) / FRAME_DATA_SIZE);
 
-    fn get(&self) -> Option<&Self::Item> {
-        self.can_frame.as_ref()
+    // If the CRC is split across the last two frames the last frame only holds the
+    // second CRC byte, and padding would have to follow the CRC, so don't pad there.
+    if last_frame_data < 2 {
+        return 0;
     }
 
-    // I'm sure I could take an optimization pass at the logic here
-    fn advance(&mut self) {
-        let bytes_left = self.transfer.payload().len() - self.payload_offset;
-
-        if bytes_left == 0 && self.crc_left == 0 {
-            let _ = self.can_frame.take();
-            return;
-        }
+    round_up_frame_len(last_frame_data + 1) - (last_frame_data + 1)
+}
 
-        let is_end = bytes_left <= 63;
-        let mut copy_len = core::cmp::min(bytes_left, 63);
+impl<C: embedded_time::Clock> Transport<C> for FdCan {
+    type Frame = FdCanFrame<C>;
+    type FrameMetadata = FrameMetadata;
+    type RxMetadata = RxMetadata;
+    type TxMetadata = TxMetadata;
 
-        let frame = self.can_frame.get_or_insert_with(|| {
-            FdCanFrame::new(self.transfer.timestamp(), self.frame_id.as_raw())
-        });
+    const MTU_SIZE: usize = 64;
+    const CRC_SIZE: usize = 2;
+    const MAX_TRANSFER_ID: TransferId = <Can as Transport<C>>::MAX_TRANSFER_ID;
 
-        if self.is_start && is_end {
-            // Single frame transfer, no CRC
-            frame
-                .payload
-                .extend(self.transfer.payload()[0..copy_len].iter().copied());
-            self.payload_offset += bytes_left;
-            unsafe {
-                frame
-                    .payload
-                    .push_unchecked(TailByte::new(true, true, true, self.transfer.transfer_id()).0)
-            }
+    fn get_crc_padded_size(requested_size: usize) -> usize {
+        if requested_size <= FRAME_DATA_SIZE {
+            requested_size + padding_size(requested_size)
         } else {
-            // Handle CRC
-            let out_data =
-                &self.transfer.payload()[self.payload_offset..self.payload_offset + copy_len];
-            self.crc.digest(out_data);
-            frame.payload.extend(out_data.iter().copied());
-
-            // Increment offset
-            self.payload_offset += copy_len;
-
-            // Finished with our data, now we deal with crc
-            // (we can't do anything if bytes_left == 7, so ignore that case)
-            if bytes_left < 63 {
-                let crc = &self.crc.get_crc().to_be_bytes();
+            requested_size + padding_size(requested_size) + 2
+        }
+    }
 
-                // TODO I feel like this logic could be cleaned up somehow
-                if self.crc_left == 2 {
-                    if 63 - bytes_left >= 2 {
-                        // Iter doesn't work. Internal type is &u8 but extend
-                        // expects u8
-                        frame.payload.push(crc[0]);
-                        frame.payload.push(crc[1]);
-                        self.crc_left = 0;
-                        copy_len += 2;
-                    } else {
-                        // SAFETY: only written if we have enough space
-                        unsafe {
-                            frame.payload.push_unchecked(crc[0]);
-                        }
-                        self.crc_left = 1;
-                        copy_len += 1;
-                    }
-                } else if self.crc_left == 1 {
-                    // SAFETY: only written if we have enough space
-                    unsafe {
-                        frame.payload.push_unchecked(crc[1]);
-                    }
-                    self.crc_left = 0;
-                    copy_len += 1;
-                }
-            }
+    fn update_rx_metadata(
+        transport_metadata: &mut Self::RxMetadata,
+        frame_metadata: Self::FrameMetadata,
+        frame: &crate::transfer::Frame<C>,
+    ) -> Result<usize, RxError> {
+        // Toggle and CRC handling is identical to classic CAN
+        <Can as Transport<C>>::update_rx_metadata(transport_metadata, frame_metadata, frame)
+    }
 
-            // SAFETY: should only copy at most 7 elements prior to here
-            unsafe {
-                frame.payload.push_unchecked(
-                    TailByte::new(
-                        self.is_start,
-                        is_end,
-                        self.toggle,
-                        self.transfer.transfer_id(),
-                    )
-                    .0,
-                );
-            }
-            copy_len += 1;
+    fn process_tx_crc(buffer: &mut [u8], data_size: usize) -> usize {
+        let padding = padding_size(data_size);
+        buffer[data_size..data_size + padding].fill(0);
+        let data_size = data_size + padding;
 
-            // Advance state of iter
-            self.toggle = !self.toggle;
+        if data_size <= FRAME_DATA_SIZE {
+            // Single frame transfers don't get CRC
+            return data_size;
         }
 
-        self.is_start = false;
+        // Padding is included in the CRC
+        let mut crc = CRCu16::crc16ccitt_false();
+        crc.digest(&buffer[0..data_size]);
 
-        // Set DLC and 0-pad remaining bytes in DLC length
-        // TODO find a better solution for this
-        let zeroes = [0u8; 16];
-        frame.dlc = match copy_len {
-            0..=8 => copy_len as u8,
-            9..=12 => {
-                frame
-                    .payload
-                    .try_extend_from_slice(&zeroes[0..12 - copy_len])
-                    .unwrap();
-                9
-            }
-            13..=16 => {
-                frame
-                    .payload
-                    .try_extend_from_slice(&zeroes[0..16 - copy_len])
-                    .unwrap();
-                10
-            }
-            17..=20 => {
-                frame
-                    .payload
-                    .try_extend_from_slice(&zeroes[0..20 - copy_len])
-                    .unwrap();
-                11
-            }
-            21..=24 => {
-                frame
-                    .payload
-                    .try_extend_from_slice(&zeroes[0..24 - copy_len])
-                    .unwrap();
-                12
-            }
-            25..=32 => {
-                frame
-                    .payload
-                    .try_extend_from_slice(&zeroes[0..32 - copy_len])
-                    .unwrap();
-                13
-            }
-            33..=48 => {
-                frame
-                    .payload
-                    .try_extend_from_slice(&zeroes[0..48 - copy_len])
-                    .unwrap();
-                14
-            }
-            49..=64 => {
-                frame
-                    .payload
-                    .try_extend_from_slice(&zeroes[0..64 - copy_len])
-                    .unwrap();
-                15
-            }
-            _ => panic!("Copied data should never exceed 64 bytes!"),
-        };
-    }
+        let crc = crc.get_crc().to_be_bytes();
+        buffer[data_size] = crc[0];
+        buffer[data_size + 1] = crc[1];
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let mut bytes_left = self.transfer.payload().len() - self.payload_offset;
+        data_size + 2
+    }
 
-        // Single frame transfer
-        if self.is_start && bytes_left <= 7 {
-            return (1, Some(1));
+    fn rx_process_frame<'a>(
+        frame: &'a Self::Frame,
+    ) -> Result<(crate::transfer::Frame<'a, C>, Self::FrameMetadata), RxError> {
+        // Anything else can't have been put on the bus by a CAN FD controller
+        if frame.payload.len() != round_up_frame_len(frame.payload.len()) {
+            return Err(RxError::InvalidPayload);
         }
 
-        // Multi-frame, so include CRC
-        bytes_left += 2;
-        let mut frames = bytes_left / 7;
-        if bytes_left % 7 > 0 {
-            frames += 1;
+        process_frame(
+            frame.timestamp,
+            frame.id,
+            &frame.payload,
+            <Self as Transport<C>>::MTU_SIZE,
+        )
+    }
+
+    fn transmit_frame(
+        transfer_metadata: &TransferMetadata<C>,
+        transport_metadata: &mut Self::TxMetadata,
+        data: &[u8],
+        node_id: Option<NodeId>,
+        timestamp: embedded_time::Instant<C>,
+    ) -> Result<(Self::Frame, usize), TxError> {
+        // CRC and padding included in data, calculated when creating a TX transfer
+        let last_frame = data.len() <= FRAME_DATA_SIZE;
+        let (frame_id, tail_byte) = next_frame_header(
+            transfer_metadata,
+            transport_metadata,
+            node_id,
+            data,
+            last_frame,
+        )?;
+
+        let consume_len = core::cmp::min(FRAME_DATA_SIZE, data.len());
+        let mut payload = ArrayVec::from_iter(data[0..consume_len].iter().copied());
+        // SAFETY, length of data in payload ensured to be 63 or less
+        unsafe {
+            payload.push_unchecked(tail_byte.0);
         }
 
-        (frames, Some(frames))
+        Ok((
+            Self::Frame {
+                timestamp,
+                id: frame_id,
+                payload,
+            },
+            consume_len,
+        ))
     }
 }
 
 // TODO convert to embedded-hal PR type
 /// Extended CAN FD frame (the only one supported by UAVCAN/CAN)
+///
+/// The payload length always matches a valid CAN FD frame length, see [`FdCanFrame::dlc`].
 #[derive(Clone, Debug)]
 pub struct FdCanFrame<C: embedded_time::Clock> {
     pub timestamp: Timestamp<C>,
     pub id: ExtendedId,
-    // Seperate here because there are extra semantics around CAN-FD DLC
-    pub dlc: u8,
     pub payload: ArrayVec<[u8; 64]>,
 }
 
 impl<C: embedded_time::Clock> FdCanFrame<C> {
-    fn new(timestamp: Timestamp<C>, id: u32) -> Self {
+    pub fn new(timestamp: Timestamp<C>, id: u32, data: &[u8]) -> Self {
         Self {
             timestamp,
             // TODO get rid of this expect, it probably isn't necessary, just added quickly
             id: ExtendedId::new(id).expect("invalid ID"),
-            dlc: 0,
-            payload: ArrayVec::<[u8; 64]>::new(),
+            payload: ArrayVec::<[u8; 64]>::from_iter(data.iter().copied()),
         }
     }
+
+    /// DLC to put on the wire for this frame.
+    pub fn dlc(&self) -> u8 {
+        len_to_dlc(self.payload.len())
+    }
 }
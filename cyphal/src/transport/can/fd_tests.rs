@@ -0,0 +1,288 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::time::TestClock;
+use arrayvec::ArrayVec;
+use embedded_can::ExtendedId;
+use embedded_time::Clock;
+
+use super::bitfields::TailByte;
+use super::{fd::*, legacy::*, *};
+use crate::transfer::TransferMetadata;
+use crate::transport::Transport;
+use crate::*;
+
+/// Builds a frame of any length, for testing reception.
+fn make_frame(id: ExtendedId, len: usize, tail_byte: TailByte) -> FdCanFrame<TestClock> {
+    let clock = TestClock::default();
+    let mut frame = FdCanFrame {
+        timestamp: clock.try_now().unwrap(),
+        id,
+        payload: ArrayVec::new(),
+    };
+    frame.payload.extend((0..len as u8 - 1).map(|_| 0));
+    frame.payload.push(tail_byte.0);
+    frame
+}
+
+#[test]
+fn dlc_conversions() {
+    for (dlc, len) in [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64]
+        .iter()
+        .enumerate()
+    {
+        assert_eq!(dlc_to_len(dlc as u8), *len);
+        assert_eq!(len_to_dlc(*len), dlc as u8);
+    }
+
+    assert_eq!(round_up_frame_len(9), 12);
+    assert_eq!(round_up_frame_len(33), 48);
+    assert_eq!(round_up_frame_len(49), 64);
+    assert_eq!(len_to_dlc(65), 15);
+    assert_eq!(dlc_to_len(16), 64);
+}
+
+/// Ensure that valid message frames are recieved properly.
+#[test]
+fn receive_message_frame() {
+    let frame = make_frame(
+        CanMessageId::new(Priority::Nominal, 0, Some(41)),
+        12,
+        TailByte::new(true, true, true, 0),
+    );
+
+    let (frame, _) = FdCan::rx_process_frame(&frame).expect("Error processing message frame");
+    assert_eq!(frame.metadata.source_node_id, Some(41));
+    assert_eq!(frame.metadata.destination_node_id, None);
+    assert_eq!(frame.metadata.transfer_kind, TransferKind::Message);
+    assert!(frame.first_frame && frame.last_frame);
+    // Padding is delivered as part of the payload
+    assert_eq!(frame.payload.len(), 11);
+}
+
+/// Ensure that valid service frames are recieved properly.
+#[test]
+fn receive_service_frame() {
+    let frame = make_frame(
+        CanServiceId::new(Priority::Nominal, true, 0, 42, 41),
+        64,
+        TailByte::new(true, true, true, 0),
+    );
+
+    let (frame, _) = FdCan::rx_process_frame(&frame).expect("Error processing service frame");
+    assert_eq!(frame.metadata.source_node_id, Some(41));
+    assert_eq!(frame.metadata.destination_node_id, Some(42));
+    assert_eq!(frame.metadata.transfer_kind, TransferKind::Request);
+    assert_eq!(frame.payload.len(), 63);
+}
+
+/// Any transmitted frame must at minimum have a tail byte, so discard empty frames.
+#[test]
+fn discard_empty_frame() {
+    let clock = TestClock::default();
+    let frame = FdCanFrame {
+        timestamp: clock.try_now().unwrap(),
+        id: ExtendedId::ZERO,
+        payload: ArrayVec::new(),
+    };
+    let err = FdCan::rx_process_frame(&frame).expect_err("Empty frame did not error out.");
+    assert!(matches!(err, RxError::FrameEmpty));
+}
+
+/// Lengths between valid DLC values can't come off the bus.
+#[test]
+fn discard_invalid_length() {
+    let frame = make_frame(
+        CanMessageId::new(Priority::Nominal, 0, Some(41)),
+        10,
+        TailByte::new(true, true, true, 0),
+    );
+    let err = FdCan::rx_process_frame(&frame).unwrap_err();
+    assert!(matches!(err, RxError::InvalidPayload));
+}
+
+/// Anonymous transfers must be limited to single frames.
+#[test]
+fn discard_anon_multi_frame() {
+    let frame = make_frame(
+        CanMessageId::new(Priority::Nominal, 0, None),
+        64,
+        TailByte::new(true, false, true, 0),
+    );
+    let err = FdCan::rx_process_frame(&frame).unwrap_err();
+    assert!(matches!(err, RxError::AnonNotSingleFrame));
+}
+
+/// Non-last frames must fill the 64 byte MTU, not the classic CAN one.
+#[test]
+fn tail_byte_checks() {
+    let frame = make_frame(
+        CanMessageId::new(Priority::Nominal, 0, Some(41)),
+        48,
+        TailByte::new(true, false, true, 0),
+    );
+    let err = FdCan::rx_process_frame(&frame).unwrap_err();
+    assert!(matches!(err, RxError::NonLastUnderUtilization));
+
+    let frame = make_frame(
+        CanMessageId::new(Priority::Nominal, 0, Some(41)),
+        8,
+        TailByte::new(true, true, false, 0),
+    );
+    let err = FdCan::rx_process_frame(&frame).unwrap_err();
+    assert!(matches!(err, RxError::TransferStartMissingToggle));
+}
+
+fn make_generic_message_transfer() -> TransferMetadata<TestClock> {
+    let clock = TestClock::default();
+    TransferMetadata {
+        timestamp: clock.try_now().unwrap(),
+        priority: Priority::Nominal,
+        transfer_kind: TransferKind::Message,
+        port_id: 0,
+        source_node_id: None,
+        destination_node_id: None,
+        transfer_id: 0,
+    }
+}
+
+/// Runs a payload through the same steps a transfer manager would, returning every frame
+/// generated for it.
+fn transmit_all(
+    node_id: Option<NodeId>,
+    payload: &[u8],
+) -> Result<Vec<FdCanFrame<TestClock>>, TxError> {
+    let metadata = make_generic_message_transfer();
+    let mut buffer =
+        vec![0xAAu8; <FdCan as Transport<TestClock>>::get_crc_padded_size(payload.len())];
+    buffer[0..payload.len()].copy_from_slice(payload);
+    let len = <FdCan as Transport<TestClock>>::process_tx_crc(&mut buffer, payload.len());
+    assert!(len <= buffer.len());
+
+    let mut tx_metadata = TxMetadata::default();
+    let mut offset = 0;
+    let mut frames = Vec::new();
+    while offset < len {
+        let (frame, consumed) = FdCan::transmit_frame(
+            &metadata,
+            &mut tx_metadata,
+            &buffer[offset..len],
+            node_id,
+            metadata.timestamp,
+        )?;
+        offset += consumed;
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
+/// Transmits and receives a payload, checking framing along the way. Returns the frames.
+fn round_trip(payload_len: usize, expected_frames: usize) -> Vec<FdCanFrame<TestClock>> {
+    let payload: Vec<u8> = (0..payload_len).map(|i| i as u8).collect();
+    let frames = transmit_all(Some(12), &payload).unwrap();
+    assert_eq!(frames.len(), expected_frames);
+
+    let mut rx_metadata = RxMetadata::default();
+    let mut received = Vec::new();
+    let mut crc_size = 0;
+    for (i, frame) in frames.iter().enumerate() {
+        // Every frame must land on a valid DLC
+        assert_eq!(frame.payload.len(), round_up_frame_len(frame.payload.len()));
+        assert_eq!(dlc_to_len(frame.dlc()), frame.payload.len());
+        if i != frames.len() - 1 {
+            assert_eq!(frame.payload.len(), 64);
+        }
+
+        let (rx_frame, frame_metadata) = FdCan::rx_process_frame(frame).unwrap();
+        crc_size = FdCan::update_rx_metadata(&mut rx_metadata, frame_metadata, &rx_frame).unwrap();
+        assert_eq!(rx_frame.first_frame, i == 0);
+        assert_eq!(rx_frame.last_frame, i == frames.len() - 1);
+        received.extend_from_slice(rx_frame.payload);
+    }
+    // CRC is only reported for multi-frame transfers
+    assert_eq!(crc_size, if frames.len() > 1 { 2 } else { 0 });
+    received.truncate(received.len() - crc_size);
+
+    // Payload comes back intact, followed by zero padding
+    assert_eq!(&received[0..payload_len], payload.as_slice());
+    assert!(received[payload_len..].iter().all(|b| *b == 0));
+
+    frames
+}
+
+#[test]
+fn single_frame_padding() {
+    // 8 bytes + tail byte = 9, so pad to 12
+    let frames = round_trip(8, 1);
+    assert_eq!(frames[0].payload.len(), 12);
+    assert_eq!(&frames[0].payload[8..11], &[0, 0, 0]);
+
+    // Exact fits don't get padded
+    let frames = round_trip(7, 1);
+    assert_eq!(frames[0].payload.len(), 8);
+    let frames = round_trip(63, 1);
+    assert_eq!(frames[0].payload.len(), 64);
+}
+
+#[test]
+fn anon_single_frame_only() {
+    assert!(transmit_all(None, &[0u8; 63]).is_ok());
+    let err = transmit_all(None, &[0u8; 64]).unwrap_err();
+    assert!(matches!(err, TxError::AnonNotSingleFrame));
+}
+
+/// Padding in multi-frame transfers goes before the CRC, and is covered by it.
+#[test]
+fn multi_frame_padding_before_crc() {
+    // 70 bytes: 63 in the first frame, 7 data + 2 CRC + tail = 10 -> padded to 12
+    let frames = round_trip(70, 2);
+    let last = &frames[1].payload;
+    assert_eq!(last.len(), 12);
+    assert_eq!(&last[0..7], &[63, 64, 65, 66, 67, 68, 69]);
+    assert_eq!(&last[7..9], &[0, 0]);
+
+    let mut crc = crc_any::CRCu16::crc16ccitt_false();
+    crc.digest(&(0..70u8).collect::<Vec<u8>>());
+    crc.digest(&[0u8, 0]);
+    assert_eq!(&last[9..11], &crc.get_crc().to_be_bytes());
+}
+
+/// CRC is entirely contained in the final frame.
+#[test]
+fn crc_exclusive() {
+    let frames = round_trip(126, 3);
+    assert_eq!(frames[2].payload.len(), 3);
+}
+
+/// Tests that frame generation operates correctly when the CRC is split between the last
+/// two frames.
+#[test]
+fn crc_split() {
+    let payload: Vec<u8> = (0..125u8).collect();
+    let frames = transmit_all(Some(12), &payload).unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[1].payload.len(), 64);
+    assert_eq!(frames[2].payload.len(), 2);
+
+    let mut crc = crc_any::CRCu16::crc16ccitt_false();
+    crc.digest(&payload);
+    let crc = crc.get_crc().to_be_bytes();
+    assert_eq!(frames[1].payload[62], crc[0]);
+    assert_eq!(frames[2].payload[0], crc[1]);
+
+    // Reception validates the split CRC and strips both halves
+    round_trip(125, 3);
+}
+
+#[test]
+fn padded_size_fits_output() {
+    for len in 0..300 {
+        let padded = <FdCan as Transport<TestClock>>::get_crc_padded_size(len);
+        let mut buffer = vec![0u8; padded];
+        assert_eq!(
+            <FdCan as Transport<TestClock>>::process_tx_crc(&mut buffer, len),
+            padded
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Acceptance filters for extended CAN IDs, as found in CAN controllers and SocketCAN, and
+//! working them out from the ports a node takes transfers on.
+
+use embedded_can::ExtendedId;
+
+use super::bitfields::{CanMessageId, CanServiceId};
+use crate::types::NodeId;
+use crate::{Subscription, TransferKind};
+
+/// Accepts the extended IDs whose bits selected by `mask` match those of `id`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Filter {
+    pub id: u32,
+    pub mask: u32,
+}
+
+impl Filter {
+    /// Creates a filter, clearing the bits of `id` that `mask` ignores and anything beyond the
+    /// 29 bits of an extended ID.
+    pub fn new(id: u32, mask: u32) -> Self {
+        let mask = mask & ExtendedId::MAX.as_raw();
+        Self {
+            id: id & mask,
+            mask,
+        }
+    }
+
+    /// Whether a frame with this ID gets through the filter.
+    pub fn accepts(&self, id: ExtendedId) -> bool {
+        id.as_raw() & self.mask == self.id
+    }
+
+    /// Smallest filter accepting everything either filter accepts.
+    pub fn merge(&self, other: &Filter) -> Filter {
+        Filter::new(self.id, self.mask & other.mask & !(self.id ^ other.id))
+    }
+
+    /// Whether this filter accepts everything `other` accepts.
+    pub fn covers(&self, other: &Filter) -> bool {
+        self.mask & !other.mask == 0 && other.id & self.mask == self.id
+    }
+}
+
+/// Up to how many ports [`acceptance_filters`] searches every way of merging them.
+const EXHAUSTIVE_MERGE_PORTS: usize = 10;
+
+/// Accepts every extended ID.
+const ACCEPT_ALL: &[Filter] = &[Filter { id: 0, mask: 0 }];
+
+/// Computes at most `banks.len()` filters letting through every frame a node with these
+/// subscriptions takes in, and as few others as possible, returning the part of `banks` they
+/// were written to.
+///
+/// Message subscriptions take in the subject from any source, anonymous ones included. Request
+/// and response subscriptions only take in transfers addressed to `node_id`, so there are none
+/// while the node is anonymous. Without any subscriptions no filters are returned, so nothing
+/// gets through.
+///
+/// Each port gets an exact filter. If that's more than there are banks, they are merged into
+/// the filters admitting the fewest IDs between them. Up to [`EXHAUSTIVE_MERGE_PORTS`] ports
+/// every way of grouping them is searched; past that the search would take too long, so each
+/// port is merged in turn, merging the closest pair of filters whenever the banks are full,
+/// which may admit more than needed. With no banks at all the controller can't filter, so a
+/// single filter accepting everything is returned.
+pub fn acceptance_filters<'a>(
+    subscriptions: &[Subscription],
+    node_id: Option<NodeId>,
+    banks: &'a mut [Filter],
+) -> &'a [Filter] {
+    if banks.is_empty() {
+        return ACCEPT_ALL;
+    }
+
+    let exact = subscriptions
+        .iter()
+        .filter_map(|subscription| port_filter(subscription, node_id));
+    let mut ports = heapless::Vec::<Filter, EXHAUSTIVE_MERGE_PORTS>::new();
+    let len = if exact
+        .clone()
+        .all(|filter| ports.contains(&filter) || ports.push(filter).is_ok())
+    {
+        best_merge(&ports, banks)
+    } else {
+        greedy_merge(exact, banks)
+    };
+
+    let filters = &mut banks[0..len];
+    filters.sort_unstable_by_key(|filter| (filter.id, filter.mask));
+    filters
+}
+
+/// Adds the filters to `banks` one by one, merging the closest pair of filters whenever they
+/// are full. Returns the number of banks used.
+fn greedy_merge(filters: impl Iterator<Item = Filter>, banks: &mut [Filter]) -> usize {
+    let mut len = 0;
+    for filter in filters {
+        if banks[0..len].iter().any(|bank| bank.covers(&filter)) {
+            continue;
+        }
+        if len < banks.len() {
+            banks[len] = filter;
+            len += 1;
+            continue;
+        }
+
+        // The new filter takes part as if it were in one more bank
+        let candidate = |index: usize| if index == len { filter } else { banks[index] };
+        let mut best = (0, 1);
+        let mut best_mask = 0u32;
+        for first in 0..=len {
+            for second in first + 1..=len {
+                let mask = candidate(first).merge(&candidate(second)).mask;
+                if mask.count_ones() > best_mask.count_ones() {
+                    best = (first, second);
+                    best_mask = mask;
+                }
+            }
+        }
+        let merged = candidate(best.0).merge(&candidate(best.1));
+        banks[best.0] = merged;
+        if best.1 < len {
+            banks[best.1] = filter;
+        }
+
+        // Besides the pair, the merged filter may cover others entirely
+        let mut kept = 0;
+        for index in 0..len {
+            if index == best.0 || !merged.covers(&banks[index]) {
+                banks[kept] = banks[index];
+                kept += 1;
+            }
+        }
+        len = kept;
+    }
+    len
+}
+
+/// Writes the grouping of the filters into at most `banks.len()` merged filters admitting the
+/// fewest IDs in total to `banks`, returning the number of banks used.
+fn best_merge(filters: &[Filter], banks: &mut [Filter]) -> usize {
+    let mut groups = heapless::Vec::new();
+    let mut best = heapless::Vec::new();
+    let mut best_admitted = u64::MAX;
+    search_merges(
+        filters,
+        banks.len(),
+        &mut groups,
+        &mut best,
+        &mut best_admitted,
+    );
+    banks[0..best.len()].copy_from_slice(&best);
+    best.len()
+}
+
+/// Tries adding the first of `filters` to a group of its own and to each of `groups`, then
+/// recurses on the rest, keeping the best complete grouping.
+fn search_merges(
+    filters: &[Filter],
+    banks: usize,
+    groups: &mut heapless::Vec<Filter, EXHAUSTIVE_MERGE_PORTS>,
+    best: &mut heapless::Vec<Filter, EXHAUSTIVE_MERGE_PORTS>,
+    best_admitted: &mut u64,
+) {
+    // Adding filters never admits fewer IDs, so this branch can't beat the best one
+    let so_far = admitted(groups);
+    if so_far >= *best_admitted {
+        return;
+    }
+    let Some((filter, rest)) = filters.split_first() else {
+        best.clone_from(groups);
+        *best_admitted = so_far;
+        return;
+    };
+    // Can't fail, there are no more groups than filters
+    if groups.len() < banks && groups.push(*filter).is_ok() {
+        search_merges(rest, banks, groups, best, best_admitted);
+        groups.pop();
+    }
+    for group in 0..groups.len() {
+        let unmerged = groups[group];
+        groups[group] = unmerged.merge(filter);
+        search_merges(rest, banks, groups, best, best_admitted);
+        groups[group] = unmerged;
+    }
+}
+
+/// How many extended IDs the filters admit, counting those admitted by several more than once.
+fn admitted(filters: &[Filter]) -> u64 {
+    filters
+        .iter()
+        .map(|filter| 1 << (29 - filter.mask.count_ones()))
+        .sum()
+}
+
+/// Exact filter for the frames of a subscription.
+fn port_filter(subscription: &Subscription, node_id: Option<NodeId>) -> Option<Filter> {
+    match subscription.transfer_kind {
+        TransferKind::Message => {
+            let mut id = CanMessageId(0);
+            let mut mask = CanMessageId(0);
+            id.set_subject_id(subscription.port_id);
+            mask.set_subject_id(0x1FFF);
+            mask.set_svc(true);
+            // Frames with the reserved bits set are invalid
+            mask.set_rsvd0(true);
+            mask.set_rsvd3(true);
+            Some(Filter::new(id.0, mask.0))
+        }
+        TransferKind::Request | TransferKind::Response => {
+            let mut id = CanServiceId(0);
+            let mut mask = CanServiceId(0);
+            id.set_svc(true);
+            id.set_req(subscription.transfer_kind == TransferKind::Request);
+            id.set_service_id(subscription.port_id);
+            id.set_destination_id(node_id?);
+            mask.set_svc(true);
+            mask.set_req(true);
+            mask.set_rsvd0(true);
+            mask.set_service_id(0x1FF);
+            mask.set_destination_id(0x7F);
+            Some(Filter::new(id.0, mask.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use crate::types::PortId;
+
+    use alloc::vec::Vec;
+
+    use embedded_time::duration::Milliseconds;
+
+    fn subscription(transfer_kind: TransferKind, port_id: PortId) -> Subscription {
+        Subscription::new(transfer_kind, port_id, 64, Milliseconds(1000))
+    }
+
+    fn accepted(filters: &[Filter], id: ExtendedId) -> bool {
+        filters.iter().any(|filter| filter.accepts(id))
+    }
+
+    fn message(subject_id: PortId, source: Option<NodeId>) -> ExtendedId {
+        CanMessageId::new(Priority::Nominal, subject_id, source)
+    }
+
+    fn service(request: bool, service_id: PortId, destination: NodeId) -> ExtendedId {
+        CanServiceId::new(Priority::Fast, request, service_id, destination, 7)
+    }
+
+    #[test]
+    fn exact_filters() {
+        let subscriptions = [
+            subscription(TransferKind::Message, 7509),
+            subscription(TransferKind::Request, 430),
+            subscription(TransferKind::Response, 384),
+        ];
+        let mut banks = [Filter::new(0, 0); 8];
+        let filters = acceptance_filters(&subscriptions, Some(42), &mut banks);
+        assert_eq!(filters.len(), 3);
+
+        assert!(accepted(filters, message(7509, Some(3))));
+        assert!(accepted(filters, message(7509, None)));
+        assert!(accepted(filters, service(true, 430, 42)));
+        assert!(accepted(filters, service(false, 384, 42)));
+
+        assert!(!accepted(filters, message(7508, Some(3))));
+        assert!(!accepted(filters, service(true, 430, 43)));
+        assert!(!accepted(filters, service(false, 430, 42)));
+        assert!(!accepted(filters, service(true, 384, 42)));
+
+        // Nothing can be addressed to an anonymous node
+        let filters = acceptance_filters(&subscriptions, None, &mut banks);
+        assert_eq!(filters.len(), 1);
+        assert!(accepted(filters, message(7509, None)));
+        assert!(!accepted(filters, service(true, 430, 42)));
+
+        // Without banks everything has to get through, without subscriptions nothing does
+        let filters = acceptance_filters(&subscriptions, Some(42), &mut []);
+        assert_eq!(filters, [Filter::new(0, 0)]);
+        assert!(accepted(filters, service(true, 430, 43)));
+        assert!(acceptance_filters(&[], Some(42), &mut banks).is_empty());
+    }
+
+    #[test]
+    fn merged_filters() {
+        // Subjects 100 and 101 only differ in one bit, so merging them costs the least
+        let subscriptions = [
+            subscription(TransferKind::Message, 100),
+            subscription(TransferKind::Message, 101),
+            subscription(TransferKind::Message, 7509),
+        ];
+        let mut banks = [Filter::new(0, 0); 2];
+        let filters = acceptance_filters(&subscriptions, Some(42), &mut banks);
+        assert_eq!(filters.len(), 2);
+        let exact = port_filter(&subscriptions[0], Some(42)).unwrap();
+        assert!(filters.contains(&Filter::new(exact.id, exact.mask & !(1 << 8))));
+        assert!(!accepted(filters, message(102, Some(3))));
+
+        // Merging the closest pair first, 2 and 3, leaves 8 and 15 three bits apart. Pairing 2
+        // with 8 and 3 with 15 costs two bits each instead
+        let subscriptions = [
+            subscription(TransferKind::Message, 2),
+            subscription(TransferKind::Message, 3),
+            subscription(TransferKind::Message, 8),
+            subscription(TransferKind::Message, 15),
+        ];
+        let exact: Vec<_> = subscriptions
+            .iter()
+            .filter_map(|subscription| port_filter(subscription, Some(42)))
+            .collect();
+        let mut greedy = [Filter::new(0, 0); 2];
+        assert_eq!(greedy_merge(exact.iter().copied(), &mut greedy), 2);
+        let filters = acceptance_filters(&subscriptions, Some(42), &mut banks);
+        assert_eq!(filters.len(), 2);
+        assert!(admitted(filters) < admitted(&greedy));
+        assert!(filters.contains(&exact[0].merge(&exact[2])));
+        assert!(filters.contains(&exact[1].merge(&exact[3])));
+        for port_id in [2, 3, 8, 15] {
+            assert!(accepted(filters, message(port_id, Some(3))));
+        }
+        assert!(!accepted(filters, message(4, Some(3))));
+
+        // Every subscribed port gets through however few banks there are
+        let mut subscriptions: Vec<_> = (0..20)
+            .map(|port_id| subscription(TransferKind::Message, 1000 + 37 * port_id))
+            .collect();
+        subscriptions.push(subscription(TransferKind::Request, 430));
+        subscriptions.push(subscription(TransferKind::Response, 430));
+        let mut banks = [Filter::new(0, 0); 22];
+        for len in 1..=banks.len() {
+            let filters = acceptance_filters(&subscriptions, Some(42), &mut banks[0..len]);
+            assert!(!filters.is_empty() && filters.len() <= len);
+            for port_id in (0..20).map(|port_id| 1000 + 37 * port_id) {
+                assert!(accepted(filters, message(port_id, Some(3))));
+            }
+            assert!(accepted(filters, service(true, 430, 42)));
+            assert!(accepted(filters, service(false, 430, 42)));
+        }
+        assert_eq!(
+            acceptance_filters(&subscriptions, Some(42), &mut banks).len(),
+            subscriptions.len()
+        );
+    }
+}
@@ -12,7 +12,7 @@ use super::bitfields::*;
 use crate::time::Timestamp;
 use crate::transfer::{Frame, TransferMetadata};
 use crate::transport::Transport;
-use crate::{NodeId, Priority, RxError, TransferKind, TxError};
+use crate::{NodeId, Priority, RxError, TransferId, TransferKind, TxError};
 
 use crc_any::CRCu16;
 
@@ -23,7 +23,6 @@ pub struct Can;
 #[derive(Clone, Copy, Debug)]
 pub struct FrameMetadata {
     pub toggle_bit: bool,
-    pub crc_val: Option<u16>,
 }
 
 pub struct TxMetadata {
@@ -33,11 +32,11 @@ pub struct TxMetadata {
 
 impl Default for TxMetadata {
     fn default() -> Self {
-        return Self {
+        Self {
             first_frame: true,
             // Protocol version states SOT must have toggle set
             toggle_bit: true,
-        };
+        }
     }
 }
 
@@ -48,16 +47,15 @@ pub struct RxMetadata {
 
 impl Default for RxMetadata {
     fn default() -> Self {
-        return Self {
+        Self {
             crc: CRCu16::crc16ccitt_false(),
 
             // Invert initial toggle bit, so when we check the first frame it works if it's set
             toggle_bit: false,
-        };
+        }
     }
 }
 
-// TODO we don't actually check RX CRC anywhere and CRC gets included in the transfer payload
 impl<C: embedded_time::Clock> Transport<C> for Can {
     type Frame = CanFrame<C>;
     type FrameMetadata = FrameMetadata;
@@ -66,22 +64,19 @@ impl<C: embedded_time::Clock> Transport<C> for Can {
 
     const MTU_SIZE: usize = 8;
     const CRC_SIZE: usize = 2;
+    // 5 bit transfer ID in the tail byte
+    const MAX_TRANSFER_ID: TransferId = 31;
 
     fn get_crc_padded_size(requested_size: usize) -> usize {
         // Just need to include CRC16
-        return requested_size + 2;
+        requested_size + 2
     }
 
     fn update_rx_metadata(
         transport_metadata: &mut Self::RxMetadata,
         frame_metadata: Self::FrameMetadata,
         frame: &crate::transfer::Frame<C>,
-    ) -> Result<(), RxError> {
-        println!(
-            "Previous toggle: {}, incoming toggle: {}",
-            transport_metadata.toggle_bit, frame_metadata.toggle_bit
-        );
-
+    ) -> Result<usize, RxError> {
         // Check for issues
         if frame_metadata.toggle_bit == transport_metadata.toggle_bit {
             return Err(RxError::InvalidFrameOrdering);
@@ -90,24 +85,23 @@ impl<C: embedded_time::Clock> Transport<C> for Can {
         // update metadata
         transport_metadata.toggle_bit = frame_metadata.toggle_bit;
 
-        if !(frame.last_frame && frame.first_frame) {
-            // Only handle CRC if this isn't a single-frame message
-            transport_metadata.crc.digest(frame.payload);
+        if frame.last_frame && frame.first_frame {
+            // Single-frame transfers don't have a CRC
+            return Ok(0);
         }
 
-        if let Some(crc_val) = frame_metadata.crc_val {
-            // Check CRC result for the final frame of a multi-frame transfer
-            if crc_val != transport_metadata.crc.get_crc() {
-                println!(
-                    "CRC failed; expected: 0x{:04x}, calculated: 0x{:04x}",
-                    crc_val,
-                    transport_metadata.crc.get_crc()
-                );
+        // The CRC is digested along with the data, which leaves a zero residue if it's valid.
+        // This way it doesn't matter if it's split across frames.
+        transport_metadata.crc.digest(frame.payload);
+
+        if frame.last_frame {
+            if transport_metadata.crc.get_crc() != 0 {
                 return Err(RxError::CrcError);
             }
+            return Ok(2);
         }
 
-        Ok(())
+        Ok(0)
     }
 
     fn process_tx_crc(buffer: &mut [u8], data_size: usize) -> usize {
@@ -131,115 +125,12 @@ impl<C: embedded_time::Clock> Transport<C> for Can {
     fn rx_process_frame<'a>(
         frame: &'a Self::Frame,
     ) -> Result<(crate::transfer::Frame<'a, C>, Self::FrameMetadata), RxError> {
-        // Frames cannot be empty. They must at least have a tail byte.
-        // NOTE: libcanard specifies this as only for multi-frame transfers but uses
-        // this logic.
-        if frame.payload.is_empty() {
-            return Err(RxError::FrameEmpty);
-        }
-
-        // Pull tail byte from payload
-        let tail_byte = TailByte(*frame.payload.last().unwrap());
-
-        let (crc_val, payload_len) =
-            if !tail_byte.start_of_transfer() && tail_byte.end_of_transfer() {
-                // End of multi-frame transfer, parse CRC value but don't include in payload
-                if frame.payload.len() < 3 {
-                    return Err(RxError::InvalidPayload);
-                }
-                let crc = u16::from_be_bytes([
-                    frame.payload[frame.payload.len() - 3],
-                    frame.payload[frame.payload.len() - 2],
-                ]);
-                (Some(crc), frame.payload.len() - 3)
-            } else {
-                // No CRC in single-frame transfers or non-last multi-frame transfers
-                (None, frame.payload.len() - 1)
-            };
-
-        // Protocol version states SOT must have toggle set
-        if tail_byte.start_of_transfer() && !tail_byte.toggle() {
-            return Err(RxError::TransferStartMissingToggle);
-        }
-        // Non-last frames must use the MTU fully
-        if !tail_byte.end_of_transfer() && frame.payload.len() < <Self as Transport<C>>::MTU_SIZE {
-            return Err(RxError::NonLastUnderUtilization);
-        }
-
-        let frame_metadata = FrameMetadata {
-            toggle_bit: tail_byte.toggle(),
-            crc_val: crc_val,
-        };
-
-        if CanServiceId(frame.id.as_raw()).is_svc() {
-            // Handle services
-            let id = CanServiceId(frame.id.as_raw());
-
-            // Ignore invalid frames
-            if !id.valid() {
-                return Err(RxError::InvalidCanId);
-            }
-
-            let transfer_kind = if id.is_req() {
-                TransferKind::Request
-            } else {
-                TransferKind::Response
-            };
-
-            return Ok((
-                Frame {
-                    metadata: TransferMetadata {
-                        timestamp: frame.timestamp,
-                        priority: Priority::from_u8(id.priority()).unwrap(),
-                        transfer_kind,
-                        port_id: id.service_id(),
-                        source_node_id: Some(id.source_id()),
-                        destination_node_id: Some(id.destination_id()),
-                        transfer_id: tail_byte.transfer_id(),
-                    },
-
-                    payload: &frame.payload[0..payload_len],
-                    first_frame: tail_byte.start_of_transfer(),
-                    last_frame: tail_byte.end_of_transfer(),
-                },
-                frame_metadata,
-            ));
-        } else {
-            // Handle messages
-            let id = CanMessageId(frame.id.as_raw());
-
-            // We can ignore ID in anonymous transfers
-            if id.is_anon() {
-                // Anonymous transfers can only be single-frame transfers
-                if !(tail_byte.start_of_transfer() && tail_byte.end_of_transfer()) {
-                    return Err(RxError::AnonNotSingleFrame);
-                }
-            }
-
-            if !id.valid() {
-                return Err(RxError::InvalidCanId);
-            }
-
-            return Ok((
-                Frame {
-                    metadata: TransferMetadata {
-                        timestamp: frame.timestamp,
-                        priority: Priority::from_u8(id.priority()).unwrap(),
-                        transfer_kind: TransferKind::Message,
-                        port_id: id.subject_id(),
-                        // This will be a psuedorandom value for anon transfers
-                        source_node_id: Some(id.source_id()),
-                        destination_node_id: None,
-                        transfer_id: tail_byte.transfer_id(),
-                    },
-
-                    payload: &frame.payload[0..payload_len],
-                    first_frame: tail_byte.start_of_transfer(),
-                    last_frame: tail_byte.end_of_transfer(),
-                },
-                frame_metadata,
-            ));
-        }
+        process_frame(
+            frame.timestamp,
+            frame.id,
+            &frame.payload,
+            <Self as Transport<C>>::MTU_SIZE,
+        )
     }
 
     fn transmit_frame(
@@ -250,49 +141,14 @@ impl<C: embedded_time::Clock> Transport<C> for Can {
         timestamp: embedded_time::Instant<C>,
     ) -> Result<(Self::Frame, usize), TxError> {
         // CRC included in data, calculated when creating a TX transfer
-        let first_frame = transport_metadata.first_frame;
         let last_frame = data.len() <= 7;
-        let toggle_bit = transport_metadata.toggle_bit;
-
-        // Update metadata
-        transport_metadata.first_frame = false;
-        transport_metadata.toggle_bit = !toggle_bit;
-
-        // Build CAN ID from transfer metadata
-        let frame_id = match transfer_metadata.transfer_kind {
-            TransferKind::Message => {
-                if !last_frame && node_id.is_none() {
-                    return Err(TxError::AnonNotSingleFrame);
-                }
-
-                CanMessageId::new(
-                    transfer_metadata.priority,
-                    transfer_metadata.port_id,
-                    node_id,
-                )
-            }
-            TransferKind::Request | TransferKind::Response => {
-                let source = node_id.ok_or(TxError::ServiceNoSourceID)?;
-                let destination = transfer_metadata
-                    .destination_node_id
-                    .ok_or(TxError::ServiceNoDestinationID)?;
-                CanServiceId::new(
-                    transfer_metadata.priority,
-                    transfer_metadata.transfer_kind == TransferKind::Request,
-                    transfer_metadata.port_id,
-                    destination,
-                    source,
-                )
-            }
-        };
-
-        // Build tail byte from metadata
-        let tail_byte = TailByte::new(
-            first_frame,
+        let (frame_id, tail_byte) = next_frame_header(
+            transfer_metadata,
+            transport_metadata,
+            node_id,
+            data,
             last_frame,
-            toggle_bit,
-            transfer_metadata.transfer_id,
-        );
+        )?;
 
         let consume_len = core::cmp::min(7, data.len());
         let mut payload = ArrayVec::from_iter(data[0..consume_len].iter().copied());
@@ -312,6 +168,190 @@ impl<C: embedded_time::Clock> Transport<C> for Can {
     }
 }
 
+/// Parses a received CAN or CAN FD frame into a transfer frame.
+///
+/// Shared between classic CAN and CAN FD, the only difference in framing
+/// is the MTU that non-last frames are required to fill.
+pub(super) fn process_frame<C: embedded_time::Clock>(
+    timestamp: Timestamp<C>,
+    id: ExtendedId,
+    payload: &[u8],
+    mtu: usize,
+) -> Result<(Frame<'_, C>, FrameMetadata), RxError> {
+    // Frames cannot be empty. They must at least have a tail byte.
+    // NOTE: libcanard specifies this as only for multi-frame transfers but uses
+    // this logic.
+    if payload.is_empty() {
+        return Err(RxError::FrameEmpty);
+    }
+
+    // Pull tail byte from payload
+    let tail_byte = TailByte(*payload.last().unwrap());
+
+    // Everything but the tail byte, including any part of the transfer CRC
+    let payload_len = payload.len() - 1;
+
+    // Protocol version states SOT must have toggle set
+    if tail_byte.start_of_transfer() && !tail_byte.toggle() {
+        return Err(RxError::TransferStartMissingToggle);
+    }
+    // Non-last frames must use the MTU fully
+    if !tail_byte.end_of_transfer() && payload.len() < mtu {
+        return Err(RxError::NonLastUnderUtilization);
+    }
+
+    let frame_metadata = FrameMetadata {
+        toggle_bit: tail_byte.toggle(),
+    };
+
+    if CanServiceId(id.as_raw()).is_svc() {
+        // Handle services
+        let id = CanServiceId(id.as_raw());
+
+        // Ignore invalid frames
+        if !id.valid() {
+            return Err(RxError::InvalidCanId);
+        }
+
+        let transfer_kind = if id.is_req() {
+            TransferKind::Request
+        } else {
+            TransferKind::Response
+        };
+
+        Ok((
+            Frame {
+                metadata: TransferMetadata {
+                    timestamp,
+                    priority: Priority::from_u8(id.priority()).unwrap(),
+                    transfer_kind,
+                    port_id: id.service_id(),
+                    source_node_id: Some(id.source_id()),
+                    destination_node_id: Some(id.destination_id()),
+                    transfer_id: tail_byte.transfer_id(),
+                },
+
+                payload: &payload[0..payload_len],
+                first_frame: tail_byte.start_of_transfer(),
+                last_frame: tail_byte.end_of_transfer(),
+            },
+            frame_metadata,
+        ))
+    } else {
+        // Handle messages
+        let id = CanMessageId(id.as_raw());
+
+        // We can ignore ID in anonymous transfers
+        if id.is_anon() {
+            // Anonymous transfers can only be single-frame transfers
+            if !(tail_byte.start_of_transfer() && tail_byte.end_of_transfer()) {
+                return Err(RxError::AnonNotSingleFrame);
+            }
+        }
+
+        if !id.valid() {
+            return Err(RxError::InvalidCanId);
+        }
+
+        Ok((
+            Frame {
+                metadata: TransferMetadata {
+                    timestamp,
+                    priority: Priority::from_u8(id.priority()).unwrap(),
+                    transfer_kind: TransferKind::Message,
+                    port_id: id.subject_id(),
+                    // The source ID of anonymous transfers is psuedorandom, not a node ID
+                    source_node_id: if id.is_anon() {
+                        None
+                    } else {
+                        Some(id.source_id())
+                    },
+                    destination_node_id: None,
+                    transfer_id: tail_byte.transfer_id(),
+                },
+
+                payload: &payload[0..payload_len],
+                first_frame: tail_byte.start_of_transfer(),
+                last_frame: tail_byte.end_of_transfer(),
+            },
+            frame_metadata,
+        ))
+    }
+}
+
+/// Builds the CAN ID and tail byte for the next frame of a transfer, advancing the TX metadata.
+///
+/// `data` is what's left of the transfer, which is all of it for anonymous transfers.
+pub(super) fn next_frame_header<C: embedded_time::Clock>(
+    transfer_metadata: &TransferMetadata<C>,
+    transport_metadata: &mut TxMetadata,
+    node_id: Option<NodeId>,
+    data: &[u8],
+    last_frame: bool,
+) -> Result<(ExtendedId, TailByte), TxError> {
+    let first_frame = transport_metadata.first_frame;
+    let toggle_bit = transport_metadata.toggle_bit;
+
+    // Update metadata
+    transport_metadata.first_frame = false;
+    transport_metadata.toggle_bit = !toggle_bit;
+
+    // Build CAN ID from transfer metadata
+    let frame_id = match transfer_metadata.transfer_kind {
+        TransferKind::Message => {
+            if !last_frame && node_id.is_none() {
+                return Err(TxError::AnonNotSingleFrame);
+            }
+
+            match node_id {
+                Some(_) => CanMessageId::new(
+                    transfer_metadata.priority,
+                    transfer_metadata.port_id,
+                    node_id,
+                ),
+                None => CanMessageId::anonymous(
+                    transfer_metadata.priority,
+                    transfer_metadata.port_id,
+                    pseudo_node_id(data),
+                ),
+            }
+        }
+        TransferKind::Request | TransferKind::Response => {
+            let source = node_id.ok_or(TxError::ServiceNoSourceID)?;
+            let destination = transfer_metadata
+                .destination_node_id
+                .ok_or(TxError::ServiceNoDestinationID)?;
+            CanServiceId::new(
+                transfer_metadata.priority,
+                transfer_metadata.transfer_kind == TransferKind::Request,
+                transfer_metadata.port_id,
+                destination,
+                source,
+            )
+        }
+    };
+
+    // Build tail byte from metadata
+    let tail_byte = TailByte::new(
+        first_frame,
+        last_frame,
+        toggle_bit,
+        transfer_metadata.transfer_id,
+    );
+
+    Ok((frame_id, tail_byte))
+}
+
+/// Pseudo-random source ID for an anonymous transfer, taken from a CRC of its payload.
+///
+/// Allocation requests carry a hash of the node's unique ID, so nodes requesting an ID at the
+/// same time pick different ones.
+fn pseudo_node_id(payload: &[u8]) -> NodeId {
+    let mut crc = CRCu16::crc16ccitt_false();
+    crc.digest(payload);
+    crc.get_crc() & 0x7F
+}
+
 // TODO convert to embedded-hal PR type
 /// Extended CAN frame (the only one supported by UAVCAN/CAN)
 #[derive(Clone, Debug)]
@@ -330,4 +370,27 @@ impl<C: embedded_time::Clock> CanFrame<C> {
             payload: ArrayVec::<[u8; 8]>::from_iter(data.iter().copied()),
         }
     }
+
+    /// Converts a frame received through an [`embedded_can`] driver.
+    ///
+    /// Returns `None` for standard ID and remote frames, which Cyphal doesn't use.
+    pub fn from_frame<F: embedded_can::Frame>(timestamp: Timestamp<C>, frame: &F) -> Option<Self> {
+        let embedded_can::Id::Extended(id) = frame.id() else {
+            return None;
+        };
+        if frame.is_remote_frame() || frame.data().len() > 8 {
+            return None;
+        }
+        Some(Self {
+            timestamp,
+            id,
+            payload: frame.data().iter().copied().collect(),
+        })
+    }
+
+    /// Converts into the frame type of an [`embedded_can`] driver, or `None` if the driver
+    /// rejects it.
+    pub fn to_frame<F: embedded_can::Frame>(&self) -> Option<F> {
+        F::new(self.id, &self.payload)
+    }
 }
@@ -12,15 +12,23 @@
 // TODO what exactly did we actually need GAT for?
 
 mod bitfields;
-// TODO temp uncomment
-//mod fd;
+mod driver;
+mod fd;
+mod filter;
 mod legacy;
+#[cfg(all(feature = "socketcan", target_os = "linux"))]
+mod socketcan;
 
+#[cfg(test)]
+mod fd_tests;
 #[cfg(test)]
 mod tests;
 
 // Exports
 pub use bitfields::{CanMessageId, CanServiceId};
-// TODO temp uncomment
-//pub use fd::*;
+pub use driver::{DriverError, Flushed, Pump, receive, receive_blocking, transmit_blocking};
+pub use fd::*;
+pub use filter::{Filter, acceptance_filters};
 pub use legacy::*;
+#[cfg(all(feature = "socketcan", target_os = "linux"))]
+pub use socketcan::{Error as SocketCanError, SocketCan};
@@ -0,0 +1,344 @@
+//! Linux SocketCAN driver adapter, enabled through the `socketcan` feature.
+//!
+//! Connects a node on the classic CAN transport to a raw CAN socket: received frames are
+//! timestamped with [`StdClock`] and fed to [`Node::try_receive_frame`], and the frames queued
+//! through [`Node::enqueue`] are written out by priority.
+//!
+//! ```no_run
+//! use cyphal::time::StdClock;
+//! use cyphal::transfer::HeapTransferManager;
+//! use cyphal::transport::can::{Can, Filter, SocketCan};
+//! use cyphal::Node;
+//!
+//! let clock = StdClock::new();
+//! let socket = SocketCan::open("vcan0", clock.clone()).unwrap();
+//! socket.set_filters(&[Filter::new(0, 0)]).unwrap();
+//!
+//! let mut node = Node::<_, Can, StdClock>::new(Some(42), HeapTransferManager::new());
+//! loop {
+//!     if let Ok(Some(_token)) = socket.receive_into(&mut node) {
+//!         // Handle the transfer through node.transfer_manager
+//!     }
+//!     socket.flush(&mut node).unwrap();
+//! }
+//! ```
+
+use core::mem;
+
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::vec::Vec;
+
+use embedded_time::Clock;
+
+use super::{Can, CanFrame, Filter, Flushed};
+use crate::node::TransmitFrameError;
+use crate::time::StdClock;
+use crate::transfer::TransferManager;
+use crate::{Node, RxError};
+
+/// Errors from feeding received frames to a node.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from the socket failed, or timed out with [`io::ErrorKind::WouldBlock`]
+    Io(io::Error),
+    /// The node rejected the frame
+    Rx(RxError),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Raw CAN socket bound to a single interface.
+///
+/// Only extended data frames are taken in, anything else on the bus is skipped.
+#[derive(Debug)]
+pub struct SocketCan {
+    socket: OwnedFd,
+    clock: StdClock,
+}
+
+impl SocketCan {
+    /// Opens a socket on `interface`, like `can0` or `vcan0`, timestamping received frames with
+    /// `clock`.
+    ///
+    /// The socket receives every frame on the bus until filters are set.
+    pub fn open(interface: &str, clock: StdClock) -> io::Result<Self> {
+        let name = CString::new(interface).map_err(|_| io::ErrorKind::InvalidInput)?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = check(unsafe { libc::socket(libc::PF_CAN, libc::SOCK_RAW, libc::CAN_RAW) })?;
+        // The socket is closed on drop from here on, even if binding fails
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut address: libc::sockaddr_can = unsafe { mem::zeroed() };
+        address.can_family = libc::AF_CAN as libc::sa_family_t;
+        address.can_ifindex = index as libc::c_int;
+        check(unsafe {
+            libc::bind(
+                fd,
+                &address as *const libc::sockaddr_can as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+            )
+        })?;
+
+        Ok(Self { socket, clock })
+    }
+
+    /// Only receive frames accepted by at least one of `filters`, or no frames at all if there
+    /// are none.
+    pub fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        let filters: Vec<_> = filters
+            .iter()
+            .map(|filter| libc::can_filter {
+                can_id: filter.id | libc::CAN_EFF_FLAG,
+                // Keep out standard and remote frames
+                can_mask: filter.mask | libc::CAN_EFF_FLAG | libc::CAN_RTR_FLAG,
+            })
+            .collect();
+        check(unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                libc::SOL_CAN_RAW,
+                libc::CAN_RAW_FILTER,
+                filters.as_ptr() as *const libc::c_void,
+                mem::size_of_val(filters.as_slice()) as libc::socklen_t,
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Makes [`SocketCan::receive`] give up with [`io::ErrorKind::WouldBlock`] after `timeout`,
+    /// or block until a frame arrives for `None`.
+    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
+        let timeout = timeout.unwrap_or_default();
+        let timeval = libc::timeval {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        };
+        check(unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeval as *const libc::timeval as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Receives the next extended data frame.
+    pub fn receive(&self) -> io::Result<CanFrame<StdClock>> {
+        loop {
+            let mut frame: libc::can_frame = unsafe { mem::zeroed() };
+            let len = check_size(unsafe {
+                libc::read(
+                    self.socket.as_raw_fd(),
+                    &mut frame as *mut libc::can_frame as *mut libc::c_void,
+                    mem::size_of::<libc::can_frame>(),
+                )
+            })?;
+            if len != mem::size_of::<libc::can_frame>()
+                || frame.can_id & libc::CAN_EFF_FLAG == 0
+                || frame.can_id & (libc::CAN_RTR_FLAG | libc::CAN_ERR_FLAG) != 0
+            {
+                continue;
+            }
+
+            let timestamp = self.clock.try_now().map_err(|_| io::ErrorKind::Other)?;
+            let len = core::cmp::min(frame.can_dlc as usize, frame.data.len());
+            return Ok(CanFrame::new(
+                timestamp,
+                frame.can_id & libc::CAN_EFF_MASK,
+                &frame.data[..len],
+            ));
+        }
+    }
+
+    /// Writes a frame to the bus.
+    pub fn transmit<C: embedded_time::Clock>(&self, frame: &CanFrame<C>) -> io::Result<()> {
+        let mut raw: libc::can_frame = unsafe { mem::zeroed() };
+        raw.can_id = frame.id.as_raw() | libc::CAN_EFF_FLAG;
+        raw.can_dlc = frame.payload.len() as u8;
+        raw.data[..frame.payload.len()].copy_from_slice(&frame.payload);
+        let len = check_size(unsafe {
+            libc::write(
+                self.socket.as_raw_fd(),
+                &raw as *const libc::can_frame as *const libc::c_void,
+                mem::size_of::<libc::can_frame>(),
+            )
+        })?;
+        if len != mem::size_of::<libc::can_frame>() {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        Ok(())
+    }
+
+    /// Receives the next frame and feeds it to `node`, returning the transfer it completes, if
+    /// any.
+    pub fn receive_into<
+        M: TransferManager<StdClock, Can>,
+        const PORTS: usize,
+        const REQUESTS: usize,
+        const QUEUE: usize,
+        const RESPONSE: usize,
+    >(
+        &self,
+        node: &mut Node<M, Can, StdClock, PORTS, REQUESTS, QUEUE, RESPONSE>,
+    ) -> Result<Option<M::RxTransferToken>, Error> {
+        let frame = self.receive()?;
+        node.try_receive_frame(&frame).map_err(Error::Rx)
+    }
+
+    /// Writes out every frame queued in `node` through [`Node::enqueue`], highest priority
+    /// first.
+    ///
+    /// Stops at the first frame that can't be written, which is then lost along with the rest
+    /// of its transfer.
+    pub fn flush<
+        M: TransferManager<StdClock, Can>,
+        const PORTS: usize,
+        const REQUESTS: usize,
+        const QUEUE: usize,
+        const RESPONSE: usize,
+    >(
+        &self,
+        node: &mut Node<M, Can, StdClock, PORTS, REQUESTS, QUEUE, RESPONSE>,
+    ) -> io::Result<Flushed> {
+        let mut flushed = Flushed::default();
+        loop {
+            let now = self.clock.try_now().map_err(|_| io::ErrorKind::Other)?;
+            match node.pop_frame(now) {
+                Some(Ok(frame)) => {
+                    self.transmit(&frame)?;
+                    flushed.sent += 1;
+                }
+                Some(Err(TransmitFrameError::Expired)) => flushed.expired += 1,
+                // Nothing else comes out of the queue
+                Some(Err(_)) => {}
+                None => return Ok(flushed),
+            }
+        }
+    }
+}
+
+/// Turns the -1 returned by failing libc calls into an error.
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+/// Same as [`check`], for the sizes returned by `read` and `write`.
+fn check_size(result: libc::ssize_t) -> io::Result<usize> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::HeapTransferManager;
+    use crate::{Priority, Subscription, TransferKind, TransmissionType};
+
+    use embedded_time::duration::Milliseconds;
+
+    type TestNode = Node<HeapTransferManager<StdClock, Can>, Can, StdClock>;
+
+    fn publish(node: &mut TestNode, clock: &StdClock, port_id: u16, payload: &[u8]) {
+        let now = clock.try_now().unwrap();
+        let token = node
+            .start_tx_transfer(
+                payload.len(),
+                now + Milliseconds(1000u32),
+                Priority::Nominal,
+                port_id,
+                TransmissionType::Broadcast,
+                |buf| {
+                    buf.copy_from_slice(payload);
+                    Ok::<usize, ()>(payload.len())
+                },
+            )
+            .unwrap();
+        node.enqueue(token, now).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs the vcan0 interface, see setup_vcan.sh"]
+    fn vcan0() {
+        let clock = StdClock::new();
+        let tx = SocketCan::open("vcan0", clock.clone()).unwrap();
+        let rx = SocketCan::open("vcan0", clock.clone()).unwrap();
+        rx.set_read_timeout(Some(std::time::Duration::from_millis(100)))
+            .unwrap();
+        // Messages on subject 100 only
+        rx.set_filters(&[Filter::new(100 << 8, (1 << 25) | (0x1FFF << 8))])
+            .unwrap();
+
+        let mut publisher = TestNode::new(Some(1), HeapTransferManager::new());
+        let mut subscriber = TestNode::new(Some(2), HeapTransferManager::new());
+        for port_id in [100, 101] {
+            subscriber
+                .subscribe(Subscription::new(
+                    TransferKind::Message,
+                    port_id,
+                    64,
+                    Milliseconds(1000),
+                ))
+                .unwrap();
+        }
+
+        publish(&mut publisher, &clock, 101, b"filtered");
+        publish(&mut publisher, &clock, 100, b"hello over vcan0");
+        assert_eq!(
+            tx.flush(&mut publisher).unwrap(),
+            Flushed {
+                sent: 5,
+                expired: 0
+            }
+        );
+
+        let token = loop {
+            if let Some(token) = rx.receive_into(&mut subscriber).unwrap() {
+                break token;
+            }
+        };
+        subscriber
+            .transfer_manager
+            .with_rx_transfer(token, |metadata, payload| {
+                assert_eq!(metadata.port_id, 100);
+                assert_eq!(metadata.source_node_id, Some(1));
+                assert_eq!(payload, b"hello over vcan0");
+            })
+            .unwrap();
+        assert!(matches!(
+            rx.receive_into(&mut subscriber),
+            Err(Error::Io(error)) if error.kind() == io::ErrorKind::WouldBlock
+        ));
+    }
+
+    #[test]
+    fn missing_interface() {
+        let error = SocketCan::open("nonexistent0", StdClock::new()).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::ENODEV));
+        assert_eq!(
+            SocketCan::open("vcan\0", StdClock::new())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
@@ -1,13 +1,14 @@
 use alloc::vec;
+use alloc::vec::Vec;
 
 use crate::time::TestClock;
 use arrayvec::ArrayVec;
-use embedded_hal::can::ExtendedId;
+use embedded_can::ExtendedId;
 use embedded_time::Clock;
 
 use super::bitfields::TailByte;
 use super::{legacy::*, *};
-use crate::internal::InternalRxFrame;
+use crate::transfer::{Frame, TransferMetadata};
 use crate::transport::Transport;
 use crate::*;
 
@@ -16,20 +17,20 @@ use crate::*;
 
 // TODO make this a macro or something for more relevant error messages
 fn all_frame_asserts<C: embedded_time::Clock>(
-    frame: InternalRxFrame<C>,
+    frame: Frame<C>,
     source_id: Option<NodeId>,
     destination_id: Option<NodeId>,
     start: bool,
     end: bool,
     payload: &[u8],
 ) {
-    assert!(matches!(frame.priority, Priority::Nominal));
-    assert_eq!(frame.source_node_id, source_id);
-    assert_eq!(frame.destination_node_id, destination_id);
-    assert_eq!(frame.port_id, 0);
-    assert_eq!(frame.transfer_id, 0);
-    assert_eq!(frame.start_of_transfer, start);
-    assert_eq!(frame.end_of_transfer, end);
+    assert!(matches!(frame.metadata.priority, Priority::Nominal));
+    assert_eq!(frame.metadata.source_node_id, source_id);
+    assert_eq!(frame.metadata.destination_node_id, destination_id);
+    assert_eq!(frame.metadata.port_id, 0);
+    assert_eq!(frame.metadata.transfer_id, 0);
+    assert_eq!(frame.first_frame, start);
+    assert_eq!(frame.last_frame, end);
     assert_eq!(frame.payload, payload);
 }
 
@@ -46,11 +47,10 @@ fn receive_anon_frame() {
     frame.payload.extend(0..5);
     frame.payload.push(TailByte::new(true, true, true, 0).0);
 
-    let result = Can::rx_process_frame(&Some(42), &frame);
-    let result = result.expect("Error processing anon frame");
-    let frame = result.expect("Failed to process anon frame");
+    let (frame, _) = Can::rx_process_frame(&frame).expect("Error processing anon frame");
 
-    all_frame_asserts(frame, None, None, true, true, &[0, 1, 2, 3, 4, 224]);
+    // Anonymous frames carry a pseudo-random source ID, which isn't reported
+    all_frame_asserts(frame, None, None, true, true, &[0, 1, 2, 3, 4]);
 }
 
 /// Ensure that valid message frames are recieved properly.
@@ -64,11 +64,9 @@ fn receive_message_frame() {
     };
 
     frame.payload.push(TailByte::new(true, true, true, 0).0);
-    let result = Can::rx_process_frame(&Some(42), &frame);
-    let result = result.expect("Error processing message frame");
-    let frame = result.expect("Failed to process message frame");
+    let (frame, _) = Can::rx_process_frame(&frame).expect("Error processing message frame");
 
-    all_frame_asserts(frame, Some(41), None, true, true, &[224])
+    all_frame_asserts(frame, Some(41), None, true, true, &[])
 }
 
 /// Ensure that valid service frames are recieved properly.
@@ -82,17 +80,20 @@ fn receive_service_frame() {
     };
 
     frame.payload.push(TailByte::new(true, true, true, 0).0);
-    let result = Can::rx_process_frame(&Some(42), &frame);
-    let result = result.expect("Error processing service response frame");
-    let internal_frame = result.expect("Failed to process valid service response frame");
-    all_frame_asserts(internal_frame, Some(41), Some(42), true, true, &[224]);
+    let (internal_frame, _) =
+        Can::rx_process_frame(&frame).expect("Error processing service response frame");
+    assert_eq!(
+        internal_frame.metadata.transfer_kind,
+        TransferKind::Response
+    );
+    all_frame_asserts(internal_frame, Some(41), Some(42), true, true, &[]);
 
     let mut frame = frame;
     frame.id = CanServiceId::new(Priority::Nominal, true, 0, 42, 41);
-    let result = Can::rx_process_frame(&Some(42), &frame);
-    let result = result.expect("Error processing service request frame");
-    let internal_frame = result.expect("Failed to process valid service request frame");
-    all_frame_asserts(internal_frame, Some(41), Some(42), true, true, &[224]);
+    let (internal_frame, _) =
+        Can::rx_process_frame(&frame).expect("Error processing service request frame");
+    assert_eq!(internal_frame.metadata.transfer_kind, TransferKind::Request);
+    all_frame_asserts(internal_frame, Some(41), Some(42), true, true, &[]);
 }
 
 /// Any transmitted frame must at minimum have a tail byte, so discard empty frames.
@@ -104,7 +105,7 @@ fn discard_empty_frame() {
         id: ExtendedId::ZERO,
         payload: ArrayVec::new(),
     };
-    let result = Can::rx_process_frame(&Some(42), &frame);
+    let result = Can::rx_process_frame(&frame);
     let err = result.expect_err("Empty frame did not error out.");
     assert!(
         matches!(err, RxError::FrameEmpty),
@@ -126,65 +127,21 @@ fn discard_anon_multi_frame() {
     frame.payload.extend(0..7);
 
     frame.payload.push(TailByte::new(false, true, true, 0).0);
-    let result = Can::rx_process_frame(&Some(42), &frame);
+    let result = Can::rx_process_frame(&frame);
     let err = result.unwrap_err();
     assert!(matches!(err, RxError::AnonNotSingleFrame));
 
     frame.payload[7] = TailByte::new(true, false, true, 0).0;
-    let result = Can::rx_process_frame(&Some(42), &frame);
+    let result = Can::rx_process_frame(&frame);
     let err = result.unwrap_err();
     assert!(matches!(err, RxError::AnonNotSingleFrame));
 
     frame.payload[7] = TailByte::new(false, false, true, 0).0;
-    let result = Can::rx_process_frame(&Some(42), &frame);
+    let result = Can::rx_process_frame(&frame);
     let err = result.unwrap_err();
     assert!(matches!(err, RxError::AnonNotSingleFrame));
 }
 
-/// Service transfers to non-local nodes can safely be ignored.
-#[test]
-fn discard_misguided_service_frames() {
-    let clock = TestClock::default();
-    let mut frame = CanFrame {
-        timestamp: clock.try_now().unwrap(),
-        id: CanServiceId::new(Priority::Nominal, true, 0, 31, 41),
-        payload: arrayvec::ArrayVec::<[u8; 8]>::new(),
-    };
-
-    // Request
-    frame.payload.push(TailByte::new(true, true, true, 0).0);
-    let result = Can::rx_process_frame(&Some(42), &frame);
-    let result = result.unwrap();
-    assert!(
-        matches!(result, None),
-        "Didn't discard misguided service request"
-    );
-
-    // Request (anonymous node)
-    let result = Can::rx_process_frame(&None, &frame);
-    let result = result.unwrap();
-    assert!(
-        matches!(result, None),
-        "Didn't discard service request to anonymous node"
-    );
-
-    // Response
-    frame.id = CanServiceId::new(Priority::Nominal, false, 0, 31, 41);
-    let result = Can::rx_process_frame(&Some(42), &frame);
-    let result = result.unwrap();
-    assert!(
-        matches!(result, None),
-        "Didn't discard misguided service response"
-    );
-
-    let result = Can::rx_process_frame(&None, &frame);
-    let result = result.unwrap();
-    assert!(
-        matches!(result, None),
-        "Didn't discard service response to anonymous node"
-    );
-}
-
 /// Tests that several validity checks on tail bytes are properly caught.
 #[test]
 fn tail_byte_checks() {
@@ -197,7 +154,7 @@ fn tail_byte_checks() {
     };
 
     frame.payload.push(TailByte::new(true, true, false, 0).0);
-    let result = Can::rx_process_frame(&Some(42), &frame);
+    let result = Can::rx_process_frame(&frame);
     let err = result.expect_err("Invalid toggle");
     assert!(
         matches!(err, RxError::TransferStartMissingToggle),
@@ -205,7 +162,7 @@ fn tail_byte_checks() {
     );
 
     frame.payload[0] = TailByte::new(true, false, true, 0).0;
-    let result = Can::rx_process_frame(&Some(42), &frame);
+    let result = Can::rx_process_frame(&frame);
     let err = result.expect_err("Invalid toggle");
     assert!(
         matches!(err, RxError::NonLastUnderUtilization),
@@ -213,86 +170,221 @@ fn tail_byte_checks() {
     );
 }
 
-/// Creates a transfer of message type to reduce boilerplate code in testing some
-/// CanIter functionality
-fn make_generic_message_transfer(payload: &[u8]) -> crate::transfer::Transfer<TestClock> {
+/// Creates transfer metadata of message type to reduce boilerplate code in testing
+/// frame generation.
+fn make_generic_message_transfer() -> TransferMetadata<TestClock> {
     let clock = TestClock::default();
-    crate::transfer::Transfer {
+    TransferMetadata {
         timestamp: clock.try_now().unwrap(),
         priority: Priority::Nominal,
         transfer_kind: TransferKind::Message,
         port_id: 0,
-        remote_node_id: None,
+        source_node_id: None,
+        destination_node_id: None,
         transfer_id: 0,
-        payload,
     }
 }
 
+/// Runs a payload through the same steps a transfer manager would, returning every frame
+/// generated for it.
+fn transmit_all(
+    metadata: &TransferMetadata<TestClock>,
+    node_id: Option<NodeId>,
+    payload: &[u8],
+) -> Result<Vec<CanFrame<TestClock>>, TxError> {
+    let mut buffer = vec![0u8; <Can as Transport<TestClock>>::get_crc_padded_size(payload.len())];
+    buffer[0..payload.len()].copy_from_slice(payload);
+    let len = <Can as Transport<TestClock>>::process_tx_crc(&mut buffer, payload.len());
+
+    let mut tx_metadata = TxMetadata::default();
+    let mut offset = 0;
+    let mut frames = Vec::new();
+    while offset < len {
+        let (frame, consumed) = Can::transmit_frame(
+            metadata,
+            &mut tx_metadata,
+            &buffer[offset..len],
+            node_id,
+            metadata.timestamp,
+        )?;
+        offset += consumed;
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
 /// Tests that creating new transfers populates the ID correctly.
 #[test]
 fn transfer_valid_ids() {
-    let mut transfer = make_generic_message_transfer(&[1, 2, 3]);
-
-    // User wouldn't be expected to deal with CanIter, as it's called higher up the stack,
-    // but this is the most ergonomic entry point for this test.
+    let mut transfer = make_generic_message_transfer();
 
     // Anonymous message
-    let mut can_iter = CanIter::new(&transfer, None).unwrap();
-    let frame: &CanFrame<TestClock<u32>> = can_iter.next().expect("Failed to create iter");
-    let id = CanMessageId(frame.id.as_raw());
+    let frames = transmit_all(&transfer, None, &[1, 2, 3]).unwrap();
+    let id = CanMessageId(frames[0].id.as_raw());
     assert!(id.is_message());
     assert!(id.is_anon());
     assert!(id.subject_id() == 0);
     assert!(id.priority() == Priority::Nominal as u8);
     // Source ID should be random, not sure how to handle this...
 
-    let mut can_iter = CanIter::new(&transfer, Some(12)).unwrap();
-    let frame: &CanFrame<TestClock<u32>> = can_iter.next().expect("");
-    let id = CanMessageId(frame.id.as_raw());
+    let frames = transmit_all(&transfer, Some(12), &[1, 2, 3]).unwrap();
+    let id = CanMessageId(frames[0].id.as_raw());
     assert!(id.is_message());
     assert!(!id.is_anon());
     assert!(id.subject_id() == 0);
+    assert!(id.source_id() == 12);
     assert!(id.priority() == Priority::Nominal as u8);
 
+    let err = transmit_all(&transfer, None, &[0u8; 8])
+        .expect_err("Anonymous multi-frame transfers not allowed");
+    assert!(matches!(err, TxError::AnonNotSingleFrame));
+
     transfer.transfer_kind = TransferKind::Request;
-    let err = CanIter::new(&transfer, None).expect_err("Anonymous service transfers not allowed");
+    let err = transmit_all(&transfer, None, &[1, 2, 3])
+        .expect_err("Anonymous service transfers not allowed");
     assert!(matches!(err, TxError::ServiceNoSourceID));
 
-    // TODO finish out these tests. Maybe split this into more tests as well?
+    let err = transmit_all(&transfer, Some(12), &[1, 2, 3])
+        .expect_err("Service transfers need a destination");
+    assert!(matches!(err, TxError::ServiceNoDestinationID));
+
+    transfer.destination_node_id = Some(13);
+    let frames = transmit_all(&transfer, Some(12), &[1, 2, 3]).unwrap();
+    let id = CanServiceId(frames[0].id.as_raw());
+    assert!(id.is_svc());
+    assert!(id.is_req());
+    assert!(id.service_id() == 0);
+    assert!(id.source_id() == 12);
+    assert!(id.destination_id() == 13);
 }
 
-/// Checks that the iterator produces the expected number of frames.
-fn assert_frame_count(mut iter: CanIter<TestClock>, mut expected: usize) {
-    while let Some(_frame) = iter.next() {
-        assert!(expected > 0);
-        expected -= 1;
+/// Checks that transmitting a payload produces the expected number of frames, and that
+/// every frame round-trips through reception.
+fn assert_frame_count(payload_len: usize, expected: usize) -> Vec<CanFrame<TestClock>> {
+    let transfer = make_generic_message_transfer();
+    let payload: Vec<u8> = (0..payload_len as u8).collect();
+    let frames = transmit_all(&transfer, Some(0), &payload).unwrap();
+    assert_eq!(frames.len(), expected);
+
+    for (i, frame) in frames.iter().enumerate() {
+        let tail_byte = TailByte(*frame.payload.last().unwrap());
+        assert_eq!(tail_byte.start_of_transfer(), i == 0);
+        assert_eq!(tail_byte.end_of_transfer(), i == frames.len() - 1);
+        assert_eq!(tail_byte.toggle(), i % 2 == 0);
     }
+
+    frames
 }
 
-// TODO perhaps test placement of CRC as well in these functions
-/// Tests that the iterator operates correctly when CRC portion is split between the last
+/// Single frame transfers fill up to 7 bytes and don't include a CRC.
+#[test]
+fn single_frame_no_crc() {
+    let frames = assert_frame_count(7, 1);
+    assert_eq!(&frames[0].payload[0..7], &[0, 1, 2, 3, 4, 5, 6]);
+}
+
+/// Tests that frame generation operates correctly when CRC portion is split between the last
 /// two frames.
 #[test]
 fn iter_crc_split() {
-    let buf = vec![0u8; 13];
-    let transfer = make_generic_message_transfer(buf.as_slice());
-    assert_frame_count(CanIter::new(&transfer, Some(0)).unwrap(), 3);
+    assert_frame_count(13, 3);
 }
 
-/// Tests that the iterator operates correctly when CRC portion is included with the last
+/// Tests that frame generation operates correctly when CRC portion is included with the last
 /// data frame.
 #[test]
 fn iter_crc_inclusive() {
-    let buf = vec![0u8; 12];
-    let transfer = make_generic_message_transfer(buf.as_slice());
-    assert_frame_count(CanIter::new(&transfer, Some(0)).unwrap(), 2);
+    let frames = assert_frame_count(12, 2);
+
+    let mut crc = crc_any::CRCu16::crc16ccitt_false();
+    crc.digest(&(0..12u8).collect::<Vec<u8>>());
+    assert_eq!(&frames[1].payload[5..7], &crc.get_crc().to_be_bytes());
 }
 
-/// Tests that the iterator operates correctly when the CRC portion is the entire contents
+/// Tests that frame generation operates correctly when the CRC portion is the entire contents
 /// of the last frame.
 #[test]
 fn iter_crc_exclusive() {
-    let buf = vec![0u8; 14];
-    let transfer = make_generic_message_transfer(buf.as_slice());
-    assert_frame_count(CanIter::new(&transfer, Some(0)).unwrap(), 3);
+    let frames = assert_frame_count(14, 3);
+    assert_eq!(frames[2].payload.len(), 3);
+}
+
+/// Receives every frame as a single transfer, returning the payload without the CRC.
+fn receive_all(frames: &[CanFrame<TestClock>]) -> Result<Vec<u8>, RxError> {
+    let mut rx_metadata = RxMetadata::default();
+    let mut payload = Vec::new();
+    for frame in frames.iter() {
+        let (frame, frame_metadata) = Can::rx_process_frame(frame)?;
+        let crc_size = Can::update_rx_metadata(&mut rx_metadata, frame_metadata, &frame)?;
+        payload.extend_from_slice(frame.payload);
+        payload.truncate(payload.len() - crc_size);
+    }
+
+    Ok(payload)
+}
+
+/// Multi-frame transfers with the CRC in the last frame are received with the CRC validated.
+#[test]
+fn receive_multi_frame_crc() {
+    let frames = assert_frame_count(12, 2);
+    assert_eq!(
+        receive_all(&frames).unwrap(),
+        (0..12u8).collect::<Vec<u8>>()
+    );
+
+    let mut frame = frames[1].clone();
+    frame.payload[0] ^= 0xFF;
+    let mut rx_metadata = RxMetadata::default();
+    let (first, first_metadata) = Can::rx_process_frame(&frames[0]).unwrap();
+    Can::update_rx_metadata(&mut rx_metadata, first_metadata, &first).unwrap();
+    let (last, last_metadata) = Can::rx_process_frame(&frame).unwrap();
+    let err = Can::update_rx_metadata(&mut rx_metadata, last_metadata, &last).unwrap_err();
+    assert!(matches!(err, RxError::CrcError));
+}
+
+/// Frames received out of order are caught by the toggle bit.
+#[test]
+fn receive_bad_toggle() {
+    let frames = assert_frame_count(14, 3);
+
+    let mut rx_metadata = RxMetadata::default();
+    let (first, first_metadata) = Can::rx_process_frame(&frames[0]).unwrap();
+    Can::update_rx_metadata(&mut rx_metadata, first_metadata, &first).unwrap();
+    let (last, last_metadata) = Can::rx_process_frame(&frames[2]).unwrap();
+    let err = Can::update_rx_metadata(&mut rx_metadata, last_metadata, &last).unwrap_err();
+    assert!(matches!(err, RxError::InvalidFrameOrdering));
+}
+
+/// The CRC is stripped from received payloads, wherever it falls.
+#[test]
+fn receive_strips_crc() {
+    // Single frame, no CRC at all
+    let frames = assert_frame_count(7, 1);
+    assert_eq!(receive_all(&frames).unwrap(), (0..7u8).collect::<Vec<u8>>());
+
+    // CRC entirely in the last frame
+    let frames = assert_frame_count(14, 3);
+    assert_eq!(
+        receive_all(&frames).unwrap(),
+        (0..14u8).collect::<Vec<u8>>()
+    );
+}
+
+/// A CRC split across the last two frames is still validated and stripped.
+#[test]
+fn receive_crc_split() {
+    let frames = assert_frame_count(13, 3);
+    // Last frame only holds the second CRC byte and the tail byte
+    assert_eq!(frames[2].payload.len(), 2);
+    assert_eq!(
+        receive_all(&frames).unwrap(),
+        (0..13u8).collect::<Vec<u8>>()
+    );
+
+    for (frame, byte) in [(1, 6), (2, 0)] {
+        let mut bad_frames = frames.clone();
+        bad_frames[frame].payload[byte] ^= 0xFF;
+        assert!(matches!(receive_all(&bad_frames), Err(RxError::CrcError)));
+    }
 }
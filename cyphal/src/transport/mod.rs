@@ -8,11 +8,15 @@
 
 // Declaring all of the sub transport modules here.
 pub mod can;
+pub mod serial;
+pub mod udp;
 
-use crate::NodeId;
 use crate::transfer::{Frame as TransferFrame, TransferMetadata};
+use crate::{NodeId, TransferId, TransferKind};
 use crate::{RxError, TxError};
 
+use num_traits::ToPrimitive;
+
 pub trait Transport<C: embedded_time::Clock> {
     /// Core frame type, that can get received from the link layer.
     type Frame;
@@ -29,15 +33,52 @@ pub trait Transport<C: embedded_time::Clock> {
 
     const CRC_SIZE: usize;
 
+    /// Largest transfer ID the transport can carry. Transfer IDs wrap around to 0 after it.
+    const MAX_TRANSFER_ID: TransferId;
+
+    /// Transfer ID to use for the next transfer on a port, wrapping around at the transport's modulo.
+    fn next_transfer_id(transfer_id: TransferId) -> TransferId {
+        if transfer_id >= Self::MAX_TRANSFER_ID {
+            0
+        } else {
+            transfer_id + 1
+        }
+    }
+
+    /// Key ordering the frames queued through [`crate::Node::enqueue`], the lowest going out
+    /// first. Frames with the same key go out in the order they were queued.
+    ///
+    /// Defaults to the order of CAN arbitration IDs from this node: by priority, then messages
+    /// ahead of responses ahead of requests, then by port and destination.
+    fn transmit_order(transfer_metadata: &TransferMetadata<C>) -> u32 {
+        let kind = match transfer_metadata.transfer_kind {
+            TransferKind::Message => 0,
+            TransferKind::Response => 1,
+            TransferKind::Request => 2,
+        };
+        let priority = transfer_metadata.priority.to_u32().unwrap();
+        let port_id = u32::from(transfer_metadata.port_id) & 0x1FFF;
+        let destination = u32::from(transfer_metadata.destination_node_id.unwrap_or(0)) & 0x7F;
+        (priority << 29) | (kind << 27) | (port_id << 7) | destination
+    }
+
     /// Size of payload after appending CRC and any necessary padding bytes
     fn get_crc_padded_size(requested_size: usize) -> usize;
 
     /// Update RX metadata for a newly received frame, and check for validity in transfer
+    ///
+    /// This is called with every frame in full, even once the transfer has grown past the
+    /// subscription's extent and managers are truncating the payload, so the CRC covers the
+    /// entire transfer.
+    ///
+    /// Frame payloads include the transfer CRC, as it may be split across frames. On the last
+    /// frame this returns the number of bytes at the end of the transfer that belong to the CRC,
+    /// which managers strip before handing the payload to the user. It is 0 for every other frame.
     fn update_rx_metadata(
         transport_metadata: &mut Self::RxMetadata,
         frame_metadata: Self::FrameMetadata,
         frame: &TransferFrame<C>,
-    ) -> Result<(), RxError>;
+    ) -> Result<usize, RxError>;
 
     /// Process the entire TX payload CRC, and append CRC with any required padding for this transport
     fn process_tx_crc(buffer: &mut [u8], data_size: usize) -> usize;
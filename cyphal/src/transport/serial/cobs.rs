@@ -0,0 +1,48 @@
+//! # Consistent Overhead Byte Stuffing.
+//!
+//! Encodes arbitrary data so that it contains no zero bytes, at a cost of at most one
+//! byte per 254 bytes of input. Decoding is done incrementally by
+//! [`StreamDecoder`](super::StreamDecoder).
+
+/// Largest possible encoded size of `len` bytes of input, excluding delimiters.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Encodes `src` into `dst`, returning the encoded length, or `None` if `dst` is too small.
+///
+/// No delimiters are written.
+pub fn encode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    if dst.len() < max_encoded_len(src.len()) {
+        return None;
+    }
+
+    // Index of the current code byte, filled in once the block it heads is finished
+    let mut code_index = 0;
+    let mut code = 1u8;
+    let mut out = 1;
+
+    for (i, &byte) in src.iter().enumerate() {
+        if byte != 0 {
+            dst[out] = byte;
+            out += 1;
+            code += 1;
+        }
+
+        if byte == 0 || code == 0xFF {
+            dst[code_index] = code;
+            code = 1;
+            code_index = out;
+            // A full block at the very end doesn't need an empty block after it
+            if byte == 0 || i != src.len() - 1 {
+                out += 1;
+            }
+        }
+    }
+
+    if code_index < out {
+        dst[code_index] = code;
+    }
+
+    Some(out)
+}
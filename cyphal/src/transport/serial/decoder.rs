@@ -0,0 +1,115 @@
+//! Incremental decoder turning a byte stream into serial frames.
+
+use super::transport::{SERIAL_FRAME_SIZE, SerialFrame};
+use crate::time::Timestamp;
+use crate::transport::udp::UdpHeader;
+
+/// Decodes a COBS-framed byte stream into [`SerialFrame`]s, one byte at a time.
+///
+/// Bytes can be pushed as they arrive from the link (e.g. from a UART interrupt). A frame
+/// is yielded once its closing delimiter is received. Garbage between frames, frames that
+/// are malformed and frames that are too large are silently dropped, and the decoder
+/// resynchronizes on the next delimiter.
+///
+/// ```ignore
+/// let mut decoder = StreamDecoder::new();
+/// for byte in uart.read() {
+///     if let Some(frame) = decoder.push(byte, clock.try_now().unwrap()) {
+///         node.try_receive_frame(&frame)?;
+///     }
+/// }
+/// ```
+pub struct StreamDecoder<C: embedded_time::Clock> {
+    buffer: heapless::Vec<u8, SERIAL_FRAME_SIZE>,
+    /// Timestamp of the first byte of the frame currently being decoded.
+    timestamp: Option<Timestamp<C>>,
+    /// Data bytes left in the current COBS block, the next byte is a code byte when 0.
+    block_remaining: u8,
+    /// The current block ends with an implicit zero, written once another block follows.
+    pending_zero: bool,
+    /// The current frame is invalid, drop everything until the next delimiter.
+    discard: bool,
+}
+
+impl<C: embedded_time::Clock> StreamDecoder<C> {
+    pub fn new() -> Self {
+        Self {
+            buffer: heapless::Vec::new(),
+            timestamp: None,
+            block_remaining: 0,
+            pending_zero: false,
+            discard: false,
+        }
+    }
+
+    /// Feed a single byte from the stream, returning a frame if this byte completed one.
+    pub fn push(&mut self, byte: u8, timestamp: Timestamp<C>) -> Option<SerialFrame<C>> {
+        if byte == 0 {
+            return self.finish_frame();
+        }
+
+        if self.discard {
+            return None;
+        }
+
+        if self.timestamp.is_none() {
+            self.timestamp = Some(timestamp);
+        }
+
+        if self.block_remaining == 0 {
+            // Code byte, starting a new block
+            if self.pending_zero {
+                self.write(0);
+            }
+            self.block_remaining = byte - 1;
+            self.pending_zero = byte != 0xFF;
+        } else {
+            self.write(byte);
+            self.block_remaining -= 1;
+        }
+
+        None
+    }
+
+    /// Drop any partially decoded frame.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.timestamp = None;
+        self.block_remaining = 0;
+        self.pending_zero = false;
+        self.discard = false;
+    }
+
+    fn write(&mut self, byte: u8) {
+        if self.buffer.push(byte).is_err() {
+            self.discard = true;
+        }
+    }
+
+    fn finish_frame(&mut self) -> Option<SerialFrame<C>> {
+        // Frames that ended mid-block were truncated, and anything shorter than a header
+        // (including the empty space between back-to-back delimiters) can't be a frame.
+        let valid = !self.discard
+            && self.block_remaining == 0
+            && self.buffer.len() >= UdpHeader::SIZE
+            && self.timestamp.is_some();
+
+        let frame = if valid {
+            Some(SerialFrame {
+                timestamp: self.timestamp.unwrap(),
+                data: self.buffer.clone(),
+            })
+        } else {
+            None
+        };
+
+        self.reset();
+        frame
+    }
+}
+
+impl<C: embedded_time::Clock> Default for StreamDecoder<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,20 @@
+//! Cyphal/serial transport implementation.
+//!
+//! Cyphal/serial carries the same header and CRC-32C transfer trailer as Cyphal/UDP over
+//! any byte stream (UART, USB-CDC, TCP...). Each frame is COBS-encoded so that it
+//! doesn't contain any zero bytes, and is surrounded by zero delimiters.
+//!
+//! Frames produced by the node are encoded for the wire with [`SerialFrame::encode`], and
+//! received bytes are fed into a [`StreamDecoder`], which yields frames for
+//! `Node::try_receive_frame`.
+
+pub mod cobs;
+mod decoder;
+mod transport;
+
+#[cfg(test)]
+mod tests;
+
+// Exports
+pub use decoder::StreamDecoder;
+pub use transport::*;
@@ -0,0 +1,257 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::time::TestClock;
+use embedded_time::Clock;
+
+use super::*;
+use crate::transfer::TransferMetadata;
+use crate::transport::Transport;
+use crate::transport::udp::{RxMetadata, TxMetadata, UdpHeader};
+use crate::*;
+
+fn encode_vec(src: &[u8]) -> Vec<u8> {
+    let mut dst = vec![0u8; cobs::max_encoded_len(src.len())];
+    let len = cobs::encode(src, &mut dst).unwrap();
+    dst.truncate(len);
+    dst
+}
+
+#[test]
+fn cobs_vectors() {
+    assert_eq!(encode_vec(&[]), [0x01]);
+    assert_eq!(encode_vec(&[0x00]), [0x01, 0x01]);
+    assert_eq!(encode_vec(&[0x00, 0x00]), [0x01, 0x01, 0x01]);
+    assert_eq!(
+        encode_vec(&[0x11, 0x22, 0x00, 0x33]),
+        [0x03, 0x11, 0x22, 0x02, 0x33]
+    );
+    assert_eq!(
+        encode_vec(&[0x11, 0x22, 0x33, 0x44]),
+        [0x05, 0x11, 0x22, 0x33, 0x44]
+    );
+    assert_eq!(
+        encode_vec(&[0x11, 0x00, 0x00, 0x00]),
+        [0x02, 0x11, 0x01, 0x01, 0x01]
+    );
+
+    // A full block at the end doesn't get an empty block after it
+    let data: Vec<u8> = (1..=254).collect();
+    let encoded = encode_vec(&data);
+    assert_eq!(encoded.len(), 255);
+    assert_eq!(encoded[0], 0xFF);
+    assert_eq!(&encoded[1..], &data[..]);
+
+    let data: Vec<u8> = (1..=255).collect();
+    let encoded = encode_vec(&data);
+    assert_eq!(encoded.len(), 257);
+    assert_eq!(encoded[0], 0xFF);
+    assert_eq!(&encoded[255..], &[0x02, 0xFF]);
+
+    assert_eq!(cobs::encode(&[1, 2, 3], &mut [0u8; 3]), None);
+}
+
+fn make_generic_message_transfer() -> TransferMetadata<TestClock> {
+    let clock = TestClock::default();
+    TransferMetadata {
+        timestamp: clock.try_now().unwrap(),
+        priority: Priority::Nominal,
+        transfer_kind: TransferKind::Message,
+        port_id: 100,
+        source_node_id: None,
+        destination_node_id: None,
+        transfer_id: 7,
+    }
+}
+
+/// Runs a payload through the same steps a transfer manager would, returning every frame
+/// generated for it.
+fn transmit_all(
+    metadata: &TransferMetadata<TestClock>,
+    node_id: Option<NodeId>,
+    payload: &[u8],
+) -> Vec<SerialFrame<TestClock>> {
+    let mut buffer =
+        vec![0u8; <Serial as Transport<TestClock>>::get_crc_padded_size(payload.len())];
+    buffer[0..payload.len()].copy_from_slice(payload);
+    let len = <Serial as Transport<TestClock>>::process_tx_crc(&mut buffer, payload.len());
+
+    let mut tx_metadata = TxMetadata::default();
+    let mut offset = 0;
+    let mut frames = Vec::new();
+    while offset < len {
+        let (frame, consumed) = Serial::transmit_frame(
+            metadata,
+            &mut tx_metadata,
+            &buffer[offset..len],
+            node_id,
+            metadata.timestamp,
+        )
+        .unwrap();
+        offset += consumed;
+        frames.push(frame);
+    }
+
+    frames
+}
+
+/// Encodes frames back to back into a single byte stream.
+fn write_stream(frames: &[SerialFrame<TestClock>], stream: &mut Vec<u8>) {
+    let mut buffer = [0u8; ENCODED_FRAME_SIZE];
+    for frame in frames {
+        let len = frame.encode(&mut buffer).unwrap();
+        assert!(!buffer[1..len - 1].contains(&0));
+        stream.extend_from_slice(&buffer[0..len]);
+    }
+}
+
+/// Pushes the stream into a decoder one byte at a time, like a UART would.
+fn read_stream(stream: &[u8]) -> Vec<SerialFrame<TestClock>> {
+    let clock = TestClock::default();
+    let mut decoder = StreamDecoder::new();
+    stream
+        .iter()
+        .filter_map(|byte| decoder.push(*byte, clock.try_now().unwrap()))
+        .collect()
+}
+
+/// Receives every frame, checking them as a single transfer, and returns the payload.
+fn receive_all(frames: &[SerialFrame<TestClock>]) -> Result<Vec<u8>, RxError> {
+    let mut rx_metadata = RxMetadata::default();
+    let mut payload = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let (frame, frame_metadata) = Serial::rx_process_frame(frame)?;
+        assert_eq!(frame.first_frame, i == 0);
+        assert_eq!(frame.last_frame, i == frames.len() - 1);
+        let crc_size = Serial::update_rx_metadata(&mut rx_metadata, frame_metadata, &frame)?;
+        payload.extend_from_slice(frame.payload);
+        payload.truncate(payload.len() - crc_size);
+    }
+
+    Ok(payload)
+}
+
+#[test]
+fn single_frame_round_trip() {
+    let metadata = make_generic_message_transfer();
+    let frames = transmit_all(&metadata, Some(42), &[0, 1, 0, 0, 2]);
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].data.len(), UdpHeader::SIZE + 5 + 4);
+
+    let mut stream = Vec::new();
+    write_stream(&frames, &mut stream);
+    let received = read_stream(&stream);
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].data, frames[0].data);
+
+    assert_eq!(receive_all(&received).unwrap(), [0, 1, 0, 0, 2]);
+    let header = received[0].header().unwrap();
+    assert_eq!(header.source_node_id, Some(42));
+    assert_eq!(header.transfer_id, 7);
+}
+
+#[test]
+fn multi_frame_round_trip() {
+    let metadata = make_generic_message_transfer();
+    // Plenty of zeroes and long zero-free runs, to exercise both sides of COBS
+    let payload: Vec<u8> = (0..2500u32).map(|i| (i % 300) as u8).collect();
+    let frames = transmit_all(&metadata, Some(42), &payload);
+    assert_eq!(frames.len(), 3);
+
+    let mut stream = Vec::new();
+    write_stream(&frames, &mut stream);
+    let received = read_stream(&stream);
+    assert_eq!(received.len(), 3);
+    assert_eq!(receive_all(&received).unwrap(), payload);
+}
+
+/// Line noise, truncated frames and corrupted headers must not get in the way of valid frames.
+#[test]
+fn stream_resynchronizes() {
+    let metadata = make_generic_message_transfer();
+    let first = transmit_all(&metadata, Some(1), b"first");
+    let second = transmit_all(&metadata, Some(2), b"second");
+    let third = transmit_all(&metadata, Some(3), b"third");
+
+    let mut stream = Vec::new();
+    // Garbage before any delimiter
+    stream.extend_from_slice(&[0x12, 0x34, 0x56]);
+    write_stream(&first, &mut stream);
+
+    // Truncated frame, cut off mid-block
+    let mut truncated = Vec::new();
+    write_stream(&second, &mut truncated);
+    stream.extend_from_slice(&truncated[0..10]);
+
+    // Repeated delimiters
+    stream.extend_from_slice(&[0, 0, 0]);
+
+    // Frame with a corrupted header gets through the decoder, but is rejected by the transport
+    let mut corrupted = Vec::new();
+    write_stream(&second, &mut corrupted);
+    // Delimiter, code byte, then the header: version, priority, source node ID...
+    corrupted[6] ^= 0x40;
+    stream.extend_from_slice(&corrupted);
+
+    write_stream(&third, &mut stream);
+
+    let received = read_stream(&stream);
+    assert_eq!(received.len(), 3);
+    assert_eq!(receive_all(&received[0..1]).unwrap(), b"first");
+    assert!(matches!(
+        Serial::rx_process_frame(&received[1]),
+        Err(RxError::InvalidHeader)
+    ));
+    assert_eq!(receive_all(&received[2..3]).unwrap(), b"third");
+}
+
+/// Frames that don't fit the decoder's buffer are dropped without affecting the next one.
+#[test]
+fn oversized_frame_dropped() {
+    let metadata = make_generic_message_transfer();
+    let frames = transmit_all(&metadata, Some(1), b"ok");
+
+    let mut stream = vec![0u8];
+    let junk = vec![0xAAu8; SERIAL_FRAME_SIZE + 10];
+    let mut encoded = encode_vec(&junk);
+    stream.append(&mut encoded);
+    write_stream(&frames, &mut stream);
+
+    let received = read_stream(&stream);
+    assert_eq!(received.len(), 1);
+    assert_eq!(receive_all(&received).unwrap(), b"ok");
+}
+
+/// Frames written to and read back from an in-memory pipe, in arbitrary chunks.
+#[test]
+fn pipe_round_trip() {
+    use std::io::{Read, Write};
+
+    let metadata = make_generic_message_transfer();
+    let payload: Vec<u8> = (0..1500).map(|i| (i * 13) as u8).collect();
+    let frames = transmit_all(&metadata, Some(42), &payload);
+
+    let mut stream = Vec::new();
+    write_stream(&frames, &mut stream);
+    let mut pipe = std::collections::VecDeque::new();
+    pipe.write_all(&stream).unwrap();
+
+    let clock = TestClock::default();
+    let mut decoder = StreamDecoder::new();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 37];
+    loop {
+        let len = pipe.read(&mut buf).unwrap();
+        if len == 0 {
+            break;
+        }
+        for byte in &buf[0..len] {
+            if let Some(frame) = decoder.push(*byte, clock.try_now().unwrap()) {
+                received.push(frame);
+            }
+        }
+    }
+
+    assert_eq!(received.len(), 2);
+    assert_eq!(receive_all(&received).unwrap(), payload);
+}
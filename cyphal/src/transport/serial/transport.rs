@@ -0,0 +1,115 @@
+//! Cyphal/serial transport implementation.
+
+use super::cobs;
+use crate::time::Timestamp;
+use crate::transfer::{Frame, TransferMetadata};
+use crate::transport::Transport;
+use crate::transport::udp::{
+    FrameMetadata, RxMetadata, TxMetadata, Udp, UdpHeader, next_frame_header, process_frame_data,
+};
+use crate::{NodeId, RxError, TransferId, TxError};
+
+/// Maximum number of payload bytes carried by a single frame, excluding the header.
+///
+/// The serial transport doesn't have an inherent MTU, this only bounds buffer sizes.
+pub const SERIAL_MTU: usize = 1024;
+
+/// Maximum size of a decoded frame, including the header.
+pub const SERIAL_FRAME_SIZE: usize = UdpHeader::SIZE + SERIAL_MTU;
+
+/// Maximum size of a frame on the wire, including COBS overhead and both delimiters.
+pub const ENCODED_FRAME_SIZE: usize = cobs::max_encoded_len(SERIAL_FRAME_SIZE) + 2;
+
+/// Unit struct for declaring transport type
+#[derive(Copy, Clone, Debug)]
+pub struct Serial;
+
+impl<C: embedded_time::Clock> Transport<C> for Serial {
+    type Frame = SerialFrame<C>;
+    // Same header and transfer CRC as UDP, so the metadata is shared
+    type FrameMetadata = FrameMetadata;
+    type RxMetadata = RxMetadata;
+    type TxMetadata = TxMetadata;
+
+    const MTU_SIZE: usize = SERIAL_MTU;
+    const CRC_SIZE: usize = <Udp as Transport<C>>::CRC_SIZE;
+    const MAX_TRANSFER_ID: TransferId = <Udp as Transport<C>>::MAX_TRANSFER_ID;
+
+    fn get_crc_padded_size(requested_size: usize) -> usize {
+        <Udp as Transport<C>>::get_crc_padded_size(requested_size)
+    }
+
+    fn update_rx_metadata(
+        transport_metadata: &mut Self::RxMetadata,
+        frame_metadata: Self::FrameMetadata,
+        frame: &Frame<C>,
+    ) -> Result<usize, RxError> {
+        <Udp as Transport<C>>::update_rx_metadata(transport_metadata, frame_metadata, frame)
+    }
+
+    fn process_tx_crc(buffer: &mut [u8], data_size: usize) -> usize {
+        <Udp as Transport<C>>::process_tx_crc(buffer, data_size)
+    }
+
+    fn rx_process_frame<'a>(
+        frame: &'a Self::Frame,
+    ) -> Result<(Frame<'a, C>, Self::FrameMetadata), RxError> {
+        process_frame_data(frame.timestamp, &frame.data)
+    }
+
+    fn transmit_frame(
+        transfer_metadata: &TransferMetadata<C>,
+        transport_metadata: &mut Self::TxMetadata,
+        data: &[u8],
+        node_id: Option<NodeId>,
+        timestamp: embedded_time::Instant<C>,
+    ) -> Result<(Self::Frame, usize), TxError> {
+        // CRC included in data, calculated when creating a TX transfer
+        let last_frame = data.len() <= SERIAL_MTU;
+        let header = next_frame_header(transfer_metadata, transport_metadata, node_id, last_frame)?;
+
+        let consume_len = core::cmp::min(SERIAL_MTU, data.len());
+        let mut frame_data = heapless::Vec::new();
+        // Can't fail, header and MTU both fit in a frame
+        let _ = frame_data.extend_from_slice(&header.serialize());
+        let _ = frame_data.extend_from_slice(&data[0..consume_len]);
+
+        Ok((
+            Self::Frame {
+                timestamp,
+                data: frame_data,
+            },
+            consume_len,
+        ))
+    }
+}
+
+/// A single decoded Cyphal/serial frame, header included.
+#[derive(Clone, Debug)]
+pub struct SerialFrame<C: embedded_time::Clock> {
+    pub timestamp: Timestamp<C>,
+    pub data: heapless::Vec<u8, SERIAL_FRAME_SIZE>,
+}
+
+impl<C: embedded_time::Clock> SerialFrame<C> {
+    /// Parsed frame header.
+    pub fn header(&self) -> Result<UdpHeader, RxError> {
+        UdpHeader::deserialize(&self.data)
+    }
+
+    /// Encodes the frame for the wire, delimiters included, returning the encoded length.
+    ///
+    /// Returns `None` if `buffer` is too small, [`ENCODED_FRAME_SIZE`] is always enough.
+    pub fn encode(&self, buffer: &mut [u8]) -> Option<usize> {
+        let encoded_len = cobs::max_encoded_len(self.data.len()) + 2;
+        if buffer.len() < encoded_len {
+            return None;
+        }
+
+        buffer[0] = 0;
+        let len = cobs::encode(&self.data, &mut buffer[1..])?;
+        buffer[len + 1] = 0;
+
+        Some(len + 2)
+    }
+}
@@ -0,0 +1,131 @@
+//! # Cyphal/UDP frame header.
+//!
+//! The header is byte-aligned and little-endian, with the exception of the trailing header
+//! CRC which is big-endian.
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crc_any::CRCu16;
+
+use crate::types::*;
+use crate::{Priority, RxError, TransferKind};
+
+/// Header version this implementation produces and understands.
+const HEADER_VERSION: u8 = 1;
+
+/// Node ID used in place of anonymous sources and broadcast destinations.
+const NODE_ID_UNSET: u16 = 0xFFFF;
+
+const SERVICE_NOT_MESSAGE: u16 = 0x8000;
+const REQUEST_NOT_RESPONSE: u16 = 0x4000;
+const SERVICE_ID_MASK: u16 = 0x3FFF;
+const SUBJECT_ID_MASK: u16 = 0x7FFF;
+
+const END_OF_TRANSFER: u32 = 0x8000_0000;
+const FRAME_INDEX_MASK: u32 = 0x7FFF_FFFF;
+
+/// Decoded Cyphal/UDP frame header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UdpHeader {
+    pub priority: Priority,
+    /// `None` for anonymous transfers.
+    pub source_node_id: Option<NodeId>,
+    /// `None` for broadcast (message) transfers.
+    pub destination_node_id: Option<NodeId>,
+    pub transfer_kind: TransferKind,
+    pub port_id: PortId,
+    pub transfer_id: TransferId,
+    pub frame_index: u32,
+    pub end_of_transfer: bool,
+    /// Opaque user data, zero unless an application has a use for it.
+    pub user_data: u16,
+}
+
+impl UdpHeader {
+    /// Size of a serialized header in bytes.
+    pub const SIZE: usize = 24;
+
+    /// Serialize the header, computing the header CRC.
+    pub fn serialize(&self) -> [u8; Self::SIZE] {
+        let data_specifier = match self.transfer_kind {
+            TransferKind::Message => self.port_id & SUBJECT_ID_MASK,
+            TransferKind::Request => {
+                SERVICE_NOT_MESSAGE | REQUEST_NOT_RESPONSE | (self.port_id & SERVICE_ID_MASK)
+            }
+            TransferKind::Response => SERVICE_NOT_MESSAGE | (self.port_id & SERVICE_ID_MASK),
+        };
+        let frame_index_eot = (self.frame_index & FRAME_INDEX_MASK)
+            | if self.end_of_transfer {
+                END_OF_TRANSFER
+            } else {
+                0
+            };
+
+        let mut buf = [0u8; Self::SIZE];
+        buf[0] = HEADER_VERSION;
+        buf[1] = self.priority.to_u8().unwrap();
+        buf[2..4].copy_from_slice(&self.source_node_id.unwrap_or(NODE_ID_UNSET).to_le_bytes());
+        buf[4..6].copy_from_slice(
+            &self
+                .destination_node_id
+                .unwrap_or(NODE_ID_UNSET)
+                .to_le_bytes(),
+        );
+        buf[6..8].copy_from_slice(&data_specifier.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.transfer_id.to_le_bytes());
+        buf[16..20].copy_from_slice(&frame_index_eot.to_le_bytes());
+        buf[20..22].copy_from_slice(&self.user_data.to_le_bytes());
+
+        let mut crc = CRCu16::crc16ccitt_false();
+        crc.digest(&buf[0..22]);
+        buf[22..24].copy_from_slice(&crc.get_crc().to_be_bytes());
+
+        buf
+    }
+
+    /// Parse a header from the start of a datagram, validating the header CRC.
+    pub fn deserialize(data: &[u8]) -> Result<Self, RxError> {
+        if data.len() < Self::SIZE {
+            return Err(RxError::InvalidHeader);
+        }
+
+        let mut crc = CRCu16::crc16ccitt_false();
+        crc.digest(&data[0..22]);
+        if crc.get_crc() != u16::from_be_bytes([data[22], data[23]]) {
+            return Err(RxError::InvalidHeader);
+        }
+
+        if data[0] & 0x0F != HEADER_VERSION {
+            return Err(RxError::InvalidHeader);
+        }
+
+        let priority = Priority::from_u8(data[1] & 0x07).ok_or(RxError::InvalidHeader)?;
+        let source_node_id = u16::from_le_bytes([data[2], data[3]]);
+        let destination_node_id = u16::from_le_bytes([data[4], data[5]]);
+        let data_specifier = u16::from_le_bytes([data[6], data[7]]);
+        let transfer_id = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        let frame_index_eot = u32::from_le_bytes(data[16..20].try_into().unwrap());
+        let user_data = u16::from_le_bytes([data[20], data[21]]);
+
+        let (transfer_kind, port_id) = if data_specifier & SERVICE_NOT_MESSAGE == 0 {
+            (TransferKind::Message, data_specifier & SUBJECT_ID_MASK)
+        } else if data_specifier & REQUEST_NOT_RESPONSE != 0 {
+            (TransferKind::Request, data_specifier & SERVICE_ID_MASK)
+        } else {
+            (TransferKind::Response, data_specifier & SERVICE_ID_MASK)
+        };
+
+        Ok(Self {
+            priority,
+            source_node_id: (source_node_id != NODE_ID_UNSET).then_some(source_node_id),
+            destination_node_id: (destination_node_id != NODE_ID_UNSET)
+                .then_some(destination_node_id),
+            transfer_kind,
+            port_id,
+            transfer_id,
+            frame_index: frame_index_eot & FRAME_INDEX_MASK,
+            end_of_transfer: frame_index_eot & END_OF_TRANSFER != 0,
+            user_data,
+        })
+    }
+}
@@ -0,0 +1,25 @@
+//! Cyphal/UDP transport implementation.
+//!
+//! Every frame is a single UDP datagram made of a fixed 24 byte header followed by a
+//! chunk of the transfer payload. Transfers carry a CRC-32C trailer in the final frame,
+//! including single-frame transfers.
+//!
+//! This module doesn't do any I/O itself. Received datagrams are wrapped in a
+//! [`UdpFrame`] and handed to the node, and transmitted frames are sent by the user to the
+//! endpoint returned by [`UdpFrame::endpoint`]. The node takes in the frames of a transfer in
+//! order, so datagrams from a network that may reorder them should go through a
+//! [`ReorderBuffer`] first.
+
+mod header;
+mod multicast;
+mod reorder;
+mod transport;
+
+#[cfg(test)]
+mod tests;
+
+// Exports
+pub use header::UdpHeader;
+pub use multicast::*;
+pub use reorder::ReorderBuffer;
+pub use transport::*;
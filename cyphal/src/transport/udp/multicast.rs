@@ -0,0 +1,46 @@
+//! # Cyphal/UDP multicast group mapping.
+//!
+//! Subjects and services each map onto an IPv4 multicast group, and all traffic uses the
+//! same UDP port. Receivers join the groups for the subjects they subscribe to, and the
+//! group for their own node ID to receive service transfers.
+
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use crate::TransferKind;
+use crate::types::*;
+
+/// UDP port used for all Cyphal/UDP traffic.
+pub const UDP_PORT: u16 = 9382;
+
+/// Prefix shared by all Cyphal/UDP multicast groups (239.0.0.0/15).
+const MULTICAST_PREFIX: u32 = 0xEF00_0000;
+/// Set for service groups, clear for subject groups.
+const SERVICE_BIT: u32 = 1 << 16;
+
+/// Multicast group a subject is published to.
+pub fn subject_multicast_address(subject_id: PortId) -> Ipv4Addr {
+    Ipv4Addr::from(MULTICAST_PREFIX | (subject_id & 0x7FFF) as u32)
+}
+
+/// Multicast group service transfers destined for a node are sent to.
+pub fn service_multicast_address(destination_node_id: NodeId) -> Ipv4Addr {
+    Ipv4Addr::from(MULTICAST_PREFIX | SERVICE_BIT | destination_node_id as u32)
+}
+
+/// Endpoint a transfer should be sent to.
+///
+/// Returns `None` for service transfers without a destination.
+pub fn transfer_endpoint(
+    transfer_kind: TransferKind,
+    port_id: PortId,
+    destination_node_id: Option<NodeId>,
+) -> Option<SocketAddrV4> {
+    let address = match transfer_kind {
+        TransferKind::Message => subject_multicast_address(port_id),
+        TransferKind::Request | TransferKind::Response => {
+            service_multicast_address(destination_node_id?)
+        }
+    };
+
+    Some(SocketAddrV4::new(address, UDP_PORT))
+}
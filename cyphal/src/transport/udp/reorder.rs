@@ -0,0 +1,162 @@
+//! Puts the datagrams of multi-frame transfers back in order before they reach the node.
+
+use super::transport::UdpFrame;
+use crate::TransferKind;
+use crate::time::Timestamp;
+use crate::types::{NodeId, PortId, TransferId};
+
+/// Identifies a transfer across its datagrams.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct TransferKey {
+    transfer_kind: TransferKind,
+    port_id: PortId,
+    source_node_id: Option<NodeId>,
+    destination_node_id: Option<NodeId>,
+    transfer_id: TransferId,
+}
+
+/// Progress of a multi-frame transfer handed out by [`ReorderBuffer::pop`].
+struct Progress<C: embedded_time::Clock> {
+    /// Index of the next frame to hand out
+    next_frame_index: u32,
+    /// When the last frame handed out was received
+    timestamp: Timestamp<C>,
+}
+
+/// Buffers received [`UdpFrame`]s so the frames of each transfer are handed to the node in
+/// order, whatever order the network delivered them in.
+///
+/// Transfer managers take in the frames of a transfer strictly in order, so a frame arriving
+/// ahead of its predecessors is held back until they are in, up to `FRAMES` of them. Once
+/// full, the frame that has waited longest is dropped to make room, which also clears out
+/// frames whose predecessors were lost. The progress of up to `TRANSFERS` multi-frame
+/// transfers is kept, the least recently active one being forgotten to make room. Repeated
+/// frames of a transfer in progress are dropped.
+///
+/// ```ignore
+/// let mut reorder = ReorderBuffer::<_, 4, 8>::new();
+/// let len = socket.recv(&mut buf)?;
+/// reorder.push(UdpFrame::new(clock.try_now().unwrap(), &buf[0..len]));
+/// while let Some(frame) = reorder.pop() {
+///     node.try_receive_frame(&frame)?;
+/// }
+/// ```
+pub struct ReorderBuffer<C: embedded_time::Clock, const FRAMES: usize, const TRANSFERS: usize> {
+    /// Frames not handed out yet, in the order they were received
+    frames: heapless::Vec<UdpFrame<C>, FRAMES>,
+    transfers: heapless::LinearMap<TransferKey, Progress<C>, TRANSFERS>,
+}
+
+impl<C: embedded_time::Clock, const FRAMES: usize, const TRANSFERS: usize>
+    ReorderBuffer<C, FRAMES, TRANSFERS>
+{
+    pub fn new() -> Self {
+        Self {
+            frames: heapless::Vec::new(),
+            transfers: heapless::LinearMap::new(),
+        }
+    }
+
+    /// Takes in a received frame, to be handed out by [`ReorderBuffer::pop`] once the frames
+    /// before it are.
+    pub fn push(&mut self, frame: UdpFrame<C>) {
+        if let Some((key, frame_index)) = frame_key(&frame) {
+            if frame_index < self.next_frame_index(&key) {
+                return;
+            }
+        }
+
+        if self.frames.is_full() {
+            self.frames.remove(0);
+        }
+        // Can't fail, there is room now
+        let _ = self.frames.push(frame);
+    }
+
+    /// Hands out the next frame whose predecessors have all been handed out, in the order they
+    /// were received. Should be called after [`ReorderBuffer::push`] until it returns `None`.
+    ///
+    /// Frames with an invalid header are handed out as they are, for the node to reject.
+    pub fn pop(&mut self) -> Option<UdpFrame<C>> {
+        let (index, key) =
+            self.frames
+                .iter()
+                .enumerate()
+                .find_map(|(index, frame)| match frame_key(frame) {
+                    Some((key, frame_index)) if frame_index == self.next_frame_index(&key) => {
+                        Some((index, Some(key)))
+                    }
+                    Some(_) => None,
+                    None => Some((index, None)),
+                })?;
+        let frame = self.frames.remove(index);
+
+        let Some(key) = key else {
+            return Some(frame);
+        };
+        let tracked = self.transfers.contains_key(&key);
+        // Single-frame transfers don't need tracking
+        if !tracked && frame.header().is_ok_and(|header| header.end_of_transfer) {
+            return Some(frame);
+        }
+        if !tracked && self.transfers.len() == TRANSFERS {
+            self.forget_oldest_transfer();
+        }
+        // Can't fail, there is room or the key was already there
+        let _ = self.transfers.insert(
+            key,
+            Progress {
+                next_frame_index: self.next_frame_index(&key) + 1,
+                timestamp: frame.timestamp,
+            },
+        );
+        Some(frame)
+    }
+
+    /// Drops every frame held back and forgets every transfer.
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.transfers.clear();
+    }
+
+    /// Index of the next frame of a transfer to be handed out.
+    fn next_frame_index(&self, key: &TransferKey) -> u32 {
+        self.transfers
+            .get(key)
+            .map_or(0, |progress| progress.next_frame_index)
+    }
+
+    fn forget_oldest_transfer(&mut self) {
+        let oldest = self
+            .transfers
+            .iter()
+            .min_by_key(|(_, progress)| progress.timestamp)
+            .map(|(key, _)| *key);
+        if let Some(oldest) = oldest {
+            self.transfers.remove(&oldest);
+        }
+    }
+}
+
+impl<C: embedded_time::Clock, const FRAMES: usize, const TRANSFERS: usize> Default
+    for ReorderBuffer<C, FRAMES, TRANSFERS>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Transfer a frame belongs to and its index within it, `None` if the header is invalid.
+fn frame_key<C: embedded_time::Clock>(frame: &UdpFrame<C>) -> Option<(TransferKey, u32)> {
+    let header = frame.header().ok()?;
+    Some((
+        TransferKey {
+            transfer_kind: header.transfer_kind,
+            port_id: header.port_id,
+            source_node_id: header.source_node_id,
+            destination_node_id: header.destination_node_id,
+            transfer_id: header.transfer_id,
+        },
+        header.frame_index,
+    ))
+}
@@ -0,0 +1,403 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use std::net::UdpSocket;
+
+use crate::time::TestClock;
+use embedded_time::Clock;
+
+use super::*;
+use crate::transfer::TransferMetadata;
+use crate::transport::Transport;
+use crate::*;
+
+fn make_header() -> UdpHeader {
+    UdpHeader {
+        priority: Priority::Nominal,
+        source_node_id: Some(0x1234),
+        destination_node_id: None,
+        transfer_kind: TransferKind::Message,
+        port_id: 7509,
+        transfer_id: 0x0102_0304_0506_0708,
+        frame_index: 2,
+        end_of_transfer: true,
+        user_data: 0,
+    }
+}
+
+#[test]
+fn header_layout() {
+    let bytes = make_header().serialize();
+
+    assert_eq!(bytes[0], 1, "version");
+    assert_eq!(bytes[1], 4, "priority");
+    assert_eq!(&bytes[2..4], &[0x34, 0x12], "source node ID");
+    assert_eq!(&bytes[4..6], &[0xFF, 0xFF], "broadcast destination");
+    assert_eq!(&bytes[6..8], &7509u16.to_le_bytes(), "subject ID");
+    assert_eq!(
+        &bytes[8..16],
+        &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01],
+        "transfer ID"
+    );
+    assert_eq!(
+        &bytes[16..20],
+        &[0x02, 0x00, 0x00, 0x80],
+        "frame index + EOT"
+    );
+    assert_eq!(&bytes[20..22], &[0, 0], "user data");
+
+    let mut crc = crc_any::CRCu16::crc16ccitt_false();
+    crc.digest(&bytes[0..22]);
+    assert_eq!(&bytes[22..24], &crc.get_crc().to_be_bytes(), "header CRC");
+}
+
+#[test]
+fn header_round_trip() {
+    let header = make_header();
+    assert_eq!(UdpHeader::deserialize(&header.serialize()).unwrap(), header);
+
+    let header = UdpHeader {
+        source_node_id: None,
+        ..make_header()
+    };
+    assert_eq!(UdpHeader::deserialize(&header.serialize()).unwrap(), header);
+
+    let header = UdpHeader {
+        transfer_kind: TransferKind::Request,
+        port_id: 430,
+        destination_node_id: Some(42),
+        end_of_transfer: false,
+        ..make_header()
+    };
+    let bytes = header.serialize();
+    assert_eq!(&bytes[6..8], &(0xC000u16 | 430).to_le_bytes());
+    assert_eq!(UdpHeader::deserialize(&bytes).unwrap(), header);
+
+    let header = UdpHeader {
+        transfer_kind: TransferKind::Response,
+        port_id: 430,
+        destination_node_id: Some(42),
+        ..make_header()
+    };
+    let bytes = header.serialize();
+    assert_eq!(&bytes[6..8], &(0x8000u16 | 430).to_le_bytes());
+    assert_eq!(UdpHeader::deserialize(&bytes).unwrap(), header);
+}
+
+#[test]
+fn header_rejects_corruption() {
+    let mut bytes = make_header().serialize();
+    bytes[3] ^= 0x01;
+    assert!(matches!(
+        UdpHeader::deserialize(&bytes),
+        Err(RxError::InvalidHeader)
+    ));
+
+    assert!(matches!(
+        UdpHeader::deserialize(&bytes[0..20]),
+        Err(RxError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn multicast_addresses() {
+    assert_eq!(
+        subject_multicast_address(7509),
+        core::net::Ipv4Addr::new(239, 0, 29, 85)
+    );
+    assert_eq!(
+        service_multicast_address(42),
+        core::net::Ipv4Addr::new(239, 1, 0, 42)
+    );
+
+    let endpoint = transfer_endpoint(TransferKind::Message, 100, None).unwrap();
+    assert_eq!(endpoint.port(), UDP_PORT);
+    assert!(endpoint.ip().is_multicast());
+    assert!(transfer_endpoint(TransferKind::Request, 430, None).is_none());
+}
+
+fn make_generic_message_transfer() -> TransferMetadata<TestClock> {
+    let clock = TestClock::default();
+    TransferMetadata {
+        timestamp: clock.try_now().unwrap(),
+        priority: Priority::Nominal,
+        transfer_kind: TransferKind::Message,
+        port_id: 100,
+        source_node_id: None,
+        destination_node_id: None,
+        transfer_id: 12345,
+    }
+}
+
+/// Runs a payload through the same steps a transfer manager would, returning every frame
+/// generated for it.
+fn transmit_all(
+    metadata: &TransferMetadata<TestClock>,
+    node_id: Option<NodeId>,
+    payload: &[u8],
+) -> Result<Vec<UdpFrame<TestClock>>, TxError> {
+    let mut buffer = vec![0u8; <Udp as Transport<TestClock>>::get_crc_padded_size(payload.len())];
+    buffer[0..payload.len()].copy_from_slice(payload);
+    let len = <Udp as Transport<TestClock>>::process_tx_crc(&mut buffer, payload.len());
+
+    let mut tx_metadata = TxMetadata::default();
+    let mut offset = 0;
+    let mut frames = Vec::new();
+    while offset < len {
+        let (frame, consumed) = Udp::transmit_frame(
+            metadata,
+            &mut tx_metadata,
+            &buffer[offset..len],
+            node_id,
+            metadata.timestamp,
+        )?;
+        offset += consumed;
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
+/// Receives every frame, checking them as a single transfer, and returns the payload.
+fn receive_all(frames: &[UdpFrame<TestClock>]) -> Result<Vec<u8>, RxError> {
+    let mut rx_metadata = RxMetadata::default();
+    let mut payload = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let (frame, frame_metadata) = Udp::rx_process_frame(frame)?;
+        assert_eq!(frame.first_frame, i == 0);
+        assert_eq!(frame.last_frame, i == frames.len() - 1);
+        let crc_size = Udp::update_rx_metadata(&mut rx_metadata, frame_metadata, &frame)?;
+        payload.extend_from_slice(frame.payload);
+        payload.truncate(payload.len() - crc_size);
+    }
+
+    Ok(payload)
+}
+
+#[test]
+fn single_frame_has_crc() {
+    let metadata = make_generic_message_transfer();
+    let frames = transmit_all(&metadata, Some(42), b"Hello").unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].data.len(), UdpHeader::SIZE + 5 + 4);
+
+    let mut crc = crc_any::CRCu32::crc32c();
+    crc.digest(b"Hello");
+    assert_eq!(
+        &frames[0].data[UdpHeader::SIZE + 5..],
+        &crc.get_crc().to_le_bytes()
+    );
+
+    assert_eq!(receive_all(&frames).unwrap(), b"Hello");
+
+    let (frame, _) = Udp::rx_process_frame(&frames[0]).unwrap();
+    assert_eq!(frame.metadata.transfer_id, 12345);
+    assert_eq!(frame.metadata.source_node_id, Some(42));
+    assert_eq!(frame.metadata.destination_node_id, None);
+    assert_eq!(frame.metadata.port_id, 100);
+}
+
+#[test]
+fn multi_frame_round_trip() {
+    let metadata = make_generic_message_transfer();
+    let payload: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+    let frames = transmit_all(&metadata, Some(42), &payload).unwrap();
+    assert_eq!(frames.len(), 3);
+
+    for (i, frame) in frames.iter().enumerate() {
+        let header = frame.header().unwrap();
+        assert_eq!(header.frame_index, i as u32);
+        assert_eq!(header.end_of_transfer, i == 2);
+    }
+    assert_eq!(frames[0].data.len(), DATAGRAM_SIZE);
+
+    assert_eq!(receive_all(&frames).unwrap(), payload);
+
+    // Corrupt payload
+    let mut bad_frames = frames.clone();
+    bad_frames[1].data[UdpHeader::SIZE] ^= 0xFF;
+    assert!(matches!(receive_all(&bad_frames), Err(RxError::CrcError)));
+
+    // Missing frame
+    let skipped = [frames[0].clone(), frames[2].clone()];
+    let mut rx_metadata = RxMetadata::default();
+    let (frame, frame_metadata) = Udp::rx_process_frame(&skipped[0]).unwrap();
+    Udp::update_rx_metadata(&mut rx_metadata, frame_metadata, &frame).unwrap();
+    let (frame, frame_metadata) = Udp::rx_process_frame(&skipped[1]).unwrap();
+    let err = Udp::update_rx_metadata(&mut rx_metadata, frame_metadata, &frame).unwrap_err();
+    assert!(matches!(err, RxError::InvalidFrameOrdering));
+}
+
+/// The CRC may be split across the last two datagrams, leaving fewer than 4 bytes in the last.
+#[test]
+fn crc_split() {
+    let metadata = make_generic_message_transfer();
+    let payload: Vec<u8> = (0..UDP_MTU * 2 - 2).map(|i| i as u8).collect();
+    let frames = transmit_all(&metadata, Some(42), &payload).unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[2].data.len(), UdpHeader::SIZE + 2);
+
+    assert_eq!(receive_all(&frames).unwrap(), payload);
+
+    let mut bad_frames = frames.clone();
+    bad_frames[2].data[UdpHeader::SIZE + 1] ^= 0xFF;
+    assert!(matches!(receive_all(&bad_frames), Err(RxError::CrcError)));
+}
+
+#[test]
+fn transmit_errors() {
+    let mut metadata = make_generic_message_transfer();
+    let payload = vec![0u8; 2000];
+    assert!(matches!(
+        transmit_all(&metadata, None, &payload),
+        Err(TxError::AnonNotSingleFrame)
+    ));
+
+    metadata.transfer_kind = TransferKind::Request;
+    assert!(matches!(
+        transmit_all(&metadata, None, b"hi"),
+        Err(TxError::ServiceNoSourceID)
+    ));
+    assert!(matches!(
+        transmit_all(&metadata, Some(1), b"hi"),
+        Err(TxError::ServiceNoDestinationID)
+    ));
+
+    metadata.destination_node_id = Some(2);
+    let frames = transmit_all(&metadata, Some(1), b"hi").unwrap();
+    assert_eq!(
+        frames[0].endpoint().unwrap(),
+        core::net::SocketAddrV4::new(core::net::Ipv4Addr::new(239, 1, 0, 2), UDP_PORT)
+    );
+}
+
+/// Splits a payload into frames of `mtu` bytes, as a sender with a smaller MTU would.
+fn transmit_with_mtu(
+    metadata: &TransferMetadata<TestClock>,
+    node_id: Option<NodeId>,
+    payload: &[u8],
+    mtu: usize,
+) -> Vec<UdpFrame<TestClock>> {
+    let mut buffer = vec![0u8; <Udp as Transport<TestClock>>::get_crc_padded_size(payload.len())];
+    buffer[0..payload.len()].copy_from_slice(payload);
+    let len = <Udp as Transport<TestClock>>::process_tx_crc(&mut buffer, payload.len());
+
+    let chunks: Vec<_> = buffer[0..len].chunks(mtu).collect();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let header = UdpHeader {
+                priority: metadata.priority,
+                source_node_id: node_id,
+                destination_node_id: None,
+                transfer_kind: metadata.transfer_kind,
+                port_id: metadata.port_id,
+                transfer_id: metadata.transfer_id,
+                frame_index: i as u32,
+                end_of_transfer: i == chunks.len() - 1,
+                user_data: 0,
+            };
+            let mut data = Vec::from(header.serialize());
+            data.extend_from_slice(chunk);
+            UdpFrame::new(metadata.timestamp, &data)
+        })
+        .collect()
+}
+
+/// Senders may use a smaller MTU, so frames other than the last don't have to fill ours.
+#[test]
+fn smaller_mtu_sender() {
+    let metadata = make_generic_message_transfer();
+    let payload: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+    let frames = transmit_with_mtu(&metadata, Some(42), &payload, 300);
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[0].data.len(), UdpHeader::SIZE + 300);
+
+    assert_eq!(receive_all(&frames).unwrap(), payload);
+}
+
+/// Datagrams reordered by the network come out of the reorder buffer in order.
+#[test]
+fn reordered_frames() {
+    let mut metadata = make_generic_message_transfer();
+    let payload: Vec<u8> = (0..1000).map(|i| (i * 3) as u8).collect();
+    let frames = transmit_with_mtu(&metadata, Some(42), &payload, 300);
+    metadata.transfer_id += 1;
+    let single = transmit_with_mtu(&metadata, Some(42), b"Hello", 300);
+
+    // The first frame comes in last, and the third twice
+    let mut reorder = ReorderBuffer::<TestClock, 4, 2>::new();
+    let mut received = Vec::new();
+    for frame in [
+        &frames[2], &frames[1], &single[0], &frames[2], &frames[3], &frames[0],
+    ] {
+        reorder.push(frame.clone());
+        while let Some(frame) = reorder.pop() {
+            received.push(frame);
+        }
+    }
+    assert_eq!(received.len(), 5);
+    // Single-frame transfers aren't held up
+    assert_eq!(received[0].data, single[0].data);
+    assert_eq!(receive_all(&received[1..]).unwrap(), payload);
+
+    // Frames of a transfer that's already through are dropped
+    reorder.push(frames[1].clone());
+    assert!(reorder.pop().is_none());
+
+    // Frames whose predecessors never come make way for new ones
+    metadata.transfer_id += 1;
+    let frames = transmit_with_mtu(&metadata, Some(42), &payload, 300);
+    for frame in &frames[1..] {
+        reorder.push(frame.clone());
+        assert!(reorder.pop().is_none());
+    }
+    metadata.transfer_id += 1;
+    let frames = transmit_with_mtu(&metadata, Some(42), &payload, 300);
+    let mut received = Vec::new();
+    for frame in frames.iter().rev() {
+        reorder.push(frame.clone());
+        while let Some(frame) = reorder.pop() {
+            received.push(frame);
+        }
+    }
+    assert_eq!(receive_all(&received).unwrap(), payload);
+}
+
+/// Send frames over a loopback socket and make sure they survive the trip.
+#[test]
+fn loopback_socket() {
+    let clock = TestClock::default();
+    let rx = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let tx = UdpSocket::bind("127.0.0.1:0").unwrap();
+    rx.set_read_timeout(Some(std::time::Duration::from_secs(1)))
+        .unwrap();
+
+    let metadata = make_generic_message_transfer();
+    let payload: Vec<u8> = (0..2000).map(|i| (i * 7) as u8).collect();
+    let frames = transmit_all(&metadata, Some(42), &payload).unwrap();
+    for frame in frames.iter() {
+        tx.send_to(&frame.data, rx.local_addr().unwrap()).unwrap();
+    }
+
+    let mut received = Vec::new();
+    let mut buf = [0u8; 2048];
+    for _ in 0..frames.len() {
+        let len = rx.recv(&mut buf).unwrap();
+        received.push(UdpFrame::new(clock.try_now().unwrap(), &buf[0..len]));
+    }
+
+    assert_eq!(receive_all(&received).unwrap(), payload);
+}
+
+/// Transfer IDs are 64 bits, so they only wrap around at the very end.
+#[test]
+fn transfer_id_modulo() {
+    assert_eq!(<Udp as Transport<TestClock>>::next_transfer_id(31), 32);
+    assert_eq!(
+        <Udp as Transport<TestClock>>::next_transfer_id(u64::MAX - 1),
+        u64::MAX
+    );
+    assert_eq!(<Udp as Transport<TestClock>>::next_transfer_id(u64::MAX), 0);
+}
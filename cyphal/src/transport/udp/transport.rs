@@ -0,0 +1,259 @@
+//! Cyphal/UDP transport implementation.
+
+use core::net::SocketAddrV4;
+
+use super::header::UdpHeader;
+use super::multicast::transfer_endpoint;
+use crate::time::Timestamp;
+use crate::transfer::{Frame, TransferMetadata};
+use crate::transport::Transport;
+use crate::{NodeId, RxError, TransferId, TransferKind, TxError};
+
+use crc_any::CRCu32;
+
+/// Maximum number of payload bytes carried by a single datagram, excluding the header.
+///
+/// Transmitted frames other than the last are filled up to it. Received ones may carry fewer,
+/// as other senders can use a smaller MTU.
+pub const UDP_MTU: usize = 1408;
+
+/// Maximum size of a datagram, including the header.
+pub const DATAGRAM_SIZE: usize = UdpHeader::SIZE + UDP_MTU;
+
+/// Unit struct for declaring transport type
+#[derive(Copy, Clone, Debug)]
+pub struct Udp;
+
+#[derive(Clone, Copy, Debug)]
+pub struct FrameMetadata {
+    pub frame_index: u32,
+}
+
+/// Value of a CRC-32C computed over data followed by its own (little-endian) CRC.
+const CRC32C_RESIDUE: u32 = 0x4867_4BC7;
+
+#[derive(Default)]
+pub struct TxMetadata {
+    frame_index: u32,
+}
+
+pub struct RxMetadata {
+    crc: CRCu32,
+    next_frame_index: u32,
+}
+
+impl Default for RxMetadata {
+    fn default() -> Self {
+        Self {
+            crc: CRCu32::crc32c(),
+            next_frame_index: 0,
+        }
+    }
+}
+
+impl<C: embedded_time::Clock> Transport<C> for Udp {
+    type Frame = UdpFrame<C>;
+    type FrameMetadata = FrameMetadata;
+    type RxMetadata = RxMetadata;
+    type TxMetadata = TxMetadata;
+
+    const MTU_SIZE: usize = UDP_MTU;
+    const CRC_SIZE: usize = 4;
+    const MAX_TRANSFER_ID: TransferId = TransferId::MAX;
+
+    fn get_crc_padded_size(requested_size: usize) -> usize {
+        // Every transfer gets a CRC32C, no padding
+        requested_size + 4
+    }
+
+    fn update_rx_metadata(
+        transport_metadata: &mut Self::RxMetadata,
+        frame_metadata: Self::FrameMetadata,
+        frame: &Frame<C>,
+    ) -> Result<usize, RxError> {
+        if frame_metadata.frame_index != transport_metadata.next_frame_index {
+            return Err(RxError::InvalidFrameOrdering);
+        }
+        transport_metadata.next_frame_index += 1;
+
+        // The CRC is digested along with the data, so it doesn't matter if it's split across
+        // frames, and checked against the residue at the end.
+        transport_metadata.crc.digest(frame.payload);
+
+        if frame.last_frame {
+            if transport_metadata.crc.get_crc() != CRC32C_RESIDUE {
+                return Err(RxError::CrcError);
+            }
+            return Ok(4);
+        }
+
+        Ok(0)
+    }
+
+    fn process_tx_crc(buffer: &mut [u8], data_size: usize) -> usize {
+        let mut crc = CRCu32::crc32c();
+        crc.digest(&buffer[0..data_size]);
+
+        buffer[data_size..data_size + 4].copy_from_slice(&crc.get_crc().to_le_bytes());
+
+        data_size + 4
+    }
+
+    fn rx_process_frame<'a>(
+        frame: &'a Self::Frame,
+    ) -> Result<(Frame<'a, C>, Self::FrameMetadata), RxError> {
+        process_frame_data(frame.timestamp, &frame.data)
+    }
+
+    fn transmit_frame(
+        transfer_metadata: &TransferMetadata<C>,
+        transport_metadata: &mut Self::TxMetadata,
+        data: &[u8],
+        node_id: Option<NodeId>,
+        timestamp: embedded_time::Instant<C>,
+    ) -> Result<(Self::Frame, usize), TxError> {
+        // CRC included in data, calculated when creating a TX transfer
+        let last_frame = data.len() <= UDP_MTU;
+        let header = next_frame_header(transfer_metadata, transport_metadata, node_id, last_frame)?;
+
+        let consume_len = core::cmp::min(UDP_MTU, data.len());
+        let mut frame_data = heapless::Vec::new();
+        // Can't fail, header and MTU both fit in a datagram
+        let _ = frame_data.extend_from_slice(&header.serialize());
+        let _ = frame_data.extend_from_slice(&data[0..consume_len]);
+
+        Ok((
+            Self::Frame {
+                timestamp,
+                data: frame_data,
+            },
+            consume_len,
+        ))
+    }
+}
+
+/// Parses a header-prefixed frame into a transfer frame.
+///
+/// Shared with Cyphal/serial, which uses the same header. Senders may use any MTU, so frames
+/// other than the last aren't required to be of any particular size.
+pub(crate) fn process_frame_data<C: embedded_time::Clock>(
+    timestamp: Timestamp<C>,
+    data: &[u8],
+) -> Result<(Frame<'_, C>, FrameMetadata), RxError> {
+    let header = UdpHeader::deserialize(data)?;
+    let payload = &data[UdpHeader::SIZE..];
+
+    match header.transfer_kind {
+        TransferKind::Message => {
+            if header.source_node_id.is_none()
+                && !(header.frame_index == 0 && header.end_of_transfer)
+            {
+                return Err(RxError::AnonNotSingleFrame);
+            }
+        }
+        TransferKind::Request | TransferKind::Response => {
+            // Services can't be anonymous or broadcast
+            if header.source_node_id.is_none() || header.destination_node_id.is_none() {
+                return Err(RxError::InvalidHeader);
+            }
+        }
+    }
+
+    Ok((
+        Frame {
+            metadata: TransferMetadata {
+                timestamp,
+                priority: header.priority,
+                transfer_kind: header.transfer_kind,
+                port_id: header.port_id,
+                source_node_id: header.source_node_id,
+                destination_node_id: header.destination_node_id,
+                transfer_id: header.transfer_id,
+            },
+            // Includes the transfer CRC, which may be split across the last two frames
+            payload,
+            first_frame: header.frame_index == 0,
+            last_frame: header.end_of_transfer,
+        },
+        FrameMetadata {
+            frame_index: header.frame_index,
+        },
+    ))
+}
+
+/// Builds the header for the next frame of a transfer, advancing the TX metadata.
+pub(crate) fn next_frame_header<C: embedded_time::Clock>(
+    transfer_metadata: &TransferMetadata<C>,
+    transport_metadata: &mut TxMetadata,
+    node_id: Option<NodeId>,
+    last_frame: bool,
+) -> Result<UdpHeader, TxError> {
+    let destination_node_id = match transfer_metadata.transfer_kind {
+        TransferKind::Message => {
+            if !last_frame && node_id.is_none() {
+                return Err(TxError::AnonNotSingleFrame);
+            }
+            None
+        }
+        TransferKind::Request | TransferKind::Response => {
+            node_id.ok_or(TxError::ServiceNoSourceID)?;
+            Some(
+                transfer_metadata
+                    .destination_node_id
+                    .ok_or(TxError::ServiceNoDestinationID)?,
+            )
+        }
+    };
+
+    let header = UdpHeader {
+        priority: transfer_metadata.priority,
+        source_node_id: node_id,
+        destination_node_id,
+        transfer_kind: transfer_metadata.transfer_kind,
+        port_id: transfer_metadata.port_id,
+        transfer_id: transfer_metadata.transfer_id,
+        frame_index: transport_metadata.frame_index,
+        end_of_transfer: last_frame,
+        user_data: 0,
+    };
+    transport_metadata.frame_index += 1;
+
+    Ok(header)
+}
+
+/// A single Cyphal/UDP datagram, header included.
+#[derive(Clone, Debug)]
+pub struct UdpFrame<C: embedded_time::Clock> {
+    pub timestamp: Timestamp<C>,
+    pub data: heapless::Vec<u8, DATAGRAM_SIZE>,
+}
+
+impl<C: embedded_time::Clock> UdpFrame<C> {
+    /// Wraps a received datagram. Anything past [`DATAGRAM_SIZE`] is dropped, which will
+    /// make the frame fail its CRC check.
+    pub fn new(timestamp: Timestamp<C>, data: &[u8]) -> Self {
+        let len = core::cmp::min(data.len(), DATAGRAM_SIZE);
+        Self {
+            timestamp,
+            // Can't fail, length is bounded above
+            data: heapless::Vec::from_slice(&data[0..len]).unwrap(),
+        }
+    }
+
+    /// Parsed frame header.
+    pub fn header(&self) -> Result<UdpHeader, RxError> {
+        UdpHeader::deserialize(&self.data)
+    }
+
+    /// Multicast endpoint this frame is to be sent to.
+    ///
+    /// Returns `None` if the header is invalid.
+    pub fn endpoint(&self) -> Option<SocketAddrV4> {
+        let header = self.header().ok()?;
+        transfer_endpoint(
+            header.transfer_kind,
+            header.port_id,
+            header.destination_node_id,
+        )
+    }
+}
@@ -11,4 +11,7 @@ pub type NodeId = u16;
 pub type PortId = u16;
 
 // TODO set type with min/max bounds
-pub type TransferId = u8;
+/// Transfer ID, wide enough for the largest transport (64 bits for Cyphal/UDP).
+///
+/// Transports with a smaller transfer ID field only use the low bits.
+pub type TransferId = u64;
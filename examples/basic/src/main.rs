@@ -1,78 +1,88 @@
 #![deny(warnings)]
 
-use embedded_hal::can::ExtendedId;
-use embedded_time::Clock;
-use uavcan::{
-    session::StdVecSessionManager,
+use cyphal::{
+    get_info::NodeInfo,
+    heartbeat::Mode,
+    register::{self, MemoryRegistry, PortDirection},
     time::StdClock,
-    transfer::Transfer,
-    transport::can::{Can, CanFrame as UavcanFrame, CanMetadata},
-    types::TransferId,
-    Node, Priority, StreamingIterator, Subscription, TransferKind,
+    transfer::{map_manager::MapTransferManager, TransferManager},
+    transport::can::{acceptance_filters, Can, Filter, SocketCan, SocketCanError},
+    Node, Priority, Subscription, TransferKind, TransmissionType,
 };
-
-use arrayvec::ArrayVec;
-use socketcan::{CANFrame, CANSocket};
+use embedded_time::Clock;
 
 fn main() {
     let clock = StdClock::new();
-    let mut session_manager = StdVecSessionManager::<CanMetadata, StdClock>::new();
-    session_manager
-        .subscribe(Subscription::new(
-            TransferKind::Message,
-            7509, // TODO check
-            7,
-            embedded_time::duration::Milliseconds(500),
-        ))
+    let mut node =
+        Node::<_, Can, StdClock>::new(Some(42), MapTransferManager::<StdClock, Can>::new());
+    node.subscribe(Subscription::new(
+        TransferKind::Message,
+        7509, // TODO check
+        7,
+        embedded_time::duration::Milliseconds(500),
+    ))
+    .unwrap();
+    node.subscribe(Subscription::new(
+        TransferKind::Message,
+        100,
+        200,
+        embedded_time::duration::Milliseconds(500),
+    ))
+    .unwrap();
+    node.set_mode(Mode::Operational);
+    node.set_info(NodeInfo::new("org.example.basic".into(), [0x42; 16]))
         .unwrap();
-    session_manager
-        .subscribe(Subscription::new(
-            TransferKind::Message,
-            100,
-            200,
-            embedded_time::duration::Milliseconds(500),
-        ))
-        .unwrap();
-    let mut node = Node::<_, Can, StdClock>::new(Some(42), session_manager);
 
-    let sock = CANSocket::open("vcan0").unwrap();
+    // The hello subject can be moved through the uavcan.pub.hello.id register
+    let mut registry = MemoryRegistry::new();
+    registry.insert_port(
+        PortDirection::Publication,
+        "hello",
+        "uavcan.primitive.String.1.0",
+        Some(100),
+    );
+    node.serve_registers().unwrap();
+
+    let sock = SocketCan::open("vcan0", clock.clone()).unwrap();
 
     let mut last_publish = clock.try_now().unwrap();
-    let mut transfer_id: TransferId = 30;
 
-    sock.set_read_timeout(std::time::Duration::from_millis(100))
+    sock.set_read_timeout(Some(std::time::Duration::from_millis(100)))
         .unwrap();
+    // Only take in frames for the ports above, spread over as many filters as a typical
+    // controller has
+    let mut banks = [Filter::new(0, 0); 8];
+    let filters = acceptance_filters(node.transfer_manager.subscriptions(), node.id(), &mut banks);
+    sock.set_filters(filters).unwrap();
 
     loop {
-        let socketcan_frame = sock.read_frame().ok();
-
-        if let Some(socketcan_frame) = socketcan_frame {
-            // Note that this exposes some things I *don't* like about the API
-            // 1: we should have CanFrame::new or something
-            // 2: I don't like how the payload is working
-            let mut uavcan_frame = UavcanFrame {
-                timestamp: clock.try_now().unwrap(),
-                id: ExtendedId::new(socketcan_frame.id()).expect("not a extended CAN ID"),
-                payload: ArrayVec::new(),
-            };
-            uavcan_frame
-                .payload
-                .extend(socketcan_frame.data().iter().copied());
+        // Heartbeat goes out once a second, GetInfo responses as they're requested
+        while let Some(token) = node.poll(clock.try_now().unwrap()).unwrap() {
+            node.enqueue(token, clock.try_now().unwrap()).unwrap();
+        }
+        while let Some(token) = node
+            .poll_registers(clock.try_now().unwrap(), &mut registry)
+            .unwrap()
+        {
+            node.enqueue(token, clock.try_now().unwrap()).unwrap();
+        }
 
-            let xfer = match node.try_receive_frame(uavcan_frame) {
-                Ok(xfer) => xfer,
-                Err(err) => {
-                    println!("try_receive_frame error: {:?}", err);
-                    return;
-                }
-            };
+        let token = match sock.receive_into(&mut node) {
+            Ok(token) => token,
+            Err(SocketCanError::Io(err)) if err.kind() == std::io::ErrorKind::WouldBlock => None,
+            Err(err) => {
+                println!("receive error: {:?}", err);
+                return;
+            }
+        };
 
-            if let Some(xfer) = xfer {
-                match xfer.transfer_kind {
+        if let Some(token) = token {
+            node.transfer_manager
+                .with_rx_transfer(token, |metadata, payload| match metadata.transfer_kind {
                     TransferKind::Message => {
-                        println!("UAVCAN message received!");
+                        println!("Cyphal message received!");
                         print!("\tData: ");
-                        for byte in xfer.payload {
+                        for byte in payload {
                             print!("0x{:02x} ", byte);
                         }
                         println!();
@@ -83,8 +93,8 @@ fn main() {
                     TransferKind::Response => {
                         println!("Response Received!");
                     }
-                }
-            }
+                })
+                .unwrap();
         }
 
         if clock.try_now().unwrap() - last_publish
@@ -95,39 +105,30 @@ fn main() {
             let mut str = Vec::from([hello.len() as u8, 0]);
             str.extend_from_slice(hello.as_bytes());
 
-            let transfer = Transfer {
-                timestamp: clock.try_now().unwrap(),
-                priority: Priority::Nominal,
-                transfer_kind: TransferKind::Message,
-                port_id: 100,
-                remote_node_id: None,
-                transfer_id,
-                payload: &str,
-            };
-
-            // unchecked_add is unstable :(
-            // unsafe { transfer_id.unchecked_add(1); }
-            transfer_id = (std::num::Wrapping(transfer_id) + std::num::Wrapping(1)).0;
-
-            let mut frame_iter = node.transmit(&transfer).unwrap();
-            while let Some(frame) = frame_iter.next() {
-                sock.write_frame(
-                    &CANFrame::new(frame.id.as_raw(), &frame.payload, false, false).unwrap(),
+            // The node picks the transfer ID
+            let token = node
+                .start_tx_transfer(
+                    str.len(),
+                    // Not worth sending once the next one is due
+                    clock.try_now().unwrap() + embedded_time::duration::Milliseconds(500u32),
+                    Priority::Nominal,
+                    register::port_id(&registry, PortDirection::Publication, "hello")
+                        .unwrap_or(100),
+                    TransmissionType::Broadcast,
+                    |buf| {
+                        buf.copy_from_slice(&str);
+                        Ok::<usize, ()>(str.len())
+                    },
                 )
                 .unwrap();
-
-                //print!("Can frame {}: ", i);
-                //for byte in &frame.payload {
-                //    print!("0x{:02x} ", byte);
-                //}
-                //println!("");
-
-                //if let Some(in_xfer) = node.try_receive_frame(frame).unwrap() {
-                //    println!("Received xfer!");
-                //}
-            }
+            node.enqueue(token, clock.try_now().unwrap()).unwrap();
 
             last_publish = clock.try_now().unwrap();
         }
+
+        let flushed = sock.flush(&mut node).unwrap();
+        if flushed.expired > 0 {
+            println!("{} frames expired before going out", flushed.expired);
+        }
     }
 }
@@ -11,7 +11,6 @@ extern crate std;
 mod allocator;
 mod clock;
 mod logging;
-mod util;
 
 use core::{
     alloc::Layout,
@@ -30,28 +29,23 @@ use hal::{
     fdcan::{
         config::{ClockDivider, NominalBitTiming},
         filter::{StandardFilter, StandardFilterSlot},
-        frame::TxFrameHeader,
-        id::ExtendedId,
-        id::Id,
-        FdCan, NormalOperationMode,
+        FdCan,
     },
     gpio::{GpioExt, Speed},
-    nb::block,
     prelude::*,
     rcc::{Config, PLLSrc, PllConfig, Rcc, RccExt, SysClockSrc},
-    stm32::{Peripherals, FDCAN1},
+    stm32::Peripherals,
 };
 use stm32g4xx_hal as hal;
 
-use uavcan::{
-    session::HeapSessionManager,
-    transfer::Transfer,
-    transport::can::{Can, CanMetadata},
-    Node, Priority, StreamingIterator, Subscription, TransferKind,
+use cyphal::{
+    get_info::NodeInfo,
+    heartbeat::Mode,
+    transfer::{HeapTransferManager, TransferManager},
+    transport::can::{self, Can, Pump},
+    Node, Priority, Subscription, TransferKind, TransmissionType,
 };
 
-use util::insert_u8_array_in_u32_array;
-
 static mut POOL: MaybeUninit<[u8; 1024]> = MaybeUninit::uninit();
 
 #[global_allocator]
@@ -113,46 +107,46 @@ fn main() -> ! {
     // init clock
     let clock = StmClock::new(dp.TIM7, &rcc.clocks);
 
-    let mut session_manager = HeapSessionManager::<CanMetadata, StmClock>::new();
-    session_manager
-        .subscribe(Subscription::new(
-            TransferKind::Message,
-            7509, // TODO check
-            7,
-            embedded_time::duration::Milliseconds(500),
-        ))
+    let transfer_manager = HeapTransferManager::<StmClock, Can>::new();
+    let mut node = Node::<_, Can, StmClock>::new(Some(42), transfer_manager);
+    node.subscribe(Subscription::new(
+        TransferKind::Message,
+        7509, // TODO check
+        7,
+        embedded_time::duration::Milliseconds(500),
+    ))
+    .unwrap();
+    node.set_mode(Mode::Operational);
+    node.set_info(NodeInfo::new("org.example.embedded".into(), [0x42; 16]))
         .unwrap();
 
-    let mut node = Node::<_, Can, StmClock>::new(Some(42), session_manager);
-
-    let mut transfer_id = 0u8;
     let mut last_published = clock.try_now().unwrap();
+    let mut pump = Pump::new();
 
     loop {
         let now = clock.try_now().unwrap();
+
+        // Take in everything the controller received, heartbeats are just dropped
+        while let Ok(token) = can::receive(&mut node, &mut can, &clock) {
+            if let Some(token) = token {
+                node.transfer_manager
+                    .with_rx_transfer(token, |_, _| {})
+                    .unwrap();
+            }
+        }
+
+        // Heartbeat goes out once a second, GetInfo responses as they're requested
+        while let Some(token) = node.poll(now).unwrap() {
+            node.enqueue(token, now).unwrap();
+        }
+
         if now - last_published
             > embedded_time::duration::Generic::new(1000, StmClock::SCALING_FACTOR)
         {
             // Publish string
             let hello = "Hello!";
-            let mut str = heapless::Vec::<u8, 6>::new();
-            str.extend_from_slice(hello.as_bytes()).unwrap();
 
-            let transfer = Transfer {
-                timestamp: clock.try_now().unwrap(),
-                priority: Priority::Nominal,
-                transfer_kind: TransferKind::Message,
-                port_id: 100,
-                remote_node_id: None,
-                transfer_id,
-                payload: &str,
-            };
-
-            // unchecked_add is unstable :(
-            // unsafe { transfer_id.unchecked_add(1); }
-            transfer_id += 1;
-
-            publish(&mut node, transfer, &mut can);
+            publish(&mut node, &clock, hello.as_bytes());
 
             last_published = clock.try_now().unwrap();
 
@@ -160,28 +154,33 @@ fn main() -> ! {
             delay_syst.delay(1000.ms());
             led.toggle().unwrap();
         }
+
+        // Frames the controller has no room for yet go out on a later iteration
+        pump.transmit(&mut node, &mut can, &clock).unwrap();
     }
 }
 
 pub fn publish(
-    node: &mut Node<HeapSessionManager<CanMetadata, StmClock>, Can, StmClock>,
-    transfer: Transfer<StmClock>,
-    can: &mut FdCan<FDCAN1, NormalOperationMode>,
+    node: &mut Node<HeapTransferManager<StmClock, Can>, Can, StmClock>,
+    clock: &StmClock,
+    payload: &[u8],
 ) {
-    let mut iter = node.transmit(&transfer).unwrap();
-    while let Some(frame) = iter.next() {
-        let header = TxFrameHeader {
-            bit_rate_switching: false,
-            frame_format: hal::fdcan::frame::FrameFormat::Standard,
-            id: Id::Extended(ExtendedId::new(frame.id.as_raw()).unwrap()),
-            len: frame.payload.len() as u8,
-            marker: None,
-        };
-        block!(can.transmit(header, &mut |b| {
-            insert_u8_array_in_u32_array(&frame.payload, b)
-        },))
+    let now = clock.try_now().unwrap();
+    let token = node
+        .start_tx_transfer(
+            payload.len(),
+            now + embedded_time::duration::Milliseconds(1000u32),
+            Priority::Nominal,
+            100,
+            TransmissionType::Broadcast,
+            |buf| {
+                buf.copy_from_slice(payload);
+                Ok::<usize, ()>(payload.len())
+            },
+        )
         .unwrap();
-    }
+
+    node.enqueue(token, now).unwrap();
 }
 
 fn config_rcc(rcc: Rcc) -> Rcc {